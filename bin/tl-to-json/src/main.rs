@@ -10,6 +10,12 @@
 //! variant next to it.
 //!
 //! If the file is "-", it is read from standard input instead.
+//!
+//! If the first parameter is `--request`, the rest of files are instead
+//! treated as serialized requests (such as those captured from a client's
+//! traffic), and their `json` representation is printed to standard output.
+mod request;
+
 use grammers_tl_parser::{parse_tl_file, tl};
 use std::env;
 use std::fs::File;
@@ -17,6 +23,7 @@ use std::io::{self, BufWriter, Read};
 use std::path::PathBuf;
 
 const STDIN_NAME: &str = "-";
+const REQUEST_FLAG: &str = "--request";
 
 #[derive(serde::Serialize)]
 struct Schema {
@@ -67,10 +74,32 @@ fn adapt_param(ty: &tl::Parameter) -> Parameter {
     }
 }
 
+fn print_requests(files: impl Iterator<Item = String>) -> std::io::Result<()> {
+    for fin in files {
+        let mut bytes = Vec::new();
+        if fin == STDIN_NAME {
+            io::stdin().read_to_end(&mut bytes)?;
+        } else {
+            File::open(&fin)?.read_to_end(&mut bytes)?;
+        }
+
+        let value = request::request_to_json(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        println!("{value}");
+    }
+
+    Ok(())
+}
+
 fn main() -> std::io::Result<()> {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some(REQUEST_FLAG) {
+        return print_requests(args.skip(1));
+    }
+
     // load_tl("tl/api.tl")?);
     let mut tl = String::new();
-    for fin in env::args().skip(1) {
+    for fin in args {
         if fin == STDIN_NAME {
             io::stdin().read_to_string(&mut tl)?;
         } else {
@@ -0,0 +1,98 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Convert serialized requests, such as those captured from a client's traffic, into `json`.
+use grammers_tl_types::{self as tl, deserialize, AnyFunction, Deserializable};
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The bytes could not be deserialized into any known function.
+    Deserialize(deserialize::Error),
+    /// The function was deserialized, but could not be represented as `json`.
+    Json(serde_json::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deserialize(e) => write!(f, "failed to deserialize request: {e}"),
+            Self::Json(e) => write!(f, "failed to convert request: {e}"),
+        }
+    }
+}
+
+/// Deserialize the request, starting with its constructor identifier, into a `json` object
+/// with the name of the method and its parameters.
+pub fn request_to_json(bytes: &[u8]) -> Result<Value, Error> {
+    let id = bytes
+        .get(..4)
+        .map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+        .ok_or(Error::Deserialize(deserialize::Error::UnexpectedEof))?;
+
+    let function = AnyFunction::from_bytes(bytes).map_err(Error::Deserialize)?;
+    let params = match serde_json::to_value(function).map_err(Error::Json)? {
+        // Enums are represented as `{"Variant": params}`, but the variant name is not needed.
+        Value::Object(map) => map.into_iter().next().map(|(_, params)| params),
+        _ => None,
+    };
+
+    Ok(json!({
+        "method": tl::name_for_id(id),
+        "params": params.unwrap_or(Value::Null),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tl::Serializable;
+
+    #[test]
+    fn check_request_round_trip() {
+        let request = tl::functions::help::GetNearestDc {}.to_bytes();
+        assert_eq!(
+            request_to_json(&request).unwrap(),
+            json!({"method": "help.getNearestDc", "params": {}})
+        );
+
+        let request = tl::functions::messages::GetHistory {
+            peer: tl::enums::InputPeer::PeerSelf,
+            offset_id: 10,
+            offset_date: 0,
+            add_offset: 0,
+            limit: 50,
+            max_id: 0,
+            min_id: 0,
+            hash: 0,
+        }
+        .to_bytes();
+        let value = request_to_json(&request).unwrap();
+        assert_eq!(value["method"], "messages.getHistory");
+        assert_eq!(value["params"]["offset_id"], 10);
+        assert_eq!(value["params"]["limit"], 50);
+        assert_eq!(value["params"]["peer"], "PeerSelf");
+    }
+
+    #[test]
+    fn check_unknown_request() {
+        assert!(matches!(
+            request_to_json(&[1, 2, 3, 4]),
+            Err(Error::Deserialize(
+                deserialize::Error::UnexpectedConstructor { id: 0x04030201 }
+            ))
+        ));
+        assert!(matches!(
+            request_to_json(&[1, 2]),
+            Err(Error::Deserialize(deserialize::Error::UnexpectedEof))
+        ));
+    }
+}
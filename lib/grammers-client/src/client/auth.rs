@@ -5,13 +5,15 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use super::net::connect_sender;
-use super::Client;
+use super::{net, Client};
 use crate::types::{LoginToken, PasswordToken, TermsOfService, User};
 use crate::utils;
-use grammers_crypto::two_factor_auth::{calculate_2fa, check_p_and_g};
-pub use grammers_mtsender::{AuthorizationError, InvocationError};
+use grammers_crypto::two_factor_auth::{calculate_2fa, calculate_new_password_hash, check_p_and_g};
+use grammers_crypto::{RandomSource, SystemRandom};
+pub use grammers_mtsender::{AuthorizationError, InvocationError, MigrateKind, RpcError};
+use grammers_session::{ChatHashCache, MessageBox};
 use grammers_tl_types as tl;
+use std::convert::Infallible;
 use std::fmt;
 
 /// The error type which is returned when signing in fails.
@@ -47,6 +49,17 @@ impl fmt::Display for SignInError {
 
 impl std::error::Error for SignInError {}
 
+/// The datacenter the home connection must migrate to, if the error during login indicates so.
+fn login_migrate_dc(error: &InvocationError) -> Option<i32> {
+    match error {
+        InvocationError::Rpc(err) => match err.migrate_dc()? {
+            (MigrateKind::File, _) | (MigrateKind::Stats, _) => None,
+            (_, dc_id) => Some(dc_id),
+        },
+        _ => None,
+    }
+}
+
 /// Method implementations related with the authentication of the user into the API.
 ///
 /// Most requests to the API require the user to have authorized their key, stored in the session,
@@ -75,6 +88,7 @@ impl Client {
         match self.invoke(&tl::functions::updates::GetState {}).await {
             Ok(_) => Ok(true),
             Err(InvocationError::Rpc(e)) if e.code == 401 => Ok(false),
+            Err(InvocationError::AuthKeyInvalid) => Ok(false),
             Err(err) => Err(err),
         }
     }
@@ -89,25 +103,23 @@ impl Client {
 
         let user = User::from_raw(auth.user);
 
-        let sync_state = {
-            let mut state = self.0.state.write().unwrap();
-            self.0
-                .config
-                .session
-                .set_user(user.id(), state.dc_id, user.is_bot());
-
-            state.chat_hashes.set_self_user(user.pack());
-            if let Some(us) = update_state {
-                state.message_box.set_state(us);
-                true
-            } else {
-                false
-            }
-        };
+        let mut state = self.0.state.write().unwrap();
+        state.chat_hashes.set_self_user(user.pack());
+        state.self_user = Some(user.clone());
+        let update_state = update_state.map(|us| {
+            state.message_box.set_state(us);
+            state.message_box.session_state()
+        });
 
-        if sync_state {
-            self.sync_update_state();
-        }
+        // The session should never be saved with the new user but the previous update state.
+        let Ok(()) = self.0.config.session.batch(|tx| {
+            tx.set_user(user.id(), state.dc_id, user.is_bot());
+            if let Some(update_state) = update_state {
+                tx.set_state(update_state);
+            }
+            Ok::<_, Infallible>(())
+        });
+        drop(state);
 
         Ok(user)
     }
@@ -155,18 +167,13 @@ impl Client {
 
         let result = match self.invoke(&request).await {
             Ok(x) => x,
-            Err(InvocationError::Rpc(err)) if err.code == 303 => {
-                let dc_id = err.value.unwrap() as i32;
-                let (sender, request_tx) = connect_sender(dc_id, &self.0.config).await?;
-                {
-                    *self.0.conn.sender.lock().await = sender;
-                    *self.0.conn.request_tx.write().unwrap() = request_tx;
-                    let mut state = self.0.state.write().unwrap();
-                    state.dc_id = dc_id;
+            Err(err) => match login_migrate_dc(&err) {
+                Some(dc_id) => {
+                    self.migrate_home_dc(dc_id).await?;
+                    self.invoke(&request).await?
                 }
-                self.invoke(&request).await?
-            }
-            Err(e) => return Err(e.into()),
+                None => return Err(err.into()),
+            },
         };
 
         match result {
@@ -229,27 +236,22 @@ impl Client {
                 SC::Code(code) => code,
                 SC::Success(_) => panic!("should not have logged in yet"),
             },
-            Err(InvocationError::Rpc(err)) if err.code == 303 => {
+            Err(err) => match login_migrate_dc(&err) {
                 // Since we are not logged in (we're literally requesting for
                 // the code to login now), there's no need to export the current
                 // authorization and re-import it at a different datacenter.
                 //
                 // Just connect and generate a new authorization key with it
                 // before trying again.
-                let dc_id = err.value.unwrap() as i32;
-                let (sender, request_tx) = connect_sender(dc_id, &self.0.config).await?;
-                {
-                    *self.0.conn.sender.lock().await = sender;
-                    *self.0.conn.request_tx.write().unwrap() = request_tx;
-                    let mut state = self.0.state.write().unwrap();
-                    state.dc_id = dc_id;
-                }
-                match self.invoke(&request).await? {
-                    SC::Code(code) => code,
-                    SC::Success(_) => panic!("should not have logged in yet"),
+                Some(dc_id) => {
+                    self.migrate_home_dc(dc_id).await?;
+                    match self.invoke(&request).await? {
+                        SC::Code(code) => code,
+                        SC::Success(_) => panic!("should not have logged in yet"),
+                    }
                 }
-            }
-            Err(e) => return Err(e.into()),
+                None => return Err(err.into()),
+            },
         };
 
         Ok(LoginToken {
@@ -300,15 +302,27 @@ impl Client {
     /// # }
     /// ```
     pub async fn sign_in(&self, token: &LoginToken, code: &str) -> Result<User, SignInError> {
-        match self
-            .invoke(&tl::functions::auth::SignIn {
-                phone_number: token.phone.clone(),
-                phone_code_hash: token.phone_code_hash.clone(),
-                phone_code: Some(code.to_string()),
-                email_verification: None,
-            })
-            .await
-        {
+        let request = tl::functions::auth::SignIn {
+            phone_number: token.phone.clone(),
+            phone_code_hash: token.phone_code_hash.clone(),
+            phone_code: Some(code.to_string()),
+            email_verification: None,
+        };
+
+        let result = match self.invoke(&request).await {
+            Err(err) => match login_migrate_dc(&err) {
+                Some(dc_id) => {
+                    if let Err(e) = self.migrate_home_dc(dc_id).await {
+                        return Err(SignInError::Other(net::connect_error(e)));
+                    }
+                    self.invoke(&request).await
+                }
+                None => Err(err),
+            },
+            result => result,
+        };
+
+        match result {
             Ok(tl::enums::auth::Authorization::Authorization(x)) => {
                 self.complete_login(x).await.map_err(SignInError::Other)
             }
@@ -425,11 +439,107 @@ impl Client {
         }
     }
 
+    /// Set up or change the two-factor authentication password of the logged-in user.
+    ///
+    /// If the account already has a password, the `current_password` must be provided, or the
+    /// method will fail with `PASSWORD_HASH_INVALID`, just as it would if the password was wrong.
+    ///
+    /// If a recovery `email` is provided, Telegram will send a code to it, and the method will
+    /// fail with `EMAIL_UNCONFIRMED_*` until the email is confirmed. The new password is only
+    /// set once that happens.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// // Set up a password for the first time.
+    /// client.set_2fa_password(None, "hunter2", Some("the usual"), None).await?;
+    ///
+    /// // Change it later on.
+    /// client.set_2fa_password(Some("hunter2"), "hunter3", None, None).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_2fa_password(
+        &self,
+        current_password: Option<&str>,
+        new_password: &str,
+        hint: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<(), InvocationError> {
+        let password_info = self.get_password_information().await?.password;
+
+        let password = match (password_info.has_password, current_password) {
+            (true, Some(current_password)) => {
+                let (salt1, salt2, p, g) = utils::extract_password_parameters(
+                    password_info.current_algo.as_ref().unwrap(),
+                );
+                let g_b = password_info.srp_b.clone().unwrap();
+                let a = password_info.secure_random.clone();
+
+                let (m1, g_a) = calculate_2fa(salt1, salt2, p, g, g_b, a, current_password);
+
+                tl::types::InputCheckPasswordSrp {
+                    srp_id: password_info.srp_id.unwrap(),
+                    a: g_a.to_vec(),
+                    m1: m1.to_vec(),
+                }
+                .into()
+            }
+            (true, None) => {
+                return Err(InvocationError::Rpc(RpcError {
+                    code: 400,
+                    name: "PASSWORD_HASH_INVALID".to_string(),
+                    value: None,
+                    caused_by: None,
+                }))
+            }
+            (false, _) => tl::enums::InputCheckPasswordSrp::InputCheckPasswordEmpty,
+        };
+
+        let (salt1, salt2, p, g) = utils::extract_password_parameters(&password_info.new_algo);
+        if !check_p_and_g(p, g) {
+            panic!("Failed to get correct password information from Telegram")
+        }
+
+        // The client must extend the server's salt with its own random bytes.
+        let mut new_salt1 = salt1.clone();
+        let mut random = [0; 32];
+        SystemRandom.fill(&mut random);
+        new_salt1.extend(random);
+
+        let new_password_hash = calculate_new_password_hash(&new_salt1, salt2, p, g, new_password);
+
+        self.invoke(&tl::functions::account::UpdatePasswordSettings {
+            password,
+            new_settings: tl::types::account::PasswordInputSettings {
+                new_algo: Some(
+                    tl::types::PasswordKdfAlgoSha256Sha256Pbkdf2Hmacsha512iter100000Sha256ModPow {
+                        salt1: new_salt1,
+                        salt2: salt2.clone(),
+                        g: *g,
+                        p: p.clone(),
+                    }
+                    .into(),
+                ),
+                new_password_hash: Some(new_password_hash.to_vec()),
+                hint: Some(hint.unwrap_or_default().to_string()),
+                email: email.map(|email| email.to_string()),
+                new_secure_settings: None,
+            }
+            .into(),
+        })
+        .await?;
+        Ok(())
+    }
+
     /// Signs out of the account authorized by this client's session.
     ///
-    /// If the client was not logged in, this method returns false.
+    /// If the client was not logged in, this method returns an error.
     ///
-    /// The client is not disconnected after signing out.
+    /// On success, the user, update state and authorization keys are removed from the session,
+    /// so it should be saved again to avoid reusing the old authorization. The client is not
+    /// disconnected after signing out.
     ///
     /// Note that after using this method you will have to sign in again. If all you want to do
     /// is disconnect, simply [`drop`] the [`Client`] instance.
@@ -447,7 +557,101 @@ impl Client {
     /// # }
     /// ```
     pub async fn sign_out(&self) -> Result<tl::enums::auth::LoggedOut, InvocationError> {
-        self.invoke(&tl::functions::auth::LogOut {}).await
+        // The server may acknowledge the request and close the connection without answering it.
+        // If that happens, the authorization is gone all the same, only without a token to reuse.
+        let ack_result = tl::enums::auth::LoggedOut::Out(tl::types::auth::LoggedOut {
+            future_auth_token: None,
+        });
+        let result = self
+            .0
+            .conn
+            .invoke_with_ack_result(
+                &tl::functions::auth::LogOut {},
+                &ack_result,
+                &self.0.config.params,
+                |updates| self.process_socket_updates(updates),
+            )
+            .await;
+        let logged_out = self.check_auth_key_registered(result).await?;
+        self.reset_authorization().await;
+        Ok(logged_out)
+    }
+
+    /// Forget the logged-in account, both from the session and the client's state.
+    pub(crate) async fn reset_authorization(&self) {
+        self.0.config.session.clear_authorization();
+        {
+            let mut state = self.0.state.write().unwrap();
+            state.message_box = MessageBox::new().with_timeouts(
+                self.0.config.params.no_updates_timeout,
+                self.0.config.params.possible_gap_timeout,
+            );
+            state.chat_hashes = ChatHashCache::new(None);
+            state.self_user = None;
+        }
+        // Authorizations imported into other datacenters are no longer valid either.
+        self.0.downloader_map.lock().unwrap().clear();
+    }
+
+    /// Terminate all other sessions of the logged-in user, leaving only the current one.
+    ///
+    /// Telegram may refuse to do this if the current session was created too recently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.terminate_other_sessions().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn terminate_other_sessions(&self) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::auth::ResetAuthorizations {})
+            .await?;
+        Ok(())
+    }
+
+    /// Get the active sessions of the logged-in user, including the current one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// for session in client.get_authorizations().await? {
+    ///     println!("{} ({}), last active {}", session.device_model, session.app_name, session.date_active);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_authorizations(
+        &self,
+    ) -> Result<Vec<tl::types::Authorization>, InvocationError> {
+        let tl::enums::account::Authorizations::Authorizations(authorizations) = self
+            .invoke(&tl::functions::account::GetAuthorizations {})
+            .await?;
+
+        Ok(authorizations
+            .authorizations
+            .into_iter()
+            .map(|tl::enums::Authorization::Authorization(authorization)| authorization)
+            .collect())
+    }
+
+    /// Change whether the logged-in user appears online or offline to others.
+    ///
+    /// Telegram marks the user as offline on its own after some time, so this is mostly useful
+    /// to appear offline right away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.set_online(false).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_online(&self, online: bool) -> Result<(), InvocationError> {
+        self.invoke(&set_online_request(online)).await.map(drop)
     }
 
     /// Synchronize all state to the session file and provide mutable access to it.
@@ -468,3 +672,87 @@ impl Client {
         panic!("disconnect now only works via dropping");
     }
 }
+
+fn set_online_request(online: bool) -> tl::functions::account::UpdateStatus {
+    // The request's flag means the opposite, which is easy to get wrong.
+    tl::functions::account::UpdateStatus { offline: !online }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use grammers_tl_types::Serializable;
+
+    fn rpc_error(code: i32, message: &str) -> InvocationError {
+        InvocationError::Rpc(RpcError::from(tl::types::RpcError {
+            error_code: code,
+            error_message: message.to_string(),
+        }))
+    }
+
+    #[test]
+    fn check_login_migrate_dc() {
+        assert_eq!(
+            login_migrate_dc(&rpc_error(303, "PHONE_MIGRATE_2")),
+            Some(2)
+        );
+        assert_eq!(
+            login_migrate_dc(&rpc_error(303, "NETWORK_MIGRATE_4")),
+            Some(4)
+        );
+        assert_eq!(login_migrate_dc(&rpc_error(303, "USER_MIGRATE_5")), Some(5));
+        assert_eq!(login_migrate_dc(&rpc_error(303, "FILE_MIGRATE_3")), None);
+        assert_eq!(login_migrate_dc(&rpc_error(420, "FLOOD_WAIT_2")), None);
+        assert_eq!(login_migrate_dc(&InvocationError::Dropped), None);
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_sign_in_migrates_home_dc() {
+        use grammers_mtsender::mock::MockSender;
+
+        let mut sender = MockSender::new();
+        sender
+            .expect::<tl::functions::auth::SignIn>()
+            .fail(RpcError::from(tl::types::RpcError {
+                error_code: 303,
+                error_message: "PHONE_MIGRATE_2".to_string(),
+            }));
+        sender.expect::<tl::functions::auth::SignIn>().respond(
+            tl::types::auth::Authorization {
+                setup_password_required: false,
+                otherwise_relogin_days: None,
+                tmp_sessions: None,
+                future_auth_token: None,
+                user: tl::types::UserEmpty { id: 1234 }.into(),
+            }
+            .into(),
+        );
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            client.0.state.write().unwrap().dc_id = 1;
+            let token = LoginToken {
+                phone: "+1 415 555 0132".to_string(),
+                phone_code_hash: "hash".to_string(),
+            };
+
+            let user = client.sign_in(&token, "12345").await.unwrap();
+            assert_eq!(user.id(), 1234);
+            assert_eq!(client.0.state.read().unwrap().dc_id, 2);
+
+            let session_user = client.0.config.session.get_user().unwrap();
+            assert_eq!(session_user.id, 1234);
+            assert_eq!(session_user.dc, 2);
+            assert_eq!(mock.lock().await.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    fn check_set_online_request() {
+        // The constructor identifier is followed by the `offline` flag.
+        assert_eq!(set_online_request(false).to_bytes()[4..], true.to_bytes());
+        assert_eq!(set_online_request(true).to_bytes()[4..], false.to_bytes());
+    }
+}
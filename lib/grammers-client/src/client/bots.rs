@@ -8,7 +8,10 @@
 use crate::client::messages::parse_mention_entities;
 use crate::utils::generate_random_id;
 use crate::Client;
-use crate::{types::IterBuffer, InputMessage};
+use crate::{
+    types::{InlineMessageId, IterBuffer},
+    InputMessage,
+};
 pub use grammers_mtsender::{AuthorizationError, InvocationError};
 use grammers_session::PackedChat;
 use grammers_tl_types as tl;
@@ -100,12 +103,16 @@ impl InlineResultIter {
             return result;
         }
 
+        let response = match self.client.invoke(&self.request).await {
+            Ok(response) => response,
+            Err(e) => return self.fetch_failed(e),
+        };
         let tl::enums::messages::BotResults::Results(tl::types::messages::BotResults {
             query_id,
             next_offset,
             results,
             ..
-        }) = self.client.invoke(&self.request).await?;
+        }) = response;
 
         if let Some(offset) = next_offset {
             self.request.offset = offset;
@@ -158,46 +165,74 @@ impl Client {
     /// Similar to [`Client::send_message`], advanced formatting can be achieved with the
     /// options offered by [`InputMessage`].
     ///
+    /// The edit is sent to the datacenter where the message is stored, as encoded in its
+    /// identifier.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::types::InlineMessageId;
+    ///
+    /// // For example, an identifier received from the Bot API.
+    /// let message_id: InlineMessageId = "BAAAANIEAADuuPD_LvUyxRHT6_8".parse()?;
+    /// client.edit_inline_message(message_id, "Edited!").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// [`InputMessage`]: crate::InputMessage
-    pub async fn edit_inline_message<M: Into<InputMessage>>(
+    pub async fn edit_inline_message<I: Into<InlineMessageId>, M: Into<InputMessage>>(
         &self,
-        message_id: tl::enums::InputBotInlineMessageId,
+        message_id: I,
         input_message: M,
     ) -> Result<bool, InvocationError> {
-        let message: InputMessage = input_message.into();
+        let (dc_id, request) =
+            self.edit_inline_message_request(message_id.into(), input_message.into());
+        self.invoke_in_dc(&request, dc_id).await
+    }
+
+    /// Build the request to edit an inline message, along with the datacenter it must be sent to.
+    fn edit_inline_message_request(
+        &self,
+        message_id: InlineMessageId,
+        message: InputMessage,
+    ) -> (i32, tl::functions::messages::EditInlineBotMessage) {
         let entities = parse_mention_entities(self, message.entities);
-        if message.media.as_ref().is_some_and(|media| {
-            !matches!(
-                media,
-                tl::enums::InputMedia::PhotoExternal(_)
-                    | tl::enums::InputMedia::DocumentExternal(_),
-            )
-        }) {
-            let dc_id = message_id.dc_id();
-            self.invoke_in_dc(
-                &tl::functions::messages::EditInlineBotMessage {
-                    id: message_id,
-                    message: Some(message.text),
-                    media: message.media,
-                    entities,
-                    no_webpage: !message.link_preview,
-                    reply_markup: message.reply_markup,
-                    invert_media: message.invert_media,
-                },
-                dc_id,
-            )
-            .await
-        } else {
-            self.invoke(&tl::functions::messages::EditInlineBotMessage {
-                id: message_id,
+        (
+            message_id.dc_id(),
+            tl::functions::messages::EditInlineBotMessage {
+                id: message_id.raw,
                 message: Some(message.text),
                 media: message.media,
                 entities,
-                no_webpage: !message.link_preview,
+                no_webpage: message.no_webpage,
                 reply_markup: message.reply_markup,
                 invert_media: message.invert_media,
-            })
-            .await
-        }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_inline_message_edit_is_routed_to_its_dc() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let message_id: InlineMessageId = "BAAAANIEAADuuPD_LvUyxRHT6_8".parse().unwrap();
+            assert_eq!(message_id.owner_id(), -1001234);
+            assert_eq!(message_id.message_id(), 1234);
+
+            let (dc_id, request) =
+                client.edit_inline_message_request(message_id.clone(), "edited".into());
+            assert_eq!(dc_id, 4);
+            assert_eq!(request.id, message_id.raw);
+            assert_eq!(request.message.as_deref(), Some("edited"));
+        });
     }
 }
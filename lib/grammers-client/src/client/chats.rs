@@ -10,9 +10,11 @@
 
 use super::Client;
 use crate::types::{
-    chats::AdminRightsBuilderInner, chats::BannedRightsBuilderInner, AdminRightsBuilder,
-    BannedRightsBuilder, Chat, ChatMap, IterBuffer, Message, Participant, Photo, User,
+    chats::AdminRightsBuilderInner, chats::BannedRightsBuilderInner, AdminLogEvent,
+    AdminRightsBuilder, BannedRightsBuilder, Chat, ChatMap, ChatReference, IterBuffer, Message,
+    Participant, Photo, ResolvedChat, SendAsPeer, Uploaded, User,
 };
+use crate::utils::generate_random_id;
 use grammers_mtsender::RpcError;
 pub use grammers_mtsender::{AuthorizationError, InvocationError};
 use grammers_session::{PackedChat, PackedType};
@@ -21,10 +23,70 @@ use std::collections::VecDeque;
 use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
+use web_time::{SystemTime, UNIX_EPOCH};
 
 const MAX_PARTICIPANT_LIMIT: usize = 200;
 const MAX_PHOTO_LIMIT: usize = 100;
+const MAX_ADMIN_LOG_LIMIT: usize = 100;
 const KICK_BAN_DURATION: i32 = 60; // in seconds, in case the second request fails
+/// Periods, in seconds, after which Telegram can automatically delete messages (one day to a year).
+// Usernames can change hands, so the chats they resolve to are not remembered forever.
+const RESOLVED_USERNAME_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+const AUTO_DELETE_PERIODS: std::ops::RangeInclusive<u64> = 86400..=365 * 86400;
+
+/// Build the request to set an uploaded photo or video as the profile photo.
+fn upload_profile_photo_request(file: Uploaded) -> tl::functions::photos::UploadProfilePhoto {
+    let video = file.is_video();
+    tl::functions::photos::UploadProfilePhoto {
+        fallback: false,
+        bot: None,
+        video_start_ts: if video { Some(0.0) } else { None },
+        file: if video { None } else { Some(file.raw.clone()) },
+        video: if video { Some(file.raw) } else { None },
+        video_emoji_markup: None,
+    }
+}
+
+/// Build the request to change the auto-delete period of a chat, where zero disables it.
+///
+/// Fails with `TTL_PERIOD_INVALID`, as Telegram would, if the period is out of range.
+fn set_auto_delete_request(
+    chat: PackedChat,
+    period: Option<Duration>,
+) -> Result<tl::functions::messages::SetHistoryTtl, InvocationError> {
+    let period = period.map_or(0, |period| period.as_secs());
+    if period != 0 && !AUTO_DELETE_PERIODS.contains(&period) {
+        return Err(InvocationError::Rpc(RpcError {
+            code: 400,
+            name: "TTL_PERIOD_INVALID".to_string(),
+            value: None,
+            caused_by: None,
+        }));
+    }
+    Ok(tl::functions::messages::SetHistoryTtl {
+        peer: chat.to_input_peer(),
+        period: period as i32,
+    })
+}
+
+/// Convert the `ttl_period` of a chat's full information into its auto-delete period.
+fn auto_delete_period(ttl_period: Option<i32>) -> Option<Duration> {
+    ttl_period
+        .filter(|&period| period > 0)
+        .map(|period| Duration::from_secs(period as u64))
+}
+
+/// Build the photo to set an uploaded photo or video as the photo of a group or channel.
+fn input_chat_photo(file: Uploaded) -> tl::enums::InputChatPhoto {
+    let video = file.is_video();
+    tl::types::InputChatUploadedPhoto {
+        video_start_ts: if video { Some(0.0) } else { None },
+        file: if video { None } else { Some(file.raw.clone()) },
+        video: if video { Some(file.raw) } else { None },
+        video_emoji_markup: None,
+    }
+    .into()
+}
 
 pub enum ParticipantIter {
     Empty,
@@ -167,10 +229,11 @@ impl ParticipantIter {
                 let mut chats = ChatMap::new(users, Vec::new());
                 let chats = Arc::get_mut(&mut chats).unwrap();
 
+                // Participants that are not users, such as banned channels, are skipped.
                 iter.buffer.extend(
                     participants
                         .into_iter()
-                        .map(|p| Participant::from_raw_channel(chats, p)),
+                        .filter_map(|p| Participant::from_raw_channel(chats, p)),
                 );
 
                 iter.total = Some(count as usize);
@@ -293,8 +356,7 @@ impl ProfilePhotoIter {
                     iter.request.offset += photos.len() as i32;
                 }
 
-                iter.buffer
-                    .extend(photos.into_iter().map(|x| Photo::from_raw(x)));
+                iter.buffer.extend(photos.into_iter().map(Photo::from_raw));
 
                 Ok(total)
             }
@@ -336,6 +398,183 @@ impl ProfilePhotoIter {
     }
 }
 
+pub type AdminLogIter = IterBuffer<tl::functions::channels::GetAdminLog, AdminLogEvent>;
+
+impl AdminLogIter {
+    fn new(client: &Client, chat: PackedChat) -> Self {
+        Self::from_request(
+            client,
+            MAX_ADMIN_LOG_LIMIT,
+            tl::functions::channels::GetAdminLog {
+                channel: chat
+                    .try_to_input_channel()
+                    .unwrap_or(tl::enums::InputChannel::Empty),
+                q: String::new(),
+                events_filter: None,
+                admins: None,
+                max_id: 0,
+                min_id: 0,
+                limit: 0,
+            },
+        )
+    }
+
+    /// Only return events involving messages containing the given text.
+    pub fn query(mut self, query: &str) -> Self {
+        self.request.q = query.to_string();
+        self
+    }
+
+    /// Only return events for actions performed by the given administrators.
+    pub fn admins<C: Into<PackedChat>, I: IntoIterator<Item = C>>(mut self, admins: I) -> Self {
+        self.request.admins = Some(
+            admins
+                .into_iter()
+                .filter_map(|admin| admin.into().try_to_input_user())
+                .collect(),
+        );
+        self
+    }
+
+    /// Access the filter of events, which returns all events until any kind is enabled.
+    fn events_filter(&mut self) -> &mut tl::types::ChannelAdminLogEventsFilter {
+        let tl::enums::ChannelAdminLogEventsFilter::Filter(filter) =
+            self.request.events_filter.get_or_insert_with(|| {
+                tl::types::ChannelAdminLogEventsFilter {
+                    join: false,
+                    leave: false,
+                    invite: false,
+                    ban: false,
+                    unban: false,
+                    kick: false,
+                    unkick: false,
+                    promote: false,
+                    demote: false,
+                    info: false,
+                    settings: false,
+                    pinned: false,
+                    edit: false,
+                    delete: false,
+                    group_call: false,
+                    invites: false,
+                    send: false,
+                    forums: false,
+                }
+                .into()
+            });
+        filter
+    }
+
+    /// Include members joining. Once any kind of event is included, the rest are excluded.
+    pub fn joins(mut self) -> Self {
+        self.events_filter().join = true;
+        self
+    }
+
+    /// Include members leaving. Once any kind of event is included, the rest are excluded.
+    pub fn leaves(mut self) -> Self {
+        self.events_filter().leave = true;
+        self
+    }
+
+    /// Include members being invited. Once any kind of event is included, the rest are
+    /// excluded.
+    pub fn invites(mut self) -> Self {
+        self.events_filter().invite = true;
+        self
+    }
+
+    /// Include members being banned, unbanned, kicked, or restricted. Once any kind of event is
+    /// included, the rest are excluded.
+    pub fn bans(mut self) -> Self {
+        let filter = self.events_filter();
+        filter.ban = true;
+        filter.unban = true;
+        filter.kick = true;
+        filter.unkick = true;
+        self
+    }
+
+    /// Include members being promoted or demoted, as well as changes to the default permissions.
+    /// Once any kind of event is included, the rest are excluded.
+    pub fn permission_changes(mut self) -> Self {
+        let filter = self.events_filter();
+        filter.promote = true;
+        filter.demote = true;
+        filter.settings = true;
+        self
+    }
+
+    /// Include changes to the chat information, such as its title or photo. Once any kind of
+    /// event is included, the rest are excluded.
+    pub fn info_changes(mut self) -> Self {
+        self.events_filter().info = true;
+        self
+    }
+
+    /// Include messages being pinned or unpinned. Once any kind of event is included, the rest
+    /// are excluded.
+    pub fn pins(mut self) -> Self {
+        self.events_filter().pinned = true;
+        self
+    }
+
+    /// Include messages being edited. Once any kind of event is included, the rest are excluded.
+    pub fn edits(mut self) -> Self {
+        self.events_filter().edit = true;
+        self
+    }
+
+    /// Include messages being deleted. Once any kind of event is included, the rest are
+    /// excluded.
+    pub fn deletions(mut self) -> Self {
+        self.events_filter().delete = true;
+        self
+    }
+
+    /// Add the events in a response to the buffer and prepare the request for the next page.
+    fn extend_buffer(&mut self, results: tl::enums::channels::AdminLogResults) {
+        let tl::enums::channels::AdminLogResults::Results(results) = results;
+
+        self.last_chunk = results.events.len() < self.request.limit as usize;
+        if let Some(id) = results
+            .events
+            .iter()
+            .map(|tl::enums::ChannelAdminLogEvent::Event(event)| event.id)
+            .min()
+        {
+            self.request.max_id = id;
+        }
+
+        let chats = ChatMap::new(results.users, results.chats);
+        let client = self.client.clone();
+        self.buffer.extend(
+            results
+                .events
+                .into_iter()
+                .map(|event| AdminLogEvent::from_raw(&client, event, &chats)),
+        );
+    }
+
+    /// Return the next `AdminLogEvent` from the internal buffer, filling the buffer previously
+    /// if it's empty.
+    ///
+    /// Returns `None` if the `limit` is reached or there are no events left.
+    pub async fn next(&mut self) -> Result<Option<AdminLogEvent>, InvocationError> {
+        if let Some(result) = self.next_raw() {
+            return result;
+        }
+
+        self.request.limit = self.determine_limit(MAX_ADMIN_LOG_LIMIT);
+        match self.client.invoke(&self.request).await {
+            Ok(results) => self.extend_buffer(results),
+            Err(e) => return self.fetch_failed(e),
+        }
+
+        Ok(self.pop_item())
+    }
+}
+
 fn updates_to_chat(id: Option<i64>, updates: tl::enums::Updates) -> Option<Chat> {
     use tl::enums::Updates;
 
@@ -360,6 +599,9 @@ impl Client {
     /// Resolves a username into the chat that owns it, if any.
     ///
     /// Note that this method is expensive to call, and can quickly cause long flood waits.
+    /// Because of this, chats found this way are remembered in the session for a day, unless
+    /// their usernames change, and resolving the same username again will instead fetch the
+    /// chat by its identifier, which is much cheaper.
     ///
     /// # Examples
     ///
@@ -372,6 +614,23 @@ impl Client {
     /// # }
     /// ```
     pub async fn resolve_username(&self, username: &str) -> Result<Option<Chat>, InvocationError> {
+        let session = &self.0.config.session;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i32)
+            .unwrap_or(0);
+        if let Some((packed, date)) = session.resolved_username(username) {
+            let fresh = now.saturating_sub(date) < RESOLVED_USERNAME_EXPIRY.as_secs() as i32;
+            if fresh && (packed.access_hash.is_some() || packed.ty == PackedType::Chat) {
+                match self.unpack_chat(packed).await {
+                    Ok(chat) => return Ok(Some(chat)),
+                    // The chat may no longer be accessible, so resolve the username again.
+                    Err(InvocationError::Rpc(_)) => session.forget_resolved_usernames(packed.id),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
         let tl::types::contacts::ResolvedPeer { peer, users, chats } = match self
             .invoke(&tl::functions::contacts::ResolveUsername {
                 username: username.into(),
@@ -389,7 +648,7 @@ impl Client {
             let _ = state.chat_hashes.extend(&users, &chats);
         }
 
-        Ok(match peer {
+        let chat = match peer {
             tl::enums::Peer::User(tl::types::PeerUser { user_id }) => users
                 .into_iter()
                 .map(Chat::from_user)
@@ -401,12 +660,85 @@ impl Client {
                 .into_iter()
                 .map(Chat::from_raw)
                 .find(|chat| chat.id() == chat_id),
+        };
+
+        if let Some(chat) = &chat {
+            session.set_resolved_username(username, chat.pack(), now);
+        }
+        Ok(chat)
+    }
+
+    /// Resolves a reference to a chat, such as a username or a link, as understood by
+    /// [`ChatReference::parse`].
+    ///
+    /// Usernames are resolved with [`Client::resolve_username`], and identifiers can only be
+    /// resolved if the client has previously seen the chat they belong to. Invite links to
+    /// private chats are only previewed, and will **not** cause the chat to be joined.
+    ///
+    /// Returns `None` if the reference does not point to any known chat, and fails with
+    /// `USERNAME_INVALID` if the input is not a reference to a chat at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::types::ResolvedChat;
+    ///
+    /// match client.resolve("https://t.me/username/123").await? {
+    ///     Some(ResolvedChat::Chat { chat, message_id }) => {
+    ///         println!("Found chat {:?} and message {:?}", chat.name(), message_id);
+    ///     }
+    ///     Some(ResolvedChat::Invite(invite)) => println!("Found private chat: {:?}", invite),
+    ///     None => println!("Nothing found"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn resolve(&self, reference: &str) -> Result<Option<ResolvedChat>, InvocationError> {
+        let reference = ChatReference::parse(reference).ok_or_else(|| {
+            InvocationError::Rpc(RpcError {
+                code: 400,
+                name: "USERNAME_INVALID".to_string(),
+                value: None,
+                caused_by: None,
+            })
+        })?;
+
+        Ok(match reference {
+            ChatReference::Username {
+                username,
+                message_id,
+            } => self
+                .resolve_username(&username)
+                .await?
+                .map(|chat| ResolvedChat::Chat { chat, message_id }),
+            ChatReference::InviteHash(hash) => {
+                match self
+                    .invoke(&tl::functions::messages::CheckChatInvite { hash })
+                    .await
+                {
+                    Ok(invite) => Some(ResolvedChat::Invite(invite)),
+                    Err(err) if err.is("INVITE_HASH_*") => None,
+                    Err(err) => return Err(err),
+                }
+            }
+            ChatReference::Id(id) => {
+                let packed_chat = self.0.state.read().unwrap().chat_hashes.get(id);
+                match packed_chat {
+                    Some(packed_chat) => Some(ResolvedChat::Chat {
+                        chat: self.unpack_chat(packed_chat).await?,
+                        message_id: None,
+                    }),
+                    None => None,
+                }
+            }
         })
     }
 
-    /// Fetch full information about the currently logged-in user.
+    /// Get information about the currently logged-in user.
     ///
-    /// Although this method is cheap to call, you might want to cache the results somewhere.
+    /// The user is known since signing in, so this only fetches it the first time it's needed
+    /// after connecting with a previous session. Use [`Client::refresh_me`] to fetch it again.
     ///
     /// # Examples
     ///
@@ -418,6 +750,28 @@ impl Client {
     /// # }
     /// ```
     pub async fn get_me(&self) -> Result<User, InvocationError> {
+        let cached = self.0.state.read().unwrap().self_user.clone();
+        match cached {
+            Some(user) => Ok(user),
+            None => self.refresh_me().await,
+        }
+    }
+
+    /// Fetch information about the currently logged-in user, even if it's already known.
+    ///
+    /// This is useful after changing the profile of the logged-in user, since [`Client::get_me`]
+    /// would otherwise return the information known before the change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let me = client.refresh_me().await?;
+    /// println!("Premium: {}", me.is_premium());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn refresh_me(&self) -> Result<User, InvocationError> {
         let mut res = self
             .invoke(&tl::functions::users::GetUsers {
                 id: vec![tl::enums::InputUser::UserSelf],
@@ -428,7 +782,9 @@ impl Client {
             panic!("fetching only one user should exactly return one user");
         }
 
-        Ok(User::from_raw(res.pop().unwrap()))
+        let user = User::from_raw(res.pop().unwrap());
+        self.0.state.write().unwrap().self_user = Some(user.clone());
+        Ok(user)
     }
 
     /// Iterate over the participants of a chat.
@@ -629,6 +985,136 @@ impl Client {
         ProfilePhotoIter::new(self, chat.into())
     }
 
+    /// Iterate over the administrator log of a group or channel, from most recent to oldest.
+    ///
+    /// Only administrators can access the log, which contains the actions performed in the
+    /// last 48 hours. Using this method with a chat that is not a channel or a megagroup will
+    /// fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::types::AdminLogAction;
+    ///
+    /// let mut events = client.iter_admin_log(&chat).bans();
+    ///
+    /// while let Some(event) = events.next().await? {
+    ///     if let AdminLogAction::ParticipantRestricted { after, .. } = event.action() {
+    ///         println!("{} restricted {}", event.actor_id(), after.user.id());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn iter_admin_log<C: Into<PackedChat>>(&self, chat: C) -> AdminLogIter {
+        AdminLogIter::new(self, chat.into())
+    }
+
+    /// Get all the chats whose access hash is known to the client, in no particular order.
+    ///
+    /// These are the chats the client has seen so far, which can be used without fetching them
+    /// from Telegram first, for example, to offer a list of chats to pick from while offline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn f(client: grammers_client::Client) {
+    /// for chat in client.cached_chats() {
+    ///     println!("Known chat: {} ({:?})", chat.id, chat.ty);
+    /// }
+    /// # }
+    /// ```
+    pub fn cached_chats(&self) -> Vec<PackedChat> {
+        self.0.state.read().unwrap().chat_hashes.iter().collect()
+    }
+
+    /// Look up the chat with the given identifier among the chats whose access hash is known to
+    /// the client.
+    ///
+    /// This never makes a request, so it can be used to refer to the chats seen in updates (such
+    /// as the sender of a message) without risking a flood wait. Returns `None` if the chat has
+    /// not been seen yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// if let Some(chat) = client.peer_for_id(12345) {
+    ///     client.send_message(chat, "Hi!").await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn peer_for_id(&self, id: i64) -> Option<PackedChat> {
+        self.0.state.read().unwrap().chat_hashes.get(id)
+    }
+
+    /// Fetch the complete version of a "min" chat, so that its access hash can be used.
+    ///
+    /// See [`Chat::is_min`] for what "min" chats are. Chats which are not "min" are returned
+    /// as-is. Otherwise, the access hash is looked up among the chats the client has seen, and
+    /// only if it's not found is the chat fetched from Telegram.
+    ///
+    /// Telegram can only resolve a "min" chat if it is told where it was seen. If the chat was
+    /// obtained from a message, such as its sender, said `message` should be provided.
+    ///
+    /// The returned chat may still be "min" if Telegram did not return its complete version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(message: grammers_client::types::Message, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// if let Some(sender) = message.sender() {
+    ///     let sender = client.unmin(&sender, Some(&message)).await?;
+    ///     if !sender.is_min() {
+    ///         client.send_message(&sender, "Hi!").await?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn unmin(
+        &self,
+        chat: &Chat,
+        message: Option<&Message>,
+    ) -> Result<Chat, InvocationError> {
+        let mut chat = chat.clone();
+        if !chat.is_min() {
+            return Ok(chat);
+        }
+
+        let cached = self.0.state.read().unwrap().chat_hashes.get(chat.id());
+        if let (Some(hash), Some((min, access_hash))) = (
+            cached.and_then(|packed| packed.access_hash),
+            chat.get_min_hash_ref(),
+        ) {
+            *access_hash = hash;
+            *min = false;
+            return Ok(chat);
+        }
+
+        let context = message.map(|m| (m.chat().pack().to_input_peer(), m.id()));
+        let (users, chats) = match unmin_request(&chat, context) {
+            Some(UnminRequest::User(request)) => (self.invoke(&request).await?, Vec::new()),
+            Some(UnminRequest::Channel(request)) => match self.invoke(&request).await? {
+                tl::enums::messages::Chats::Chats(chats) => (Vec::new(), chats.chats),
+                tl::enums::messages::Chats::Slice(chats) => (Vec::new(), chats.chats),
+            },
+            None => return Ok(chat),
+        };
+
+        let _ = self
+            .0
+            .state
+            .write()
+            .unwrap()
+            .chat_hashes
+            .extend(&users, &chats);
+
+        Ok(find_unmin(chat, users, chats))
+    }
+
     /// Convert a [`PackedChat`] back into a [`Chat`].
     ///
     /// # Example
@@ -693,6 +1179,159 @@ impl Client {
         })
     }
 
+    /// Set the profile photo of the logged-in user, which may also be a video.
+    ///
+    /// Whether the uploaded file is a video is determined by its file name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let uploaded = client.upload_file("photo.jpg").await?;
+    /// client.set_profile_photo(uploaded).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_profile_photo(&self, file: Uploaded) -> Result<Photo, InvocationError> {
+        let tl::enums::photos::Photo::Photo(photo) =
+            self.invoke(&upload_profile_photo_request(file)).await?;
+        Ok(Photo::from_raw(photo.photo))
+    }
+
+    /// Delete profile photos of the logged-in user, such as those returned by
+    /// [`Client::iter_profile_photos`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let me = client.get_me().await?;
+    /// if let Some(photo) = client.iter_profile_photos(&me).next().await? {
+    ///     client.delete_profile_photos(&[photo]).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn delete_profile_photos(&self, photos: &[Photo]) -> Result<(), InvocationError> {
+        let id = photos
+            .iter()
+            .map(|photo| photo.to_raw_input_media().id)
+            .collect();
+        self.invoke(&tl::functions::photos::DeletePhotos { id })
+            .await
+            .map(drop)
+    }
+
+    /// Set the photo of a group or channel, which may also be a video.
+    ///
+    /// Whether the uploaded file is a video is determined by its file name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let uploaded = client.upload_file("photo.jpg").await?;
+    /// client.set_chat_photo(&chat, uploaded).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_chat_photo<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+        file: Uploaded,
+    ) -> Result<(), InvocationError> {
+        let chat: PackedChat = chat.into();
+        let photo = input_chat_photo(file);
+        if let Some(channel) = chat.try_to_input_channel() {
+            self.invoke(&tl::functions::channels::EditPhoto { channel, photo })
+                .await
+                .map(drop)
+        } else if let Some(chat_id) = chat.try_to_chat_id() {
+            self.invoke(&tl::functions::messages::EditChatPhoto { chat_id, photo })
+                .await
+                .map(drop)
+        } else {
+            Err(InvocationError::Rpc(RpcError {
+                code: 400,
+                name: "PEER_ID_INVALID".to_string(),
+                value: None,
+                caused_by: None,
+            }))
+        }
+    }
+
+    /// Set after how long new messages in the chat are automatically deleted for everyone, or
+    /// `None` to stop deleting them.
+    ///
+    /// Fails with `TTL_PERIOD_INVALID`, without making a request, if the `period` is not between
+    /// one day and one year (inclusive).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    ///
+    /// // Delete messages after a week.
+    /// client.set_auto_delete(&chat, Some(Duration::from_secs(7 * 24 * 60 * 60))).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_auto_delete<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+        period: Option<Duration>,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&set_auto_delete_request(chat.into(), period)?)
+            .await
+            .map(drop)
+    }
+
+    /// Get after how long new messages in the chat are automatically deleted, if they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// if let Some(period) = client.get_auto_delete(&chat).await? {
+    ///     println!("Messages are deleted after {} seconds", period.as_secs());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_auto_delete<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+    ) -> Result<Option<Duration>, InvocationError> {
+        let chat: PackedChat = chat.into();
+        let full_chat = if let Some(id) = chat.try_to_input_user() {
+            let tl::enums::users::UserFull::Full(user) = self
+                .invoke(&tl::functions::users::GetFullUser { id })
+                .await?;
+            let tl::enums::UserFull::Full(user) = user.full_user;
+            return Ok(auto_delete_period(user.ttl_period));
+        } else if let Some(channel) = chat.try_to_input_channel() {
+            self.invoke(&tl::functions::channels::GetFullChannel { channel })
+                .await?
+        } else if let Some(chat_id) = chat.try_to_chat_id() {
+            self.invoke(&tl::functions::messages::GetFullChat { chat_id })
+                .await?
+        } else {
+            return Err(InvocationError::Rpc(RpcError {
+                code: 400,
+                name: "PEER_ID_INVALID".to_string(),
+                value: None,
+                caused_by: None,
+            }));
+        };
+
+        let tl::enums::messages::ChatFull::Full(full_chat) = full_chat;
+        Ok(auto_delete_period(match full_chat.full_chat {
+            tl::enums::ChatFull::Full(chat) => chat.ttl_period,
+            tl::enums::ChatFull::ChannelFull(channel) => channel.ttl_period,
+        }))
+    }
+
     /// Get permissions of participant `user` from chat `chat`.
     ///
     /// # Panics
@@ -766,7 +1405,7 @@ impl Client {
         let url_parse = url_parse_result.unwrap();
         let scheme = url_parse.scheme();
         let path = url_parse.path();
-        if url_parse.host_str().is_none() || !vec!["https", "http"].contains(&scheme) {
+        if url_parse.host_str().is_none() || !["https", "http"].contains(&scheme) {
             return None;
         }
         let host = url_parse.host_str().unwrap();
@@ -913,6 +1552,320 @@ impl Client {
     pub fn action<C: Into<PackedChat>>(&self, chat: C) -> crate::types::ActionSender {
         crate::types::ActionSender::new(self, chat)
     }
+
+    /// Block a user (or other chat), preventing them from sending you messages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(user: grammers_client::types::User, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.block(&user).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn block<C: Into<PackedChat>>(&self, chat: C) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::contacts::Block {
+            my_stories_from: false,
+            id: chat.into().to_input_peer(),
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Unblock a user (or other chat) that was previously blocked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(user: grammers_client::types::User, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.unblock(&user).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn unblock<C: Into<PackedChat>>(&self, chat: C) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::contacts::Unblock {
+            my_stories_from: false,
+            id: chat.into().to_input_peer(),
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Get the peers that messages can be sent as in a group or channel.
+    ///
+    /// Any of these peers can be used with [`InputMessage::send_as`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMessage;
+    ///
+    /// let peers = client.get_send_as_peers(&chat).await?;
+    /// if let Some(peer) = peers.into_iter().find(|peer| !peer.premium_required) {
+    ///     client
+    ///         .send_message(&chat, InputMessage::text("Hello!").send_as(&peer.chat))
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`InputMessage::send_as`]: crate::InputMessage::send_as
+    pub async fn get_send_as_peers<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+    ) -> Result<Vec<SendAsPeer>, InvocationError> {
+        self.invoke(&tl::functions::channels::GetSendAs {
+            peer: chat.into().to_input_peer(),
+        })
+        .await
+        .map(SendAsPeer::from_raw_list)
+    }
+
+    /// Add a phone number or a known user to your contacts.
+    ///
+    /// Phone numbers are imported, and may not belong to any Telegram user, in which case
+    /// [`ContactImport::NotFound`] is returned. When importing many contacts in a short period
+    /// of time, Telegram may refuse to import some of them, returning
+    /// [`ContactImport::RetryLater`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::client::chats::ContactImport;
+    ///
+    /// match client.add_contact("+1234567890", "John", "Doe").await? {
+    ///     ContactImport::Imported(user) => println!("Added {}", user.full_name()),
+    ///     ContactImport::NotFound => println!("Nobody uses that phone in Telegram"),
+    ///     ContactImport::RetryLater => println!("Too many imports, try again later"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_contact<C: Into<NewContact>>(
+        &self,
+        contact: C,
+        first_name: &str,
+        last_name: &str,
+    ) -> Result<ContactImport, InvocationError> {
+        match add_contact_request(contact.into(), first_name, last_name) {
+            ContactRequest::Add(request) => {
+                let user_id = match &request.id {
+                    tl::enums::InputUser::User(user) => user.user_id,
+                    _ => 0,
+                };
+                let users = match self.invoke(&request).await? {
+                    tl::enums::Updates::Combined(updates) => updates.users,
+                    tl::enums::Updates::Updates(updates) => updates.users,
+                    _ => Vec::new(),
+                };
+                Ok(self.find_imported_user(users, user_id))
+            }
+            ContactRequest::Import(request) => {
+                let client_id = match &request.contacts[0] {
+                    tl::enums::InputContact::InputPhoneContact(contact) => contact.client_id,
+                };
+                let tl::enums::contacts::ImportedContacts::Contacts(imported) =
+                    self.invoke(&request).await?;
+
+                if imported.retry_contacts.contains(&client_id) {
+                    return Ok(ContactImport::RetryLater);
+                }
+                let user_id = imported.imported.iter().find_map(|contact| match contact {
+                    tl::enums::ImportedContact::Contact(c) if c.client_id == client_id => {
+                        Some(c.user_id)
+                    }
+                    _ => None,
+                });
+                Ok(match user_id {
+                    Some(user_id) => self.find_imported_user(imported.users, user_id),
+                    None => ContactImport::NotFound,
+                })
+            }
+        }
+    }
+
+    /// Remove a user from your contacts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(user: grammers_client::types::User, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.delete_contact(&user).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn delete_contact<C: Into<PackedChat>>(
+        &self,
+        user: C,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::contacts::DeleteContacts {
+            id: vec![user.into().to_input_user_lossy()],
+        })
+        .await
+        .map(drop)
+    }
+
+    /// Cache the users returned after adding a contact and find the one with the given ID.
+    fn find_imported_user(&self, users: Vec<tl::enums::User>, user_id: i64) -> ContactImport {
+        let _ = self
+            .0
+            .state
+            .write()
+            .unwrap()
+            .chat_hashes
+            .extend(&users, &[]);
+
+        users
+            .into_iter()
+            .find(|user| user.id() == user_id)
+            .map(|user| ContactImport::Imported(User::from_raw(user)))
+            .unwrap_or(ContactImport::NotFound)
+    }
+}
+
+/// Who should be added as a contact by [`Client::add_contact`].
+#[derive(Clone, Debug)]
+pub enum NewContact {
+    /// A phone number, which may or may not belong to a Telegram user.
+    Phone(String),
+    /// A user which is already known (for example, because they sent you a message).
+    User(PackedChat),
+}
+
+impl From<&str> for NewContact {
+    fn from(phone: &str) -> Self {
+        Self::Phone(phone.to_string())
+    }
+}
+
+impl From<String> for NewContact {
+    fn from(phone: String) -> Self {
+        Self::Phone(phone)
+    }
+}
+
+impl From<PackedChat> for NewContact {
+    fn from(user: PackedChat) -> Self {
+        Self::User(user)
+    }
+}
+
+impl From<&User> for NewContact {
+    fn from(user: &User) -> Self {
+        Self::User(user.pack())
+    }
+}
+
+/// The outcome of adding a contact with [`Client::add_contact`].
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ContactImport {
+    /// The contact was added, and belongs to this user.
+    Imported(User),
+    /// The phone number does not belong to any Telegram user.
+    NotFound,
+    /// Telegram refused to import the contact for now, and it should be retried later.
+    RetryLater,
+}
+
+enum ContactRequest {
+    Add(tl::functions::contacts::AddContact),
+    Import(tl::functions::contacts::ImportContacts),
+}
+
+/// Known users can be added directly, but phone numbers need to be imported.
+fn add_contact_request(contact: NewContact, first_name: &str, last_name: &str) -> ContactRequest {
+    match contact {
+        NewContact::User(user) => ContactRequest::Add(tl::functions::contacts::AddContact {
+            add_phone_privacy_exception: false,
+            id: user.to_input_user_lossy(),
+            first_name: first_name.to_string(),
+            last_name: last_name.to_string(),
+            phone: String::new(),
+        }),
+        NewContact::Phone(phone) => {
+            ContactRequest::Import(tl::functions::contacts::ImportContacts {
+                contacts: vec![tl::types::InputPhoneContact {
+                    client_id: generate_random_id(),
+                    phone,
+                    first_name: first_name.to_string(),
+                    last_name: last_name.to_string(),
+                }
+                .into()],
+            })
+        }
+    }
+}
+
+enum UnminRequest {
+    User(tl::functions::users::GetUsers),
+    Channel(tl::functions::channels::GetChannels),
+}
+
+/// Build the request to fetch a "min" chat, optionally seen in the message with the given peer
+/// and identifier.
+fn unmin_request(
+    chat: &Chat,
+    context: Option<(tl::enums::InputPeer, i32)>,
+) -> Option<UnminRequest> {
+    let (id, access_hash) = match chat {
+        Chat::User(user) => (user.raw.id, user.raw.access_hash),
+        Chat::Group(group) => match &group.raw {
+            tl::enums::Chat::Channel(channel) => (channel.id, channel.access_hash),
+            _ => return None,
+        },
+        Chat::Channel(channel) => (channel.raw.id, channel.raw.access_hash),
+    };
+    let access_hash = access_hash.unwrap_or(0);
+
+    Some(match chat {
+        Chat::User(_) => UnminRequest::User(tl::functions::users::GetUsers {
+            id: vec![match context {
+                Some((peer, msg_id)) => tl::types::InputUserFromMessage {
+                    peer,
+                    msg_id,
+                    user_id: id,
+                }
+                .into(),
+                None => tl::types::InputUser {
+                    user_id: id,
+                    access_hash,
+                }
+                .into(),
+            }],
+        }),
+        Chat::Group(_) | Chat::Channel(_) => {
+            UnminRequest::Channel(tl::functions::channels::GetChannels {
+                id: vec![match context {
+                    Some((peer, msg_id)) => tl::types::InputChannelFromMessage {
+                        peer,
+                        msg_id,
+                        channel_id: id,
+                    }
+                    .into(),
+                    None => tl::types::InputChannel {
+                        channel_id: id,
+                        access_hash,
+                    }
+                    .into(),
+                }],
+            })
+        }
+    })
+}
+
+/// Find the complete version of the "min" `chat` in the response, or return it unchanged.
+fn find_unmin(chat: Chat, users: Vec<tl::enums::User>, chats: Vec<tl::enums::Chat>) -> Chat {
+    let id = chat.id();
+    users
+        .into_iter()
+        .map(Chat::from_user)
+        .chain(chats.into_iter().map(Chat::from_raw))
+        .find(|resolved| resolved.id() == id && !resolved.is_min())
+        .unwrap_or(chat)
 }
 
 #[derive(Debug, Clone)]
@@ -980,3 +1933,412 @@ impl ParticipantPermissions {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn ensure_resolved_usernames_are_cached() {
+        use grammers_mtsender::mock::MockSender;
+        use tl::Identifiable;
+
+        let chat = || tl::enums::Chat::from(tl::types::ChatEmpty { id: 123 });
+        let resolved = || {
+            tl::enums::contacts::ResolvedPeer::from(tl::types::contacts::ResolvedPeer {
+                peer: tl::types::PeerChat { chat_id: 123 }.into(),
+                chats: vec![chat()],
+                users: Vec::new(),
+            })
+        };
+        let mut sender = MockSender::new();
+        for _ in 0..3 {
+            sender
+                .expect::<tl::functions::contacts::ResolveUsername>()
+                .respond(resolved());
+        }
+        sender
+            .expect::<tl::functions::messages::GetChats>()
+            .respond(
+                tl::types::messages::Chats {
+                    chats: vec![chat()],
+                }
+                .into(),
+            );
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            let resolve_count = || async {
+                let mock = mock.lock().await;
+                let resolve_id = tl::functions::contacts::ResolveUsername::CONSTRUCTOR_ID;
+                mock.invoked()
+                    .iter()
+                    .filter(|body| body[..4] == resolve_id.to_le_bytes())
+                    .count()
+            };
+
+            match client.resolve("https://t.me/UserName/7").await {
+                Ok(Some(ResolvedChat::Chat { chat, message_id })) => {
+                    assert_eq!(chat.id(), 123);
+                    assert_eq!(message_id, Some(7));
+                }
+                _ => panic!("username was not resolved"),
+            }
+            let session = &client.0.config.session;
+            assert!(session.resolved_username("username").is_some());
+
+            // The chat is fetched by its identifier rather than resolving the username again.
+            let chat = client.resolve_username("username").await.unwrap().unwrap();
+            assert_eq!(chat.id(), 123);
+            assert_eq!(resolve_count().await, 1);
+
+            // Usernames are forgotten when they change.
+            client.extend_update_queue(
+                vec![tl::types::UpdateChannel { channel_id: 123 }.into()],
+                ChatMap::empty(),
+            );
+            assert!(session.resolved_username("username").is_none());
+            client.resolve_username("username").await.unwrap().unwrap();
+            assert_eq!(resolve_count().await, 2);
+
+            // And after a while, in case they changed hands.
+            let (packed, _) = session.resolved_username("username").unwrap();
+            session.set_resolved_username("username", packed, 0);
+            client.resolve_username("username").await.unwrap().unwrap();
+            assert_eq!(resolve_count().await, 3);
+            assert_eq!(mock.lock().await.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_peer_for_id_uses_cache() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let mut user = User::from_raw(tl::types::UserEmpty { id: 10 }.into()).raw;
+            user.access_hash = Some(1234);
+            let _ = client
+                .0
+                .state
+                .write()
+                .unwrap()
+                .chat_hashes
+                .extend(&[user.into()], &[]);
+
+            // Nobody is serving the connection, so the chat can only come from the cache.
+            let chat = client.peer_for_id(10).unwrap();
+            assert!(chat.is_user());
+            assert_eq!(chat.access_hash, Some(1234));
+            assert_eq!(client.peer_for_id(20), None);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_self_user_is_cached() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let mut user = User::from_raw(tl::types::UserEmpty { id: 10 }.into()).raw;
+            user.is_self = true;
+            user.premium = true;
+            client.0.state.write().unwrap().self_user = Some(User::from_raw(user.into()));
+
+            // Nobody is serving the connection, so the user can only come from the cache.
+            let me = client.get_me().await.unwrap();
+            assert_eq!(me.id(), 10);
+            assert!(me.is_premium());
+
+            // Refreshing always fetches the user, which fails, but leaves the cache alone.
+            assert!(client.refresh_me().await.is_err());
+            assert_eq!(client.get_me().await.unwrap().id(), 10);
+
+            client.0.state.write().unwrap().self_user = None;
+            assert!(client.get_me().await.is_err());
+        });
+    }
+
+    fn uploaded(name: &str) -> Uploaded {
+        Uploaded::from_raw(
+            tl::types::InputFile {
+                id: 1,
+                parts: 1,
+                name: name.to_string(),
+                md5_checksum: String::new(),
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn check_profile_photo_request() {
+        let request = upload_profile_photo_request(uploaded("photo.jpg"));
+        assert_eq!(request.file, Some(uploaded("photo.jpg").raw));
+        assert_eq!(request.video, None);
+        assert_eq!(request.video_start_ts, None);
+
+        let request = upload_profile_photo_request(uploaded("video.mp4"));
+        assert_eq!(request.file, None);
+        assert_eq!(request.video, Some(uploaded("video.mp4").raw));
+        assert_eq!(request.video_start_ts, Some(0.0));
+    }
+
+    #[test]
+    fn check_auto_delete_request() {
+        let chat = PackedChat {
+            ty: PackedType::Chat,
+            id: 123,
+            access_hash: None,
+        };
+
+        let request = set_auto_delete_request(chat, Some(Duration::from_secs(86400))).unwrap();
+        assert_eq!(request.peer, chat.to_input_peer());
+        assert_eq!(request.period, 86400);
+
+        let request = set_auto_delete_request(chat, None).unwrap();
+        assert_eq!(request.period, 0);
+
+        assert_eq!(
+            auto_delete_period(Some(604800)),
+            Some(Duration::from_secs(604800))
+        );
+        assert_eq!(auto_delete_period(Some(0)), None);
+        assert_eq!(auto_delete_period(None), None);
+    }
+
+    #[test]
+    fn check_auto_delete_rejects_invalid_periods() {
+        let chat = PackedChat {
+            ty: PackedType::Chat,
+            id: 123,
+            access_hash: None,
+        };
+        for secs in [60, 400 * 86400] {
+            assert!(
+                set_auto_delete_request(chat, Some(Duration::from_secs(secs)))
+                    .is_err_and(|e| e.is("TTL_PERIOD_INVALID"))
+            );
+        }
+    }
+
+    #[test]
+    fn check_chat_photo_input() {
+        match input_chat_photo(uploaded("video.mp4")) {
+            tl::enums::InputChatPhoto::InputChatUploadedPhoto(photo) => {
+                assert_eq!(photo.file, None);
+                assert_eq!(photo.video, Some(uploaded("video.mp4").raw));
+            }
+            _ => panic!("unexpected chat photo"),
+        }
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_min_users_are_resolved() {
+        use grammers_mtsender::mock::MockSender;
+        use grammers_tl_types::Deserializable;
+
+        let user = |min, access_hash| {
+            let mut user = User::from_raw(tl::types::UserEmpty { id: 10 }.into());
+            user.raw.min = min;
+            user.raw.access_hash = Some(access_hash);
+            user
+        };
+        let chat = Chat::User(user(true, 20));
+        assert!(chat.is_min());
+
+        let peer = tl::enums::InputPeer::Chat(tl::types::InputPeerChat { chat_id: 1 });
+        let request = match unmin_request(&chat, Some((peer.clone(), 7))) {
+            Some(UnminRequest::User(request)) => request,
+            _ => panic!("min user should be fetched with users.getUsers"),
+        };
+
+        let mut sender = MockSender::new();
+        sender
+            .expect_if(move |r: &tl::functions::users::GetUsers| {
+                r.id == vec![tl::types::InputUserFromMessage {
+                    peer: peer.clone(),
+                    msg_id: 7,
+                    user_id: 10,
+                }
+                .into()]
+            })
+            .respond(vec![user(false, 30).raw.into()]);
+
+        let users = block_on(sender.invoke(&request)).unwrap();
+        let users = Vec::<tl::enums::User>::from_bytes(&users).unwrap();
+
+        let resolved = find_unmin(chat, users, Vec::new());
+        assert!(!resolved.is_min());
+        assert_eq!(resolved.pack().access_hash, Some(30));
+    }
+
+    #[test]
+    fn check_contact_requests() {
+        let user = PackedChat {
+            ty: PackedType::User,
+            id: 10,
+            access_hash: Some(20),
+        };
+        match add_contact_request(user.into(), "John", "Doe") {
+            ContactRequest::Add(request) => {
+                assert_eq!(request.id, user.to_input_user_lossy());
+                assert_eq!(request.first_name, "John");
+                assert_eq!(request.last_name, "Doe");
+            }
+            ContactRequest::Import(_) => panic!("known user should not be imported"),
+        }
+
+        match add_contact_request("+1234".into(), "John", "Doe") {
+            ContactRequest::Import(request) => {
+                assert_eq!(request.contacts.len(), 1);
+                let tl::enums::InputContact::InputPhoneContact(contact) = &request.contacts[0];
+                assert_eq!(contact.phone, "+1234");
+                assert_eq!(contact.first_name, "John");
+            }
+            ContactRequest::Add(_) => panic!("phone should be imported"),
+        }
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_admin_log_pages_and_maps_bans() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let channel = PackedChat {
+                ty: PackedType::Megagroup,
+                id: 1,
+                access_hash: Some(2),
+            };
+            let mut iter = client.iter_admin_log(channel).bans();
+            match &iter.request.events_filter {
+                Some(tl::enums::ChannelAdminLogEventsFilter::Filter(filter)) => {
+                    assert!(filter.ban && filter.unban && filter.kick && filter.unkick);
+                    assert!(!filter.join && !filter.edit);
+                }
+                None => panic!("bans should be filtered"),
+            }
+
+            let banned_rights = tl::types::ChatBannedRights {
+                view_messages: true,
+                send_messages: true,
+                send_media: true,
+                send_stickers: true,
+                send_gifs: true,
+                send_games: true,
+                send_inline: true,
+                embed_links: true,
+                send_polls: true,
+                change_info: true,
+                invite_users: true,
+                pin_messages: true,
+                manage_topics: true,
+                send_photos: true,
+                send_videos: true,
+                send_roundvideos: true,
+                send_audios: true,
+                send_voices: true,
+                send_docs: true,
+                send_plain: true,
+                until_date: 0,
+            };
+            let ban = tl::types::ChannelAdminLogEvent {
+                id: 50,
+                date: 1000,
+                user_id: 10,
+                action: tl::types::ChannelAdminLogEventActionParticipantToggleBan {
+                    prev_participant: tl::types::ChannelParticipant {
+                        user_id: 20,
+                        date: 0,
+                        subscription_until_date: None,
+                    }
+                    .into(),
+                    new_participant: tl::types::ChannelParticipantBanned {
+                        left: true,
+                        peer: tl::types::PeerUser { user_id: 20 }.into(),
+                        kicked_by: 10,
+                        date: 1000,
+                        banned_rights: banned_rights.clone().into(),
+                    }
+                    .into(),
+                }
+                .into(),
+            };
+            let channel_ban = tl::types::ChannelAdminLogEvent {
+                id: 45,
+                date: 950,
+                user_id: 10,
+                action: tl::types::ChannelAdminLogEventActionParticipantToggleBan {
+                    prev_participant: tl::types::ChannelParticipantLeft {
+                        peer: tl::types::PeerChannel { channel_id: 30 }.into(),
+                    }
+                    .into(),
+                    new_participant: tl::types::ChannelParticipantBanned {
+                        left: true,
+                        peer: tl::types::PeerChannel { channel_id: 30 }.into(),
+                        kicked_by: 10,
+                        date: 950,
+                        banned_rights: banned_rights.into(),
+                    }
+                    .into(),
+                }
+                .into(),
+            };
+            let join = tl::types::ChannelAdminLogEvent {
+                id: 40,
+                date: 900,
+                user_id: 20,
+                action: tl::enums::ChannelAdminLogEventAction::ParticipantJoin,
+            };
+
+            iter.request.limit = 3;
+            iter.extend_buffer(
+                tl::types::channels::AdminLogResults {
+                    events: vec![ban.into(), channel_ban.into(), join.into()],
+                    chats: Vec::new(),
+                    users: vec![
+                        tl::types::UserEmpty { id: 10 }.into(),
+                        tl::types::UserEmpty { id: 20 }.into(),
+                    ],
+                }
+                .into(),
+            );
+            assert!(!iter.last_chunk);
+            assert_eq!(iter.request.max_id, 40);
+
+            let event = iter.next().await.unwrap().unwrap();
+            assert_eq!(event.id(), 50);
+            assert_eq!(event.actor().map(|user| user.id()), Some(10));
+            match event.action() {
+                crate::types::AdminLogAction::ParticipantRestricted { before, after } => {
+                    assert_eq!(before.user.id(), 20);
+                    assert_eq!(after.user.id(), 20);
+                    match &after.role {
+                        crate::types::Role::Banned(banned) => {
+                            assert_eq!(banned.kicked_by(), 10);
+                            assert!(banned.restrictions().view_messages());
+                        }
+                        role => panic!("unexpected role: {role:?}"),
+                    }
+                }
+                action => panic!("unexpected action: {action:?}"),
+            }
+
+            // Channels are not users, so they can't be represented as a participant.
+            let event = iter.next().await.unwrap().unwrap();
+            assert_eq!(event.id(), 45);
+            assert!(matches!(
+                event.action(),
+                crate::types::AdminLogAction::Other(_)
+            ));
+
+            let event = iter.next().await.unwrap().unwrap();
+            assert_eq!(event.id(), 40);
+            assert!(matches!(
+                event.action(),
+                crate::types::AdminLogAction::Joined
+            ));
+        });
+    }
+}
@@ -6,15 +6,18 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use grammers_mtproto::mtp;
-use grammers_mtsender::{self as sender, ReconnectionPolicy, Sender, ServerAddr};
+use grammers_mtsender::{
+    self as sender, ConnectionParams, ConnectionState, ReconnectionPolicy, Sender, ServerAddr,
+};
 use grammers_session::{ChatHashCache, MessageBox, Session};
 use grammers_tl_types as tl;
 use sender::Enqueuer;
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::sync::atomic::AtomicU32;
-use std::sync::{Arc, RwLock};
-use tokio::sync::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{watch, Mutex as AsyncMutex, OnceCell};
 use web_time::Instant;
 
 use super::net;
@@ -33,6 +36,10 @@ pub struct Config {
     /// Developer's API ID, required to interact with the Telegram's API.
     ///
     /// You may obtain your own in <https://my.telegram.org/auth>.
+    ///
+    /// Every connection made by the client, including those to other datacenters, uses this
+    /// API ID. Processes hosting several applications can create one client per application,
+    /// each with its own API ID and hash.
     pub api_id: i32,
 
     /// Developer's API hash, required to interact with Telegram's API.
@@ -48,11 +55,23 @@ pub struct Config {
 /// API.
 #[derive(Clone)]
 pub struct InitParams {
+    /// Model of the device the client runs on, shown in the list of active sessions.
+    ///
+    /// By default, the operating system type and bitness are used.
     pub device_model: String,
+    /// Version of the operating system the client runs on.
     pub system_version: String,
+    /// Version of the application, shown in the list of active sessions.
+    ///
+    /// By default, the version of this library is used.
     pub app_version: String,
+    /// Code of the language used by the system, such as `"en"`.
     pub system_lang_code: String,
+    /// Code of the language the application is using, such as `"en"`.
     pub lang_code: String,
+    /// Name of the [language pack](https://core.telegram.org/api/localization) the application
+    /// uses, which is empty for applications without an official language pack.
+    pub lang_pack: String,
     /// Should the client catch-up on updates sent to it while it was offline?
     ///
     /// By default, updates sent while the client was offline are ignored.
@@ -75,6 +94,16 @@ pub struct InitParams {
     /// On flood, the library will retry *once*. If the flood error occurs a second time after
     /// sleeping, the error will be returned.
     pub flood_sleep_threshold: u32,
+    /// How many times a request should be automatically retried when it fails due to a
+    /// transient server error, such as an internal error or a timeout.
+    ///
+    /// See [`InvocationError::is_transient`] for which errors are considered transient. The
+    /// library will wait for a second before each retry.
+    ///
+    /// By default, requests are not retried, and the error is returned instead.
+    ///
+    /// [`InvocationError::is_transient`]: grammers_mtsender::InvocationError::is_transient
+    pub transient_error_retries: u32,
     /// How many updates may be buffered by the client at any given time.
     ///
     /// Telegram passively sends updates to the client through the open connection, so they must
@@ -95,6 +124,41 @@ pub struct InitParams {
     ///
     /// When the limit is `Some`, a buffer to hold that many updates will be pre-allocated.
     pub update_queue_limit: Option<usize>,
+    /// How long the client may go without receiving updates from a chat before fetching them
+    /// manually.
+    ///
+    /// Shorter values mean the client will catch up sooner if updates are lost, at the cost of
+    /// more requests, which may be desirable for bots but not for battery-sensitive clients.
+    ///
+    /// By default, the value recommended by Telegram's documentation is used (15 minutes).
+    pub no_updates_timeout: Duration,
+    /// How long the client should wait for a gap in the updates to be resolved on its own before
+    /// fetching the missing updates manually.
+    ///
+    /// By default, half a second is used.
+    pub possible_gap_timeout: Duration,
+    /// How long to wait for the rest of the messages of an album before returning them all at
+    /// once as an [`Update::Album`](crate::Update::Album).
+    ///
+    /// Telegram sends each message of an album as a separate update, so they are buffered by
+    /// [`Client::next_update`] until this long has passed without receiving another one of them,
+    /// or until an unrelated update arrives.
+    ///
+    /// By default, this is `None`, and every message of an album is returned on its own.
+    pub album_timeout: Option<Duration>,
+    /// How long [`Client::catch_up`] may keep fetching the updates missed while offline before
+    /// giving up, so that it can't loop forever if the server keeps reporting more updates.
+    ///
+    /// By default, one minute is used.
+    pub catch_up_timeout: Duration,
+    /// Whether the logged-in user should be marked as offline right after sending a message with
+    /// [`Client::send_message`] or [`Client::send_album`].
+    ///
+    /// Sending messages makes the user appear online, which may not be desirable for accounts
+    /// that are automated. See also [`Client::set_online`].
+    ///
+    /// By default, this is `false`, and the user appears online as usual.
+    pub offline_after_send: bool,
     /// URL of the proxy to use. Requires the `proxy` feature to be enabled.
     ///
     /// The scheme must be `socks5`. Username and password are optional.
@@ -107,7 +171,7 @@ pub struct InitParams {
 
     /// specify the reconnection policy which will be used by client to determine whether to re-connect on failure or not.
     ///
-    ///it can be one of the 2 default implementation [`NoReconnect`] and [`FixedReconnect`];
+    ///it can be one of the default implementations [`NoReconnect`], [`FixedReconnect`] and [`ExponentialBackoff`];
     ///
     /// **OR** your own custom implementation of trait [`ReconnectionPolicy`].
     ///
@@ -115,8 +179,23 @@ pub struct InitParams {
     ///
     /// [`NoReconnect`]: grammers_mtsender::NoReconnect
     /// [`FixedReconnect`]: grammers_mtsender::FixedReconnect
+    /// [`ExponentialBackoff`]: grammers_mtsender::ExponentialBackoff
     /// [`ReconnectionPolicy`]: grammers_mtsender::ReconnectionPolicy
     pub reconnection_policy: &'static dyn ReconnectionPolicy,
+    /// Parameters used by every connection the client makes, including those to other
+    /// datacenters, such as how many requests may be in flight at once.
+    ///
+    /// The [`ConnectionParams::resolver`] is used to look up the host of the `proxy_url`
+    /// (if any), both when first connecting and when reconnecting.
+    ///
+    /// When [`ConnectionParams::max_in_flight`] is reached and requests should not wait for
+    /// capacity, they fail with [`InvocationError::WouldBlock`].
+    ///
+    /// By default, there is no limit.
+    ///
+    /// [`ConnectionParams::max_in_flight`]: grammers_mtsender::ConnectionParams::max_in_flight
+    /// [`InvocationError::WouldBlock`]: grammers_mtsender::InvocationError::WouldBlock
+    pub connection_params: ConnectionParams,
 }
 
 pub(crate) struct ClientInner {
@@ -125,8 +204,9 @@ pub(crate) struct ClientInner {
     pub(crate) config: Config,
     pub(crate) conn: Connection,
     pub(crate) state: RwLock<ClientState>,
-    // Stores per-datacenter downloader instances
-    pub(crate) downloader_map: AsyncRwLock<HashMap<i32, Arc<Connection>>>,
+    // Stores per-datacenter downloader instances. Each is only connected once, even when many
+    // tasks need it at the same time, without blocking the connections to other datacenters.
+    pub(crate) downloader_map: Mutex<HashMap<i32, Arc<OnceCell<Arc<Connection>>>>>,
 }
 
 pub(crate) struct ClientState {
@@ -137,11 +217,18 @@ pub(crate) struct ClientState {
     // This is used to avoid spamming the log.
     pub(crate) last_update_limit_warn: Option<Instant>,
     pub(crate) updates: VecDeque<(tl::enums::Update, Arc<crate::types::ChatMap>)>,
+    // Messages of an album waiting for the rest to arrive before being returned.
+    pub(crate) album: Option<super::updates::PendingAlbum>,
+    // The logged-in user, known since signing in, to avoid fetching it again.
+    pub(crate) self_user: Option<crate::types::User>,
+    // Whether the message box was getting difference when the user was last told about it.
+    pub(crate) catching_up: bool,
 }
 
 pub(crate) struct Connection {
     pub(crate) sender: AsyncMutex<Sender<net::Transport, mtp::Encrypted>>,
     pub(crate) request_tx: RwLock<Enqueuer>,
+    pub(crate) state: RwLock<watch::Receiver<ConnectionState>>,
     pub(crate) step_counter: AtomicU32,
 }
 
@@ -184,13 +271,21 @@ impl Default for InitParams {
             app_version: env!("CARGO_PKG_VERSION").to_string(),
             system_lang_code,
             lang_code,
+            lang_pack: String::new(),
             catch_up: false,
             server_addr: None,
             flood_sleep_threshold: 60,
+            transient_error_retries: 0,
             update_queue_limit: Some(100),
+            no_updates_timeout: MessageBox::DEFAULT_NO_UPDATES_TIMEOUT,
+            possible_gap_timeout: MessageBox::DEFAULT_POSSIBLE_GAP_TIMEOUT,
+            album_timeout: None,
+            catch_up_timeout: Duration::from_secs(60),
+            offline_after_send: false,
             #[cfg(feature = "proxy")]
             proxy_url: None,
             reconnection_policy: &grammers_mtsender::NoReconnect,
+            connection_params: ConnectionParams::default(),
         }
     }
 }
@@ -5,7 +5,7 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use crate::types::{ChatMap, Dialog, IterBuffer, Message};
+use crate::types::{ChatMap, Dialog, Folder, IterBuffer, Message};
 use crate::Client;
 use grammers_mtsender::InvocationError;
 use grammers_session::PackedChat;
@@ -14,8 +14,22 @@ use std::collections::HashMap;
 
 const MAX_LIMIT: usize = 100;
 
+/// The identifier of the folder where archived dialogs are kept.
+pub const ARCHIVE_FOLDER_ID: i32 = 1;
+
 pub type DialogIter = IterBuffer<tl::functions::messages::GetDialogs, Dialog>;
 
+/// The position of a dialog within the list of dialogs, used to resume a [`DialogIter`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogOffset {
+    /// Date of the last message in the dialog.
+    pub date: i32,
+    /// Identifier of the last message in the dialog.
+    pub id: i32,
+    /// The chat of the dialog.
+    pub peer: tl::enums::InputPeer,
+}
+
 impl DialogIter {
     fn new(client: &Client) -> Self {
         // TODO let users tweak all the options from the request
@@ -34,6 +48,61 @@ impl DialogIter {
         )
     }
 
+    /// Only return dialogs inside the given folder.
+    ///
+    /// The main folder has identifier `0`, and the archive has [`ARCHIVE_FOLDER_ID`].
+    pub fn folder_id(mut self, folder_id: i32) -> Self {
+        self.request.folder_id = Some(folder_id);
+        self
+    }
+
+    /// Only return dialogs which belong to the given chat folder.
+    ///
+    /// Unlike [`DialogIter::folder_id`], chat folders are not known by the server when listing
+    /// dialogs, so all dialogs are still fetched, but those not matching the folder's rules
+    /// (see [`Folder::contains`]) are skipped. Archived dialogs are only fetched if this is
+    /// combined with [`DialogIter::folder_id`].
+    pub fn folder(mut self, folder: Folder) -> Self {
+        self.filter = Some(Box::new(move |dialog| folder.contains(dialog)));
+        self
+    }
+
+    /// Continue the iteration right after the dialog at the given offset.
+    ///
+    /// The offset is usually obtained from [`DialogIter::last_offset`] on a previous iterator.
+    /// Pinned dialogs are not returned again when resuming.
+    pub fn offset(mut self, offset: DialogOffset) -> Self {
+        self.request.exclude_pinned = true;
+        self.request.offset_date = offset.date;
+        self.request.offset_id = offset.id;
+        self.request.offset_peer = offset.peer;
+        self
+    }
+
+    /// The offset of the last dialog returned by the iterator.
+    ///
+    /// If no dialog has been returned yet, this is the offset the iterator started with.
+    /// Passing it to [`DialogIter::offset`] on a new iterator will resume the iteration right
+    /// after the last dialog that was returned, for example, after it stopped early because of
+    /// an error.
+    pub fn last_offset(&self) -> DialogOffset {
+        DialogOffset {
+            date: self.request.offset_date,
+            id: self.request.offset_id,
+            peer: self.request.offset_peer.clone(),
+        }
+    }
+
+    /// Point the offsets to the given dialog, so that the next request continues after it.
+    fn set_offset(&mut self, dialog: &Dialog) {
+        self.request.exclude_pinned = true;
+        if let Some(last_message) = &dialog.last_message {
+            self.request.offset_date = last_message.raw.date;
+            self.request.offset_id = last_message.raw.id;
+        }
+        self.request.offset_peer = dialog.chat().pack().to_input_peer();
+    }
+
     /// Determines how many dialogs there are in total.
     ///
     /// This only performs a network call if `next` has not been called before.
@@ -59,10 +128,32 @@ impl DialogIter {
     ///
     /// Returns `None` if the `limit` is reached or there are no dialogs left.
     pub async fn next(&mut self) -> Result<Option<Dialog>, InvocationError> {
-        if let Some(result) = self.next_raw() {
-            return result;
+        loop {
+            let dialog = match self.next_raw() {
+                Some(result) => result?,
+                None => match self.fill_buffer().await {
+                    Ok(()) => self.pop_item(),
+                    Err(e) => return self.fetch_failed(e),
+                },
+            };
+
+            // The offsets always point to the last dialog returned, so that the next request
+            // continues right after it, and so that the iteration can be resumed from there.
+            // Dialogs skipped by the filter are accounted for too, or they would be fetched again.
+            if let Some(dialog) = &dialog {
+                self.set_offset(dialog);
+                if self.filter.as_ref().is_some_and(|filter| !filter(dialog)) {
+                    self.fetched -= 1;
+                    continue;
+                }
+            }
+
+            return Ok(dialog);
         }
+    }
 
+    /// Performs the network call and fills the buffer with the dialogs.
+    async fn fill_buffer(&mut self) -> Result<(), InvocationError> {
         use tl::enums::messages::Dialogs;
 
         self.request.limit = self.determine_limit(MAX_LIMIT);
@@ -112,25 +203,7 @@ impl DialogIter {
             }));
         }
 
-        // Don't bother updating offsets if this is the last time stuff has to be fetched.
-        if !self.last_chunk && !self.buffer.is_empty() {
-            self.request.exclude_pinned = true;
-            if let Some(last_message) = self
-                .buffer
-                .iter()
-                .rev()
-                .find_map(|dialog| dialog.last_message.as_ref())
-            {
-                self.request.offset_date = last_message.raw.date;
-                self.request.offset_id = last_message.raw.id;
-            }
-            self.request.offset_peer = self.buffer[self.buffer.len() - 1]
-                .chat()
-                .pack()
-                .to_input_peer();
-        }
-
-        Ok(self.pop_item())
+        Ok(())
     }
 }
 
@@ -159,6 +232,80 @@ impl Client {
         DialogIter::new(self)
     }
 
+    /// Returns the chat folders (also known as dialog filters) of the logged-in account.
+    ///
+    /// The default folder with all chats is not included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// for folder in client.get_folders().await? {
+    ///     println!("Dialogs in {}:", folder.title);
+    ///     let mut dialogs = client.iter_dialogs().folder(folder);
+    ///     while let Some(dialog) = dialogs.next().await? {
+    ///         println!("- {}", dialog.chat().name().unwrap_or_default());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_folders(&self) -> Result<Vec<Folder>, InvocationError> {
+        self.invoke(&tl::functions::messages::GetDialogFilters {})
+            .await
+            .map(Folder::from_raw_list)
+    }
+
+    /// Moves a dialog to the archive folder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.archive_dialog(&chat).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn archive_dialog<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+    ) -> Result<(), InvocationError> {
+        self.move_dialog_to_folder(chat, ARCHIVE_FOLDER_ID).await
+    }
+
+    /// Moves a dialog out of the archive folder and back into the main folder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.unarchive_dialog(&chat).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn unarchive_dialog<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+    ) -> Result<(), InvocationError> {
+        self.move_dialog_to_folder(chat, 0).await
+    }
+
+    async fn move_dialog_to_folder<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+        folder_id: i32,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::folders::EditPeerFolders {
+            folder_peers: vec![tl::types::InputFolderPeer {
+                peer: chat.into().to_input_peer(),
+                folder_id,
+            }
+            .into()],
+        })
+        .await
+        .map(drop)
+    }
+
     /// Deletes a dialog, effectively removing it from your list of open conversations.
     ///
     /// The dialog is only deleted for yourself.
@@ -258,3 +405,31 @@ impl Client {
         .map(drop)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn ensure_folder_id_is_requested() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            assert_eq!(client.iter_dialogs().request.folder_id, None);
+
+            let dialogs = client.iter_dialogs().folder_id(ARCHIVE_FOLDER_ID);
+            let request = tl::Serializable::to_bytes(&dialogs.request);
+            let expected = tl::Serializable::to_bytes(&tl::functions::messages::GetDialogs {
+                exclude_pinned: false,
+                folder_id: Some(1),
+                offset_date: 0,
+                offset_id: 0,
+                offset_peer: tl::enums::InputPeer::Empty,
+                limit: 0,
+                hash: 0,
+            });
+            assert_eq!(request, expected);
+        });
+    }
+}
@@ -6,13 +6,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::types::{photo_sizes::PhotoSize, Downloadable, Uploaded};
-use crate::utils::generate_random_id;
+use crate::types::{
+    photo_sizes::PhotoSize, Downloadable, Media, Message, UploadExpired, UploadProgress, Uploaded,
+};
 use crate::Client;
-use futures_util::stream::{FuturesUnordered, StreamExt as _};
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt as _};
 use grammers_mtsender::InvocationError;
+use grammers_session::PackedChat;
 use grammers_tl_types as tl;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use tokio::{
     io::{self, AsyncRead, AsyncReadExt},
     sync::Mutex as AsyncMutex,
@@ -20,7 +24,6 @@ use tokio::{
 
 #[cfg(feature = "fs")]
 use {
-    crate::types::Media,
     std::{io::SeekFrom, path::Path},
     tokio::{
         fs,
@@ -32,14 +35,36 @@ use {
 pub const MIN_CHUNK_SIZE: i32 = 4 * 1024;
 pub const MAX_CHUNK_SIZE: i32 = 512 * 1024;
 const FILE_MIGRATE_ERROR: i32 = 303;
+/// CDN datacenters serve smaller chunks than the rest.
+const MAX_CDN_CHUNK_SIZE: i32 = 128 * 1024;
+/// How many times in a row a CDN datacenter may ask for the same chunk to be reuploaded.
+const MAX_CDN_REUPLOADS: usize = 3;
 const BIG_FILE_SIZE: usize = 10 * 1024 * 1024;
 const WORKER_COUNT: usize = 4;
 
+/// The default maximum size, in bytes, of the files downloaded into memory.
+pub const DEFAULT_MAX_DOWNLOAD_BYTES: usize = 50 * 1024 * 1024;
+
 pub struct DownloadIter {
     client: Client,
     done: bool,
     request: tl::functions::upload::GetFile,
     photo_size_data: Option<Vec<u8>>,
+    cdn: Option<CdnDownload>,
+    /// Chat and message identifier where the file came from, used to refresh its file reference.
+    source: Option<(PackedChat, i32)>,
+}
+
+/// The state of a download which was redirected to a CDN datacenter.
+struct CdnDownload {
+    /// Datacenter which made the redirect, and must be used to fetch hashes or reupload parts.
+    origin_dc: Option<i32>,
+    dc_id: i32,
+    file_token: Vec<u8>,
+    key: [u8; 32],
+    iv: [u8; 16],
+    /// Hashes of the decrypted file, by the offset of the range they cover.
+    hashes: HashMap<i64, tl::types::FileHash>,
 }
 
 impl DownloadIter {
@@ -58,18 +83,19 @@ impl DownloadIter {
 
     fn new_from_file_location(client: &Client, location: tl::enums::InputFileLocation) -> Self {
         // TODO let users tweak all the options from the request
-        // TODO cdn support
         Self {
             client: client.clone(),
             done: false,
             request: tl::functions::upload::GetFile {
                 precise: false,
-                cdn_supported: false,
+                cdn_supported: true,
                 location,
                 offset: 0,
                 limit: MAX_CHUNK_SIZE,
             },
             photo_size_data: None,
+            cdn: None,
+            source: None,
         }
     }
 
@@ -93,6 +119,8 @@ impl DownloadIter {
                 limit: MAX_CHUNK_SIZE,
             },
             photo_size_data: Some(data),
+            cdn: None,
+            source: None,
         }
     }
 
@@ -103,6 +131,9 @@ impl DownloadIter {
     ///
     /// Panics if `size` is not divisible by `MIN_CHUNK_SIZE`, or if `size` is not in contained in
     /// the range `MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE`.
+    ///
+    /// Files served by a CDN datacenter can only be verified in ranges of 128 KiB, so if the
+    /// download is redirected to one, the size (and offset) must be a multiple of that.
     pub fn chunk_size(mut self, size: i32) -> Self {
         assert!((MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&size) && size % MIN_CHUNK_SIZE == 0);
         self.request.limit = size;
@@ -117,7 +148,47 @@ impl DownloadIter {
         self
     }
 
+    /// Sets the message containing the file being downloaded.
+    ///
+    /// File references expire after some time. If the message is known, the download will fetch
+    /// it again to refresh the reference and retry once when Telegram reports that it expired.
+    pub fn source_message(mut self, message: &Message) -> Self {
+        self.source = Some((message.chat().pack(), message.id()));
+        self
+    }
+
+    /// Download the rest of the file into memory.
+    ///
+    /// If the file turns out to be larger than `max_size` bytes, the download stops and an error
+    /// of kind [`io::ErrorKind::InvalidData`] is returned instead.
+    pub async fn collect_bytes(mut self, max_size: usize) -> Result<Vec<u8>, io::Error> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.next().await.map_err(io::Error::other)? {
+            if bytes.len() + chunk.len() > max_size {
+                return Err(too_large(max_size));
+            }
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+
+    /// Turn the download into a stream of chunks, which ends after the last chunk or the first
+    /// error.
+    ///
+    /// See [`DownloadIter::next`] for how each chunk is fetched.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Vec<u8>, InvocationError>> {
+        stream::try_unfold(self, |mut download| async move {
+            Ok(download.next().await?.map(|chunk| (chunk, download)))
+        })
+    }
+
     /// Fetch and return the next chunk.
+    ///
+    /// If the file is served by a CDN datacenter, each chunk is decrypted and verified against
+    /// the hashes provided by Telegram. If the verification fails,
+    /// [`InvocationError::CdnHashMismatch`] is returned, and the download should not continue.
+    /// The same is true for [`InvocationError::CdnReuploadLimit`], returned if the chunk is still
+    /// missing after being reuploaded to the CDN several times. Chunks fetched from a CDN are at most 128 KiB in size.
     pub async fn next(&mut self) -> Result<Option<Vec<u8>>, InvocationError> {
         if self.done {
             return Ok(None);
@@ -130,37 +201,266 @@ impl DownloadIter {
 
         use tl::enums::upload::File;
 
+        if let Some(cdn) = self.cdn.as_mut() {
+            let bytes = cdn
+                .fetch(&self.client, self.request.offset, self.request.limit)
+                .await?;
+            return Ok(self.advance(bytes));
+        }
+
         // TODO handle maybe FILEREF_UPGRADE_NEEDED
         let mut dc: Option<u32> = None;
         loop {
-            let result = match dc.take() {
+            let origin_dc = dc.take().map(|dc| dc as i32);
+            let result = match origin_dc {
                 None => self.client.invoke(&self.request).await,
-                Some(dc) => self.client.invoke_in_dc(&self.request, dc as i32).await,
+                Some(dc) => self.client.invoke_in_dc(&self.request, dc).await,
             };
 
             break match result {
-                Ok(File::File(f)) => {
-                    if f.bytes.len() < self.request.limit as usize {
-                        self.done = true;
-                        if f.bytes.is_empty() {
-                            return Ok(None);
-                        }
-                    }
-
-                    self.request.offset += self.request.limit as i64;
-                    Ok(Some(f.bytes))
-                }
-                Ok(File::CdnRedirect(_)) => {
-                    panic!("API returned File::CdnRedirect even though cdn_supported = false");
+                Ok(File::File(f)) => Ok(self.advance(f.bytes)),
+                Ok(File::CdnRedirect(redirect)) => {
+                    self.request.limit = self.request.limit.min(MAX_CDN_CHUNK_SIZE);
+                    let cdn = self.cdn.insert(CdnDownload::new(redirect, origin_dc)?);
+                    let bytes = cdn
+                        .fetch(&self.client, self.request.offset, self.request.limit)
+                        .await?;
+                    Ok(self.advance(bytes))
                 }
                 Err(InvocationError::Rpc(err)) if err.code == FILE_MIGRATE_ERROR => {
                     dc = err.value;
                     continue;
                 }
+                Err(e) if is_file_reference_expired(&e) => match self.source.take() {
+                    Some((chat, id)) => {
+                        let media = refetch_media(&self.client, chat, id).await?;
+                        if !self.refresh_file_reference(media) {
+                            break Err(e);
+                        }
+                        dc = origin_dc.map(|dc| dc as u32);
+                        continue;
+                    }
+                    None => Err(e),
+                },
                 Err(e) => Err(e),
             };
         }
     }
+
+    /// Replace the file reference of the location being downloaded with the one from the
+    /// refetched media, returning `false` if it could not be found.
+    fn refresh_file_reference(&mut self, media: Option<Media>) -> bool {
+        let file_reference = match media
+            .and_then(|media| media.to_raw_input_location())
+            .as_mut()
+            .and_then(file_reference_mut)
+        {
+            Some(file_reference) => std::mem::take(file_reference),
+            None => return false,
+        };
+        match file_reference_mut(&mut self.request.location) {
+            Some(old) => {
+                *old = file_reference;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move past the chunk that was just fetched, returning it if it's not empty.
+    fn advance(&mut self, bytes: Vec<u8>) -> Option<Vec<u8>> {
+        if bytes.len() < self.request.limit as usize {
+            self.done = true;
+            if bytes.is_empty() {
+                return None;
+            }
+        }
+
+        self.request.offset += self.request.limit as i64;
+        Some(bytes)
+    }
+}
+
+impl CdnDownload {
+    fn new(
+        redirect: tl::types::upload::FileCdnRedirect,
+        origin_dc: Option<i32>,
+    ) -> Result<Self, InvocationError> {
+        let invalid = || {
+            InvocationError::Read(
+                io::Error::new(io::ErrorKind::InvalidData, "invalid CDN encryption key").into(),
+            )
+        };
+        let mut cdn = Self {
+            origin_dc,
+            dc_id: redirect.dc_id,
+            file_token: redirect.file_token,
+            key: redirect.encryption_key.try_into().map_err(|_| invalid())?,
+            iv: redirect.encryption_iv.try_into().map_err(|_| invalid())?,
+            hashes: HashMap::new(),
+        };
+        cdn.add_hashes(redirect.file_hashes);
+        Ok(cdn)
+    }
+
+    /// Fetch the chunk found at `offset` from the CDN datacenter, then decrypt and verify it.
+    async fn fetch(
+        &mut self,
+        client: &Client,
+        offset: i64,
+        limit: i32,
+    ) -> Result<Vec<u8>, InvocationError> {
+        let mut reuploads = 0;
+        let mut bytes = loop {
+            let request = tl::functions::upload::GetCdnFile {
+                file_token: self.file_token.clone(),
+                offset,
+                limit,
+            };
+            match client.invoke_in_cdn(&request, self.dc_id).await? {
+                tl::enums::upload::CdnFile::File(f) => break f.bytes,
+                tl::enums::upload::CdnFile::ReuploadNeeded(reupload) => {
+                    if reuploads == MAX_CDN_REUPLOADS {
+                        return Err(InvocationError::CdnReuploadLimit);
+                    }
+                    reuploads += 1;
+
+                    let request = tl::functions::upload::ReuploadCdnFile {
+                        file_token: self.file_token.clone(),
+                        request_token: reupload.request_token,
+                    };
+                    let hashes = self.invoke_in_origin(client, &request).await?;
+                    self.add_hashes(hashes);
+                }
+            }
+        };
+
+        self.decrypt(offset, &mut bytes);
+        while let Some(hash_offset) = self.missing_hash(offset, bytes.len()) {
+            let request = tl::functions::upload::GetCdnFileHashes {
+                file_token: self.file_token.clone(),
+                offset: hash_offset,
+            };
+            let hashes = self.invoke_in_origin(client, &request).await?;
+            self.add_hashes(hashes);
+            if self.missing_hash(offset, bytes.len()) == Some(hash_offset) {
+                // The server did not provide it, so verification will fail.
+                break;
+            }
+        }
+        self.verify(offset, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Invoke a request in the datacenter which redirected the download to the CDN.
+    async fn invoke_in_origin<R: tl::RemoteCall>(
+        &self,
+        client: &Client,
+        request: &R,
+    ) -> Result<R::Return, InvocationError> {
+        match self.origin_dc {
+            None => client.invoke(request).await,
+            Some(dc) => client.invoke_in_dc(request, dc).await,
+        }
+    }
+
+    fn add_hashes(&mut self, hashes: Vec<tl::enums::FileHash>) {
+        self.hashes.extend(
+            hashes
+                .into_iter()
+                .map(|tl::enums::FileHash::Hash(hash)| (hash.offset, hash)),
+        );
+    }
+
+    fn decrypt(&self, offset: i64, bytes: &mut [u8]) {
+        grammers_crypto::cdn::decrypt_file_part(bytes, &self.key, &self.iv, offset);
+    }
+
+    /// Offset of the first range in the chunk whose hash is not known yet, if any.
+    fn missing_hash(&self, offset: i64, len: usize) -> Option<i64> {
+        let end = offset + len as i64;
+        let mut pos = offset;
+        while pos < end {
+            match self.hashes.get(&pos) {
+                Some(hash) if hash.limit > 0 => pos += hash.limit as i64,
+                Some(_) => return None,
+                None => return Some(pos),
+            }
+        }
+        None
+    }
+
+    /// Check the decrypted chunk found at `offset` against the known hashes.
+    fn verify(&self, offset: i64, bytes: &[u8]) -> Result<(), InvocationError> {
+        let mut start = 0;
+        while start < bytes.len() {
+            let hash = self
+                .hashes
+                .get(&(offset + start as i64))
+                .filter(|hash| hash.limit > 0)
+                .ok_or(InvocationError::CdnHashMismatch)?;
+            let end = bytes.len().min(start + hash.limit as usize);
+            if grammers_crypto::cdn::file_hash(&bytes[start..end]) != hash.hash.as_slice() {
+                return Err(InvocationError::CdnHashMismatch);
+            }
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a file is larger than `max_size` bytes.
+fn too_large(max_size: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("file is larger than the maximum of {max_size} bytes"),
+    )
+}
+
+/// Fail early if the size of the downloadable is known to be larger than `max_size` bytes.
+pub(crate) fn check_download_size(
+    downloadable: &Downloadable,
+    max_size: usize,
+) -> Result<(), io::Error> {
+    match downloadable {
+        Downloadable::Media(Media::Document(document))
+            if usize::try_from(document.size()).is_ok_and(|size| size > max_size) =>
+        {
+            Err(too_large(max_size))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether the error indicates that the file reference used to access a file has expired.
+pub(crate) fn is_file_reference_expired(error: &InvocationError) -> bool {
+    error.is("FILE_REFERENCE_EXPIRED")
+}
+
+/// Fetch the message again to get its media with an up-to-date file reference.
+pub(crate) async fn refetch_media(
+    client: &Client,
+    chat: PackedChat,
+    id: i32,
+) -> Result<Option<Media>, InvocationError> {
+    Ok(client
+        .get_messages_by_id(chat, &[id])
+        .await?
+        .pop()
+        .flatten()
+        .and_then(|message| message.media()))
+}
+
+/// The file reference of the location, if it has one.
+fn file_reference_mut(location: &mut tl::enums::InputFileLocation) -> Option<&mut Vec<u8>> {
+    use tl::enums::InputFileLocation as L;
+    match location {
+        L::Location(l) => Some(&mut l.file_reference),
+        L::InputDocumentFileLocation(l) => Some(&mut l.file_reference),
+        L::InputPhotoFileLocation(l) => Some(&mut l.file_reference),
+        L::InputPhotoLegacyFileLocation(l) => Some(&mut l.file_reference),
+        _ => None,
+    }
 }
 
 /// Method implementations related to uploading or downloading files.
@@ -186,6 +486,66 @@ impl Client {
         DownloadIter::new(self, downloadable)
     }
 
+    /// Returns a stream over the chunks of a media document that will be downloaded.
+    ///
+    /// This is the same as [`Client::iter_download`], but can be used with the combinators
+    /// of streams. Each chunk is yielded as soon as it's fetched, with the offset advancing
+    /// internally, and downloads redirected to another datacenter or a CDN are handled
+    /// transparently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(downloadable: grammers_client::types::Downloadable, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use futures_util::TryStreamExt;
+    ///
+    /// let mut size = 0;
+    /// let mut chunks = std::pin::pin!(client.download_chunks(&downloadable));
+    /// while let Some(chunk) = chunks.try_next().await? {
+    ///     size += chunk.len();
+    /// }
+    ///
+    /// println!("Downloaded {size} bytes");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn download_chunks(
+        &self,
+        downloadable: &Downloadable,
+    ) -> impl Stream<Item = Result<Vec<u8>, InvocationError>> {
+        self.iter_download(downloadable).into_stream()
+    }
+
+    /// Downloads a media file into memory.
+    ///
+    /// This is meant for small files, such as thumbnails, so an error of kind
+    /// [`io::ErrorKind::InvalidData`] is returned if the file is larger than `max_size` bytes.
+    /// [`DEFAULT_MAX_DOWNLOAD_BYTES`] is a reasonable limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(downloadable: grammers_client::types::Downloadable, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::client::files::DEFAULT_MAX_DOWNLOAD_BYTES;
+    ///
+    /// let bytes = client
+    ///     .download_media_bytes(&downloadable, DEFAULT_MAX_DOWNLOAD_BYTES)
+    ///     .await?;
+    /// println!("Downloaded {} bytes", bytes.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn download_media_bytes(
+        &self,
+        downloadable: &Downloadable,
+        max_size: usize,
+    ) -> Result<Vec<u8>, io::Error> {
+        check_download_size(downloadable, max_size)?;
+        self.iter_download(downloadable)
+            .collect_bytes(max_size)
+            .await
+    }
+
     /// Downloads a media file into the specified path.
     ///
     /// If the file already exists, it will be overwritten.
@@ -226,10 +586,7 @@ impl Client {
                     photo_size.data()
                 }
                 _ => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "media not downloadable",
-                    ));
+                    return Err(io::Error::other("media not downloadable"));
                 }
             };
 
@@ -248,11 +605,7 @@ impl Client {
     #[cfg(feature = "fs")]
     async fn load<P: AsRef<Path>>(path: P, download: &mut DownloadIter) -> Result<(), io::Error> {
         let mut file = fs::File::create(path).await?;
-        while let Some(chunk) = download
-            .next()
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
-        {
+        while let Some(chunk) = download.next().await.map_err(io::Error::other)? {
             file.write_all(&chunk).await?;
         }
 
@@ -355,8 +708,7 @@ impl Client {
 
         // Check if all tasks finished succesfully
         for task in tasks {
-            task.await?
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            task.await?.map_err(io::Error::other)?;
         }
         Ok(())
     }
@@ -405,7 +757,49 @@ impl Client {
         size: usize,
         name: String,
     ) -> Result<Uploaded, io::Error> {
-        let file_id = generate_random_id();
+        self.resume_upload(&mut UploadProgress::new(), stream, size, name)
+            .await
+    }
+
+    /// Uploads an async stream to Telegram servers, continuing a previous upload if possible.
+    ///
+    /// This behaves like [`Client::upload_stream`], except that the `file_id` and the parts which
+    /// have already been acknowledged by the server are taken from (and recorded into) `progress`.
+    /// If the upload fails midway, the same `progress` may be used to call this method again,
+    /// and only the parts that are still missing will be sent.
+    ///
+    /// The stream must always start at the beginning of the file, and both the `size` and its
+    /// contents must not change between attempts. Parts that were already uploaded are still
+    /// read from the stream, but are not sent again.
+    ///
+    /// If the server no longer remembers the `file_id` (for example, because too much time has
+    /// passed), the progress is reset and an error wrapping [`UploadExpired`] is returned. The
+    /// upload can then be retried from the start (with a rewound stream).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client, some_vec: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::types::UploadProgress;
+    ///
+    /// let mut progress = UploadProgress::new();
+    /// let uploaded_file = loop {
+    ///     let mut stream = std::io::Cursor::new(some_vec);
+    ///     match client.resume_upload(&mut progress, &mut stream, some_vec.len(), "a.jpg".to_string()).await {
+    ///         Ok(uploaded) => break uploaded,
+    ///         Err(e) => println!("upload failed ({e}), {} parts done", progress.uploaded_parts().len()),
+    ///     }
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn resume_upload<S: AsyncRead + Unpin>(
+        &self,
+        progress: &mut UploadProgress,
+        stream: &mut S,
+        size: usize,
+        name: String,
+    ) -> Result<Uploaded, io::Error> {
         let name = if name.is_empty() {
             "a".to_string()
         } else {
@@ -413,43 +807,42 @@ impl Client {
         };
 
         let big_file = size > BIG_FILE_SIZE;
-        let parts = PartStream::new(stream, size);
+        let file_id = progress.file_id();
+        let parts = PartStream::new(stream, size, !big_file);
         let total_parts = parts.total_parts();
+        let workers = if big_file { WORKER_COUNT } else { 1 };
 
-        if big_file {
-            let parts = Arc::new(parts);
-            let mut tasks = FuturesUnordered::new();
-            for _ in 0..WORKER_COUNT {
-                let handle = self.clone();
-                let parts = Arc::clone(&parts);
-                let task = async move {
-                    while let Some((part, bytes)) = parts.next_part().await? {
-                        let ok = handle
-                            .invoke(&tl::functions::upload::SaveBigFilePart {
-                                file_id,
-                                file_part: part,
-                                file_total_parts: total_parts,
-                                bytes,
-                            })
-                            .await
-                            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-                        if !ok {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                "server failed to store uploaded data",
-                            ));
-                        }
-                    }
-                    Ok(())
-                };
-                tasks.push(task);
+        let result = upload_parts(&parts, progress, workers, |part, bytes| async move {
+            if big_file {
+                self.invoke(&tl::functions::upload::SaveBigFilePart {
+                    file_id,
+                    file_part: part,
+                    file_total_parts: total_parts,
+                    bytes,
+                })
+                .await
+            } else {
+                self.invoke(&tl::functions::upload::SaveFilePart {
+                    file_id,
+                    file_part: part,
+                    bytes,
+                })
+                .await
             }
+        })
+        .await;
 
-            while let Some(res) = tasks.next().await {
-                res?;
+        match result {
+            Ok(()) => {}
+            Err(UploadError::Io(e)) => return Err(e),
+            Err(UploadError::Invocation(e)) if is_stale_upload(&e) => {
+                progress.restart();
+                return Err(io::Error::other(UploadExpired { error: e }));
             }
+            Err(UploadError::Invocation(e)) => return Err(io::Error::other(e)),
+        }
 
+        if big_file {
             Ok(Uploaded::from_raw(
                 tl::types::InputFileBig {
                     id: file_id,
@@ -459,31 +852,12 @@ impl Client {
                 .into(),
             ))
         } else {
-            let mut md5 = md5::Context::new();
-            while let Some((part, bytes)) = parts.next_part().await? {
-                md5.consume(&bytes);
-                let ok = self
-                    .invoke(&tl::functions::upload::SaveFilePart {
-                        file_id,
-                        file_part: part,
-                        bytes,
-                    })
-                    .await
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-                if !ok {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "server failed to store uploaded data",
-                    ));
-                }
-            }
             Ok(Uploaded::from_raw(
                 tl::types::InputFile {
                     id: file_id,
                     parts: total_parts,
                     name,
-                    md5_checksum: format!("{:x}", md5.compute()),
+                    md5_checksum: format!("{:x}", parts.compute_md5().await),
                 }
                 .into(),
             ))
@@ -533,6 +907,7 @@ impl Client {
 struct PartStreamInner<'a, S: AsyncRead + Unpin> {
     stream: &'a mut S,
     current_part: i32,
+    md5: Option<md5::Context>,
 }
 
 struct PartStream<'a, S: AsyncRead + Unpin> {
@@ -541,12 +916,13 @@ struct PartStream<'a, S: AsyncRead + Unpin> {
 }
 
 impl<'a, S: AsyncRead + Unpin> PartStream<'a, S> {
-    fn new(stream: &'a mut S, size: usize) -> Self {
-        let total_parts = ((size + MAX_CHUNK_SIZE as usize - 1) / MAX_CHUNK_SIZE as usize) as i32;
+    fn new(stream: &'a mut S, size: usize, md5: bool) -> Self {
+        let total_parts = size.div_ceil(MAX_CHUNK_SIZE as usize) as i32;
         Self {
             inner: AsyncMutex::new(PartStreamInner {
                 stream,
                 current_part: 0,
+                md5: md5.then(md5::Context::new),
             }),
             total_parts,
         }
@@ -585,8 +961,418 @@ impl<'a, S: AsyncRead + Unpin> PartStream<'a, S> {
             buffer[..read].to_vec()
         };
 
+        if let Some(md5) = lock.md5.as_mut() {
+            md5.consume(&bytes);
+        }
+
         let res = Ok(Some((lock.current_part, bytes)));
         lock.current_part += 1;
         res
     }
+
+    /// Checksum of all the parts read so far. Panics if it was not requested on creation.
+    async fn compute_md5(self) -> md5::Digest {
+        self.inner.into_inner().md5.unwrap().compute()
+    }
+}
+
+enum UploadError {
+    Io(io::Error),
+    Invocation(InvocationError),
+}
+
+/// Whether the server rejected a part because it no longer knows about the upload's `file_id`.
+fn is_stale_upload(error: &InvocationError) -> bool {
+    error.is("FILE_PART_MISSING") || error.is("FILE_PARTS_INVALID") || error.is("FILE_ID_INVALID")
+}
+
+/// Reads every part from `parts` using up to `workers` concurrent tasks, and calls `send` for
+/// those not yet present in `progress`, recording the ones which are successfully acknowledged.
+async fn upload_parts<S, F, Fut>(
+    parts: &PartStream<'_, S>,
+    progress: &mut UploadProgress,
+    workers: usize,
+    send: F,
+) -> Result<(), UploadError>
+where
+    S: AsyncRead + Unpin,
+    F: Fn(i32, Vec<u8>) -> Fut,
+    Fut: Future<Output = Result<bool, InvocationError>>,
+{
+    let progress = Mutex::new(progress);
+    let mut tasks = (0..workers)
+        .map(|_| async {
+            while let Some((part, bytes)) = parts.next_part().await.map_err(UploadError::Io)? {
+                if progress.lock().unwrap().is_uploaded(part) {
+                    continue;
+                }
+                let ok = send(part, bytes).await.map_err(UploadError::Invocation)?;
+                if !ok {
+                    return Err(UploadError::Io(io::Error::other(
+                        "server failed to store uploaded data",
+                    )));
+                }
+                progress.lock().unwrap().mark_uploaded(part);
+            }
+            Ok(())
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    while let Some(res) = tasks.next().await {
+        res?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use grammers_mtsender::RpcError;
+
+    fn rpc_error(name: &str) -> InvocationError {
+        InvocationError::Rpc(RpcError {
+            code: 400,
+            name: name.to_string(),
+            value: None,
+            caused_by: None,
+        })
+    }
+
+    fn upload(
+        data: &[u8],
+        progress: &mut UploadProgress,
+        fail_at: Option<(i32, &str)>,
+    ) -> (Vec<i32>, Result<(), UploadError>) {
+        let sent = Mutex::new(Vec::new());
+        let mut stream = std::io::Cursor::new(data);
+        let parts = PartStream::new(&mut stream, data.len(), false);
+        let result =
+            futures_util::FutureExt::now_or_never(upload_parts(&parts, progress, 1, |part, _| {
+                let result = match fail_at {
+                    Some((at, name)) if at == part => Err(rpc_error(name)),
+                    _ => {
+                        sent.lock().unwrap().push(part);
+                        Ok(true)
+                    }
+                };
+                std::future::ready(result)
+            }))
+            .unwrap();
+        (sent.into_inner().unwrap(), result)
+    }
+
+    #[test]
+    fn check_upload_resumes_from_missing_parts() {
+        let data = vec![0; 4 * MAX_CHUNK_SIZE as usize + 1];
+        let mut progress = UploadProgress::new();
+        let file_id = progress.file_id();
+
+        let (sent, result) = upload(&data, &mut progress, Some((2, "INTERNAL")));
+        assert_eq!(sent, vec![0, 1]);
+        assert!(matches!(result, Err(UploadError::Invocation(_))));
+        assert_eq!(progress, UploadProgress::resume(file_id, [0, 1]));
+
+        let (sent, result) = upload(&data, &mut progress, None);
+        assert_eq!(sent, vec![2, 3, 4]);
+        assert!(result.is_ok());
+        assert_eq!(progress, UploadProgress::resume(file_id, 0..5));
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_expired_uploads_are_restarted() {
+        use grammers_mtsender::mock::MockSender;
+
+        let mut sender = MockSender::new();
+        sender
+            .expect::<tl::functions::upload::SaveFilePart>()
+            .fail(RpcError {
+                code: 400,
+                name: "FILE_PART_MISSING".to_string(),
+                value: None,
+                caused_by: None,
+            });
+
+        block_on(async {
+            let (client, _mock) = Client::mocked_for_tests(sender).await;
+            let data = vec![0; 10];
+            let mut progress = UploadProgress::resume(1234, []);
+
+            let error = client
+                .resume_upload(
+                    &mut progress,
+                    &mut std::io::Cursor::new(&data),
+                    data.len(),
+                    "a.txt".to_string(),
+                )
+                .await
+                .unwrap_err();
+            let expired = error
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<UploadExpired>())
+                .unwrap();
+            assert!(expired.error().is("FILE_PART_MISSING"));
+            assert_ne!(progress.file_id(), 1234);
+            assert!(progress.uploaded_parts().is_empty());
+        });
+    }
+
+    #[test]
+    fn check_stale_upload_errors() {
+        assert!(is_stale_upload(&rpc_error("FILE_PART_MISSING")));
+        assert!(is_stale_upload(&rpc_error("FILE_PARTS_INVALID")));
+        assert!(!is_stale_upload(&rpc_error("FILE_PART_TOO_BIG")));
+        assert!(!is_stale_upload(&InvocationError::Dropped));
+    }
+
+    #[test]
+    fn check_cdn_parts_are_decrypted_and_verified() {
+        const HASH_SIZE: usize = 128 * 1024;
+        let (key, iv) = ([1; 32], [2; 16]);
+        let file = (0..2 * HASH_SIZE + 10)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let hashes = file
+            .chunks(HASH_SIZE)
+            .enumerate()
+            .map(|(i, range)| {
+                tl::types::FileHash {
+                    offset: (i * HASH_SIZE) as i64,
+                    limit: HASH_SIZE as i32,
+                    hash: grammers_crypto::cdn::file_hash(range).to_vec(),
+                }
+                .into()
+            })
+            .collect::<Vec<_>>();
+
+        let mut encrypted = file.clone();
+        grammers_crypto::cdn::decrypt_file_part(&mut encrypted, &key, &iv, 0);
+
+        let mut cdn = CdnDownload::new(
+            tl::types::upload::FileCdnRedirect {
+                dc_id: 203,
+                file_token: vec![7],
+                encryption_key: key.to_vec(),
+                encryption_iv: iv.to_vec(),
+                file_hashes: hashes[..1].to_vec(),
+            },
+            None,
+        )
+        .unwrap();
+
+        // The first chunk is covered by the hashes provided with the redirect.
+        let mut chunk = encrypted[..HASH_SIZE].to_vec();
+        cdn.decrypt(0, &mut chunk);
+        assert_eq!(cdn.missing_hash(0, chunk.len()), None);
+        assert!(cdn.verify(0, &chunk).is_ok());
+        assert_eq!(chunk, file[..HASH_SIZE]);
+
+        // The rest need to be fetched.
+        let offset = HASH_SIZE as i64;
+        let mut chunk = encrypted[HASH_SIZE..].to_vec();
+        cdn.decrypt(offset, &mut chunk);
+        assert_eq!(cdn.missing_hash(offset, chunk.len()), Some(offset));
+        cdn.add_hashes(hashes[1..].to_vec());
+        assert_eq!(cdn.missing_hash(offset, chunk.len()), None);
+        assert!(cdn.verify(offset, &chunk).is_ok());
+        assert_eq!(chunk, file[HASH_SIZE..]);
+
+        // Tampered parts fail to verify.
+        let mut chunk = encrypted[HASH_SIZE..].to_vec();
+        chunk[HASH_SIZE] ^= 1;
+        cdn.decrypt(offset, &mut chunk);
+        assert!(cdn
+            .verify(offset, &chunk)
+            .is_err_and(|e| matches!(e, InvocationError::CdnHashMismatch)));
+    }
+
+    fn photo(file_reference: Vec<u8>) -> tl::types::MessageMediaPhoto {
+        tl::types::MessageMediaPhoto {
+            spoiler: false,
+            photo: Some(
+                tl::types::Photo {
+                    has_stickers: false,
+                    id: 1,
+                    access_hash: 2,
+                    file_reference,
+                    date: 0,
+                    sizes: vec![tl::types::PhotoSize {
+                        r#type: "x".to_string(),
+                        w: 1,
+                        h: 1,
+                        size: 1,
+                    }
+                    .into()],
+                    video_sizes: None,
+                    dc_id: 2,
+                }
+                .into(),
+            ),
+            ttl_seconds: None,
+        }
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_download_stream_yields_chunks_in_order() {
+        use futures_util::TryStreamExt;
+        use grammers_mtsender::mock::MockSender;
+
+        let size = MIN_CHUNK_SIZE as usize;
+        let mut sender = MockSender::new();
+        for (offset, len) in [(0, size), (size, size), (2 * size, 10)] {
+            sender
+                .expect_if(move |r: &tl::functions::upload::GetFile| r.offset == offset as i64)
+                .respond(
+                    tl::types::upload::File {
+                        r#type: tl::enums::storage::FileType::FilePartial,
+                        mtime: 0,
+                        bytes: vec![(offset / size) as u8; len],
+                    }
+                    .into(),
+                );
+        }
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            let media = Media::from_raw(photo(Vec::new()).into()).unwrap();
+
+            let chunks = client
+                .iter_download(&Downloadable::Media(media))
+                .chunk_size(MIN_CHUNK_SIZE)
+                .into_stream()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(chunks, vec![vec![0; size], vec![1; size], vec![2; 10]]);
+
+            let mock = mock.lock().await;
+            assert_eq!(mock.invoked().len(), 3);
+            assert_eq!(mock.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_cdn_reuploads_are_bounded() {
+        use grammers_mtsender::mock::MockSender;
+
+        let mut sender = MockSender::new();
+        sender.expect::<tl::functions::upload::GetFile>().respond(
+            tl::types::upload::FileCdnRedirect {
+                dc_id: 203,
+                file_token: vec![7],
+                encryption_key: vec![1; 32],
+                encryption_iv: vec![2; 16],
+                file_hashes: Vec::new(),
+            }
+            .into(),
+        );
+        for _ in 0..MAX_CDN_REUPLOADS {
+            sender
+                .expect_if(|r: &tl::functions::upload::GetCdnFile| r.limit == MAX_CDN_CHUNK_SIZE)
+                .respond(
+                    tl::types::upload::CdnFileReuploadNeeded {
+                        request_token: vec![8],
+                    }
+                    .into(),
+                );
+            sender
+                .expect::<tl::functions::upload::ReuploadCdnFile>()
+                .respond(Vec::new());
+        }
+        sender
+            .expect::<tl::functions::upload::GetCdnFile>()
+            .respond(
+                tl::types::upload::CdnFileReuploadNeeded {
+                    request_token: vec![8],
+                }
+                .into(),
+            );
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            // The mock can only serve the keys of regular datacenters, so reuse one of those.
+            let address = "149.154.175.53:443".parse().unwrap();
+            client.0.config.session.insert_dc_tcp(
+                203,
+                &address,
+                crate::client::net::mock::auth_key(3),
+            );
+            let media = Media::from_raw(photo(Vec::new()).into()).unwrap();
+
+            // The CDN keeps asking for a reupload, so the download gives up.
+            let mut download = client.iter_download(&Downloadable::Media(media));
+            let error = download.next().await.unwrap_err();
+            assert!(matches!(error, InvocationError::CdnReuploadLimit));
+
+            let mock = mock.lock().await;
+            assert_eq!(mock.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_download_bytes_is_capped() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let data = vec![1, 2, 3, 4, 5];
+
+            let download = DownloadIter::new_from_photo_size(&client, data.clone());
+            assert_eq!(download.collect_bytes(5).await.unwrap(), data);
+
+            let download = DownloadIter::new_from_photo_size(&client, data.clone());
+            let error = download.collect_bytes(4).await.unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+            let message = Message::from_raw(
+                &client,
+                crate::types::message::EMPTY_MESSAGE.into(),
+                &crate::ChatMap::empty(),
+            )
+            .unwrap();
+            assert_eq!(message.download_media_bytes().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_expired_file_reference_is_refreshed() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let message = Message::from_raw(
+                &client,
+                tl::types::Message {
+                    id: 5,
+                    media: Some(photo(vec![3]).into()),
+                    ..crate::types::message::EMPTY_MESSAGE
+                }
+                .into(),
+                &crate::ChatMap::empty(),
+            )
+            .unwrap();
+
+            let mut download = client
+                .iter_download(&Downloadable::Media(message.media().unwrap()))
+                .source_message(&message);
+            assert_eq!(download.source, Some((message.chat().pack(), 5)));
+            assert!(is_file_reference_expired(&rpc_error(
+                "FILE_REFERENCE_EXPIRED"
+            )));
+            assert!(!is_file_reference_expired(&rpc_error("FILE_ID_INVALID")));
+
+            // The refetched message has media with a new reference.
+            let media = Media::from_raw(photo(vec![4]).into());
+            assert!(download.refresh_file_reference(media));
+            assert_eq!(
+                file_reference_mut(&mut download.request.location),
+                Some(&mut vec![4])
+            );
+
+            // The message no longer has media.
+            assert!(!download.refresh_file_reference(None));
+        });
+    }
 }
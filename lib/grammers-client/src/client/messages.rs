@@ -7,8 +7,9 @@
 // except according to those terms.
 
 //! Methods related to sending messages.
+use crate::types::input_message::input_live_location;
 use crate::types::message::EMPTY_MESSAGE;
-use crate::types::{InputReactions, IterBuffer, Message};
+use crate::types::{InputReactions, IterBuffer, Message, ReplyMarkup};
 use crate::utils::{generate_random_id, generate_random_ids};
 use crate::{types, ChatMap, Client, InputMedia};
 use chrono::{DateTime, FixedOffset};
@@ -19,6 +20,95 @@ use log::{log_enabled, warn, Level};
 use std::collections::HashMap;
 use tl::enums::InputPeer;
 
+/// Messages are fetched by ID differently depending on whether they belong to a channel or not.
+#[derive(Debug)]
+enum GetMessages {
+    Channel(tl::functions::channels::GetMessages),
+    Common(tl::functions::messages::GetMessages),
+}
+
+impl GetMessages {
+    fn new(chat: PackedChat, id: Vec<tl::enums::InputMessage>) -> Self {
+        if let Some(channel) = chat.try_to_input_channel() {
+            Self::Channel(tl::functions::channels::GetMessages { channel, id })
+        } else {
+            Self::Common(tl::functions::messages::GetMessages { id })
+        }
+    }
+
+    fn is_channel(&self) -> bool {
+        matches!(self, Self::Channel(_))
+    }
+
+    async fn invoke(
+        &self,
+        client: &Client,
+    ) -> Result<tl::enums::messages::Messages, InvocationError> {
+        match self {
+            Self::Channel(request) => client.invoke(request).await,
+            Self::Common(request) => client.invoke(request).await,
+        }
+    }
+}
+
+/// Find the message being replied to by `message` in the response to `GetMessages`.
+///
+/// Outside of channels, message IDs are shared by all chats, so `filter_req` should be set to
+/// only accept the reply if it belongs to the same chat as the message.
+fn reply_from_response(
+    client: &Client,
+    message: &Message,
+    res: tl::enums::messages::Messages,
+    filter_req: bool,
+) -> Option<Message> {
+    use tl::enums::messages::Messages;
+
+    let (messages, users, chats) = match res {
+        Messages::Messages(m) => (m.messages, m.users, m.chats),
+        Messages::Slice(m) => (m.messages, m.users, m.chats),
+        Messages::ChannelMessages(m) => (m.messages, m.users, m.chats),
+        Messages::NotModified(_) => {
+            panic!("API returned Messages::NotModified even though GetMessages was used")
+        }
+    };
+
+    let chats = ChatMap::new(users, chats);
+    messages
+        .into_iter()
+        .flat_map(|m| Message::from_raw(client, m, &chats))
+        .next()
+        .filter(|m| !filter_req || m.raw.peer_id == message.raw.peer_id)
+}
+
+/// Map the messages in the response to `GetMessages` by their identifier.
+///
+/// Messages which could not be retrieved are returned as empty, and so are skipped, as are those
+/// outside of `chat`, since outside of channels message IDs are shared by all chats.
+fn messages_by_id(
+    client: &Client,
+    chat: PackedChat,
+    res: tl::enums::messages::Messages,
+) -> HashMap<i32, Message> {
+    use tl::enums::messages::Messages;
+
+    let (messages, users, chats) = match res {
+        Messages::Messages(m) => (m.messages, m.users, m.chats),
+        Messages::Slice(m) => (m.messages, m.users, m.chats),
+        Messages::ChannelMessages(m) => (m.messages, m.users, m.chats),
+        Messages::NotModified(_) => {
+            panic!("API returned Messages::NotModified even though GetMessages was used")
+        }
+    };
+
+    let chats = ChatMap::new(users, chats);
+    messages
+        .into_iter()
+        .flat_map(|m| Message::from_raw(client, m, &chats))
+        .filter(|m| m.chat().pack() == chat)
+        .map(|m| (m.raw.id, m))
+        .collect()
+}
+
 fn map_random_ids_to_messages(
     client: &Client,
     random_ids: &[i64],
@@ -85,6 +175,36 @@ fn map_random_ids_to_messages(
     }
 }
 
+/// If `media` refers to a file that was uploaded but not yet sent, replace it with a reference
+/// to the file the server stored after sending it in `sent`, so that it doesn't need to be sent
+/// again. Returns `true` if the media was replaced.
+fn reuse_uploaded_media(
+    media: &mut Option<tl::enums::InputMedia>,
+    sent: Option<types::Media>,
+) -> bool {
+    use tl::enums::InputMedia as IM;
+
+    let spoiler = match media {
+        Some(IM::UploadedPhoto(photo)) => photo.spoiler,
+        Some(IM::UploadedDocument(document)) => document.spoiler,
+        _ => return false,
+    };
+
+    match (media.as_ref(), sent.and_then(|m| m.to_raw_input_media())) {
+        (Some(IM::UploadedPhoto(_)), Some(IM::Photo(mut photo))) => {
+            photo.spoiler = spoiler;
+            *media = Some(photo.into());
+            true
+        }
+        (Some(IM::UploadedDocument(_)), Some(IM::Document(mut document))) => {
+            document.spoiler = spoiler;
+            *media = Some(document.into());
+            true
+        }
+        _ => false,
+    }
+}
+
 pub(crate) fn parse_mention_entities(
     client: &Client,
     mut entities: Vec<tl::enums::MessageEntity>,
@@ -115,6 +235,66 @@ pub(crate) fn parse_mention_entities(
     Some(entities)
 }
 
+/// The random identifier to send the message with, so that Telegram can detect duplicates.
+fn send_random_id(message: &types::InputMessage) -> i64 {
+    message.random_id.unwrap_or_else(generate_random_id)
+}
+
+fn send_message_request(
+    chat: PackedChat,
+    message: &types::InputMessage,
+    random_id: i64,
+    entities: Option<Vec<tl::enums::MessageEntity>>,
+) -> tl::functions::messages::SendMessage {
+    tl::functions::messages::SendMessage {
+        no_webpage: message.no_webpage,
+        silent: message.silent,
+        background: message.background,
+        clear_draft: message.clear_draft,
+        peer: chat.to_input_peer(),
+        reply_to: message.input_reply_to(),
+        message: message.text.clone(),
+        random_id,
+        reply_markup: message.reply_markup.clone(),
+        entities,
+        schedule_date: message.schedule_date,
+        send_as: message.send_as.clone(),
+        noforwards: false,
+        update_stickersets_order: false,
+        invert_media: message.invert_media,
+        quick_reply_shortcut: None,
+        effect: message.effect,
+    }
+}
+
+fn send_media_request(
+    chat: PackedChat,
+    message: &types::InputMessage,
+    media: tl::enums::InputMedia,
+    random_id: i64,
+    entities: Option<Vec<tl::enums::MessageEntity>>,
+) -> tl::functions::messages::SendMedia {
+    tl::functions::messages::SendMedia {
+        silent: message.silent,
+        background: message.background,
+        clear_draft: message.clear_draft,
+        peer: chat.to_input_peer(),
+        reply_to: message.input_reply_to(),
+        media,
+        message: message.text.clone(),
+        random_id,
+        reply_markup: message.reply_markup.clone(),
+        entities,
+        schedule_date: message.schedule_date,
+        send_as: message.send_as.clone(),
+        noforwards: false,
+        update_stickersets_order: false,
+        invert_media: message.invert_media,
+        quick_reply_shortcut: None,
+        effect: message.effect,
+    }
+}
+
 const MAX_LIMIT: usize = 100;
 
 impl<R: tl::RemoteCall<Return = tl::enums::messages::Messages>> IterBuffer<R, Message> {
@@ -142,12 +322,24 @@ impl<R: tl::RemoteCall<Return = tl::enums::messages::Messages>> IterBuffer<R, Me
     ///
     /// The `request.limit` should be set to the right value before calling this method.
     async fn fill_buffer(&mut self, limit: i32) -> Result<Option<i32>, InvocationError> {
+        let response = self.client.invoke(&self.request).await?;
+        Ok(self.extend_buffer(response, limit))
+    }
+
+    /// Fills the buffer with the response, and returns the `offset_rate` if any.
+    fn extend_buffer(
+        &mut self,
+        response: tl::enums::messages::Messages,
+        limit: i32,
+    ) -> Option<i32> {
         use tl::enums::messages::Messages;
 
-        let (messages, users, chats, rate) = match self.client.invoke(&self.request).await? {
+        let (messages, users, chats, rate) = match response {
             Messages::Messages(m) => {
+                // All the remaining messages are here, which may not be all of them if this is
+                // not the first page.
                 self.last_chunk = true;
-                self.total = Some(m.messages.len());
+                self.total = Some(self.fetched + self.buffer.len() + m.messages.len());
                 (m.messages, m.users, m.chats, None)
             }
             Messages::Slice(m) => {
@@ -166,8 +358,11 @@ impl<R: tl::RemoteCall<Return = tl::enums::messages::Messages>> IterBuffer<R, Me
                 self.total = Some(m.count as usize);
                 (m.messages, m.users, m.chats, None)
             }
-            Messages::NotModified(_) => {
-                panic!("API returned Messages::NotModified even though hash = 0")
+            Messages::NotModified(m) => {
+                // Only possible if the request had a hash, and nothing changed since.
+                self.last_chunk = true;
+                self.total = Some(m.count as usize);
+                return None;
             }
         };
 
@@ -186,7 +381,7 @@ impl<R: tl::RemoteCall<Return = tl::enums::messages::Messages>> IterBuffer<R, Me
                 .flat_map(|message| Message::from_raw(&client, message, &chats)),
         );
 
-        Ok(rate)
+        rate
     }
 }
 
@@ -215,11 +410,74 @@ impl MessageIter {
         self
     }
 
+    #[deprecated(note = "use `offset_date` instead, which also resets the `offset_id`")]
     pub fn max_date(mut self, offset: i32) -> Self {
         self.request.offset_date = offset;
         self
     }
 
+    /// Start returning the messages sent before the given date, instead of the most recent ones.
+    ///
+    /// This replaces any previous [`MessageIter::offset_id`]. Only the first request uses the
+    /// date, and the following ones continue from the last message returned, as usual.
+    ///
+    /// ```
+    /// use chrono::DateTime;
+    ///
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// // Iterate over the messages sent before Dec, 25th 2022
+    /// let date = DateTime::parse_from_rfc3339("2022-12-25T00:00:00-00:00").unwrap();
+    ///
+    /// let mut messages = client.iter_messages(&chat).offset_date(&date);
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn offset_date(mut self, date_time: &DateTime<FixedOffset>) -> Self {
+        self.request.offset_id = 0;
+        self.request.offset_date = date_time.timestamp() as i32;
+        self
+    }
+
+    /// The identifier of the last message returned by the iterator.
+    ///
+    /// If no message has been returned yet, this is the offset the iterator started with.
+    /// Passing it to [`MessageIter::offset_id`] on a new iterator will resume the iteration
+    /// right after the last message that was returned, for example, after it stopped early
+    /// because of an error.
+    pub fn last_offset_id(&self) -> i32 {
+        self.request.offset_id
+    }
+
+    /// Changes the hash of the messages that were previously fetched with the same options.
+    ///
+    /// If the messages have not changed since, Telegram will not send them again, and the
+    /// iterator will not return any message. The hash can be computed with
+    /// [`MessageIter::compute_hash`].
+    pub fn hash(mut self, hash: i64) -> Self {
+        self.request.hash = hash;
+        self
+    }
+
+    /// Computes the hash of the given message identifiers, in the order they were returned,
+    /// as described in [hash generation](https://core.telegram.org/api/offsets#hash-generation).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use grammers_client::client::messages::MessageIter;
+    ///
+    /// assert_eq!(MessageIter::compute_hash([1, 2, 3]), 565224272838726);
+    /// ```
+    pub fn compute_hash<I: IntoIterator<Item = i32>>(ids: I) -> i64 {
+        ids.into_iter().fold(0u64, |mut hash, id| {
+            hash ^= hash >> 21;
+            hash ^= hash << 35;
+            hash ^= hash >> 4;
+            hash.wrapping_add(id as u64)
+        }) as i64
+    }
+
     /// Determines how many messages there are in total.
     ///
     /// This only performs a network call if `next` has not been called before.
@@ -233,21 +491,25 @@ impl MessageIter {
     ///
     /// Returns `None` if the `limit` is reached or there are no messages left.
     pub async fn next(&mut self) -> Result<Option<Message>, InvocationError> {
-        if let Some(result) = self.next_raw() {
-            return result;
-        }
-
-        self.request.limit = self.determine_limit(MAX_LIMIT);
-        self.fill_buffer(self.request.limit).await?;
+        let message = match self.next_raw() {
+            Some(result) => result?,
+            None => {
+                self.request.limit = self.determine_limit(MAX_LIMIT);
+                if let Err(e) = self.fill_buffer(self.request.limit).await {
+                    return self.fetch_failed(e);
+                }
+                self.pop_item()
+            }
+        };
 
-        // Don't bother updating offsets if this is the last time stuff has to be fetched.
-        if !self.last_chunk && !self.buffer.is_empty() {
-            let last = &self.buffer[self.buffer.len() - 1];
-            self.request.offset_id = last.raw.id;
-            self.request.offset_date = last.raw.date;
+        // The offsets always point to the last message returned, so that the next request
+        // continues right after it, and so that the iteration can be resumed from there.
+        if let Some(message) = &message {
+            self.request.offset_id = message.raw.id;
+            self.request.offset_date = message.raw.date;
         }
 
-        Ok(self.pop_item())
+        Ok(message)
     }
 }
 
@@ -362,7 +624,9 @@ impl SearchIter {
         }
 
         self.request.limit = self.determine_limit(MAX_LIMIT);
-        self.fill_buffer(self.request.limit).await?;
+        if let Err(e) = self.fill_buffer(self.request.limit).await {
+            return self.fetch_failed(e);
+        }
 
         // Don't bother updating offsets if this is the last time stuff has to be fetched.
         if !self.last_chunk && !self.buffer.is_empty() {
@@ -435,7 +699,10 @@ impl GlobalSearchIter {
         }
 
         self.request.limit = self.determine_limit(MAX_LIMIT);
-        let offset_rate = self.fill_buffer(self.request.limit).await?;
+        let offset_rate = match self.fill_buffer(self.request.limit).await {
+            Ok(offset_rate) => offset_rate,
+            Err(e) => return self.fetch_failed(e),
+        };
 
         // Don't bother updating offsets if this is the last time stuff has to be fetched.
         if !self.last_chunk && !self.buffer.is_empty() {
@@ -484,73 +751,19 @@ impl Client {
     ) -> Result<Message, InvocationError> {
         let chat = chat.into();
         let message = message.into();
-        let random_id = generate_random_id();
+        let random_id = send_random_id(&message);
         let entities = parse_mention_entities(self, message.entities.clone());
         let updates = if let Some(media) = message.media.clone() {
-            self.invoke(&tl::functions::messages::SendMedia {
-                silent: message.silent,
-                background: message.background,
-                clear_draft: message.clear_draft,
-                peer: chat.to_input_peer(),
-                reply_to: message.reply_to.map(|reply_to_msg_id| {
-                    tl::types::InputReplyToMessage {
-                        reply_to_msg_id,
-                        top_msg_id: None,
-                        reply_to_peer_id: None,
-                        quote_text: None,
-                        quote_entities: None,
-                        quote_offset: None,
-                    }
-                    .into()
-                }),
-                media,
-                message: message.text.clone(),
-                random_id,
-                reply_markup: message.reply_markup.clone(),
-                entities,
-                schedule_date: message.schedule_date,
-                send_as: None,
-                noforwards: false,
-                update_stickersets_order: false,
-                invert_media: message.invert_media,
-                quick_reply_shortcut: None,
-                effect: None,
-            })
+            self.invoke(&send_media_request(
+                chat, &message, media, random_id, entities,
+            ))
             .await
         } else {
-            self.invoke(&tl::functions::messages::SendMessage {
-                no_webpage: !message.link_preview,
-                silent: message.silent,
-                background: message.background,
-                clear_draft: message.clear_draft,
-                peer: chat.to_input_peer(),
-                reply_to: message.reply_to.map(|reply_to_msg_id| {
-                    tl::types::InputReplyToMessage {
-                        reply_to_msg_id,
-                        top_msg_id: None,
-                        reply_to_peer_id: None,
-                        quote_text: None,
-                        quote_entities: None,
-                        quote_offset: None,
-                    }
-                    .into()
-                }),
-                message: message.text.clone(),
-                random_id,
-                reply_markup: message.reply_markup.clone(),
-                entities,
-                schedule_date: message.schedule_date,
-                send_as: None,
-                noforwards: false,
-                update_stickersets_order: false,
-                invert_media: message.invert_media,
-                quick_reply_shortcut: None,
-                effect: None,
-            })
-            .await
+            self.invoke(&send_message_request(chat, &message, random_id, entities))
+                .await
         }?;
 
-        Ok(match updates {
+        let message = match updates {
             tl::enums::Updates::UpdateShortSentMessage(updates) => {
                 Message::from_raw_short_updates(self, updates, message, chat)
             }
@@ -575,7 +788,97 @@ impl Client {
                     }
                 }
             }
-        })
+        };
+        self.mark_offline_after_send().await;
+        Ok(message)
+    }
+
+    /// Mark the user as offline if configured to do so after sending messages.
+    ///
+    /// The messages were sent regardless, so failing to do this is not an error.
+    async fn mark_offline_after_send(&self) {
+        if self.0.config.params.offline_after_send {
+            if let Err(e) = self.set_online(false).await {
+                warn!("failed to mark the user as offline after sending: {e}");
+            }
+        }
+    }
+
+    /// Get the animated effects that can be used when sending messages.
+    ///
+    /// The server will return an error when trying to send a message with an effect that the
+    /// logged-in user cannot use, such as those that require Telegram Premium.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMessage;
+    ///
+    /// let effects = client.get_available_effects().await?;
+    /// if let Some(effect) = effects.iter().find(|effect| !effect.premium_required) {
+    ///     client
+    ///         .send_message(&chat, InputMessage::text("Surprise!").effect(effect.id))
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_available_effects(
+        &self,
+    ) -> Result<Vec<types::MessageEffect>, InvocationError> {
+        self.invoke(&tl::functions::messages::GetAvailableEffects { hash: 0 })
+            .await
+            .map(types::MessageEffect::from_raw_list)
+    }
+
+    /// Sends the same message to multiple chats.
+    ///
+    /// The message is sent to each chat in order. If the message contains a file that was
+    /// uploaded with [`Client::upload_file`], the file is only sent over the network once, and
+    /// the copy the server stored is reused when sending the message to the rest of chats.
+    ///
+    /// The result of sending the message to each chat is returned in the same order as the
+    /// chats, so that a failure to send to some of them doesn't prevent sending to the rest.
+    ///
+    /// If the message has a [random identifier](types::InputMessage::random_id), every chat
+    /// gets a different one derived from it by adding the chat's position, so that retrying
+    /// the same call still can't send the message twice to any of the chats.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chats: Vec<grammers_client::types::Chat>, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMessage;
+    ///
+    /// let uploaded = client.upload_file("/path/to/photo.jpg").await?;
+    /// let message = InputMessage::text("Look at this!").photo(uploaded);
+    ///
+    /// for result in client.send_to_many(&chats, message).await {
+    ///     if let Err(e) = result {
+    ///         println!("Failed to send the message: {e}");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_to_many<C: Into<PackedChat>, M: Into<types::InputMessage>>(
+        &self,
+        chats: impl IntoIterator<Item = C>,
+        message: M,
+    ) -> Vec<Result<Message, InvocationError>> {
+        let mut message = message.into();
+        let mut results = Vec::new();
+        for (i, chat) in chats.into_iter().enumerate() {
+            let mut chat_message = message.clone();
+            chat_message.random_id = message.random_id.map(|id| id.wrapping_add(i as i64));
+            let result = self.send_message(chat, chat_message).await;
+            if let Ok(sent) = &result {
+                reuse_uploaded_media(&mut message.media, sent.media());
+            }
+            results.push(result);
+        }
+        results
     }
 
     /// Sends a album to the desired chat.
@@ -611,29 +914,8 @@ impl Client {
 
         // Upload external files
         for media in medias.iter_mut() {
-            let raw_media = media.media.clone().unwrap();
-
-            if matches!(
-                raw_media,
-                tl::enums::InputMedia::UploadedPhoto(_)
-                    | tl::enums::InputMedia::PhotoExternal(_)
-                    | tl::enums::InputMedia::UploadedDocument(_)
-                    | tl::enums::InputMedia::DocumentExternal(_)
-            ) {
-                let uploaded = self
-                    .invoke(&tl::functions::messages::UploadMedia {
-                        business_connection_id: None,
-                        peer: chat.to_input_peer(),
-                        media: raw_media,
-                    })
-                    .await?;
-                media.media = Some(
-                    types::Media::from_raw(uploaded)
-                        .unwrap()
-                        .to_raw_input_media()
-                        .unwrap(),
-                );
-            }
+            let raw_media = media.media.take().unwrap();
+            media.media = Some(self.upload_media(chat, raw_media).await?);
         }
 
         let first_media = medias.first().unwrap();
@@ -680,13 +962,46 @@ impl Client {
             })
             .await?;
 
-        Ok(map_random_ids_to_messages(self, &random_ids, updates))
+        let messages = map_random_ids_to_messages(self, &random_ids, updates);
+        self.mark_offline_after_send().await;
+        Ok(messages)
+    }
+
+    /// Upload media referring to local files (or to external files) into the chat, so that it
+    /// can be used where only media already stored by Telegram is accepted.
+    async fn upload_media(
+        &self,
+        chat: PackedChat,
+        media: tl::enums::InputMedia,
+    ) -> Result<tl::enums::InputMedia, InvocationError> {
+        if !matches!(
+            media,
+            tl::enums::InputMedia::UploadedPhoto(_)
+                | tl::enums::InputMedia::PhotoExternal(_)
+                | tl::enums::InputMedia::UploadedDocument(_)
+                | tl::enums::InputMedia::DocumentExternal(_)
+        ) {
+            return Ok(media);
+        }
+
+        let uploaded = self
+            .invoke(&tl::functions::messages::UploadMedia {
+                business_connection_id: None,
+                peer: chat.to_input_peer(),
+                media,
+            })
+            .await?;
+        Ok(types::Media::from_raw(uploaded)
+            .unwrap()
+            .to_raw_input_media()
+            .unwrap())
     }
 
     /// Edits an existing message.
     ///
     /// Similar to [`Client::send_message`], advanced formatting can be achieved with the
-    /// options offered by [`InputMessage`].
+    /// options offered by [`InputMessage`]. Media referring to local files is uploaded into the
+    /// chat first.
     ///
     /// See also: [`Message::edit`].
     ///
@@ -700,6 +1015,19 @@ impl Client {
     /// # }
     /// ```
     ///
+    /// The media of the message can also be replaced, along with its caption:
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMessage;
+    ///
+    /// let old_message_id = 123;
+    /// let photo = client.upload_file("photo.jpg").await?;
+    /// client.edit_message(&chat, old_message_id, InputMessage::text("New caption").photo(photo)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// [`InputMessage`]: crate::InputMessage
     // TODO don't require nasty InputPeer
     pub async fn edit_message<C: Into<PackedChat>, M: Into<types::InputMessage>>(
@@ -708,15 +1036,20 @@ impl Client {
         message_id: i32,
         new_message: M,
     ) -> Result<(), InvocationError> {
+        let chat = chat.into();
         let new_message = new_message.into();
+        let media = match new_message.media {
+            Some(media) => Some(self.upload_media(chat, media).await?),
+            None => None,
+        };
         let entities = parse_mention_entities(self, new_message.entities);
         self.invoke(&tl::functions::messages::EditMessage {
-            no_webpage: !new_message.link_preview,
+            no_webpage: new_message.no_webpage,
             invert_media: new_message.invert_media,
-            peer: chat.into().to_input_peer(),
+            peer: chat.to_input_peer(),
             id: message_id,
             message: Some(new_message.text),
-            media: new_message.media,
+            media,
             reply_markup: new_message.reply_markup,
             entities,
             schedule_date: new_message.schedule_date,
@@ -727,6 +1060,132 @@ impl Client {
         Ok(())
     }
 
+    /// Replaces the media of an existing message, along with its caption.
+    ///
+    /// Media referring to local files, such as those uploaded with [`Client::upload_file`], is
+    /// uploaded into the chat first. Telegram may refuse to edit messages that are too old.
+    ///
+    /// See also: [`Message::edit_media`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMedia;
+    ///
+    /// let old_message_id = 123;
+    /// let photo = client.upload_file("photo.jpg").await?;
+    /// client.edit_message_media(&chat, old_message_id, InputMedia::caption("New photo").photo(photo)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn edit_message_media<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+        message_id: i32,
+        media: InputMedia,
+    ) -> Result<(), InvocationError> {
+        let chat = chat.into();
+        let raw_media = match media.media {
+            Some(raw_media) => Some(self.upload_media(chat, raw_media).await?),
+            None => None,
+        };
+        let entities = parse_mention_entities(self, media.entities);
+        self.invoke(&tl::functions::messages::EditMessage {
+            no_webpage: false,
+            invert_media: false,
+            peer: chat.to_input_peer(),
+            id: message_id,
+            message: Some(media.caption),
+            media: raw_media,
+            reply_markup: None,
+            entities,
+            schedule_date: None,
+            quick_reply_shortcut_id: None,
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Edits the reply markup of an existing message, leaving its text and media untouched.
+    ///
+    /// See also: [`Message::edit_reply_markup`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::{button, reply_markup};
+    ///
+    /// let old_message_id = 123;
+    /// let markup = reply_markup::inline(vec![vec![button::inline("Done", b"done")]]);
+    /// client.edit_message_reply_markup(&chat, old_message_id, &markup).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn edit_message_reply_markup<C: Into<PackedChat>, RM: ReplyMarkup>(
+        &self,
+        chat: C,
+        message_id: i32,
+        markup: &RM,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::messages::EditMessage {
+            no_webpage: false,
+            invert_media: false,
+            peer: chat.into().to_input_peer(),
+            id: message_id,
+            message: None,
+            media: None,
+            reply_markup: Some(markup.to_reply_markup().raw),
+            entities: None,
+            schedule_date: None,
+            quick_reply_shortcut_id: None,
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Updates the position of a live location sent with [`InputMessage::live_location`].
+    ///
+    /// See also: [`Message::edit_live_location`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(chat: grammers_client::types::Chat, client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let live_location_id = 123;
+    /// client.edit_live_location(&chat, live_location_id, 40.4, -3.7).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`InputMessage::live_location`]: crate::InputMessage::live_location
+    pub async fn edit_live_location<C: Into<PackedChat>>(
+        &self,
+        chat: C,
+        message_id: i32,
+        lat: f64,
+        long: f64,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::messages::EditMessage {
+            no_webpage: false,
+            invert_media: false,
+            peer: chat.into().to_input_peer(),
+            id: message_id,
+            message: None,
+            media: Some(input_live_location(lat, long, None)),
+            reply_markup: None,
+            entities: None,
+            schedule_date: None,
+            quick_reply_shortcut_id: None,
+        })
+        .await?;
+
+        Ok(())
+    }
+
     /// Deletes up to 100 messages in a chat.
     ///
     /// <div class="stab unstable">
@@ -849,28 +1308,6 @@ impl Client {
         &self,
         message: &Message,
     ) -> Result<Option<Message>, InvocationError> {
-        /// Helper method to fetch a single message by its input message.
-        async fn get_message(
-            client: &Client,
-            chat: PackedChat,
-            id: tl::enums::InputMessage,
-        ) -> Result<(tl::enums::messages::Messages, bool), InvocationError> {
-            if let Some(channel) = chat.try_to_input_channel() {
-                client
-                    .invoke(&tl::functions::channels::GetMessages {
-                        id: vec![id],
-                        channel,
-                    })
-                    .await
-                    .map(|res| (res, false))
-            } else {
-                client
-                    .invoke(&tl::functions::messages::GetMessages { id: vec![id] })
-                    .await
-                    .map(|res| (res, true))
-            }
-        }
-
         // TODO shouldn't this method take in a message id anyway?
         let chat = message.chat().pack();
         let reply_to_message_id = match message.reply_to_message_id() {
@@ -881,33 +1318,19 @@ impl Client {
         let input_id =
             tl::enums::InputMessage::ReplyTo(tl::types::InputMessageReplyTo { id: message.raw.id });
 
-        let (res, filter_req) = match get_message(self, chat, input_id).await {
-            Ok(tup) => tup,
+        let request = GetMessages::new(chat, vec![input_id]);
+        let filter_req = !request.is_channel();
+        let res = match request.invoke(self).await {
+            Ok(res) => res,
             Err(_) => {
                 let input_id = tl::enums::InputMessage::Id(tl::types::InputMessageId {
                     id: reply_to_message_id,
                 });
-                get_message(self, chat, input_id).await?
+                GetMessages::new(chat, vec![input_id]).invoke(self).await?
             }
         };
 
-        use tl::enums::messages::Messages;
-
-        let (messages, users, chats) = match res {
-            Messages::Messages(m) => (m.messages, m.users, m.chats),
-            Messages::Slice(m) => (m.messages, m.users, m.chats),
-            Messages::ChannelMessages(m) => (m.messages, m.users, m.chats),
-            Messages::NotModified(_) => {
-                panic!("API returned Messages::NotModified even though GetMessages was used")
-            }
-        };
-
-        let chats = ChatMap::new(users, chats);
-        Ok(messages
-            .into_iter()
-            .flat_map(|m| Message::from_raw(self, m, &chats))
-            .next()
-            .filter(|m| !filter_req || m.raw.peer_id == message.raw.peer_id))
+        Ok(reply_from_response(self, message, res, filter_req))
     }
 
     /// Iterate over the message history of a chat, from most recent to oldest.
@@ -973,13 +1396,16 @@ impl Client {
         GlobalSearchIter::new(self)
     }
 
-    /// Get up to 100 messages using their ID.
+    /// Get messages using their ID.
     ///
     /// Returns the new retrieved messages in a list. Those messages that could not be retrieved
     /// or do not belong to the input chat will be `None`. The length of the resulting list is the
     /// same as the length of the input message IDs, and the indices from the list of IDs map to
     /// the indices in the result so you can map them into the new list.
     ///
+    /// Telegram only allows fetching up to 100 messages at a time, so more IDs than that will
+    /// be fetched with multiple requests.
+    ///
     /// # Examples
     ///
     /// ```
@@ -998,35 +1424,16 @@ impl Client {
         message_ids: &[i32],
     ) -> Result<Vec<Option<Message>>, InvocationError> {
         let chat = chat.into();
-        let id = message_ids
-            .iter()
-            .map(|&id| tl::enums::InputMessage::Id(tl::types::InputMessageId { id }))
-            .collect();
-
-        let result = if let Some(channel) = chat.try_to_input_channel() {
-            self.invoke(&tl::functions::channels::GetMessages { channel, id })
-                .await
-        } else {
-            self.invoke(&tl::functions::messages::GetMessages { id })
-                .await
-        }?;
-
-        let (messages, users, chats) = match result {
-            tl::enums::messages::Messages::Messages(m) => (m.messages, m.users, m.chats),
-            tl::enums::messages::Messages::Slice(m) => (m.messages, m.users, m.chats),
-            tl::enums::messages::Messages::ChannelMessages(m) => (m.messages, m.users, m.chats),
-            tl::enums::messages::Messages::NotModified(_) => {
-                panic!("API returned Messages::NotModified even though GetMessages was used")
-            }
-        };
+        let mut map = HashMap::with_capacity(message_ids.len());
+        for message_ids in message_ids.chunks(MAX_LIMIT) {
+            let id = message_ids
+                .iter()
+                .map(|&id| tl::enums::InputMessage::Id(tl::types::InputMessageId { id }))
+                .collect();
 
-        let chats = ChatMap::new(users, chats);
-        let mut map = messages
-            .into_iter()
-            .flat_map(|m| Message::from_raw(self, m, &chats))
-            .filter(|m| m.chat().pack() == chat)
-            .map(|m| (m.raw.id, m))
-            .collect::<HashMap<_, _>>();
+            let result = GetMessages::new(chat, id).invoke(self).await?;
+            map.extend(messages_by_id(self, chat, result));
+        }
 
         Ok(message_ids.iter().map(|id| map.remove(id)).collect())
     }
@@ -1055,13 +1462,7 @@ impl Client {
         // TODO return types::Message and print its text in the example
         let id = vec![tl::enums::InputMessage::Pinned];
 
-        let result = if let Some(channel) = chat.try_to_input_channel() {
-            self.invoke(&tl::functions::channels::GetMessages { channel, id })
-                .await
-        } else {
-            self.invoke(&tl::functions::messages::GetMessages { id })
-                .await
-        }?;
+        let result = GetMessages::new(chat, id).invoke(self).await?;
 
         let (messages, users, chats) = match result {
             tl::enums::messages::Messages::Messages(m) => (m.messages, m.users, m.chats),
@@ -1218,3 +1619,645 @@ impl Client {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+
+    #[test]
+    fn check_history_hash() {
+        assert_eq!(MessageIter::compute_hash([]), 0);
+        assert_eq!(MessageIter::compute_hash([1, 2, 3]), 565224272838726);
+        assert_eq!(
+            MessageIter::compute_hash([100, 99, 98, 97, 96]),
+            313271983672802173
+        );
+        assert_eq!(
+            MessageIter::compute_hash([i32::MAX, 5, i32::MAX]),
+            3816827293657923588
+        );
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_edit_media_uploads_file() {
+        use grammers_mtsender::mock::MockSender;
+
+        let photo = tl::types::Photo {
+            has_stickers: false,
+            id: 1,
+            access_hash: 2,
+            file_reference: vec![3],
+            date: 0,
+            sizes: vec![tl::types::PhotoSize {
+                r#type: "x".to_string(),
+                w: 1,
+                h: 1,
+                size: 1,
+            }
+            .into()],
+            video_sizes: None,
+            dc_id: 2,
+        };
+
+        let mut sender = MockSender::new();
+        sender
+            .expect::<tl::functions::upload::SaveFilePart>()
+            .respond(true);
+        sender
+            .expect_if(|r: &tl::functions::messages::UploadMedia| {
+                matches!(r.media, tl::enums::InputMedia::UploadedPhoto(_))
+            })
+            .respond(
+                tl::types::MessageMediaPhoto {
+                    spoiler: false,
+                    photo: Some(photo.into()),
+                    ttl_seconds: None,
+                }
+                .into(),
+            );
+        sender
+            .expect_if(|r: &tl::functions::messages::EditMessage| {
+                let uploaded_photo = match &r.media {
+                    Some(tl::enums::InputMedia::Photo(media)) => match &media.id {
+                        tl::enums::InputPhoto::Photo(photo) => photo.id == 1,
+                        tl::enums::InputPhoto::Empty => false,
+                    },
+                    _ => false,
+                };
+                r.id == 5 && r.message.as_deref() == Some("New photo") && uploaded_photo
+            })
+            .respond(tl::types::UpdatesTooLong {}.into());
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            let message = Message::from_raw(
+                &client,
+                tl::types::Message {
+                    id: 5,
+                    ..EMPTY_MESSAGE
+                }
+                .into(),
+                &ChatMap::empty(),
+            )
+            .unwrap();
+
+            let data = [1; 16];
+            let uploaded = client
+                .upload_stream(
+                    &mut std::io::Cursor::new(&data),
+                    data.len(),
+                    "photo.jpg".to_string(),
+                )
+                .await
+                .unwrap();
+            message
+                .edit_media(InputMedia::caption("New photo").photo(uploaded))
+                .await
+                .unwrap();
+
+            let mock = mock.lock().await;
+            assert_eq!(mock.invoked().len(), 3);
+            assert_eq!(mock.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_send_to_many_uses_distinct_random_ids() {
+        use grammers_mtsender::mock::MockSender;
+
+        let mut sender = MockSender::new();
+        for (random_id, chat_id) in [(100, 1), (101, 2), (102, 3)] {
+            sender
+                .expect_if(move |r: &tl::functions::messages::SendMessage| {
+                    r.random_id == random_id
+                        && r.peer
+                            == tl::types::InputPeerUser {
+                                user_id: chat_id,
+                                access_hash: 0,
+                            }
+                            .into()
+                })
+                .respond(
+                    tl::types::UpdateShortSentMessage {
+                        out: true,
+                        id: chat_id as i32,
+                        pts: 1,
+                        pts_count: 1,
+                        date: 0,
+                        media: None,
+                        entities: None,
+                        ttl_period: None,
+                    }
+                    .into(),
+                );
+        }
+
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            let chats = (1..=3).map(|id| PackedChat {
+                ty: grammers_session::PackedType::User,
+                id,
+                access_hash: Some(0),
+            });
+
+            let message = types::InputMessage::text("Hi all").random_id(100);
+            let results = client.send_to_many(chats, message).await;
+            assert!(results.iter().all(Result::is_ok));
+            assert_eq!(mock.lock().await.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_messages_by_id_skip_empty() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chat = PackedChat {
+                ty: grammers_session::PackedType::User,
+                id: 1,
+                access_hash: None,
+            };
+            let message = |id| {
+                tl::types::Message {
+                    id,
+                    peer_id: chat.to_peer(),
+                    ..EMPTY_MESSAGE
+                }
+                .into()
+            };
+
+            let mut map = messages_by_id(
+                &client,
+                chat,
+                tl::types::messages::Messages {
+                    messages: vec![
+                        message(10),
+                        tl::types::MessageEmpty {
+                            id: 20,
+                            peer_id: None,
+                        }
+                        .into(),
+                        message(30),
+                    ],
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+            );
+
+            let messages = [10, 20, 30]
+                .iter()
+                .map(|id| map.remove(id).map(|m| m.id()))
+                .collect::<Vec<_>>();
+            assert_eq!(messages, vec![Some(10), None, Some(30)]);
+        });
+    }
+
+    #[test]
+    fn check_custom_random_id_is_sent() {
+        use tl::{Deserializable, Serializable};
+
+        let chat = PackedChat {
+            ty: grammers_session::PackedType::User,
+            id: 1,
+            access_hash: Some(2),
+        };
+        let message = types::InputMessage::text("once").random_id(1234);
+        let request = send_message_request(chat, &message, send_random_id(&message), None);
+        // Functions are deserialized without their constructor identifier.
+        let request =
+            tl::functions::messages::SendMessage::from_bytes(&request.to_bytes()[4..]).unwrap();
+        assert_eq!(request.random_id, 1234);
+
+        // Retrying with the same message reuses the identifier, which Telegram deduplicates.
+        assert_eq!(send_random_id(&message.clone()), 1234);
+
+        let message = types::InputMessage::text("twice");
+        assert_ne!(send_random_id(&message), send_random_id(&message));
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_history_starts_at_offset_date() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chat = PackedChat {
+                ty: grammers_session::PackedType::User,
+                id: 1,
+                access_hash: Some(2),
+            };
+            let date = DateTime::parse_from_rfc3339("2022-12-25T00:00:00-00:00").unwrap();
+            let mut iter = MessageIter::new(&client, chat)
+                .offset_id(50)
+                .offset_date(&date);
+            assert_eq!(iter.request.offset_id, 0);
+            assert_eq!(iter.request.offset_date, 1671926400);
+
+            let message = |id, date| {
+                tl::types::Message {
+                    id,
+                    date,
+                    peer_id: chat.to_peer(),
+                    ..EMPTY_MESSAGE
+                }
+                .into()
+            };
+            iter.extend_buffer(
+                tl::types::messages::MessagesSlice {
+                    inexact: false,
+                    count: 100,
+                    next_rate: None,
+                    offset_id_offset: None,
+                    messages: vec![message(20, 1671926000), message(19, 1671925000)],
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+                2,
+            );
+            while !iter.buffer.is_empty() {
+                iter.next().await.unwrap();
+            }
+
+            // The next page continues right after the last message returned.
+            assert_eq!(iter.request.offset_id, 19);
+            assert_eq!(iter.request.offset_date, 1671925000);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_not_modified_history_is_empty() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chat = PackedChat {
+                ty: grammers_session::PackedType::User,
+                id: 1,
+                access_hash: Some(2),
+            };
+            let mut iter = MessageIter::new(&client, chat).hash(1234);
+            assert_eq!(iter.request.hash, 1234);
+
+            let rate = iter.extend_buffer(
+                tl::types::messages::MessagesNotModified { count: 10 }.into(),
+                100,
+            );
+            assert_eq!(rate, None);
+            assert_eq!(iter.total().await.unwrap(), 10);
+            assert!(iter.next().await.unwrap().is_none());
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_total_is_known_after_first_page() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chat = PackedChat {
+                ty: grammers_session::PackedType::User,
+                id: 1,
+                access_hash: Some(2),
+            };
+            let messages = || {
+                [30, 20, 10]
+                    .into_iter()
+                    .map(|id| {
+                        tl::types::Message {
+                            id,
+                            ..EMPTY_MESSAGE
+                        }
+                        .into()
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            // A slice only contains some of the messages.
+            let mut iter = MessageIter::new(&client, chat);
+            assert_eq!(iter.known_total(), None);
+            iter.extend_buffer(
+                tl::types::messages::MessagesSlice {
+                    inexact: false,
+                    count: 100,
+                    next_rate: None,
+                    offset_id_offset: None,
+                    messages: messages(),
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+                3,
+            );
+            assert_eq!(iter.known_total(), Some(100));
+
+            // A channel's slice works the same way.
+            let mut iter = MessageIter::new(&client, chat);
+            iter.extend_buffer(
+                tl::types::messages::ChannelMessages {
+                    inexact: false,
+                    pts: 1,
+                    count: 50,
+                    offset_id_offset: None,
+                    messages: messages(),
+                    topics: Vec::new(),
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+                3,
+            );
+            assert_eq!(iter.known_total(), Some(50));
+
+            // But all of them are returned otherwise.
+            let mut iter = MessageIter::new(&client, chat);
+            iter.extend_buffer(
+                tl::types::messages::Messages {
+                    messages: messages(),
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+                3,
+            );
+            assert_eq!(iter.known_total(), Some(3));
+            assert_eq!(iter.total().await.unwrap(), 3);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_history_stops_on_error_and_can_resume() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chat = PackedChat {
+                ty: grammers_session::PackedType::User,
+                id: 1,
+                access_hash: Some(2),
+            };
+            let mut iter = MessageIter::new(&client, chat).stop_on_error(true);
+
+            // First page.
+            iter.extend_buffer(
+                tl::types::messages::MessagesSlice {
+                    inexact: false,
+                    count: 100,
+                    next_rate: None,
+                    offset_id_offset: None,
+                    messages: [90, 80, 70]
+                        .into_iter()
+                        .map(|id| {
+                            tl::types::Message {
+                                id,
+                                date: id * 10,
+                                ..EMPTY_MESSAGE
+                            }
+                            .into()
+                        })
+                        .collect(),
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into(),
+                3,
+            );
+            for id in [90, 80, 70] {
+                assert_eq!(iter.next().await.unwrap().unwrap().id(), id);
+            }
+            assert_eq!(iter.last_offset_id(), 70);
+            assert_eq!(iter.request.offset_date, 700);
+
+            // Second page.
+            let result = iter.fetch_failed::<Message>(InvocationError::Dropped);
+            assert!(result.unwrap().is_none());
+            assert!(iter.next().await.unwrap().is_none());
+            assert!(matches!(iter.take_error(), Some(InvocationError::Dropped)));
+            assert_eq!(iter.last_offset_id(), 70);
+
+            let resumed = MessageIter::new(&client, chat).offset_id(iter.last_offset_id());
+            assert_eq!(resumed.request.offset_id, 70);
+
+            // Non-transient errors are always propagated.
+            let error = InvocationError::Rpc(grammers_mtsender::RpcError {
+                code: 400,
+                name: "PEER_ID_INVALID".to_string(),
+                value: None,
+                caused_by: None,
+            });
+            assert!(iter.fetch_failed::<Message>(error).is_err());
+        });
+    }
+
+    fn sent_photo() -> types::Media {
+        types::Media::from_raw(
+            tl::types::MessageMediaPhoto {
+                spoiler: false,
+                photo: Some(
+                    tl::types::Photo {
+                        has_stickers: false,
+                        id: 1,
+                        access_hash: 2,
+                        file_reference: vec![3],
+                        date: 0,
+                        sizes: Vec::new(),
+                        video_sizes: None,
+                        dc_id: 2,
+                    }
+                    .into(),
+                ),
+                ttl_seconds: None,
+            }
+            .into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn check_uploaded_media_is_reused_once() {
+        let mut media = Some(
+            tl::types::InputMediaUploadedPhoto {
+                spoiler: true,
+                file: tl::types::InputFile {
+                    id: 10,
+                    parts: 1,
+                    name: "photo.jpg".to_string(),
+                    md5_checksum: String::new(),
+                }
+                .into(),
+                stickers: None,
+                ttl_seconds: None,
+            }
+            .into(),
+        );
+
+        // Sending to the first chat uploads the file, and the rest reuse the sent photo.
+        assert!(reuse_uploaded_media(&mut media, Some(sent_photo())));
+        let expected = tl::enums::InputMedia::Photo(tl::types::InputMediaPhoto {
+            spoiler: true,
+            id: tl::types::InputPhoto {
+                id: 1,
+                access_hash: 2,
+                file_reference: vec![3],
+            }
+            .into(),
+            ttl_seconds: None,
+        });
+        assert_eq!(media, Some(expected.clone()));
+
+        for _ in 0..5 {
+            assert!(!reuse_uploaded_media(&mut media, Some(sent_photo())));
+            assert_eq!(media, Some(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn check_other_media_is_not_reused() {
+        let mut media = None;
+        assert!(!reuse_uploaded_media(&mut media, Some(sent_photo())));
+        assert_eq!(media, None);
+
+        let mut media = Some(
+            tl::types::InputMediaPhotoExternal {
+                spoiler: false,
+                url: "https://example.com/cat.jpg".to_string(),
+                ttl_seconds: None,
+            }
+            .into(),
+        );
+        let original = media.clone();
+        assert!(!reuse_uploaded_media(&mut media, Some(sent_photo())));
+        assert_eq!(media, original);
+    }
+
+    #[test]
+    fn check_effect_is_sent() {
+        use grammers_tl_types::Serializable;
+
+        let chat = PackedChat {
+            ty: grammers_session::PackedType::User,
+            id: 1,
+            access_hash: Some(2),
+        };
+
+        let message = types::InputMessage::text("hi");
+        let request = send_message_request(chat, &message, 0, None);
+        assert_eq!(request.effect, None);
+        let plain = request.to_bytes();
+
+        let message = message.effect(5104841245755180586);
+        let request = send_message_request(chat, &message, 0, None);
+        assert_eq!(request.effect, Some(5104841245755180586));
+        let bytes = request.to_bytes();
+        assert_eq!(bytes.len(), plain.len() + 8);
+        assert!(bytes.ends_with(&5104841245755180586i64.to_le_bytes()));
+
+        let media = tl::enums::InputMedia::Empty;
+        let request = send_media_request(chat, &message, media, 0, None);
+        assert_eq!(request.effect, Some(5104841245755180586));
+    }
+
+    #[test]
+    fn check_get_messages_uses_chat_peer() {
+        let id = vec![tl::enums::InputMessage::Id(tl::types::InputMessageId {
+            id: 7,
+        })];
+
+        let channel = PackedChat {
+            ty: grammers_session::PackedType::Megagroup,
+            id: 1,
+            access_hash: Some(2),
+        };
+        match GetMessages::new(channel, id.clone()) {
+            GetMessages::Channel(request) => {
+                assert_eq!(
+                    request.channel,
+                    tl::types::InputChannel {
+                        channel_id: 1,
+                        access_hash: 2,
+                    }
+                    .into()
+                );
+                assert_eq!(request.id, id);
+            }
+            request => panic!("unexpected request: {request:?}"),
+        }
+
+        let user = PackedChat {
+            ty: grammers_session::PackedType::User,
+            id: 1,
+            access_hash: Some(2),
+        };
+        match GetMessages::new(user, id.clone()) {
+            GetMessages::Common(request) => assert_eq!(request.id, id),
+            request => panic!("unexpected request: {request:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_get_reply() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let peer = tl::enums::Peer::User(tl::types::PeerUser { user_id: 1 });
+            let message = |id, peer_id, reply_to_msg_id: Option<i32>| {
+                let reply_to = reply_to_msg_id.map(|reply_to_msg_id| {
+                    tl::types::MessageReplyHeader {
+                        reply_to_scheduled: false,
+                        forum_topic: false,
+                        quote: false,
+                        reply_to_msg_id: Some(reply_to_msg_id),
+                        reply_to_peer_id: None,
+                        reply_from: None,
+                        reply_media: None,
+                        reply_to_top_id: None,
+                        quote_text: None,
+                        quote_entities: None,
+                        quote_offset: None,
+                    }
+                    .into()
+                });
+                tl::types::Message {
+                    id,
+                    peer_id,
+                    reply_to,
+                    ..EMPTY_MESSAGE
+                }
+            };
+            let response = |message: tl::types::Message| {
+                tl::types::messages::Messages {
+                    messages: vec![message.into()],
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                }
+                .into()
+            };
+
+            // Not a reply, so there is nothing to fetch (or the offline client would fail).
+            let chats = ChatMap::empty();
+            let original =
+                Message::from_raw(&client, message(2, peer.clone(), None).into(), &chats).unwrap();
+            assert!(original.get_reply().await.unwrap().is_none());
+
+            let original =
+                Message::from_raw(&client, message(2, peer.clone(), Some(1)).into(), &chats)
+                    .unwrap();
+
+            // The reply must come from the same chat unless it's a channel.
+            let other = tl::enums::Peer::User(tl::types::PeerUser { user_id: 3 });
+            let res = response(message(1, other.clone(), None));
+            assert!(reply_from_response(&client, &original, res, true).is_none());
+            let res = response(message(1, other, None));
+            assert!(reply_from_response(&client, &original, res, false).is_some());
+
+            let res = response(message(1, peer, None));
+            let reply = reply_from_response(&client, &original, res, true).unwrap();
+            assert_eq!(reply.id(), 1);
+
+            // Once fetched, the reply is cached for all clones.
+            original.reply.set(Some(reply)).unwrap();
+            let reply = original.clone().get_reply().await.unwrap().unwrap();
+            assert_eq!(reply.id(), 1);
+        });
+    }
+}
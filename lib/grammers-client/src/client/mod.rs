@@ -14,6 +14,8 @@ pub mod dialogs;
 pub mod files;
 pub mod messages;
 pub mod net;
+pub mod stickers;
+pub mod takeout;
 pub mod updates;
 
 pub use auth::SignInError;
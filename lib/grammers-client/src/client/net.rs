@@ -6,24 +6,30 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use super::client::{ClientState, Connection};
-use super::{Client, ClientInner, Config};
+use super::{Client, ClientInner, Config, InitParams};
 use crate::utils;
+use grammers_crypto::rsa;
 use grammers_mtproto::mtp;
 use grammers_mtproto::transport;
 use grammers_mtsender::ServerAddr;
 use grammers_mtsender::{
-    self as sender, utils::sleep, AuthorizationError, InvocationError, RpcError, Sender,
+    self as sender, utils::sleep, AuthorizationError, ConnectionState, InvocationError, RpcError,
+    Sender,
 };
-use grammers_session::{ChatHashCache, MessageBox};
+use grammers_session::{ChatHashCache, MessageBox, Session};
 use grammers_tl_types::{self as tl, Deserializable};
-use log::{debug, info};
+use log::{debug, info, warn};
 use sender::Enqueuer;
-use std::collections::{HashMap, VecDeque};
-use std::net::Ipv4Addr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, RwLock};
-use tokio::sync::oneshot::error::TryRecvError;
-use tokio::sync::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tokio::sync::{watch, Mutex as AsyncMutex, OnceCell};
+
+/// Where the serialized result of an enqueued request will be sent.
+type ResultReceiver = oneshot::Receiver<Result<Vec<u8>, InvocationError>>;
 
 /// Socket addresses to Telegram datacenters, where the index into this array
 /// represents the data center ID.
@@ -63,22 +69,32 @@ pub(crate) type Transport = transport::Obfuscated<transport::Intermediate>;
 
 const DEFAULT_DC: i32 = 2;
 
+/// How long to wait before retrying a request that failed due to a transient server error.
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 pub(crate) async fn connect_sender(
     dc_id: i32,
     config: &Config,
-) -> Result<(Sender<Transport, mtp::Encrypted>, Enqueuer), AuthorizationError> {
-    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
-    let transport = transport::Full::new();
-
-    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
-    let transport = transport::Obfuscated::new(transport::Intermediate::new());
+) -> Result<Connection, AuthorizationError> {
+    connect_sender_with_keys(dc_id, config, &[]).await
+}
 
+/// Like [`connect_sender`], but a new authorization key may also be generated using the given
+/// RSA keys (needed by CDN datacenters).
+async fn connect_sender_with_keys(
+    dc_id: i32,
+    config: &Config,
+    keys: &[rsa::Key],
+) -> Result<Connection, AuthorizationError> {
     let addr: ServerAddr = if let Some(ref sa) = config.params.server_addr {
         sa.clone()
     } else {
         #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
         let addr = {
-            let tcp_addr = DC_ADDRESSES[dc_id as usize].into();
+            let tcp_addr = config
+                .session
+                .dc_addr(dc_id)
+                .unwrap_or_else(|| DC_ADDRESSES[dc_id as usize].into());
 
             #[cfg(not(feature = "proxy"))]
             let addr = ServerAddr::Tcp { address: tcp_addr };
@@ -104,70 +120,149 @@ pub(crate) async fn connect_sender(
         addr
     };
 
-    let (mut sender, request_tx) = if let Some(auth_key) = config.session.dc_auth_key(dc_id) {
+    let auth_key = config.session.dc_auth_key(dc_id);
+    if auth_key.is_some() {
         info!(
             "creating a new sender with existing auth key to dc {} {:?}",
             dc_id, addr
         );
-
-        sender::connect_with_auth(transport, addr, auth_key, config.params.reconnection_policy)
-            .await?
     } else {
         info!(
             "creating a new sender and auth key in dc {} {:?}",
             dc_id, addr
         );
+    }
 
-        let (sender, tx) =
-            sender::connect(transport, addr.clone(), config.params.reconnection_policy).await?;
+    let connection = dial_network(addr.clone(), auth_key, keys, config).await?;
+    if auth_key.is_none() {
+        let auth_key = connection.sender.lock().await.auth_key();
+        store_auth_key(&config.session, dc_id, &addr, auth_key);
+    }
+    Ok(connection)
+}
 
-        match addr {
-            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
-            ServerAddr::Tcp { ref address, .. } => {
-                config
-                    .session
-                    .insert_dc_tcp(dc_id, address, sender.auth_key());
-            }
-            #[cfg(all(
-                not(all(target_arch = "wasm32", target_os = "unknown")),
-                feature = "proxy"
-            ))]
-            ServerAddr::Proxied { ref address, .. } => {
-                config
-                    .session
-                    .insert_dc_tcp(dc_id, address, sender.auth_key());
-            }
-            #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
-            ServerAddr::Ws { ref address } => {
-                config
-                    .session
-                    .insert_dc_ws(dc_id, address, sender.auth_key());
-            }
+/// Turn the failure to connect into an [`InvocationError`], for the methods that can only return
+/// that. Failing to generate the authorization key is reported as invalid data being read.
+pub(crate) fn connect_error(error: AuthorizationError) -> InvocationError {
+    match error {
+        AuthorizationError::Invoke(e) => e,
+        AuthorizationError::Gen(e) => {
+            InvocationError::Read(std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
         }
-        (sender, tx)
-    };
+    }
+}
+
+/// Open a new connection to the address and initialize it, either with the given authorization
+/// key, or generating a new one with the given RSA keys (or the well-known ones if empty).
+async fn dial_network(
+    addr: ServerAddr,
+    auth_key: Option<[u8; 256]>,
+    keys: &[rsa::Key],
+    config: &Config,
+) -> Result<Connection, AuthorizationError> {
+    let (mut sender, request_tx) = open_sender(addr, auth_key, keys, config).await?;
 
     // TODO handle -404 (we had a previously-valid authkey, but server no longer knows about it)
     // TODO all up-to-date server addresses should be stored in the session for future initial connections
-    let _remote_config = sender
-        .invoke(&tl::functions::InvokeWithLayer {
-            layer: tl::LAYER,
-            query: tl::functions::InitConnection {
-                api_id: config.api_id,
-                device_model: config.params.device_model.clone(),
-                system_version: config.params.system_version.clone(),
-                app_version: config.params.app_version.clone(),
-                system_lang_code: config.params.system_lang_code.clone(),
-                lang_pack: "".into(),
-                lang_code: config.params.lang_code.clone(),
-                proxy: None,
-                params: None,
-                query: tl::functions::help::GetConfig {},
-            },
-        })
-        .await?;
+    //      (for now, this must be done explicitly via `Client::refresh_dc_options`)
+    let _remote_config = sender.invoke(&init_connection(config)).await?;
+
+    Ok(Connection::new(sender, request_tx))
+}
+
+/// Open a new connection to the address with the parameters of the configuration, without
+/// initializing it.
+async fn open_sender(
+    addr: ServerAddr,
+    auth_key: Option<[u8; 256]>,
+    keys: &[rsa::Key],
+    config: &Config,
+) -> Result<(Sender<Transport, mtp::Encrypted>, Enqueuer), AuthorizationError> {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    let transport = transport::Full::new();
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    let transport = transport::Obfuscated::new(transport::Intermediate::new());
+
+    let params = config.params.connection_params;
+    let policy = config.params.reconnection_policy;
+    Ok(match auth_key {
+        Some(auth_key) => {
+            let mtp = mtp::Encrypted::build().finish(auth_key);
+            sender::connect_with_params(transport, mtp, addr, params, policy).await?
+        }
+        None => {
+            let mtp = mtp::Plain::new();
+            let (sender, request_tx) =
+                sender::connect_with_params(transport, mtp, addr, params, policy).await?;
+            sender::generate_auth_key_with_keys(sender, request_tx, keys).await?
+        }
+    })
+}
+
+/// Persist a newly-generated authorization key for the datacenter at the given address, so that
+/// later connections to it can reuse the key instead of generating a new one.
+fn store_auth_key(session: &Session, dc_id: i32, addr: &ServerAddr, auth_key: [u8; 256]) {
+    match addr {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        ServerAddr::Tcp { address } => {
+            session.insert_dc_tcp(dc_id, address, auth_key);
+        }
+        #[cfg(all(
+            not(all(target_arch = "wasm32", target_os = "unknown")),
+            feature = "proxy"
+        ))]
+        ServerAddr::Proxied { address, .. } => {
+            session.insert_dc_tcp(dc_id, address, auth_key);
+        }
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        ServerAddr::Ws { address } => {
+            session.insert_dc_ws(dc_id, address, auth_key);
+        }
+    }
+}
+
+/// The request that must be sent first on every new connection, so that Telegram knows which
+/// layer and application parameters to use for the rest of the requests sent through it.
+fn init_connection(
+    config: &Config,
+) -> tl::functions::InvokeWithLayer<tl::functions::InitConnection<tl::functions::help::GetConfig>> {
+    tl::functions::InvokeWithLayer {
+        layer: tl::LAYER,
+        query: tl::functions::InitConnection {
+            api_id: config.api_id,
+            device_model: config.params.device_model.clone(),
+            system_version: config.params.system_version.clone(),
+            app_version: config.params.app_version.clone(),
+            system_lang_code: config.params.system_lang_code.clone(),
+            lang_pack: config.params.lang_pack.clone(),
+            lang_code: config.params.lang_code.clone(),
+            proxy: None,
+            params: None,
+            query: tl::functions::help::GetConfig {},
+        },
+    }
+}
 
-    Ok((sender, request_tx))
+/// Store the address of every datacenter usable by the client in the session.
+///
+/// Only the first option for each datacenter and IP version is used, as that's the one
+/// Telegram would prefer clients to use.
+fn apply_dc_options(session: &Session, options: &[tl::enums::DcOption]) {
+    let mut seen = HashSet::new();
+    for tl::enums::DcOption::Option(option) in options {
+        // CDN datacenters have their own IDs, so they can be stored alongside the rest.
+        if option.media_only || option.tcpo_only {
+            continue;
+        }
+        let ip = match option.ip_address.parse::<IpAddr>() {
+            Ok(ip) if ip.is_ipv6() == option.ipv6 => ip,
+            _ => continue,
+        };
+        if seen.insert((option.id, option.ipv6)) {
+            session.set_dc_addr(option.id, &SocketAddr::new(ip, option.port as u16));
+        }
+    }
 }
 
 /// Method implementations directly related with network connectivity.
@@ -206,7 +301,7 @@ impl Client {
             .get_user()
             .map(|u| u.dc)
             .unwrap_or(DEFAULT_DC);
-        let (sender, request_tx) = connect_sender(dc_id, &config).await?;
+        let conn = connect_sender(dc_id, &config).await?;
         let message_box = if config.params.catch_up {
             if let Some(state) = config.session.get_state() {
                 MessageBox::load(state)
@@ -217,7 +312,11 @@ impl Client {
             // If the user doesn't want to bother with catching up on previous update, start with
             // pristine state instead.
             MessageBox::new()
-        };
+        }
+        .with_timeouts(
+            config.params.no_updates_timeout,
+            config.params.possible_gap_timeout,
+        );
 
         // Pre-allocate the right `VecDeque` size if a limit is given.
         let updates = if let Some(limit) = config.params.update_queue_limit {
@@ -240,15 +339,18 @@ impl Client {
         let client = Self(Arc::new(ClientInner {
             id: utils::generate_random_id(),
             config,
-            conn: Connection::new(sender, request_tx),
+            conn,
             state: RwLock::new(ClientState {
                 dc_id,
                 message_box,
                 chat_hashes: ChatHashCache::new(self_user.map(|u| (u.id, u.bot))),
                 last_update_limit_warn: None,
                 updates,
+                album: None,
+                self_user: None,
+                catching_up: false,
             }),
-            downloader_map: AsyncRwLock::new(HashMap::new()),
+            downloader_map: Mutex::new(HashMap::new()),
         }));
 
         if should_get_state {
@@ -296,14 +398,33 @@ impl Client {
         &self,
         request: &R,
     ) -> Result<R::Return, InvocationError> {
-        self.0
+        let result = self
+            .0
             .conn
-            .invoke(
-                request,
-                self.0.config.params.flood_sleep_threshold,
-                |updates| self.process_socket_updates(updates),
-            )
-            .await
+            .invoke(request, &self.0.config.params, |updates| {
+                self.process_socket_updates(updates)
+            })
+            .await;
+        self.check_auth_key_registered(result).await
+    }
+
+    /// Turn an `AUTH_KEY_UNREGISTERED` error while logged in into [`InvocationError::AuthKeyInvalid`].
+    ///
+    /// The logged-in account was terminated elsewhere, so retrying cannot succeed. The dead key is
+    /// removed from the session so that it's not loaded again, and the client becomes logged out.
+    /// When not logged in, the error simply means the user has yet to sign in, so it's kept as-is.
+    pub(crate) async fn check_auth_key_registered<T>(
+        &self,
+        result: Result<T, InvocationError>,
+    ) -> Result<T, InvocationError> {
+        match result {
+            Err(e) if e.is("AUTH_KEY_UNREGISTERED") && self.0.config.session.signed_in() => {
+                warn!("authorization key is no longer registered; logging out");
+                self.reset_authorization().await;
+                Err(InvocationError::AuthKeyInvalid)
+            }
+            result => result,
+        }
     }
 
     async fn export_authorization(
@@ -321,12 +442,83 @@ impl Client {
         }
     }
 
+    /// Connect to a CDN datacenter, which uses its own RSA keys and needs no authorization.
+    async fn connect_cdn_sender(&self, dc_id: i32) -> Result<Arc<Connection>, InvocationError> {
+        self.downloader_cell(dc_id)
+            .get_or_try_init(|| self.connect_cdn(dc_id))
+            .await
+            .cloned()
+    }
+
+    async fn connect_cdn(&self, dc_id: i32) -> Result<Arc<Connection>, InvocationError> {
+        let session = &self.0.config.session;
+        if self.0.config.params.server_addr.is_none() && session.dc_addr(dc_id).is_none() {
+            self.refresh_dc_options().await?;
+            if session.dc_addr(dc_id).is_none() {
+                return Err(InvocationError::Rpc(RpcError {
+                    code: 400,
+                    name: "DC_ID_INVALID".to_string(),
+                    value: None,
+                    caused_by: None,
+                }));
+            }
+        }
+
+        let keys = if session.dc_auth_key(dc_id).is_none() {
+            let tl::enums::CdnConfig::Config(config) =
+                self.invoke(&tl::functions::help::GetCdnConfig {}).await?;
+            config
+                .public_keys
+                .into_iter()
+                .filter_map(|tl::enums::CdnPublicKey::Key(key)| {
+                    (key.dc_id == dc_id)
+                        .then(|| rsa::Key::from_pem(&key.public_key))
+                        .flatten()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        debug!("Connecting new CDN datacenter {}", dc_id);
+        match connect_sender_with_keys(dc_id, &self.0.config, &keys).await {
+            Ok(connection) => Ok(Arc::new(connection)),
+            Err(e) => Err(connect_error(e)),
+        }
+    }
+
+    /// Invoke a request in a CDN datacenter, connecting to it first if needed.
+    pub(crate) async fn invoke_in_cdn<R: tl::RemoteCall>(
+        &self,
+        request: &R,
+        dc_id: i32,
+    ) -> Result<R::Return, InvocationError> {
+        self.connect_cdn_sender(dc_id)
+            .await?
+            .invoke(request, &self.0.config.params, drop)
+            .await
+    }
+
+    /// The cell holding the connection to the datacenter, which may not be connected yet.
+    fn downloader_cell(&self, dc_id: i32) -> Arc<OnceCell<Arc<Connection>>> {
+        let mut map = self.0.downloader_map.lock().unwrap();
+        Arc::clone(map.entry(dc_id).or_default())
+    }
+
     async fn connect_sender(&self, dc_id: i32) -> Result<Arc<Connection>, InvocationError> {
-        let mut mutex = self.0.downloader_map.write().await;
+        // Only one task connects, while the rest wait for it. Importing the authorization more
+        // than once concurrently can fail with `AUTH_BYTES_INVALID`.
+        self.downloader_cell(dc_id)
+            .get_or_try_init(|| self.connect_and_import(dc_id))
+            .await
+            .cloned()
+    }
+
+    async fn connect_and_import(&self, dc_id: i32) -> Result<Arc<Connection>, InvocationError> {
         debug!("Connecting new datacenter {}", dc_id);
         match connect_sender(dc_id, &self.0.config).await {
-            Ok((new_sender, new_tx)) => {
-                let new_downloader = Arc::new(Connection::new(new_sender, new_tx));
+            Ok(connection) => {
+                let new_downloader = Arc::new(connection);
 
                 // export auth
                 let authorization = self.export_authorization(dc_id).await?;
@@ -337,38 +529,113 @@ impl Client {
                     bytes: authorization.bytes,
                 };
                 new_downloader
-                    .invoke(&request, self.0.config.params.flood_sleep_threshold, drop)
+                    .invoke(&request, &self.0.config.params, drop)
                     .await?;
 
-                mutex.insert(dc_id, new_downloader.clone());
-                Ok(new_downloader.clone())
-            }
-            Err(AuthorizationError::Invoke(e)) => Err(e),
-            Err(AuthorizationError::Gen(e)) => {
-                panic!("authorization key generation failed: {e}")
+                Ok(new_downloader)
             }
+            Err(e) => Err(connect_error(e)),
         }
     }
 
-    async fn get_downloader(&self, dc_id: i32) -> Result<Option<Arc<Connection>>, InvocationError> {
-        return Ok({
-            let guard = self.0.downloader_map.read().await;
-            guard.get(&dc_id).cloned()
-        });
+    /// Reconnect the main connection to a different datacenter, making it the new home.
+    ///
+    /// The authorization is not transferred, so this is only meant to be used while signing in.
+    /// The new datacenter is persisted to the session once the login completes.
+    pub(crate) async fn migrate_home_dc(&self, dc_id: i32) -> Result<(), AuthorizationError> {
+        info!("migrating home datacenter to {}", dc_id);
+        let connection = connect_sender(dc_id, &self.0.config).await?;
+        self.0.conn.replace(connection).await;
+        self.0.state.write().unwrap().dc_id = dc_id;
+        Ok(())
     }
 
+    /// Make sure there is an authorized connection to the given datacenter, connecting to it if
+    /// needed.
+    ///
+    /// If the session has no authorization key for the datacenter, a new one is generated and
+    /// saved into the session. The current authorization is then exported into the connection,
+    /// which will be reused by later calls to [`Client::invoke_in_dc`] with the same datacenter.
+    ///
+    /// Calling this method is never required, but it can be used to connect ahead of time, for
+    /// example, before downloading media stored in a different datacenter.
+    ///
+    /// If `dc_id` is the client's own datacenter, this does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.connect_to_dc(4).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect_to_dc(&self, dc_id: i32) -> Result<(), InvocationError> {
+        if dc_id != self.0.state.read().unwrap().dc_id {
+            self.connect_sender(dc_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Invoke a raw API call in the given datacenter, rather than the one the client is
+    /// connected to.
+    ///
+    /// If `dc_id` is the client's own datacenter, this is the same as [`Client::invoke`].
+    /// Otherwise, the first call for that datacenter will open a new connection to it, and
+    /// export the current authorization into it, so that it can be reused by later calls.
+    ///
+    /// <div class="stab unstable">
+    ///
+    /// **Warning**: this method is **not** part of the stability guarantees of semantic
+    /// versioning. It **may** break during *minor* version changes (but not on patch version
+    /// changes). Use with care.
+    ///
+    /// </div>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_tl_types as tl;
+    ///
+    /// let config = client.invoke_in_dc(&tl::functions::help::GetConfig {}, 4).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub async fn invoke_in_dc<R: tl::RemoteCall>(
         &self,
         request: &R,
         dc_id: i32,
     ) -> Result<R::Return, InvocationError> {
-        let downloader = match self.get_downloader(dc_id).await? {
-            None => self.connect_sender(dc_id).await?,
-            Some(fd) => fd,
-        };
-        downloader
-            .invoke(request, self.0.config.params.flood_sleep_threshold, drop)
+        if dc_id == self.0.state.read().unwrap().dc_id {
+            return self.invoke(request).await;
+        }
+
+        let downloader = self.connect_sender(dc_id).await?;
+        match downloader
+            .invoke(request, &self.0.config.params, drop)
             .await
+        {
+            Err(e) if e.is("AUTH_KEY_UNREGISTERED") => {
+                // The imported authorization is no longer valid, so it must be imported again.
+                // Only forget the connection if no other task has already replaced it.
+                {
+                    let mut map = self.0.downloader_map.lock().unwrap();
+                    if map
+                        .get(&dc_id)
+                        .and_then(|cell| cell.get())
+                        .is_some_and(|current| Arc::ptr_eq(current, &downloader))
+                    {
+                        map.remove(&dc_id);
+                    }
+                }
+                self.connect_sender(dc_id)
+                    .await?
+                    .invoke(request, &self.0.config.params, drop)
+                    .await
+            }
+            result => result,
+        }
     }
 
     /// Perform a single network step.
@@ -376,6 +643,10 @@ impl Client {
     /// Most commonly, you will want to use the higher-level abstraction [`Client::next_update`]
     /// instead.
     ///
+    /// The client does not spawn any task to drive the connection, so calling this method in a
+    /// loop from any task is enough for requests to make progress. This makes it possible to use
+    /// the client in single-threaded runtimes, or to integrate it into an existing event loop.
+    ///
     /// # Examples
     ///
     /// ```
@@ -393,6 +664,62 @@ impl Client {
         Ok(())
     }
 
+    /// The current state of the connection to the home datacenter.
+    ///
+    /// The state changes while [`Client::step`] is being called, according to the
+    /// [`crate::ReconnectionPolicy`] configured in the [`crate::InitParams`].
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.0.conn.state.read().unwrap().borrow()
+    }
+
+    /// Subscribe to changes in the state of the connection to the home datacenter.
+    ///
+    /// This can be used, for example, to show that the client is reconnecting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::ConnectionState;
+    ///
+    /// let mut state = client.watch_connection_state();
+    /// while state.changed().await.is_ok() {
+    ///     if let ConnectionState::Reconnecting { attempt } = *state.borrow() {
+    ///         println!("Reconnecting (attempt {attempt})...");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.0.conn.state.read().unwrap().clone()
+    }
+
+    /// Fetch the up-to-date list of datacenter addresses from Telegram, and store them in the
+    /// session, so that future connections (including those made when migrating to a different
+    /// datacenter) use them.
+    ///
+    /// The authorization keys to each datacenter are preserved. Media-only datacenters are
+    /// ignored, as are those which require a transport with obfuscation.
+    ///
+    /// The session must be saved afterwards for the changes to persist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.refresh_dc_options().await?;
+    /// client.session().save_to_file("hello-world.session")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn refresh_dc_options(&self) -> Result<(), InvocationError> {
+        let tl::enums::Config::Config(config) =
+            self.invoke(&tl::functions::help::GetConfig {}).await?;
+        apply_dc_options(&self.0.config.session, &config.dc_options);
+        Ok(())
+    }
+
     /// Run the client by repeatedly calling [`Client::step`] until a graceful disconnection
     /// occurs, or a network error occurs. Incoming updates are ignored and simply dropped.
     /// instead.
@@ -413,48 +740,210 @@ impl Client {
     }
 }
 
+/// Lets tests connect a [`Client`] to a local [`MockServer`], including the connections to other
+/// datacenters, or to a socket that's never served, without needing access to the network.
+///
+/// [`MockServer`]: grammers_mtsender::mock::MockServer
+#[cfg(all(test, not(all(target_arch = "wasm32", target_os = "unknown"))))]
+pub(crate) mod mock {
+    use super::*;
+    use grammers_mtsender::mock::MockSender;
+
+    pub(crate) type SharedMock = Arc<AsyncMutex<MockSender>>;
+
+    /// The authorization key stored for the datacenter in the session of mocked clients.
+    pub(crate) fn auth_key(dc_id: i32) -> [u8; 256] {
+        [dc_id as u8; 256]
+    }
+
+    /// A sender connected to a local socket that will never be driven.
+    pub(crate) async fn unserved_sender(
+        auth_key: [u8; 256],
+    ) -> (Sender<Transport, mtp::Encrypted>, Enqueuer) {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        sender::connect_with_auth(
+            Transport::new(),
+            ServerAddr::Tcp {
+                address: listener.local_addr().unwrap(),
+            },
+            auth_key,
+            &sender::NoReconnect,
+        )
+        .await
+        .unwrap()
+    }
+}
+
+/// Run the future to completion on a new single-threaded runtime, as tests need.
+#[cfg(test)]
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[cfg(test)]
+impl Client {
+    /// Creates a client connected to a local socket that will never be driven, so that tests may
+    /// exercise the state kept by the client without needing access to Telegram's servers.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub(crate) async fn offline_for_tests() -> Self {
+        let (sender, request_tx) = mock::unserved_sender([0; 256]).await;
+        Self::for_tests(Config::for_tests(), Connection::new(sender, request_tx))
+    }
+
+    /// Creates a client whose requests, to any datacenter, are answered by the `sender`.
+    ///
+    /// The returned mock can be used to inspect the requests that were made.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub(crate) async fn mocked_for_tests(
+        sender: grammers_mtsender::mock::MockSender,
+    ) -> (Self, mock::SharedMock) {
+        let dc_ids = 1..=DC_ADDRESSES.len() as i32 - 1;
+        let auth_keys = dc_ids.clone().map(mock::auth_key).collect::<Vec<_>>();
+        let server = grammers_mtsender::mock::MockServer::start(sender, &auth_keys)
+            .await
+            .unwrap();
+
+        // Every datacenter is served by the same mock, with a known key, as it can't make new ones.
+        let mut config = Config::for_tests();
+        config.params.server_addr = Some(ServerAddr::Tcp {
+            address: server.address(),
+        });
+        for dc_id in dc_ids {
+            config
+                .session
+                .insert_dc_tcp(dc_id, &server.address(), mock::auth_key(dc_id));
+        }
+
+        let conn = connect_sender(DEFAULT_DC, &config).await.unwrap();
+        (Self::for_tests(config, conn), Arc::clone(server.sender()))
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn for_tests(config: Config, conn: Connection) -> Self {
+        Self(Arc::new(ClientInner {
+            id: utils::generate_random_id(),
+            config,
+            conn,
+            state: RwLock::new(ClientState {
+                dc_id: DEFAULT_DC,
+                message_box: MessageBox::new(),
+                chat_hashes: ChatHashCache::new(None),
+                last_update_limit_warn: None,
+                updates: VecDeque::new(),
+                album: None,
+                self_user: None,
+                catching_up: false,
+            }),
+            downloader_map: Mutex::new(HashMap::new()),
+        }))
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn for_tests() -> Self {
+        Self {
+            session: grammers_session::Session::new(),
+            api_id: 0,
+            api_hash: String::new(),
+            params: Default::default(),
+        }
+    }
+}
+
 impl Connection {
     fn new(sender: Sender<Transport, mtp::Encrypted>, request_tx: Enqueuer) -> Self {
         Self {
+            state: RwLock::new(sender.connection_state()),
             sender: AsyncMutex::new(sender),
             request_tx: RwLock::new(request_tx),
             step_counter: AtomicU32::new(0),
         }
     }
 
+    /// Take over the sender of a different connection, such as one to a different datacenter.
+    async fn replace(&self, other: Connection) {
+        *self.state.write().unwrap() = other.state.into_inner().unwrap();
+        *self.sender.lock().await = other.sender.into_inner();
+        *self.request_tx.write().unwrap() = other.request_tx.into_inner().unwrap();
+    }
+
     pub(crate) async fn invoke<R: tl::RemoteCall, F: Fn(Vec<tl::enums::Updates>)>(
         &self,
         request: &R,
-        flood_sleep_threshold: u32,
+        params: &InitParams,
+        on_updates: F,
+    ) -> Result<R::Return, InvocationError> {
+        self.invoke_with::<R, _, _>(params, on_updates, |tx| tx.enqueue(request))
+            .await
+    }
+
+    /// Like [`Connection::invoke`], but `ack_result` is used as the result if the server only
+    /// acknowledges the request and its answer is lost (see [`Enqueuer::enqueue_with_ack_result`]).
+    pub(crate) async fn invoke_with_ack_result<R: tl::RemoteCall, F: Fn(Vec<tl::enums::Updates>)>(
+        &self,
+        request: &R,
+        ack_result: &R::Return,
+        params: &InitParams,
         on_updates: F,
+    ) -> Result<R::Return, InvocationError>
+    where
+        R::Return: tl::Serializable,
+    {
+        self.invoke_with::<R, _, _>(params, on_updates, |tx| {
+            tx.enqueue_with_ack_result(request, ack_result)
+        })
+        .await
+    }
+
+    async fn invoke_with<
+        R: tl::RemoteCall,
+        F: Fn(Vec<tl::enums::Updates>),
+        E: Fn(&Enqueuer) -> ResultReceiver,
+    >(
+        &self,
+        params: &InitParams,
+        on_updates: F,
+        enqueue: E,
     ) -> Result<R::Return, InvocationError> {
-        let mut slept_flood = false;
+        let mut retries = Retries::new(params);
 
-        let mut rx = { self.request_tx.read().unwrap().enqueue(request) };
         loop {
-            match rx.try_recv() {
-                Ok(response) => match response {
-                    Ok(body) => break R::Return::from_bytes(&body).map_err(|e| e.into()),
-                    Err(InvocationError::Rpc(RpcError {
-                        name,
-                        code: 420,
-                        value: Some(seconds),
-                        ..
-                    })) if !slept_flood && seconds <= flood_sleep_threshold => {
-                        let delay = std::time::Duration::from_secs(seconds as _);
+            match self.send(&on_updates, &enqueue).await {
+                Ok(body) => break R::Return::from_bytes(&body).map_err(|e| e.into()),
+                Err(InvocationError::Rpc(error)) => match retries.delay_for(&error) {
+                    Some(delay) => {
                         info!(
                             "sleeping on {} for {:?} before retrying {}",
-                            name,
+                            error.name,
                             delay,
                             std::any::type_name::<R>()
                         );
                         sleep(delay).await;
-                        slept_flood = true;
-                        rx = self.request_tx.read().unwrap().enqueue(request);
                         continue;
                     }
-                    Err(e) => break Err(e),
+                    None => break Err(InvocationError::Rpc(error)),
                 },
+                Err(e) => break Err(e),
+            }
+        }
+    }
+
+    /// Enqueue the request once, and wait for its serialized response.
+    async fn send<F: Fn(Vec<tl::enums::Updates>), E: Fn(&Enqueuer) -> ResultReceiver>(
+        &self,
+        on_updates: &F,
+        enqueue: &E,
+    ) -> Result<Vec<u8>, InvocationError> {
+        let mut rx = { enqueue(&self.request_tx.read().unwrap()) };
+        loop {
+            match rx.try_recv() {
+                Ok(response) => break response,
                 Err(TryRecvError::Empty) => {
                     on_updates(self.step().await?);
                 }
@@ -480,3 +969,472 @@ impl Connection {
         }
     }
 }
+
+/// Keeps track of which errors a request may still be retried on.
+struct Retries {
+    flood_sleep_threshold: u32,
+    slept_flood: bool,
+    transient_retries_left: u32,
+}
+
+impl Retries {
+    fn new(params: &InitParams) -> Self {
+        Self {
+            flood_sleep_threshold: params.flood_sleep_threshold,
+            slept_flood: false,
+            transient_retries_left: params.transient_error_retries,
+        }
+    }
+
+    /// How long to wait before retrying the request that failed with the given error, if it
+    /// should be retried at all.
+    fn delay_for(&mut self, error: &RpcError) -> Option<Duration> {
+        if let Some(seconds) = error.flood_wait() {
+            if !self.slept_flood && seconds <= self.flood_sleep_threshold {
+                self.slept_flood = true;
+                return Some(Duration::from_secs(seconds as _));
+            }
+        } else if error.is_transient() && self.transient_retries_left > 0 {
+            self.transient_retries_left -= 1;
+            return Some(TRANSIENT_RETRY_DELAY);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn ensure_home_dc_uses_main_connection() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+
+            // Nobody is serving the connection, so the request can only fail, but no new
+            // connection to another datacenter should have been made for it.
+            let request = tl::functions::help::GetConfig {};
+            assert!(client.invoke_in_dc(&request, DEFAULT_DC).await.is_err());
+            assert!(client.0.downloader_map.lock().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_dc_connections_are_reused() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+
+            // The home datacenter is always connected.
+            client.connect_to_dc(DEFAULT_DC).await.unwrap();
+            assert!(client.0.downloader_map.lock().unwrap().is_empty());
+
+            // Any existing connection is reused rather than authorizing a new one.
+            let (sender, request_tx) = mock::unserved_sender([0; 256]).await;
+            let connection = Arc::new(Connection::new(sender, request_tx));
+            client.0.downloader_map.lock().unwrap().insert(
+                4,
+                Arc::new(OnceCell::new_with(Some(Arc::clone(&connection)))),
+            );
+
+            client.connect_to_dc(4).await.unwrap();
+            let map = client.0.downloader_map.lock().unwrap();
+            assert_eq!(map.len(), 1);
+            assert!(Arc::ptr_eq(map[&4].get().unwrap(), &connection));
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_requests_are_answered_by_mock() {
+        block_on(async {
+            let mut sender = grammers_mtsender::mock::MockSender::new();
+            sender.expect::<tl::functions::Ping>().flood_wait(0);
+            sender.expect::<tl::functions::Ping>().respond(
+                tl::types::Pong {
+                    msg_id: 0,
+                    ping_id: 42,
+                }
+                .into(),
+            );
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+
+            // The flood wait is short enough to be retried by the client itself.
+            let tl::enums::Pong::Pong(pong) = client
+                .invoke(&tl::functions::Ping { ping_id: 42 })
+                .await
+                .unwrap();
+            assert_eq!(pong.ping_id, 42);
+
+            let mock = mock.lock().await;
+            assert_eq!(mock.invoked().len(), 2);
+            assert_eq!(mock.remaining_rules(), 0);
+        });
+    }
+
+    /// How many of the requests made to the mock had the given constructor identifier.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn count_invoked(mock: &grammers_mtsender::mock::MockSender, constructor_id: u32) -> usize {
+        mock.invoked()
+            .iter()
+            .filter(|body| body[..4] == constructor_id.to_le_bytes())
+            .count()
+    }
+
+    /// Expect the authorization to be exported from the home datacenter and imported once.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn expect_authorization_transfer(sender: &mut grammers_mtsender::mock::MockSender) {
+        sender
+            .expect::<tl::functions::auth::ExportAuthorization>()
+            .respond(
+                tl::types::auth::ExportedAuthorization {
+                    id: 1,
+                    bytes: vec![1, 2, 3],
+                }
+                .into(),
+            );
+        sender
+            .expect::<tl::functions::auth::ImportAuthorization>()
+            .respond(
+                tl::types::auth::AuthorizationSignUpRequired {
+                    terms_of_service: None,
+                }
+                .into(),
+            );
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn get_file_request() -> tl::functions::upload::GetFile {
+        tl::functions::upload::GetFile {
+            precise: false,
+            cdn_supported: false,
+            location: tl::types::InputDocumentFileLocation {
+                id: 1,
+                access_hash: 2,
+                file_reference: Vec::new(),
+                thumb_size: String::new(),
+            }
+            .into(),
+            offset: 0,
+            limit: 1024 * 1024,
+        }
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn get_file_response() -> tl::enums::upload::File {
+        tl::types::upload::File {
+            r#type: tl::enums::storage::FileType::FileUnknown,
+            mtime: 0,
+            bytes: vec![0; 16],
+        }
+        .into()
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_other_dc_imports_authorization_once() {
+        block_on(async {
+            let mut sender = grammers_mtsender::mock::MockSender::new();
+            expect_authorization_transfer(&mut sender);
+            for _ in 0..3 {
+                sender
+                    .expect::<tl::functions::upload::GetFile>()
+                    .respond(get_file_response());
+            }
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            client.0.state.write().unwrap().dc_id = 1;
+
+            let request = get_file_request();
+            for _ in 0..3 {
+                client.invoke_in_dc(&request, DEFAULT_DC).await.unwrap();
+            }
+
+            // Only the first request needed to connect, and the rest reused its connection.
+            let mock = mock.lock().await;
+            use tl::Identifiable;
+            let export_id = tl::functions::auth::ExportAuthorization::CONSTRUCTOR_ID;
+            let import_id = tl::functions::auth::ImportAuthorization::CONSTRUCTOR_ID;
+            assert_eq!(count_invoked(&mock, export_id), 1);
+            assert_eq!(count_invoked(&mock, import_id), 1);
+            assert_eq!(mock.invoked().len(), 5);
+            assert_eq!(mock.remaining_rules(), 0);
+
+            let map = client.0.downloader_map.lock().unwrap();
+            assert_eq!(map.len(), 1);
+            assert!(map[&DEFAULT_DC].initialized());
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_concurrent_downloads_import_authorization_once() {
+        block_on(async {
+            let mut sender = grammers_mtsender::mock::MockSender::new();
+            expect_authorization_transfer(&mut sender);
+            for _ in 0..10 {
+                sender
+                    .expect::<tl::functions::upload::GetFile>()
+                    .respond(get_file_response());
+            }
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            client.0.state.write().unwrap().dc_id = 1;
+
+            // Every download waits for the same connection rather than importing its own.
+            let request = get_file_request();
+            let results = futures_util::future::join_all(
+                (0..10).map(|_| client.invoke_in_dc(&request, DEFAULT_DC)),
+            )
+            .await;
+            assert!(results.iter().all(Result::is_ok));
+
+            let mock = mock.lock().await;
+            use tl::Identifiable;
+            let export_id = tl::functions::auth::ExportAuthorization::CONSTRUCTOR_ID;
+            let import_id = tl::functions::auth::ImportAuthorization::CONSTRUCTOR_ID;
+            assert_eq!(count_invoked(&mock, export_id), 1);
+            assert_eq!(count_invoked(&mock, import_id), 1);
+            assert_eq!(mock.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_generated_auth_keys_are_stored() {
+        let session = Session::new();
+        assert_eq!(session.dc_auth_key(2), None);
+
+        let address = "149.154.167.51:443".parse().unwrap();
+        store_auth_key(&session, 2, &ServerAddr::Tcp { address }, [7; 256]);
+        assert_eq!(session.dc_auth_key(2), Some([7; 256]));
+        assert_eq!(session.dc_addr(2), Some(address));
+        assert_eq!(session.dc_auth_key(4), None);
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_stored_auth_keys_are_reused() {
+        block_on(async {
+            let mut sender = grammers_mtsender::mock::MockSender::new();
+            expect_authorization_transfer(&mut sender);
+            expect_authorization_transfer(&mut sender);
+            let (client, mock) = Client::mocked_for_tests(sender).await;
+            let session = &client.0.config.session;
+            let connection_to = |dc_id| {
+                let map = client.0.downloader_map.lock().unwrap();
+                Arc::clone(map[&dc_id].get().unwrap())
+            };
+            let auth_key = session.dc_auth_key(4).unwrap();
+
+            // The first connection uses the key stored in the session.
+            client.connect_to_dc(4).await.unwrap();
+            let connection = connection_to(4);
+            assert_eq!(connection.sender.lock().await.auth_key(), auth_key);
+
+            // Later calls reuse the same connection without making any request.
+            client.connect_to_dc(4).await.unwrap();
+            assert!(Arc::ptr_eq(&connection_to(4), &connection));
+            assert_eq!(mock.lock().await.invoked().len(), 2);
+
+            // New connections keep using the stored key.
+            client.0.downloader_map.lock().unwrap().clear();
+            client.connect_to_dc(4).await.unwrap();
+            let connection = connection_to(4);
+            assert_eq!(connection.sender.lock().await.auth_key(), auth_key);
+            assert_eq!(session.dc_auth_key(4), Some(auth_key));
+            assert_eq!(mock.lock().await.remaining_rules(), 0);
+        });
+    }
+
+    #[test]
+    fn check_transient_errors_are_retried() {
+        let error = |code, name: &str| RpcError {
+            code,
+            name: name.to_string(),
+            value: None,
+            caused_by: None,
+        };
+        let mut retries = Retries::new(&InitParams {
+            transient_error_retries: 2,
+            ..Default::default()
+        });
+
+        assert_eq!(retries.delay_for(&error(400, "BAD_REQUEST")), None);
+        assert_eq!(
+            retries.delay_for(&error(500, "INTERNAL")),
+            Some(TRANSIENT_RETRY_DELAY)
+        );
+        assert_eq!(
+            retries.delay_for(&error(-503, "TIMEOUT")),
+            Some(TRANSIENT_RETRY_DELAY)
+        );
+        assert_eq!(retries.delay_for(&error(500, "INTERNAL")), None);
+
+        // Not retried by default.
+        let mut retries = Retries::new(&InitParams::default());
+        assert_eq!(retries.delay_for(&error(500, "INTERNAL")), None);
+    }
+
+    #[test]
+    fn check_flood_waits_are_retried_once() {
+        let flood = |seconds| RpcError {
+            code: 420,
+            name: "FLOOD_WAIT".to_string(),
+            value: Some(seconds),
+            caused_by: None,
+        };
+        let mut retries = Retries::new(&InitParams {
+            flood_sleep_threshold: 10,
+            ..Default::default()
+        });
+
+        assert_eq!(retries.delay_for(&flood(11)), None);
+        assert_eq!(retries.delay_for(&flood(5)), Some(Duration::from_secs(5)));
+        assert_eq!(retries.delay_for(&flood(5)), None);
+    }
+
+    #[test]
+    fn check_init_connection_uses_params() {
+        let config = Config {
+            session: Session::new(),
+            api_id: 1234,
+            api_hash: String::new(),
+            params: InitParams {
+                device_model: "Test Device".to_string(),
+                app_version: "9.8.7".to_string(),
+                lang_pack: "android".to_string(),
+                ..Default::default()
+            },
+        };
+
+        let request = init_connection(&config);
+        assert_eq!(request.layer, tl::LAYER);
+        assert_eq!(request.query.api_id, 1234);
+
+        let bytes = tl::Serializable::to_bytes(&request);
+        let contains = |s: &str| bytes.windows(s.len()).any(|w| w == s.as_bytes());
+        assert!(contains("Test Device"));
+        assert!(contains("9.8.7"));
+        assert!(contains("android"));
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_connections_use_params() {
+        let config = Config {
+            session: Session::new(),
+            api_id: 1234,
+            api_hash: String::new(),
+            params: InitParams {
+                connection_params: sender::ConnectionParams {
+                    max_in_flight: Some(3),
+                    wait_for_capacity: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        };
+
+        block_on(async {
+            let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let addr = ServerAddr::Tcp {
+                address: listener.local_addr().unwrap(),
+            };
+            let (sender, _) = open_sender(addr, Some([0; 256]), &[], &config)
+                .await
+                .unwrap();
+
+            let params = sender.connection_params();
+            assert_eq!(params.max_in_flight, Some(3));
+            assert!(!params.wait_for_capacity);
+        });
+    }
+
+    #[test]
+    fn check_init_connection_is_per_client() {
+        let config = |api_id| Config {
+            session: Session::new(),
+            api_id,
+            api_hash: format!("hash{api_id}"),
+            params: Default::default(),
+        };
+        let (first, second) = (config(1111), config(2222));
+
+        assert_eq!(init_connection(&first).query.api_id, 1111);
+        assert_eq!(init_connection(&second).query.api_id, 2222);
+        assert_eq!(init_connection(&first).query.api_id, 1111);
+    }
+
+    fn dc_option(id: i32, ip_address: &str, ipv6: bool, media_only: bool) -> tl::enums::DcOption {
+        tl::types::DcOption {
+            ipv6,
+            media_only,
+            tcpo_only: false,
+            cdn: false,
+            r#static: false,
+            this_port_only: false,
+            id,
+            ip_address: ip_address.to_string(),
+            port: 443,
+            secret: None,
+        }
+        .into()
+    }
+
+    #[test]
+    fn check_dc_options_are_refreshed() {
+        let session = Session::new();
+        session.insert_dc_tcp(2, &"149.154.167.51:443".parse().unwrap(), [2; 256]);
+
+        apply_dc_options(
+            &session,
+            &[
+                dc_option(2, "10.0.0.1", false, true),
+                dc_option(2, "10.0.0.2", false, false),
+                dc_option(2, "10.0.0.3", false, false),
+                dc_option(2, "::2", true, false),
+                dc_option(4, "::4", true, false),
+            ],
+        );
+
+        assert_eq!(session.dc_addr(2), Some("10.0.0.2:443".parse().unwrap()));
+        assert_eq!(session.dc_auth_key(2), Some([2; 256]));
+        assert_eq!(session.dc_addr(4), Some("[::4]:443".parse().unwrap()));
+        assert_eq!(session.dc_auth_key(4), None);
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_unregistered_auth_key_is_cleared() {
+        use grammers_mtsender::mock::MockSender;
+        use grammers_mtsender::RpcError;
+
+        block_on(async {
+            let mut sender = MockSender::new();
+            for _ in 0..2 {
+                sender
+                    .expect::<tl::functions::updates::GetState>()
+                    .fail(RpcError::from(tl::types::RpcError {
+                        error_code: 401,
+                        error_message: "AUTH_KEY_UNREGISTERED".to_string(),
+                    }));
+            }
+            let (client, _) = Client::mocked_for_tests(sender).await;
+            let session = &client.0.config.session;
+            session.insert_dc_tcp(2, &"149.154.167.51:443".parse().unwrap(), [2; 256]);
+
+            // Before signing in, the key is still needed to do so.
+            let result = client.invoke(&tl::functions::updates::GetState {}).await;
+            assert!(matches!(result, Err(InvocationError::Rpc(_))));
+            assert_eq!(session.dc_auth_key(2), Some([2; 256]));
+
+            // Once signed in, the session was terminated, and the key is useless.
+            session.set_user(1234, 2, false);
+            let result = client.invoke(&tl::functions::updates::GetState {}).await;
+            assert!(matches!(result, Err(InvocationError::AuthKeyInvalid)));
+            assert_eq!(session.dc_auth_key(2), None);
+            assert!(!session.signed_in());
+            assert!(client.0.state.read().unwrap().self_user.is_none());
+        });
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Methods related to stickers and sticker sets.
+
+use super::Client;
+use crate::types::media::Sticker;
+use crate::types::{InputStickerSet, StickerSet};
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+
+impl Client {
+    /// Fetch a sticker set, along with all of its stickers.
+    ///
+    /// The set can be referred to by its short name, or by using [`InputStickerSet`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let set = client.get_sticker_set("short_name").await?;
+    ///
+    /// for sticker in set.stickers() {
+    ///     println!("{} (animated: {})", sticker.emoji(), sticker.is_animated());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_sticker_set<S: Into<InputStickerSet>>(
+        &self,
+        set: S,
+    ) -> Result<StickerSet, InvocationError> {
+        match self
+            .invoke(&tl::functions::messages::GetStickerSet {
+                stickerset: set.into().raw,
+                hash: 0,
+            })
+            .await?
+        {
+            tl::enums::messages::StickerSet::Set(set) => Ok(StickerSet::from_raw(set)),
+            tl::enums::messages::StickerSet::NotModified => {
+                panic!("API returned StickerSetNotModified even though hash = 0")
+            }
+        }
+    }
+
+    /// Install a sticker set, so that it shows up in the logged-in user's list of stickers.
+    ///
+    /// If `archived` is `true`, the set will be installed as archived.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.install_sticker_set("short_name", false).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn install_sticker_set<S: Into<InputStickerSet>>(
+        &self,
+        set: S,
+        archived: bool,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::messages::InstallStickerSet {
+            stickerset: set.into().raw,
+            archived,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Uninstall a sticker set, removing it from the logged-in user's list of stickers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// client.uninstall_sticker_set("short_name").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn uninstall_sticker_set<S: Into<InputStickerSet>>(
+        &self,
+        set: S,
+    ) -> Result<(), InvocationError> {
+        self.invoke(&tl::functions::messages::UninstallStickerSet {
+            stickerset: set.into().raw,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Search for stickers associated with the given emoji.
+    ///
+    /// This includes stickers from installed sets as well as suggestions from other sets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// for sticker in client.search_stickers("😀").await? {
+    ///     println!("Found a sticker for {}", sticker.emoji());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn search_stickers(&self, emoji: &str) -> Result<Vec<Sticker>, InvocationError> {
+        Ok(stickers_from_raw(
+            self.invoke(&search_stickers_request(emoji)).await?,
+        ))
+    }
+
+    /// Get the stickers the logged-in user sent most recently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let recent = client.get_recent_stickers().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_recent_stickers(&self) -> Result<Vec<Sticker>, InvocationError> {
+        match self
+            .invoke(&tl::functions::messages::GetRecentStickers {
+                attached: false,
+                hash: 0,
+            })
+            .await?
+        {
+            tl::enums::messages::RecentStickers::Stickers(recent) => Ok(recent
+                .stickers
+                .into_iter()
+                .filter_map(Sticker::from_raw_document)
+                .collect()),
+            tl::enums::messages::RecentStickers::NotModified => {
+                panic!("API returned RecentStickersNotModified even though hash = 0")
+            }
+        }
+    }
+
+    /// Get the covers of the sticker sets currently featured by Telegram.
+    ///
+    /// Each featured set is represented by the stickers shown as its cover, if any. The
+    /// complete set can be fetched with [`Client::get_sticker_set`], by using the set
+    /// the sticker belongs to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let featured = client.get_featured_stickers().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_featured_stickers(&self) -> Result<Vec<Sticker>, InvocationError> {
+        use tl::enums::StickerSetCovered as S;
+
+        match self
+            .invoke(&tl::functions::messages::GetFeaturedStickers { hash: 0 })
+            .await?
+        {
+            tl::enums::messages::FeaturedStickers::Stickers(featured) => Ok(featured
+                .sets
+                .into_iter()
+                .flat_map(|set| match set {
+                    S::Covered(set) => vec![set.cover],
+                    S::StickerSetMultiCovered(set) => set.covers,
+                    S::StickerSetFullCovered(set) => set.documents,
+                    S::StickerSetNoCovered(_) => Vec::new(),
+                })
+                .filter_map(Sticker::from_raw_document)
+                .collect()),
+            tl::enums::messages::FeaturedStickers::NotModified(_) => {
+                panic!("API returned FeaturedStickersNotModified even though hash = 0")
+            }
+        }
+    }
+}
+
+fn search_stickers_request(emoji: &str) -> tl::functions::messages::GetStickers {
+    tl::functions::messages::GetStickers {
+        emoticon: emoji.to_string(),
+        hash: 0,
+    }
+}
+
+fn stickers_from_raw(stickers: tl::enums::messages::Stickers) -> Vec<Sticker> {
+    match stickers {
+        tl::enums::messages::Stickers::Stickers(stickers) => stickers
+            .stickers
+            .into_iter()
+            .filter_map(Sticker::from_raw_document)
+            .collect(),
+        tl::enums::messages::Stickers::NotModified => {
+            panic!("API returned StickersNotModified even though hash = 0")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tl::{Identifiable, Serializable};
+
+    fn document(id: i64, attributes: Vec<tl::enums::DocumentAttribute>) -> tl::enums::Document {
+        tl::types::Document {
+            id,
+            access_hash: 0,
+            file_reference: Vec::new(),
+            date: 0,
+            mime_type: "image/webp".to_string(),
+            size: 0,
+            thumbs: None,
+            video_thumbs: None,
+            dc_id: 2,
+            attributes,
+        }
+        .into()
+    }
+
+    #[test]
+    fn check_search_stickers() {
+        let request = search_stickers_request("😀");
+        assert_eq!(request.emoticon, "😀");
+        assert_eq!(
+            request.to_bytes()[..4],
+            tl::functions::messages::GetStickers::CONSTRUCTOR_ID.to_le_bytes()
+        );
+
+        let stickers = stickers_from_raw(
+            tl::types::messages::Stickers {
+                hash: 0,
+                stickers: vec![
+                    document(
+                        1,
+                        vec![tl::types::DocumentAttributeSticker {
+                            mask: false,
+                            alt: "😀".to_string(),
+                            stickerset: tl::enums::InputStickerSet::Empty,
+                            mask_coords: None,
+                        }
+                        .into()],
+                    ),
+                    // Documents which are not stickers are skipped.
+                    document(2, Vec::new()),
+                ],
+            }
+            .into(),
+        );
+        assert_eq!(stickers.len(), 1);
+        assert_eq!(stickers[0].document.id(), 1);
+        assert_eq!(stickers[0].emoji(), "😀");
+    }
+}
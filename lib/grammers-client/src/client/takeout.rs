@@ -0,0 +1,57 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Methods related to exporting the account's data through takeout sessions.
+
+use super::Client;
+use crate::types::Takeout;
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+
+impl Client {
+    /// Start a takeout session, used to export the logged-in account's own data.
+    ///
+    /// Requests made through the returned [`Takeout`] have relaxed flood limits, which makes
+    /// it the appropriate way to fetch large amounts of data, such as the entire message
+    /// history. The `init` request determines which kinds of data will be exported.
+    ///
+    /// Telegram may require the user to confirm the takeout from another session, in which case
+    /// the error `TAKEOUT_INIT_DELAY_X` is returned, and the session can be started after `X`
+    /// seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_tl_types as tl;
+    ///
+    /// let takeout = client
+    ///     .takeout(tl::functions::account::InitTakeoutSession {
+    ///         contacts: true,
+    ///         message_users: false,
+    ///         message_chats: false,
+    ///         message_megagroups: false,
+    ///         message_channels: false,
+    ///         files: false,
+    ///         file_max_size: None,
+    ///     })
+    ///     .await?;
+    ///
+    /// let contacts = takeout.invoke(&tl::functions::contacts::GetContacts { hash: 0 }).await?;
+    /// takeout.finish(true).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn takeout(
+        &self,
+        init: tl::functions::account::InitTakeoutSession,
+    ) -> Result<Takeout, InvocationError> {
+        let tl::enums::account::Takeout::Takeout(takeout) = self.invoke(&init).await?;
+        Ok(Takeout::new(self, takeout.id))
+    }
+}
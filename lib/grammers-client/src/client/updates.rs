@@ -11,10 +11,11 @@
 use super::Client;
 use crate::types::{ChatMap, Update};
 use futures_util::future::{select, Either};
+use futures_util::stream::{self, Stream};
 use grammers_mtsender::utils::sleep_until;
 pub use grammers_mtsender::{AuthorizationError, InvocationError};
 use grammers_session::channel_id;
-pub use grammers_session::{PrematureEndReason, UpdateState};
+pub use grammers_session::{Gap, PrematureEndReason, UpdateState};
 use grammers_tl_types as tl;
 use std::pin::pin;
 use std::sync::Arc;
@@ -24,6 +25,37 @@ use web_time::Instant;
 /// How long to wait after warning the user that the updates limit was exceeded.
 const UPDATE_LIMIT_EXCEEDED_LOG_COOLDOWN: Duration = Duration::from_secs(300);
 
+/// The messages of an album received so far.
+pub(crate) struct PendingAlbum {
+    grouped_id: i64,
+    updates: Vec<(tl::enums::Update, Arc<ChatMap>)>,
+    /// When to stop waiting for more messages of the album.
+    deadline: Instant,
+}
+
+/// The next thing to handle from the queue of updates.
+#[allow(clippy::large_enum_variant)]
+enum RawEvent {
+    Update(tl::enums::Update, Arc<ChatMap>),
+    /// Whether the client started or finished catching up.
+    CatchingUp(bool),
+}
+
+/// The identifier shared by the messages in the same album, if the update is one of them.
+fn grouped_id(update: &tl::enums::Update) -> Option<i64> {
+    match update {
+        tl::enums::Update::NewMessage(tl::types::UpdateNewMessage {
+            message: tl::enums::Message::Message(message),
+            ..
+        })
+        | tl::enums::Update::NewChannelMessage(tl::types::UpdateNewChannelMessage {
+            message: tl::enums::Message::Message(message),
+            ..
+        }) => message.grouped_id,
+        _ => None,
+    }
+}
+
 impl Client {
     /// Returns the next update from the buffer where they are queued until used.
     ///
@@ -48,14 +80,137 @@ impl Client {
     /// ```
     pub async fn next_update(&self) -> Result<Update, InvocationError> {
         loop {
-            let (update, chats) = self.next_raw_update().await?;
+            let album_deadline = self
+                .0
+                .state
+                .read()
+                .unwrap()
+                .album
+                .as_ref()
+                .map(|album| album.deadline);
 
-            if let Some(update) = Update::new(self, update, &chats) {
+            let event = match album_deadline {
+                None => self.next_raw_event().await?,
+                Some(deadline) => {
+                    let update = pin!(self.next_raw_event());
+                    let sleep = pin!(sleep_until(deadline));
+                    match select(update, sleep).await {
+                        Either::Left((update, _)) => update?,
+                        Either::Right(_) => match self.flush_album() {
+                            Some(album) => return Ok(album),
+                            None => continue,
+                        },
+                    }
+                }
+            };
+            let (update, chats) = match event {
+                RawEvent::Update(update, chats) => (update, chats),
+                RawEvent::CatchingUp(started) => return Ok(Update::CatchingUp { started }),
+            };
+
+            let update = match self.0.config.params.album_timeout {
+                Some(timeout) => self.group_album(update, chats, timeout),
+                None => Update::new(self, update, &chats),
+            };
+            if let Some(update) = update {
                 return Ok(update);
             }
         }
     }
 
+    /// Buffer the update if it's a message in an album, and return the update that should be
+    /// handled now, if any.
+    fn group_album(
+        &self,
+        update: tl::enums::Update,
+        chats: Arc<ChatMap>,
+        timeout: Duration,
+    ) -> Option<Update> {
+        let grouped_id = grouped_id(&update);
+        let standalone = {
+            let state = &mut *self.0.state.write().unwrap();
+            match (&mut state.album, grouped_id) {
+                (Some(album), Some(id)) if album.grouped_id == id => {
+                    album.updates.push((update, chats));
+                    album.deadline = Instant::now() + timeout;
+                    return None;
+                }
+                (Some(_), _) => {
+                    // The album was interrupted, so it's complete. The update is handled next.
+                    state.updates.push_front((update, chats));
+                    None
+                }
+                (None, Some(id)) => {
+                    state.album = Some(PendingAlbum {
+                        grouped_id: id,
+                        updates: vec![(update, chats)],
+                        deadline: Instant::now() + timeout,
+                    });
+                    return None;
+                }
+                (None, None) => Some((update, chats)),
+            }
+        };
+
+        match standalone {
+            Some((update, chats)) => Update::new(self, update, &chats),
+            None => self.flush_album(),
+        }
+    }
+
+    /// Take the buffered album, if any, and turn it into a single update.
+    fn flush_album(&self) -> Option<Update> {
+        let album = self.0.state.write().unwrap().album.take()?;
+        let mut messages = album
+            .updates
+            .into_iter()
+            .filter_map(|(update, chats)| match Update::new(self, update, &chats) {
+                Some(Update::NewMessage(message)) => Some(message),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // A lone message is not much of an album.
+        match messages.len() {
+            0 => None,
+            1 => messages.pop().map(Update::NewMessage),
+            _ => Some(Update::Album(messages)),
+        }
+    }
+
+    /// Returns a [`Stream`] over the updates received by the client.
+    ///
+    /// This behaves the same as calling [`Client::next_update`] in a loop. Because updates are
+    /// buffered by the client until they are used, the stream may be polled intermittently, or
+    /// dropped and created again, without causing any of the buffered updates to be lost.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use futures_util::StreamExt;
+    /// use grammers_client::Update;
+    ///
+    /// let mut updates = std::pin::pin!(client.updates().filter_map(|update| async move {
+    ///     match update {
+    ///         Ok(Update::NewMessage(message)) if !message.outgoing() => Some(message),
+    ///         _ => None,
+    ///     }
+    /// }));
+    ///
+    /// while let Some(message) = updates.next().await {
+    ///     println!("{}", message.text());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn updates(&self) -> impl Stream<Item = Result<Update, InvocationError>> {
+        stream::unfold(self.clone(), |client| async move {
+            let update = client.next_update().await;
+            Some((update, client))
+        })
+    }
+
     /// Returns the next raw update and associated chat map from the buffer where they are queued until used.
     ///
     /// # Example
@@ -79,117 +234,271 @@ impl Client {
         &self,
     ) -> Result<(tl::enums::Update, Arc<ChatMap>), InvocationError> {
         loop {
-            let (deadline, get_diff, get_channel_diff) = {
+            if let RawEvent::Update(update, chats) = self.next_raw_event().await? {
+                return Ok((update, chats));
+            }
+        }
+    }
+
+    /// Like [`Client::next_raw_update`], but also reporting when the client starts or finishes
+    /// catching up, which happens once the queued updates have been handled.
+    async fn next_raw_event(&self) -> Result<RawEvent, InvocationError> {
+        loop {
+            let deadline = {
                 let state = &mut *self.0.state.write().unwrap();
-                if let Some(update) = state.updates.pop_front() {
-                    return Ok(update);
+                if let Some((update, chats)) = state.updates.pop_front() {
+                    return Ok(RawEvent::Update(update, chats));
                 }
-                (
-                    state.message_box.check_deadlines(), // first, as it might trigger differences
-                    state.message_box.get_difference(),
-                    state.message_box.get_channel_difference(&state.chat_hashes),
-                )
+                // First, as it might trigger differences.
+                let deadline = state.message_box.check_deadlines();
+                let catching_up = state.message_box.is_getting_difference();
+                if catching_up != state.catching_up {
+                    state.catching_up = catching_up;
+                    return Ok(RawEvent::CatchingUp(catching_up));
+                }
+                deadline
             };
 
-            if let Some(request) = get_diff {
-                let response = self.invoke(&request).await?;
-                let (updates, users, chats) = {
-                    let state = &mut *self.0.state.write().unwrap();
-                    state
-                        .message_box
-                        .apply_difference(response, &mut state.chat_hashes)
-                };
-                self.extend_update_queue(updates, ChatMap::new(users, chats));
+            if self.fetch_pending_difference().await? {
                 continue;
             }
 
-            if let Some(request) = get_channel_diff {
-                let maybe_response = self.invoke(&request).await;
-
-                let response = match maybe_response {
-                    Ok(r) => r,
-                    Err(e) if e.is("PERSISTENT_TIMESTAMP_OUTDATED") => {
-                        // According to Telegram's docs:
-                        // "Channel internal replication issues, try again later (treat this like an RPC_CALL_FAIL)."
-                        // We can treat this as "empty difference" and not update the local pts.
-                        // Then this same call will be retried when another gap is detected or timeout expires.
-                        //
-                        // Another option would be to literally treat this like an RPC_CALL_FAIL and retry after a few
-                        // seconds, but if Telegram is having issues it's probably best to wait for it to send another
-                        // update (hinting it may be okay now) and retry then.
-                        //
-                        // This is a bit hacky because MessageBox doesn't really have a way to "not update" the pts.
-                        // Instead we manually extract the previously-known pts and use that.
-                        log::warn!("Getting difference for channel updates caused PersistentTimestampOutdated; ending getting difference prematurely until server issues are resolved");
-                        {
-                            self.0
-                                .state
-                                .write()
-                                .unwrap()
-                                .message_box
-                                .end_channel_difference(
-                                    &request,
-                                    PrematureEndReason::TemporaryServerIssues,
-                                );
-                        }
-                        continue;
+            let sleep = pin!(async { sleep_until(deadline).await });
+            let step = pin!(async { self.step().await });
+
+            match select(sleep, step).await {
+                Either::Left(_) => {}
+                Either::Right((step, _)) => step?,
+            }
+        }
+    }
+
+    /// Fetch the difference for one of the entries which are missing updates, if any, and queue
+    /// the resulting updates. Returns whether there was a difference to fetch.
+    async fn fetch_pending_difference(&self) -> Result<bool, InvocationError> {
+        let (get_diff, missing_hash, get_channel_diff) = {
+            let state = &mut *self.0.state.write().unwrap();
+            let get_diff = state.message_box.get_difference();
+            let missing_hash = state.message_box.channel_missing_hash(&state.chat_hashes);
+            let get_channel_diff = match missing_hash {
+                Some(_) => None,
+                None => state.message_box.get_channel_difference(&state.chat_hashes),
+            };
+            (get_diff, missing_hash, get_channel_diff)
+        };
+
+        if let Some(request) = get_diff {
+            let response = self.invoke(&request).await?;
+            self.apply_difference(response);
+            return Ok(true);
+        }
+
+        if let Some(channel_id) = missing_hash {
+            if !self.fetch_channel_hash(channel_id).await? {
+                log::info!(
+                    "cannot find the hash of channel {} to fetch its updates",
+                    channel_id
+                );
+                let state = &mut *self.0.state.write().unwrap();
+                state.message_box.forget_channel(channel_id);
+            }
+            return Ok(true);
+        }
+
+        if let Some(request) = get_channel_diff {
+            let maybe_response = self.invoke(&request).await;
+
+            let response = match maybe_response {
+                Ok(r) => r,
+                Err(e) if e.is("PERSISTENT_TIMESTAMP_OUTDATED") => {
+                    // According to Telegram's docs:
+                    // "Channel internal replication issues, try again later (treat this like an RPC_CALL_FAIL)."
+                    // We can treat this as "empty difference" and not update the local pts.
+                    // Then this same call will be retried when another gap is detected or timeout expires.
+                    //
+                    // Another option would be to literally treat this like an RPC_CALL_FAIL and retry after a few
+                    // seconds, but if Telegram is having issues it's probably best to wait for it to send another
+                    // update (hinting it may be okay now) and retry then.
+                    //
+                    // This is a bit hacky because MessageBox doesn't really have a way to "not update" the pts.
+                    // Instead we manually extract the previously-known pts and use that.
+                    log::warn!("Getting difference for channel updates caused PersistentTimestampOutdated; ending getting difference prematurely until server issues are resolved");
+                    {
+                        self.0
+                            .state
+                            .write()
+                            .unwrap()
+                            .message_box
+                            .end_channel_difference(
+                                &request,
+                                PrematureEndReason::TemporaryServerIssues,
+                            );
                     }
-                    Err(e) if e.is("CHANNEL_PRIVATE") => {
-                        log::info!(
-                            "Account is now banned in {} so we can no longer fetch updates from it",
-                            channel_id(&request)
-                                .map(|i| i.to_string())
-                                .unwrap_or_else(|| "empty channel".into())
-                        );
-                        {
-                            self.0
-                                .state
-                                .write()
-                                .unwrap()
-                                .message_box
-                                .end_channel_difference(&request, PrematureEndReason::Banned);
-                        }
-                        continue;
+                    return Ok(true);
+                }
+                Err(e) if e.is("CHANNEL_PRIVATE") => {
+                    log::info!(
+                        "Account is now banned in {} so we can no longer fetch updates from it",
+                        channel_id(&request)
+                            .map(|i| i.to_string())
+                            .unwrap_or_else(|| "empty channel".into())
+                    );
+                    {
+                        self.0
+                            .state
+                            .write()
+                            .unwrap()
+                            .message_box
+                            .end_channel_difference(&request, PrematureEndReason::Banned);
                     }
-                    Err(InvocationError::Rpc(rpc_error)) if rpc_error.code == 500 => {
-                        log::warn!("Telegram is having internal issues: {:#?}", rpc_error);
-                        {
-                            self.0
-                                .state
-                                .write()
-                                .unwrap()
-                                .message_box
-                                .end_channel_difference(
-                                    &request,
-                                    PrematureEndReason::TemporaryServerIssues,
-                                );
-                        }
-                        continue;
+                    return Ok(true);
+                }
+                Err(InvocationError::Rpc(rpc_error)) if rpc_error.code == 500 => {
+                    log::warn!("Telegram is having internal issues: {:#?}", rpc_error);
+                    {
+                        self.0
+                            .state
+                            .write()
+                            .unwrap()
+                            .message_box
+                            .end_channel_difference(
+                                &request,
+                                PrematureEndReason::TemporaryServerIssues,
+                            );
                     }
-                    Err(e) => return Err(e),
-                };
+                    return Ok(true);
+                }
+                Err(e) => return Err(e),
+            };
 
-                let (updates, users, chats) = {
-                    let state = &mut *self.0.state.write().unwrap();
-                    state.message_box.apply_channel_difference(
-                        request,
-                        response,
-                        &mut state.chat_hashes,
-                    )
-                };
+            let (updates, users, chats) = {
+                let state = &mut *self.0.state.write().unwrap();
+                state.message_box.apply_channel_difference(
+                    request,
+                    response,
+                    &mut state.chat_hashes,
+                )
+            };
 
-                self.extend_update_queue(updates, ChatMap::new(users, chats));
-                continue;
-            }
+            self.extend_update_queue(updates, ChatMap::new(users, chats));
+            return Ok(true);
+        }
 
-            let sleep = pin!(async { sleep_until(deadline.into()).await });
-            let step = pin!(async { self.step().await });
+        Ok(false)
+    }
 
-            match select(sleep, step).await {
-                Either::Left(_) => {}
-                Either::Right((step, _)) => step?,
+    /// Queue the updates from the account-wide difference, as returned by Telegram.
+    fn apply_difference(&self, response: tl::enums::updates::Difference) {
+        let (updates, users, chats) = {
+            let state = &mut *self.0.state.write().unwrap();
+            state
+                .message_box
+                .apply_difference(response, &mut state.chat_hashes)
+        };
+        self.extend_update_queue(updates, ChatMap::new(users, chats));
+    }
+
+    /// Fetch all the updates that were missed while the client was offline, and return them
+    /// along with any other update that was queued but not yet handled.
+    ///
+    /// This keeps fetching differences until there are none left, so that all missed updates
+    /// can be handled at once, instead of one by one as [`Client::next_update`] would do.
+    /// Whether updates missed before connecting are fetched at all depends on
+    /// [`InitParams::catch_up`](crate::InitParams::catch_up).
+    ///
+    /// To avoid looping forever if the server keeps reporting more updates, this gives up
+    /// after [`InitParams::catch_up_timeout`](crate::InitParams::catch_up_timeout), returning
+    /// the updates fetched so far. The rest will be fetched by [`Client::next_update`] as usual.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let missed = client.catch_up().await?;
+    /// println!("{} updates were missed while offline", missed.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn catch_up(&self) -> Result<Vec<Update>, InvocationError> {
+        let timeout = self.0.config.params.catch_up_timeout;
+        let deadline = Instant::now() + timeout;
+        self.0.state.write().unwrap().message_box.check_deadlines();
+        while self.fetch_pending_difference().await? {
+            if Instant::now() >= deadline {
+                log::warn!("giving up on catching up after {:?}", timeout);
+                break;
             }
         }
+
+        let updates = std::mem::take(&mut self.0.state.write().unwrap().updates);
+        Ok(updates
+            .into_iter()
+            .filter_map(|(update, chats)| Update::new(self, update, &chats))
+            .collect())
+    }
+
+    /// Try to find the access hash of a channel knowing only its identifier, so that its
+    /// difference can be fetched. Returns whether the hash is now known.
+    async fn fetch_channel_hash(&self, channel_id: i64) -> Result<bool, InvocationError> {
+        let chats = match self
+            .invoke(&tl::functions::channels::GetChannels {
+                id: vec![tl::types::InputChannel {
+                    channel_id,
+                    access_hash: 0,
+                }
+                .into()],
+            })
+            .await
+        {
+            Ok(tl::enums::messages::Chats::Chats(chats)) => chats.chats,
+            Ok(tl::enums::messages::Chats::Slice(chats)) => chats.chats,
+            Err(InvocationError::Rpc(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        let state = &mut *self.0.state.write().unwrap();
+        let _ = state.chat_hashes.extend(&[], &chats);
+        Ok(state.chat_hashes.get(channel_id).is_some())
+    }
+
+    /// Feed updates obtained elsewhere to the client's message box, and return the resulting
+    /// updates in the order they should be handled.
+    ///
+    /// This is only useful when integrating updates from an external source. Updates received
+    /// by the client itself are processed automatically, and can be obtained with
+    /// [`Client::next_update`] as usual.
+    ///
+    /// The updates are checked for gaps just like any other update. If [`Gap`] is returned,
+    /// the updates were not applied, and the difference must be fetched to recover the missing
+    /// ones. This is already done automatically the next time [`Client::next_update`] is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn f(client: grammers_client::Client, updates: grammers_tl_types::enums::Updates) {
+    /// match client.process_raw_updates(updates) {
+    ///     Ok(updates) => println!("got {} updates", updates.len()),
+    ///     Err(_) => println!("some updates were missed, difference must be fetched"),
+    /// }
+    /// # }
+    /// ```
+    pub fn process_raw_updates(&self, updates: tl::enums::Updates) -> Result<Vec<Update>, Gap> {
+        let (updates, users, chats) = {
+            let state = &mut *self.0.state.write().unwrap();
+            state
+                .message_box
+                .ensure_known_peer_hashes(&updates, &mut state.chat_hashes)?;
+            state
+                .message_box
+                .process_updates(updates, &state.chat_hashes)?
+        };
+
+        self.forget_changed_usernames(&updates);
+        let chats = ChatMap::new(users, chats);
+        Ok(updates
+            .into_iter()
+            .filter_map(|update| Update::new(self, update, &chats))
+            .collect())
     }
 
     pub(crate) fn process_socket_updates(&self, all_updates: Vec<tl::enums::Updates>) {
@@ -232,7 +541,28 @@ impl Client {
         }
     }
 
-    fn extend_update_queue(&self, mut updates: Vec<tl::enums::Update>, chat_map: Arc<ChatMap>) {
+    /// Forget the usernames resolved to chats whose usernames may have changed.
+    fn forget_changed_usernames(&self, updates: &[tl::enums::Update]) {
+        let session = &self.0.config.session;
+        for update in updates {
+            match update {
+                tl::enums::Update::UserName(update) => {
+                    session.forget_resolved_usernames(update.user_id)
+                }
+                tl::enums::Update::Channel(update) => {
+                    session.forget_resolved_usernames(update.channel_id)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn extend_update_queue(
+        &self,
+        mut updates: Vec<tl::enums::Update>,
+        chat_map: Arc<ChatMap>,
+    ) {
+        self.forget_changed_usernames(&updates);
         let mut state = self.0.state.write().unwrap();
 
         if let Some(limit) = self.0.config.params.update_queue_limit {
@@ -274,7 +604,9 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::net::block_on;
     use core::future::Future;
+    use futures_util::StreamExt;
 
     fn get_client() -> Client {
         panic!()
@@ -289,4 +621,272 @@ mod tests {
             typeck(get_client().next_update());
         }
     }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn ensure_update_stream_preserves_order() {
+        let deletion = |id| {
+            tl::types::UpdateDeleteMessages {
+                messages: vec![id],
+                pts: id,
+                pts_count: 1,
+            }
+            .into()
+        };
+        let deleted_id = |update: Option<Result<Update, InvocationError>>| match update {
+            Some(Ok(Update::MessageDeleted(deletion))) => deletion.messages()[0],
+            _ => panic!("unexpected update"),
+        };
+
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let mut updates = pin!(client.updates());
+
+            client.extend_update_queue((1..=3).map(deletion).collect(), ChatMap::empty());
+            assert_eq!(deleted_id(updates.next().await), 1);
+            assert_eq!(deleted_id(updates.next().await), 2);
+
+            // Updates arriving while the stream is not being polled must not be lost.
+            client.extend_update_queue((4..=5).map(deletion).collect(), ChatMap::empty());
+            let rest = updates
+                .take(3)
+                .map(|u| deleted_id(Some(u)))
+                .collect::<Vec<_>>();
+            assert_eq!(rest.await, vec![3, 4, 5]);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn ensure_raw_updates_report_gaps() {
+        let updates = |seq, pts| {
+            tl::types::Updates {
+                updates: vec![tl::types::UpdateDeleteMessages {
+                    messages: vec![pts],
+                    pts,
+                    pts_count: 1,
+                }
+                .into()],
+                users: Vec::new(),
+                chats: Vec::new(),
+                date: 1,
+                seq,
+            }
+            .into()
+        };
+
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            client.0.state.write().unwrap().message_box.set_state(
+                tl::types::updates::State {
+                    pts: 1,
+                    qts: 1,
+                    date: 1,
+                    seq: 1,
+                    unread_count: 0,
+                }
+                .into(),
+            );
+
+            match client.process_raw_updates(updates(2, 2)).as_deref() {
+                Ok([Update::MessageDeleted(deletion)]) => assert_eq!(deletion.messages(), [2]),
+                _ => panic!("update should have been applied"),
+            }
+
+            assert!(client.process_raw_updates(updates(5, 5)).is_err());
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_catching_up_is_reported() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            client.0.state.write().unwrap().message_box.set_state(
+                tl::types::updates::State {
+                    pts: 1,
+                    qts: 1,
+                    date: 1,
+                    seq: 1,
+                    unread_count: 0,
+                }
+                .into(),
+            );
+
+            // Too many updates are pending, so there's a gap and the difference must be fetched.
+            assert!(client
+                .process_raw_updates(tl::enums::Updates::TooLong)
+                .is_err());
+            assert!(matches!(
+                client.next_update().await,
+                Ok(Update::CatchingUp { started: true })
+            ));
+
+            client.apply_difference(tl::types::updates::DifferenceEmpty { date: 2, seq: 1 }.into());
+            assert!(matches!(
+                client.next_update().await,
+                Ok(Update::CatchingUp { started: false })
+            ));
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_albums_are_grouped() {
+        let message = |id, grouped_id| {
+            tl::types::UpdateNewMessage {
+                message: tl::types::Message {
+                    id,
+                    grouped_id,
+                    ..crate::types::message::EMPTY_MESSAGE
+                }
+                .into(),
+                pts: 0,
+                pts_count: 0,
+            }
+            .into()
+        };
+        let timeout = Duration::from_secs(1);
+
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chats = ChatMap::empty();
+
+            assert!(client
+                .group_album(message(1, Some(5)), chats.clone(), timeout)
+                .is_none());
+            assert!(client
+                .group_album(message(2, Some(5)), chats.clone(), timeout)
+                .is_none());
+
+            // A message outside the album completes it, and is returned right after.
+            match client.group_album(message(3, None), chats.clone(), timeout) {
+                Some(Update::Album(messages)) => {
+                    assert_eq!(
+                        messages.iter().map(|m| m.id()).collect::<Vec<_>>(),
+                        vec![1, 2]
+                    );
+                }
+                update => panic!("unexpected update: {update:?}"),
+            }
+            match client.next_update().await {
+                Ok(Update::NewMessage(message)) => assert_eq!(message.id(), 3),
+                update => panic!("unexpected update: {update:?}"),
+            }
+
+            // Standalone messages are not buffered.
+            match client.group_album(message(4, None), chats.clone(), timeout) {
+                Some(Update::NewMessage(message)) => assert_eq!(message.id(), 4),
+                update => panic!("unexpected update: {update:?}"),
+            }
+
+            // Albums are returned once the wait is over, even if nothing interrupts them.
+            assert!(client
+                .group_album(message(6, Some(7)), chats.clone(), timeout)
+                .is_none());
+            assert!(matches!(client.flush_album(), Some(Update::NewMessage(_))));
+            assert!(client.flush_album().is_none());
+        });
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn catch_up_sender() -> grammers_mtsender::mock::MockSender {
+        let message = |id| {
+            tl::types::Message {
+                id,
+                ..crate::types::message::EMPTY_MESSAGE
+            }
+            .into()
+        };
+
+        let mut sender = grammers_mtsender::mock::MockSender::new();
+        sender
+            .expect_if(|r: &tl::functions::updates::GetDifference| r.pts == 1)
+            .respond(
+                tl::types::updates::DifferenceSlice {
+                    new_messages: vec![message(2), message(3)],
+                    new_encrypted_messages: Vec::new(),
+                    other_updates: Vec::new(),
+                    chats: Vec::new(),
+                    users: Vec::new(),
+                    intermediate_state: tl::types::updates::State {
+                        pts: 3,
+                        qts: 1,
+                        date: 2,
+                        seq: 1,
+                        unread_count: 0,
+                    }
+                    .into(),
+                }
+                .into(),
+            );
+        sender
+            .expect_if(|r: &tl::functions::updates::GetDifference| r.pts == 3)
+            .respond(tl::types::updates::DifferenceEmpty { date: 3, seq: 1 }.into());
+        sender
+    }
+
+    /// Make the client think it was last online when the account had the given `pts`.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn load_update_state(client: &Client, pts: i32) {
+        client.0.state.write().unwrap().message_box =
+            grammers_session::MessageBox::load(UpdateState {
+                pts,
+                qts: 1,
+                date: 1,
+                seq: 1,
+                channels: Vec::new(),
+            });
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn message_ids(updates: Vec<Update>) -> Vec<i32> {
+        updates
+            .into_iter()
+            .map(|update| match update {
+                Update::NewMessage(message) => message.id(),
+                _ => panic!("unexpected update"),
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_catch_up_fetches_until_empty() {
+        block_on(async {
+            let (client, mock) = Client::mocked_for_tests(catch_up_sender()).await;
+            load_update_state(&client, 1);
+
+            let received = message_ids(client.catch_up().await.unwrap());
+            assert_eq!(received, vec![2, 3]);
+            {
+                let mock = mock.lock().await;
+                assert_eq!(mock.invoked().len(), 2);
+                assert_eq!(mock.remaining_rules(), 0);
+            }
+
+            // Nothing is pending anymore, so no more requests are made.
+            assert!(client.catch_up().await.unwrap().is_empty());
+            assert_eq!(mock.lock().await.invoked().len(), 2);
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_catch_up_gives_up_after_timeout() {
+        block_on(async {
+            let (mut client, mock) = Client::mocked_for_tests(catch_up_sender()).await;
+            Arc::get_mut(&mut client.0)
+                .unwrap()
+                .config
+                .params
+                .catch_up_timeout = Duration::ZERO;
+            load_update_state(&client, 1);
+
+            // Only the first slice is fetched before giving up.
+            let received = message_ids(client.catch_up().await.unwrap());
+            assert_eq!(received, vec![2, 3]);
+            assert_eq!(mock.lock().await.remaining_rules(), 1);
+        });
+    }
 }
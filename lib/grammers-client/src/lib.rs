@@ -56,6 +56,9 @@ pub use client::{Client, Config, InitParams, SignInError};
 pub use types::{button, reply_markup, ChatMap, InputMedia, InputMessage, Update};
 
 pub use grammers_mtproto::transport;
-pub use grammers_mtsender::{FixedReconnect, InvocationError, NoReconnect, ReconnectionPolicy};
+pub use grammers_mtsender::{
+    ConnectionParams, ConnectionState, ExponentialBackoff, FixedReconnect, InvocationError,
+    NoReconnect, ReconnectionPolicy,
+};
 pub use grammers_session as session;
 pub use grammers_tl_types;
@@ -7,6 +7,7 @@
 // except according to those terms.
 #![cfg(any(feature = "markdown", feature = "html"))]
 
+use super::utf16::{byte_to_utf16, utf16_len, utf16_to_byte};
 use std::cmp::Ordering;
 use std::fmt::{self, Write as _};
 
@@ -17,7 +18,7 @@ pub const MENTION_URL_PREFIX: &str = "tg://user?id=";
 /// Telegram considers the length of the string with surrogate pairs.
 pub fn telegram_string_len(string: &str) -> i32 {
     // https://en.wikipedia.org/wiki/Plane_(Unicode)#Overview
-    string.encode_utf16().count() as i32
+    utf16_len(string) as i32
 }
 
 /// Updates the length of the latest `MessageEntity` inside the specified vector.
@@ -143,49 +144,33 @@ pub fn after(index: usize, part: u8, offset: i32) -> Position {
 ///
 /// The insertions do not need to be sorted before-hand, as this method takes care of that.
 pub fn inject_into_message(message: &str, mut insertions: Vec<(Position, Segment)>) -> String {
-    // Allocate exactly as much as needed, then walk through the UTF-16-encoded message,
-    // applying insertions at the exact position they occur.
+    // Allocate exactly as much as needed, then walk through the message, converting the UTF-16
+    // offset of each insertion into the byte offset where it should be applied.
     let mut result = String::with_capacity(
         message.len() + insertions.iter().map(|(_, what)| what.len()).sum::<usize>(),
     );
 
-    insertions.sort_unstable_by(|(a, _), (b, _)| b.cmp(a)); // sort in reverse so we can pop
+    insertions.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
 
-    let mut char_buffer = [0u8; 4]; // temporary storage to re-encode chars as utf-8
-    let mut prev_point = None; // temporary storage for utf-16 surrogate pairs
+    let total = utf16_len(message);
+    let mut byte = 0; // how much of the message has been copied, in bytes
+    let mut utf16 = 0; // how much of the message has been copied, in utf-16 code units
 
-    for (index, point) in message.encode_utf16().enumerate() {
-        loop {
-            match insertions.last() {
-                Some((at, what)) if at.offset as usize == index => {
-                    write!(result, "{}", what).unwrap();
-                    insertions.pop();
-                }
-                _ => break,
-            }
-        }
-
-        let c = if let Some(previous) = prev_point.take() {
-            char::decode_utf16([previous, point])
-                .next()
-                .unwrap()
-                .unwrap()
-        } else {
-            match char::decode_utf16([point]).next().unwrap() {
-                Ok(c) => c,
-                Err(unpaired) => {
-                    prev_point = Some(unpaired.unpaired_surrogate());
-                    continue;
-                }
-            }
-        };
-
-        result.push_str(c.encode_utf8(&mut char_buffer));
-    }
+    for (at, what) in insertions {
+        // Offsets past the end are applied at the end, and those which fall between the
+        // surrogates of a pair are applied before it.
+        let rest = &message[byte..];
+        let target = (at.offset as usize).clamp(utf16, total) - utf16;
+        let len = utf16_to_byte(rest, target)
+            .or_else(|| utf16_to_byte(rest, target - 1))
+            .unwrap();
 
-    while let Some((_, what)) = insertions.pop() {
+        result.push_str(&rest[..len]);
+        byte += len;
+        utf16 += byte_to_utf16(rest, len).unwrap();
         write!(result, "{}", what).unwrap();
     }
 
+    result.push_str(&message[byte..]);
     result
 }
@@ -74,17 +74,21 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
                         entities.push(tl::types::MessageEntitySpoiler { offset, length }.into());
                     }
                     tag!("code") => {
-                        match entities.iter_mut().rev().next() {
+                        match entities.iter_mut().next_back() {
                             // If the previous tag is an open `<pre>`, don't add `<code>`;
                             // we most likely want to indicate `class="language-foo"`.
                             Some(tl::enums::MessageEntity::Pre(e)) if e.length == 0 => {
+                                // The class may contain more than one name, such as when
+                                // syntax highlighters add their own.
                                 e.language = attrs
                                     .into_iter()
-                                    .find(|a| {
-                                        a.name.local == tag!("class")
-                                            && a.value.starts_with(CODE_LANG_PREFIX)
+                                    .filter(|a| a.name.local == tag!("class"))
+                                    .find_map(|a| {
+                                        a.value
+                                            .split_whitespace()
+                                            .find_map(|c| c.strip_prefix(CODE_LANG_PREFIX))
+                                            .map(|lang| lang.to_string())
                                     })
-                                    .map(|a| a.value[CODE_LANG_PREFIX.len()..].to_string())
                                     .unwrap_or_else(|| "".to_string());
                             }
                             _ => {
@@ -110,8 +114,8 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
                             .map(|a| a.value.to_string())
                             .unwrap_or_else(|| "".to_string());
 
-                        if url.starts_with(MENTION_URL_PREFIX) {
-                            let user_id = url[MENTION_URL_PREFIX.len()..].parse::<i64>().unwrap();
+                        if let Some(user_id) = url.strip_prefix(MENTION_URL_PREFIX) {
+                            let user_id = user_id.parse::<i64>().unwrap();
                             entities.push(
                                 tl::types::MessageEntityMentionName {
                                     offset,
@@ -158,7 +162,7 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
                         update_entity_len!(Spoiler(offset) in entities);
                     }
                     tag!("code") => {
-                        match entities.iter_mut().rev().next() {
+                        match entities.iter_mut().next_back() {
                             // If the previous tag is an open `<pre>`, don't update `<code>` len;
                             // we most likely want to indicate `class="language-foo"`.
                             Some(tl::enums::MessageEntity::Pre(e)) if e.length == 0 => {}
@@ -171,7 +175,7 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
                         update_entity_len!(Pre(offset) in entities);
                     }
                     tag!("a") => {
-                        match entities.iter_mut().rev().next() {
+                        match entities.iter_mut().next_back() {
                             // If the previous url is a mention, don't close with `</a>`;
                             Some(tl::enums::MessageEntity::MentionName(_)) => {
                                 update_entity_len!(MentionName(offset) in entities);
@@ -198,7 +202,7 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
         }
     }
 
-    let mut input = BufferQueue::default();
+    let input = BufferQueue::default();
     input.push_back(StrTendril::from_slice(message).try_reinterpret().unwrap());
 
     let tok = Tokenizer::new(
@@ -209,7 +213,7 @@ pub fn parse_html_message(message: &str) -> (String, Vec<tl::enums::MessageEntit
         },
         Default::default(),
     );
-    let _ = tok.feed(&mut input);
+    let _ = tok.feed(&input);
     tok.end();
 
     let Sink { text, entities, .. } = tok.sink;
@@ -492,6 +496,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_pre_code_block_lang() {
+        let (text, entities) =
+            parse_html_message("<pre><code class=\"language-python\">x=1</code></pre>");
+
+        assert_eq!(text, "x=1");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 0,
+                length: 3,
+                language: "python".to_string()
+            }
+            .into()]
+        );
+
+        let (text, entities) =
+            parse_html_message("<pre><code class=\"hljs language-python\">x=1</code></pre>");
+
+        assert_eq!(text, "x=1");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 0,
+                length: 3,
+                language: "python".to_string()
+            }
+            .into()]
+        );
+    }
+
+    #[test]
+    fn parse_bare_pre() {
+        let (text, entities) = parse_html_message("<pre>x=1</pre>");
+
+        assert_eq!(text, "x=1");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 0,
+                length: 3,
+                language: "".to_string()
+            }
+            .into()]
+        );
+    }
+
     #[test]
     fn parse_empty_pre_and_lang() {
         let (text, entities) = parse_html_message(
@@ -546,6 +597,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_emoji() {
+        // The italics start between the surrogates of the emoji, and end past the text.
+        let generated = generate_html_message(
+            "a中🦀b",
+            &[
+                tl::types::MessageEntityBold {
+                    offset: 1,
+                    length: 3,
+                }
+                .into(),
+                tl::types::MessageEntityItalic {
+                    offset: 3,
+                    length: 10,
+                }
+                .into(),
+            ],
+        );
+        assert_eq!(generated, "a<b>中<i>🦀</b>b</i>");
+    }
+
     #[test]
     fn parse_then_unparse() {
         let html = "Some <b>bold</b>, <i>italics</i> inline <code>code</code>, \
@@ -14,13 +14,57 @@ use crate::update_entity_len;
 use grammers_tl_types as tl;
 use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
 
+/// The source of every fenced code block in the message, in order, if it's never closed.
+///
+/// CommonMark lets an unterminated fence run until the end of the document, which is rarely
+/// what was meant in a chat message. A fence is closed if anything (other than whitespace) is
+/// left in the block after its contents, which also works for blocks inside containers such as
+/// quotes, where every line starts with a prefix.
+fn unterminated_fences(message: &str) -> Vec<Option<&str>> {
+    let mut fences = Vec::new();
+    // Where the current block starts, and where its contents (or opening fence) end.
+    let mut block = None;
+    for (event, range) in Parser::new(message).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                let opening_end = message[range.clone()]
+                    .find('\n')
+                    .map_or(range.end, |i| range.start + i);
+                block = Some((range.start, opening_end));
+            }
+            Event::Text(_) => {
+                if let Some((_, contents_end)) = block.as_mut() {
+                    *contents_end = range.end;
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((start, contents_end)) = block.take() {
+                    let closed = !message[contents_end..range.end].trim().is_empty();
+                    fences.push((!closed).then(|| &message[start..range.end]));
+                }
+            }
+            _ => {}
+        }
+    }
+    fences
+}
+
 pub fn parse_markdown_message(message: &str) -> (String, Vec<tl::enums::MessageEntity>) {
     let mut text = String::with_capacity(message.len());
     let mut entities = Vec::new();
 
     let mut offset = 0;
     let length = 0;
+    let mut unterminated = unterminated_fences(message).into_iter();
+    let mut in_literal_block = false;
     Parser::new(message).for_each(|event| match event {
+        // unterminated ```fence, kept as-is
+        _ if in_literal_block => {
+            if let Event::End(TagEnd::CodeBlock) = event {
+                in_literal_block = false;
+            }
+        }
+
         // text
         Event::Text(string) => {
             text.push_str(&string);
@@ -53,8 +97,8 @@ pub fn parse_markdown_message(message: &str) -> (String, Vec<tl::enums::MessageE
 
         // [text link](https://example.com) or [user mention](tg://user?id=12345678)
         Event::Start(Tag::Link { dest_url, .. }) => {
-            if dest_url.starts_with(MENTION_URL_PREFIX) {
-                let user_id = dest_url[MENTION_URL_PREFIX.len()..].parse::<i64>().unwrap();
+            if let Some(user_id) = dest_url.strip_prefix(MENTION_URL_PREFIX) {
+                let user_id = user_id.parse::<i64>().unwrap();
                 entities.push(
                     tl::types::MessageEntityMentionName {
                         offset,
@@ -94,6 +138,15 @@ pub fn parse_markdown_message(message: &str) -> (String, Vec<tl::enums::MessageE
 
         // ```lang\npre```
         Event::Start(Tag::CodeBlock(kind)) => {
+            if let CodeBlockKind::Fenced(_) = kind {
+                if let Some(source) = unterminated.next().flatten() {
+                    text.push_str(source);
+                    offset += telegram_string_len(source);
+                    in_literal_block = true;
+                    return;
+                }
+            }
+
             let lang = match kind {
                 CodeBlockKind::Indented => "".to_string(),
                 CodeBlockKind::Fenced(lang) => lang.to_string(),
@@ -328,6 +381,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_fenced_code_block() {
+        let (text, entities) = parse_markdown_message("Code:\n\n```rust\nfn main() {}\n```\nDone");
+        assert_eq!(text, "Code:\n\nfn main() {}\nDone");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 7,
+                length: 13,
+                language: "rust".to_string()
+            }
+            .into()]
+        );
+
+        let (text, entities) = parse_markdown_message("```rust\nfn main() {}\n````");
+        assert_eq!(text, "fn main() {}");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 0,
+                length: 13,
+                language: "rust".to_string()
+            }
+            .into()]
+        );
+
+        let (text, entities) = parse_markdown_message("> ```rust\n> fn main() {}\n> ```");
+        assert_eq!(text, "fn main() {}");
+        assert_eq!(
+            entities,
+            vec![tl::types::MessageEntityPre {
+                offset: 0,
+                length: 13,
+                language: "rust".to_string()
+            }
+            .into()]
+        );
+    }
+
+    #[test]
+    fn parse_unterminated_fenced_code_block() {
+        let (text, entities) = parse_markdown_message("Code:\n\n```rust\nfn main() {}");
+        assert_eq!(text, "Code:\n\n```rust\nfn main() {}");
+        assert_eq!(entities, vec![]);
+
+        let (text, entities) = parse_markdown_message("````\nfn main() {}\n```");
+        assert_eq!(text, "````\nfn main() {}\n```");
+        assert_eq!(entities, vec![]);
+
+        let (text, entities) = parse_markdown_message("> ```rust\n> fn main() {}");
+        assert_eq!(text, "```rust\n> fn main() {}");
+        assert_eq!(entities, vec![]);
+    }
+
     #[test]
     fn parse_nested_entities() {
         // CommonMark won't allow the following: "Some **bold _both** italics_"
@@ -355,6 +462,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_emoji() {
+        // The italics start between the surrogates of the emoji, and end past the text.
+        let generated = generate_markdown_message(
+            "a中🦀b",
+            &[
+                tl::types::MessageEntityBold {
+                    offset: 1,
+                    length: 3,
+                }
+                .into(),
+                tl::types::MessageEntityItalic {
+                    offset: 3,
+                    length: 10,
+                }
+                .into(),
+            ],
+        );
+        assert_eq!(generated, "a**中_🦀**b_");
+    }
+
     #[test]
     fn parse_then_unparse() {
         let markdown = "Some **bold 🤷🏽‍♀️**, _italics_, inline `🤷🏽‍♀️ code`, \
@@ -12,6 +12,8 @@
 #[cfg(any(feature = "markdown", feature = "html"))]
 mod common;
 
+pub mod utf16;
+
 #[cfg(feature = "html")]
 mod html;
 #[cfg(feature = "html")]
@@ -0,0 +1,121 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between the UTF-8 byte offsets used by Rust strings and the UTF-16 code unit
+//! offsets used by Telegram, such as in the `offset` and `length` of message entities.
+//!
+//! Characters outside of the Basic Multilingual Plane, such as most emoji, are encoded as a
+//! surrogate pair in UTF-16, and so count as two code units.
+
+/// The length of the string in UTF-16 code units.
+///
+/// # Examples
+///
+/// ```
+/// use grammers_client::parsers::utf16::utf16_len;
+///
+/// assert_eq!(utf16_len("a中🦀"), 4);
+/// ```
+pub fn utf16_len(string: &str) -> usize {
+    string.chars().map(char::len_utf16).sum()
+}
+
+/// Convert a UTF-8 byte offset into the string to the equivalent offset in UTF-16 code units.
+///
+/// Returns `None` if the offset is out of bounds or does not lie on a character boundary.
+///
+/// # Examples
+///
+/// ```
+/// use grammers_client::parsers::utf16::byte_to_utf16;
+///
+/// assert_eq!(byte_to_utf16("a中🦀", 4), Some(2));
+/// ```
+pub fn byte_to_utf16(string: &str, byte: usize) -> Option<usize> {
+    string.get(..byte).map(utf16_len)
+}
+
+/// Convert an offset into the string in UTF-16 code units to the equivalent UTF-8 byte offset.
+///
+/// Returns `None` if the offset is out of bounds or lies in the middle of a surrogate pair.
+///
+/// # Examples
+///
+/// ```
+/// use grammers_client::parsers::utf16::utf16_to_byte;
+///
+/// assert_eq!(utf16_to_byte("a中🦀", 2), Some(4));
+/// ```
+pub fn utf16_to_byte(string: &str, utf16: usize) -> Option<usize> {
+    let mut units = 0;
+    for (byte, c) in string.char_indices() {
+        if units == utf16 {
+            return Some(byte);
+        }
+        if units > utf16 {
+            return None;
+        }
+        units += c.len_utf16();
+    }
+    (units == utf16).then_some(string.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 'a' is 1 byte and 1 unit, '中' is 3 bytes and 1 unit, '🦀' is 4 bytes and 2 units.
+    const TEXT: &str = "a中🦀b";
+
+    #[test]
+    fn check_utf16_len() {
+        assert_eq!(utf16_len(""), 0);
+        assert_eq!(utf16_len("abc"), 3);
+        assert_eq!(utf16_len("中"), 1);
+        assert_eq!(utf16_len("🦀"), 2);
+        assert_eq!(utf16_len(TEXT), 5);
+        assert_eq!(utf16_len(TEXT), TEXT.encode_utf16().count());
+    }
+
+    #[test]
+    fn check_byte_to_utf16() {
+        assert_eq!(byte_to_utf16(TEXT, 0), Some(0));
+        assert_eq!(byte_to_utf16(TEXT, 1), Some(1));
+        assert_eq!(byte_to_utf16(TEXT, 4), Some(2));
+        assert_eq!(byte_to_utf16(TEXT, 8), Some(4));
+        assert_eq!(byte_to_utf16(TEXT, 9), Some(5));
+
+        // Inside of '中' and '🦀', or past the end.
+        assert_eq!(byte_to_utf16(TEXT, 2), None);
+        assert_eq!(byte_to_utf16(TEXT, 6), None);
+        assert_eq!(byte_to_utf16(TEXT, 10), None);
+    }
+
+    #[test]
+    fn check_utf16_to_byte() {
+        assert_eq!(utf16_to_byte(TEXT, 0), Some(0));
+        assert_eq!(utf16_to_byte(TEXT, 1), Some(1));
+        assert_eq!(utf16_to_byte(TEXT, 2), Some(4));
+        assert_eq!(utf16_to_byte(TEXT, 4), Some(8));
+        assert_eq!(utf16_to_byte(TEXT, 5), Some(9));
+
+        // Between the surrogates of '🦀', or past the end.
+        assert_eq!(utf16_to_byte(TEXT, 3), None);
+        assert_eq!(utf16_to_byte(TEXT, 6), None);
+        assert_eq!(utf16_to_byte("", 0), Some(0));
+        assert_eq!(utf16_to_byte("", 1), None);
+    }
+
+    #[test]
+    fn check_conversions_round_trip() {
+        for (byte, _) in TEXT.char_indices().chain([(TEXT.len(), ' ')]) {
+            let utf16 = byte_to_utf16(TEXT, byte).unwrap();
+            assert_eq!(utf16_to_byte(TEXT, utf16), Some(byte));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::{Chat, ChatMap, Message, Participant, Restrictions, User};
+use crate::{utils, Client};
+use chrono::{DateTime, Utc};
+use grammers_tl_types as tl;
+use std::fmt;
+use std::sync::Arc;
+
+/// An action performed in a group or channel, as recorded in its administrator log.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
+pub enum AdminLogAction {
+    /// The title was changed.
+    TitleChanged { before: String, after: String },
+    /// The description was changed.
+    AboutChanged { before: String, after: String },
+    /// The main username was changed.
+    UsernameChanged { before: String, after: String },
+    /// The list of usernames was changed.
+    UsernamesChanged {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    /// The photo was changed.
+    PhotoChanged,
+    /// Whether members can invite others was toggled.
+    InvitesToggled(bool),
+    /// Whether messages are signed with the name of their author was toggled.
+    SignaturesToggled(bool),
+    /// Whether new members can see the message history was toggled.
+    PreHistoryHiddenToggled(bool),
+    /// Whether messages can be forwarded and saved was toggled.
+    NoForwardsToggled(bool),
+    /// Whether the group is a forum was toggled.
+    ForumToggled(bool),
+    /// Whether the aggressive anti-spam system is enabled was toggled.
+    AntiSpamToggled(bool),
+    /// A message was pinned or unpinned, depending on [`Message::pinned`].
+    MessagePinned(Message),
+    /// A message was edited.
+    MessageEdited { before: Message, after: Message },
+    /// A message was deleted.
+    MessageDeleted(Message),
+    /// A message was sent.
+    MessageSent(Message),
+    /// A poll was stopped.
+    PollStopped(Message),
+    /// The user who performed the action joined.
+    Joined,
+    /// The user who performed the action joined through an invite link.
+    JoinedByInvite { link: Option<String> },
+    /// The user who performed the action joined after their request was approved.
+    JoinedByRequest {
+        link: Option<String>,
+        approved_by: i64,
+    },
+    /// The user who performed the action left.
+    Left,
+    /// A user was invited.
+    Invited(Participant),
+    /// A user was banned, unbanned, kicked, or had their restrictions changed.
+    ///
+    /// Whether they are now banned can be determined with the [`Role`] of `after`.
+    ///
+    /// [`Role`]: super::Role
+    ParticipantRestricted {
+        before: Participant,
+        after: Participant,
+    },
+    /// A user was promoted or demoted, or had their administrator rights changed.
+    ParticipantPromoted {
+        before: Participant,
+        after: Participant,
+    },
+    /// The default restrictions for all members were changed.
+    DefaultRestrictionsChanged {
+        before: Restrictions,
+        after: Restrictions,
+    },
+    /// The linked discussion group or channel was changed.
+    LinkedChatChanged { before: i64, after: i64 },
+    /// The slow mode delay, in seconds, was changed.
+    SlowModeChanged { before: i32, after: i32 },
+    /// The time-to-live of new messages, in seconds, was changed.
+    HistoryTtlChanged { before: i32, after: i32 },
+    /// Any other action, which is not given a more convenient representation.
+    ///
+    /// Actions involving participants that are not users, such as channels banned from a group,
+    /// are also reported this way.
+    Other(tl::enums::ChannelAdminLogEventAction),
+}
+
+/// An event from the administrator log of a group or channel, which contains what action was
+/// performed, who did it, and when.
+#[derive(Clone)]
+pub struct AdminLogEvent {
+    id: i64,
+    date: i32,
+    user_id: i64,
+    action: AdminLogAction,
+    chats: Arc<ChatMap>,
+}
+
+fn invite_link(invite: tl::enums::ExportedChatInvite) -> Option<String> {
+    match invite {
+        tl::enums::ExportedChatInvite::ChatInviteExported(invite) => Some(invite.link),
+        tl::enums::ExportedChatInvite::ChatInvitePublicJoinRequests => None,
+    }
+}
+
+impl AdminLogAction {
+    fn from_raw(
+        client: &Client,
+        action: tl::enums::ChannelAdminLogEventAction,
+        chats: &Arc<ChatMap>,
+    ) -> Self {
+        use tl::enums::ChannelAdminLogEventAction as A;
+
+        let message =
+            |message: &tl::enums::Message| Message::from_raw(client, message.clone(), chats);
+        let participant = |participant: &tl::enums::ChannelParticipant| {
+            Participant::from_raw_channel_ref(chats, participant.clone())
+        };
+
+        match action {
+            A::ChangeTitle(a) => Self::TitleChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ChangeAbout(a) => Self::AboutChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ChangeUsername(a) => Self::UsernameChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ChangeUsernames(a) => Self::UsernamesChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ChangePhoto(_) => Self::PhotoChanged,
+            A::ToggleInvites(a) => Self::InvitesToggled(a.new_value),
+            A::ToggleSignatures(a) => Self::SignaturesToggled(a.new_value),
+            A::TogglePreHistoryHidden(a) => Self::PreHistoryHiddenToggled(a.new_value),
+            A::ToggleNoForwards(a) => Self::NoForwardsToggled(a.new_value),
+            A::ToggleForum(a) => Self::ForumToggled(a.new_value),
+            A::ToggleAntiSpam(a) => Self::AntiSpamToggled(a.new_value),
+            A::UpdatePinned(ref a) => match message(&a.message) {
+                Some(message) => Self::MessagePinned(message),
+                None => Self::Other(action),
+            },
+            A::EditMessage(ref a) => match (message(&a.prev_message), message(&a.new_message)) {
+                (Some(before), Some(after)) => Self::MessageEdited { before, after },
+                _ => Self::Other(action),
+            },
+            A::DeleteMessage(ref a) => match message(&a.message) {
+                Some(message) => Self::MessageDeleted(message),
+                None => Self::Other(action),
+            },
+            A::SendMessage(ref a) => match message(&a.message) {
+                Some(message) => Self::MessageSent(message),
+                None => Self::Other(action),
+            },
+            A::StopPoll(ref a) => match message(&a.message) {
+                Some(message) => Self::PollStopped(message),
+                None => Self::Other(action),
+            },
+            A::ParticipantJoin => Self::Joined,
+            A::ParticipantJoinByInvite(a) => Self::JoinedByInvite {
+                link: invite_link(a.invite),
+            },
+            A::ParticipantJoinByRequest(a) => Self::JoinedByRequest {
+                link: invite_link(a.invite),
+                approved_by: a.approved_by,
+            },
+            A::ParticipantLeave => Self::Left,
+            A::ParticipantInvite(ref a) => match participant(&a.participant) {
+                Some(participant) => Self::Invited(participant),
+                None => Self::Other(action),
+            },
+            A::ParticipantToggleBan(ref a) => {
+                match (
+                    participant(&a.prev_participant),
+                    participant(&a.new_participant),
+                ) {
+                    (Some(before), Some(after)) => Self::ParticipantRestricted { before, after },
+                    _ => Self::Other(action),
+                }
+            }
+            A::ParticipantToggleAdmin(ref a) => {
+                match (
+                    participant(&a.prev_participant),
+                    participant(&a.new_participant),
+                ) {
+                    (Some(before), Some(after)) => Self::ParticipantPromoted { before, after },
+                    _ => Self::Other(action),
+                }
+            }
+            A::DefaultBannedRights(a) => Self::DefaultRestrictionsChanged {
+                before: Restrictions::from_raw(a.prev_banned_rights.into()),
+                after: Restrictions::from_raw(a.new_banned_rights.into()),
+            },
+            A::ChangeLinkedChat(a) => Self::LinkedChatChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ToggleSlowMode(a) => Self::SlowModeChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            A::ChangeHistoryTtl(a) => Self::HistoryTtlChanged {
+                before: a.prev_value,
+                after: a.new_value,
+            },
+            action => Self::Other(action),
+        }
+    }
+}
+
+impl AdminLogEvent {
+    pub(crate) fn from_raw(
+        client: &Client,
+        event: tl::enums::ChannelAdminLogEvent,
+        chats: &Arc<ChatMap>,
+    ) -> Self {
+        let tl::enums::ChannelAdminLogEvent::Event(event) = event;
+        Self {
+            id: event.id,
+            date: event.date,
+            user_id: event.user_id,
+            action: AdminLogAction::from_raw(client, event.action, chats),
+            chats: Arc::clone(chats),
+        }
+    }
+
+    /// The identifier of this event, which is used to page through the log.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// The date when the action was performed.
+    pub fn date(&self) -> DateTime<Utc> {
+        utils::date(self.date)
+    }
+
+    /// The identifier of the user who performed the action.
+    pub fn actor_id(&self) -> i64 {
+        self.user_id
+    }
+
+    /// The user who performed the action, if they were included in the response.
+    pub fn actor(&self) -> Option<User> {
+        match self.chats.get(
+            &tl::types::PeerUser {
+                user_id: self.user_id,
+            }
+            .into(),
+        ) {
+            Some(Chat::User(user)) => Some(user.clone()),
+            _ => None,
+        }
+    }
+
+    /// The action that was performed.
+    pub fn action(&self) -> &AdminLogAction {
+        &self.action
+    }
+}
+
+impl fmt::Debug for AdminLogEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminLogEvent")
+            .field("id", &self.id)
+            .field("date", &self.date())
+            .field("actor_id", &self.user_id)
+            .field("action", &self.action)
+            .finish()
+    }
+}
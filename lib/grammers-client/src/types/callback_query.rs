@@ -106,6 +106,12 @@ impl CallbackQuery {
         self.inline_msg_id.is_some()
     }
 
+    /// The identifier of the inline message that contains the pressed button, if the callback
+    /// query was generated from one.
+    pub fn inline_message_id(&self) -> Option<types::InlineMessageId> {
+        self.inline_msg_id.clone().map(types::InlineMessageId::from)
+    }
+
     /// Load the `Message` that contains the pressed inline button.
     pub async fn load_message(&self) -> Result<types::Message, InvocationError> {
         Ok(self
@@ -118,7 +124,7 @@ impl CallbackQuery {
     }
 
     /// Answer the callback query.
-    pub fn answer(&self) -> Answer {
+    pub fn answer(&self) -> Answer<'_> {
         Answer {
             request: tl::functions::messages::SetBotCallbackAnswer {
                 alert: false,
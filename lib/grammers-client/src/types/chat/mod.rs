@@ -172,6 +172,23 @@ impl Chat {
         }
     }
 
+    /// Whether this chat was received as a "min" constructor.
+    ///
+    /// Telegram sends these when the logged-in account can see a chat without having access to
+    /// it, such as the author of a message in a large group. Their access hash can only be used
+    /// to download their profile photo, so using them in other requests will fail. Use
+    /// [`Client::unmin`](crate::Client::unmin) to fetch the complete chat first.
+    pub fn is_min(&self) -> bool {
+        match self {
+            Self::User(user) => user.raw.min,
+            Self::Group(group) => match &group.raw {
+                tl::enums::Chat::Channel(channel) => channel.min,
+                _ => false,
+            },
+            Self::Channel(channel) => channel.raw.min,
+        }
+    }
+
     // If `Self` has `min` `access_hash`, returns a mutable reference to both `min` and `access_hash`.
     //
     // This serves as a way of checking "is it min?" and "update the access hash" both in one.
@@ -184,8 +201,16 @@ impl Chat {
                 (m @ true, Some(ah)) => Some((m, ah)),
                 _ => None,
             },
-            // Small group chats don't have an `access_hash` to begin with.
-            Self::Group(_group) => None,
+            Self::Group(group) => match &mut group.raw {
+                tl::enums::Chat::Channel(channel) => {
+                    match (&mut channel.min, channel.access_hash.as_mut()) {
+                        (m @ true, Some(ah)) => Some((m, ah)),
+                        _ => None,
+                    }
+                }
+                // Small group chats don't have an `access_hash` to begin with.
+                _ => None,
+            },
             Self::Channel(channel) => {
                 match (&mut channel.raw.min, channel.raw.access_hash.as_mut()) {
                     (m @ true, Some(ah)) => Some((m, ah)),
@@ -195,6 +220,19 @@ impl Chat {
         }
     }
 
+    /// The datacenter where the chat's profile photo is stored, if it has one.
+    ///
+    /// This is a good hint of the datacenter where the rest of the chat's media lives, so it can be
+    /// used to send download requests to the right datacenter up-front. When `None`, the home
+    /// datacenter of the logged-in account should be used instead.
+    pub fn dc_id(&self) -> Option<i32> {
+        match self {
+            Self::User(user) => user.photo().map(|photo| photo.dc_id),
+            Self::Group(group) => group.photo().map(|photo| photo.dc_id),
+            Self::Channel(channel) => channel.photo().map(|photo| photo.dc_id),
+        }
+    }
+
     // get an chat photo downloadable
     pub fn photo_downloadable(&self, big: bool) -> Option<crate::types::Downloadable> {
         let peer = self.pack().to_input_peer();
@@ -235,3 +273,38 @@ impl From<&Chat> for PackedChat {
         chat.pack()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_dc_id_comes_from_photo() {
+        let mut chat = Chat::from_raw(
+            tl::types::ChannelForbidden {
+                broadcast: true,
+                megagroup: false,
+                id: 1234,
+                access_hash: 5678,
+                title: "Channel".to_string(),
+                until_date: None,
+            }
+            .into(),
+        );
+        assert_eq!(chat.dc_id(), None);
+
+        match &mut chat {
+            Chat::Channel(channel) => {
+                channel.raw.photo = tl::types::ChatPhoto {
+                    has_video: false,
+                    photo_id: 1,
+                    stripped_thumb: None,
+                    dc_id: 4,
+                }
+                .into()
+            }
+            _ => panic!("forbidden broadcast channel should be a channel"),
+        }
+        assert_eq!(chat.dc_id(), Some(4));
+    }
+}
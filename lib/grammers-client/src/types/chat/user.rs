@@ -267,6 +267,13 @@ impl User {
         self.raw.bot
     }
 
+    /// Does this user have a [Telegram Premium] subscription?
+    ///
+    /// [Telegram Premium]: https://telegram.org/faq_premium
+    pub fn is_premium(&self) -> bool {
+        self.raw.premium
+    }
+
     /// If the current user is a bot, does it have [privacy mode] enabled?
     ///
     /// * Bots with privacy enabled won't see messages in groups unless they are replied or the
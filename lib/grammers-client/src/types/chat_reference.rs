@@ -0,0 +1,241 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::Chat;
+use grammers_tl_types as tl;
+
+/// Hosts which may be used in links pointing to public usernames or invites.
+const LINK_HOSTS: [&str; 3] = ["t.me", "telegram.me", "telegram.dog"];
+
+/// A reference to a chat, such as the ones found in links or typed by users.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChatReference {
+    /// A public username (without the leading `@`), and the message it pointed to, if any.
+    Username {
+        username: String,
+        message_id: Option<i32>,
+    },
+
+    /// The hash of the invite link to a private chat.
+    InviteHash(String),
+
+    /// The identifier of a chat.
+    Id(i64),
+}
+
+/// The result of resolving a [`ChatReference`].
+#[derive(Clone, Debug)]
+pub enum ResolvedChat {
+    /// The chat that was referenced, and the message it pointed to, if any.
+    Chat { chat: Chat, message_id: Option<i32> },
+
+    /// A preview of the chat behind the invite, which has **not** been joined.
+    Invite(tl::enums::ChatInvite),
+}
+
+impl ChatReference {
+    /// Parse the input into a reference to a chat.
+    ///
+    /// The following inputs are understood:
+    ///
+    /// * `@username` or `username`.
+    /// * A numeric chat identifier, such as `12345678`.
+    /// * `t.me/username` and `t.me/username/123`, where `123` is the message identifier.
+    /// * `t.me/+hash` and `t.me/joinchat/hash` for invite links.
+    /// * `tg://resolve?domain=username&post=123` and `tg://join?invite=hash`.
+    ///
+    /// Links may optionally start with `https://` or `http://`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use grammers_client::types::ChatReference;
+    ///
+    /// assert_eq!(
+    ///     ChatReference::parse("https://t.me/username/123"),
+    ///     Some(ChatReference::Username {
+    ///         username: "username".to_string(),
+    ///         message_id: Some(123),
+    ///     })
+    /// );
+    /// ```
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Some(username) = input.strip_prefix('@') {
+            return Self::username(username, None);
+        }
+        if let Ok(id) = input.parse() {
+            return Some(Self::Id(id));
+        }
+        if let Some(uri) = input.strip_prefix("tg://") {
+            return Self::parse_tg_uri(uri);
+        }
+
+        let link = input
+            .strip_prefix("https://")
+            .or_else(|| input.strip_prefix("http://"))
+            .unwrap_or(input);
+        let link = link.strip_prefix("www.").unwrap_or(link);
+
+        match link.split_once('/') {
+            Some((host, path)) if LINK_HOSTS.contains(&host.to_ascii_lowercase().as_str()) => {
+                Self::parse_link_path(path)
+            }
+            Some(_) => None,
+            None => Self::username(link, None),
+        }
+    }
+
+    fn parse_link_path(path: &str) -> Option<Self> {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+
+        match segments.as_slice() {
+            [hash] if hash.starts_with('+') => Self::invite_hash(&hash[1..]),
+            ["joinchat", hash] => Self::invite_hash(hash),
+            ["s", username] | [username] => Self::username(username, None),
+            [username, message_id] => Self::username(username, Some(message_id.parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    fn parse_tg_uri(uri: &str) -> Option<Self> {
+        let (action, query) = uri.split_once('?')?;
+        let param = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find_map(|(key, value)| (key == name).then_some(value))
+        };
+
+        match action.trim_end_matches('/') {
+            "resolve" => {
+                let message_id = match param("post") {
+                    Some(post) => Some(post.parse().ok()?),
+                    None => None,
+                };
+                Self::username(param("domain")?, message_id)
+            }
+            "join" => Self::invite_hash(param("invite")?),
+            _ => None,
+        }
+    }
+
+    fn username(username: &str, message_id: Option<i32>) -> Option<Self> {
+        let mut chars = username.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        valid.then(|| Self::Username {
+            username: username.to_string(),
+            message_id,
+        })
+    }
+
+    fn invite_hash(hash: &str) -> Option<Self> {
+        (!hash.is_empty()).then(|| Self::InviteHash(hash.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn username(username: &str, message_id: Option<i32>) -> Option<ChatReference> {
+        Some(ChatReference::Username {
+            username: username.to_string(),
+            message_id,
+        })
+    }
+
+    fn invite(hash: &str) -> Option<ChatReference> {
+        Some(ChatReference::InviteHash(hash.to_string()))
+    }
+
+    #[test]
+    fn parse_usernames() {
+        assert_eq!(
+            ChatReference::parse("@username"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("user_name1"),
+            username("user_name1", None)
+        );
+        assert_eq!(ChatReference::parse("@1username"), None);
+        assert_eq!(ChatReference::parse("@user-name"), None);
+        assert_eq!(ChatReference::parse("@"), None);
+    }
+
+    #[test]
+    fn parse_ids() {
+        assert_eq!(
+            ChatReference::parse("12345678"),
+            Some(ChatReference::Id(12345678))
+        );
+    }
+
+    #[test]
+    fn parse_links() {
+        assert_eq!(
+            ChatReference::parse("t.me/username"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("https://t.me/username/"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("http://telegram.me/username?start=1"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("https://t.me/s/username"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("https://t.me/username/123"),
+            username("username", Some(123))
+        );
+        assert_eq!(ChatReference::parse("https://t.me/username/abc"), None);
+        assert_eq!(ChatReference::parse("https://example.com/username"), None);
+    }
+
+    #[test]
+    fn parse_invite_links() {
+        assert_eq!(
+            ChatReference::parse("https://t.me/+AbC-123"),
+            invite("AbC-123")
+        );
+        assert_eq!(
+            ChatReference::parse("https://t.me/joinchat/AbC-123"),
+            invite("AbC-123")
+        );
+        assert_eq!(ChatReference::parse("https://t.me/+"), None);
+    }
+
+    #[test]
+    fn parse_tg_uris() {
+        assert_eq!(
+            ChatReference::parse("tg://resolve?domain=username"),
+            username("username", None)
+        );
+        assert_eq!(
+            ChatReference::parse("tg://resolve?post=123&domain=username"),
+            username("username", Some(123))
+        );
+        assert_eq!(
+            ChatReference::parse("tg://join?invite=AbC-123"),
+            invite("AbC-123")
+        );
+        assert_eq!(ChatReference::parse("tg://resolve?post=123"), None);
+        assert_eq!(ChatReference::parse("tg://settings"), None);
+    }
+}
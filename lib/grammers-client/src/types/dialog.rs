@@ -42,4 +42,83 @@ impl Dialog {
     pub fn chat(&self) -> &Chat {
         &self.chat
     }
+
+    /// The identifier of the folder this dialog is in.
+    ///
+    /// Dialogs in the main folder have identifier `0`, and archived dialogs have
+    /// [`ARCHIVE_FOLDER_ID`](crate::client::dialogs::ARCHIVE_FOLDER_ID).
+    pub fn folder_id(&self) -> i32 {
+        match &self.raw {
+            tl::enums::Dialog::Dialog(dialog) => dialog.folder_id.unwrap_or(0),
+            // The dialog representing a folder is always shown in the main folder.
+            tl::enums::Dialog::Folder(_) => 0,
+        }
+    }
+
+    /// Whether this dialog is pinned at the top of the folder it's in.
+    pub fn is_pinned(&self) -> bool {
+        match &self.raw {
+            tl::enums::Dialog::Dialog(dialog) => dialog.pinned,
+            tl::enums::Dialog::Folder(dialog) => dialog.pinned,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialog(id: i64, pinned: bool, folder_id: Option<i32>) -> tl::enums::Dialog {
+        tl::types::Dialog {
+            pinned,
+            unread_mark: false,
+            view_forum_as_messages: false,
+            peer: tl::types::PeerChat { chat_id: id }.into(),
+            top_message: 0,
+            read_inbox_max_id: 0,
+            read_outbox_max_id: 0,
+            unread_count: 0,
+            unread_mentions_count: 0,
+            unread_reactions_count: 0,
+            notify_settings: tl::types::PeerNotifySettings {
+                show_previews: None,
+                silent: None,
+                mute_until: None,
+                ios_sound: None,
+                android_sound: None,
+                other_sound: None,
+                stories_muted: None,
+                stories_hide_sender: None,
+                stories_ios_sound: None,
+                stories_android_sound: None,
+                stories_other_sound: None,
+            }
+            .into(),
+            pts: None,
+            draft: None,
+            folder_id,
+            ttl_period: None,
+        }
+        .into()
+    }
+
+    #[test]
+    fn check_dialog_folder_and_pin() {
+        let chats = ChatMap::new(
+            vec![],
+            vec![
+                tl::types::ChatEmpty { id: 1 }.into(),
+                tl::types::ChatEmpty { id: 2 }.into(),
+            ],
+        );
+        let mut messages = HashMap::new();
+
+        let pinned = Dialog::new(dialog(1, true, Some(1)), &mut messages, &chats);
+        assert!(pinned.is_pinned());
+        assert_eq!(pinned.folder_id(), 1);
+
+        let unpinned = Dialog::new(dialog(2, false, None), &mut messages, &chats);
+        assert!(!unpinned.is_pinned());
+        assert_eq!(unpinned.folder_id(), 0);
+    }
 }
@@ -47,6 +47,7 @@ impl ChatPhoto {
 
 #[derive(Clone, Debug, PartialEq)]
 #[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
 pub enum Downloadable {
     Media(crate::types::Media),
     UserProfilePhoto(UserProfilePhoto),
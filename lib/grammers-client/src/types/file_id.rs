@@ -0,0 +1,270 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use grammers_tl_types::{self as tl, deserialize::Cursor, Deserializable};
+use std::fmt;
+use std::str::FromStr;
+
+// https://github.com/tdlib/td/blob/master/td/telegram/files/FileLocation.h
+const WEB_LOCATION_FLAG: i32 = 1 << 24;
+const FILE_REFERENCE_FLAG: i32 = 1 << 25;
+// Versions older than this one don't have a file reference, which is needed to send the file.
+const MIN_VERSION: u8 = 4;
+
+/// The kind of file a [`FileId`] refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FileIdKind {
+    Thumbnail,
+    ProfilePhoto,
+    Photo,
+    Voice,
+    Video,
+    Document,
+    Encrypted,
+    Temp,
+    Sticker,
+    Audio,
+    Animation,
+    EncryptedThumbnail,
+    Wallpaper,
+    VideoNote,
+    SecureRaw,
+    Secure,
+    Background,
+    DocumentAsFile,
+}
+
+/// The error type which is returned when a [`FileId`] cannot be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileIdError {
+    /// The file identifier is not valid URL-safe base64.
+    InvalidEncoding,
+    /// The file identifier uses a version of the format which is not supported.
+    UnsupportedVersion(u8),
+    /// The file identifier refers to a kind of file which is not known.
+    UnknownKind(i32),
+    /// The file identifier ended before all of its fields could be read.
+    Truncated,
+    /// The file identifier refers to a file stored outside of Telegram, which has no identifier.
+    WebLocation,
+}
+
+impl fmt::Display for FileIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEncoding => write!(f, "file id error: invalid base64"),
+            Self::UnsupportedVersion(v) => write!(f, "file id error: unsupported version {v}"),
+            Self::UnknownKind(k) => write!(f, "file id error: unknown file kind {k}"),
+            Self::Truncated => write!(f, "file id error: truncated"),
+            Self::WebLocation => write!(f, "file id error: refers to a web location"),
+        }
+    }
+}
+
+impl std::error::Error for FileIdError {}
+
+impl From<tl::deserialize::Error> for FileIdError {
+    fn from(_: tl::deserialize::Error) -> Self {
+        Self::Truncated
+    }
+}
+
+/// A file identifier in the format used by the [Bot API], which can be used to send the file
+/// again without uploading it.
+///
+/// See [`InputMessage::file_id`](crate::InputMessage::file_id).
+///
+/// [Bot API]: https://core.telegram.org/bots/api#sending-files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileId {
+    /// What kind of file this is.
+    pub kind: FileIdKind,
+    /// The datacenter where the file is stored.
+    pub dc_id: i32,
+    /// The identifier of the photo or document.
+    pub id: i64,
+    /// The access hash of the photo or document.
+    pub access_hash: i64,
+    /// The file reference needed to use the photo or document.
+    pub file_reference: Vec<u8>,
+}
+
+impl FileIdKind {
+    fn from_raw(kind: i32) -> Result<Self, FileIdError> {
+        use FileIdKind::*;
+        Ok(match kind {
+            0 => Thumbnail,
+            1 => ProfilePhoto,
+            2 => Photo,
+            3 => Voice,
+            4 => Video,
+            5 => Document,
+            6 => Encrypted,
+            7 => Temp,
+            8 => Sticker,
+            9 => Audio,
+            10 => Animation,
+            11 => EncryptedThumbnail,
+            12 => Wallpaper,
+            13 => VideoNote,
+            14 => SecureRaw,
+            15 => Secure,
+            16 => Background,
+            17 => DocumentAsFile,
+            _ => return Err(FileIdError::UnknownKind(kind)),
+        })
+    }
+
+    fn is_photo(self) -> bool {
+        matches!(self, Self::Thumbnail | Self::ProfilePhoto | Self::Photo)
+    }
+}
+
+/// Undo the run-length encoding of zeros, where a zero is followed by how many zeros there are.
+fn rle_decode(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut zero = false;
+    for &byte in data {
+        if zero {
+            result.resize(result.len() + byte as usize, 0);
+            zero = false;
+        } else if byte == 0 {
+            zero = true;
+        } else {
+            result.push(byte);
+        }
+    }
+    result
+}
+
+impl FromStr for FileId {
+    type Err = FileIdError;
+
+    fn from_str(file_id: &str) -> Result<Self, Self::Err> {
+        let data = URL_SAFE_NO_PAD
+            .decode(file_id.trim_end_matches('='))
+            .map_err(|_| FileIdError::InvalidEncoding)?;
+        let data = rle_decode(&data);
+
+        // The last byte is the version, and the one before it, the sub-version.
+        let data = match data.split_last() {
+            Some((&version, data)) if version >= MIN_VERSION => {
+                data.split_last().ok_or(FileIdError::Truncated)?.1
+            }
+            Some((&version, _)) => return Err(FileIdError::UnsupportedVersion(version)),
+            None => return Err(FileIdError::Truncated),
+        };
+
+        let mut buf = Cursor::from_slice(data);
+        let kind = i32::deserialize(&mut buf)?;
+        let dc_id = i32::deserialize(&mut buf)?;
+        if kind & WEB_LOCATION_FLAG != 0 {
+            return Err(FileIdError::WebLocation);
+        }
+        let file_reference = if kind & FILE_REFERENCE_FLAG != 0 {
+            Vec::<u8>::deserialize(&mut buf)?
+        } else {
+            Vec::new()
+        };
+        let kind = FileIdKind::from_raw(kind & !(WEB_LOCATION_FLAG | FILE_REFERENCE_FLAG))?;
+        let id = i64::deserialize(&mut buf)?;
+        let access_hash = i64::deserialize(&mut buf)?;
+
+        // Photos are followed by the size they refer to, which is not needed to send them.
+        Ok(Self {
+            kind,
+            dc_id,
+            id,
+            access_hash,
+            file_reference,
+        })
+    }
+}
+
+impl FileId {
+    pub(crate) fn to_input_media(&self, ttl_seconds: Option<i32>) -> tl::enums::InputMedia {
+        if self.kind.is_photo() {
+            tl::types::InputMediaPhoto {
+                spoiler: false,
+                id: tl::types::InputPhoto {
+                    id: self.id,
+                    access_hash: self.access_hash,
+                    file_reference: self.file_reference.clone(),
+                }
+                .into(),
+                ttl_seconds,
+            }
+            .into()
+        } else {
+            tl::types::InputMediaDocument {
+                spoiler: false,
+                id: tl::types::InputDocument {
+                    id: self.id,
+                    access_hash: self.access_hash,
+                    file_reference: self.file_reference.clone(),
+                }
+                .into(),
+                ttl_seconds,
+                query: None,
+            }
+            .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_bot_api_file_id_is_decoded() {
+        let file_id: FileId =
+            "BQACAgIAAx0BAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHQACclnlAv8LY0vrfhaCC-_d7i8E"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            file_id,
+            FileId {
+                kind: FileIdKind::Document,
+                dc_id: 2,
+                id: 5432198765432101234,
+                access_hash: -1234567890123456789,
+                file_reference: (1..30).collect(),
+            }
+        );
+
+        let file_id: FileId = "AgACAgQAAwQBAAIFAAOxaN46AAQVzVsHAAQBAAd4AAMvBA"
+            .parse()
+            .unwrap();
+        assert_eq!(file_id.kind, FileIdKind::Photo);
+        assert_eq!(file_id.dc_id, 4);
+        assert_eq!(file_id.id, 987654321);
+        assert_eq!(file_id.access_hash, 123456789);
+        assert_eq!(file_id.file_reference, vec![1, 0, 0, 5]);
+    }
+
+    #[test]
+    fn check_invalid_file_ids_fail() {
+        assert_eq!(
+            "not a file id!".parse::<FileId>(),
+            Err(FileIdError::InvalidEncoding)
+        );
+        assert_eq!("".parse::<FileId>(), Err(FileIdError::Truncated));
+        assert_eq!(
+            "BQADAgADTQAHLwQ".parse::<FileId>(),
+            Err(FileIdError::Truncated)
+        );
+        // Version 2, which predates file references.
+        assert_eq!(
+            "BQADAgADTQAHWAAHAg".parse::<FileId>(),
+            Err(FileIdError::UnsupportedVersion(2))
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::{Chat, Dialog};
+use crate::client::dialogs::ARCHIVE_FOLDER_ID;
+use grammers_tl_types as tl;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// A chat folder (also known as dialog filter), used to organize the dialogs.
+///
+/// Unlike the archive, which dialogs are explicitly moved to, a dialog is in a folder when it
+/// matches the folder's rules. Use [`Folder::contains`] to check this.
+///
+/// See [`Client::get_folders`](crate::Client::get_folders).
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Folder {
+    /// The identifier of the folder.
+    pub id: i32,
+    /// The name of the folder.
+    pub title: String,
+    /// The emoji used as the icon of the folder, if any.
+    pub emoticon: Option<String>,
+    /// Whether all private chats with contacts are included.
+    pub contacts: bool,
+    /// Whether all private chats with users that are not contacts are included.
+    pub non_contacts: bool,
+    /// Whether all groups are included.
+    pub groups: bool,
+    /// Whether all broadcast channels are included.
+    pub broadcasts: bool,
+    /// Whether all private chats with bots are included.
+    pub bots: bool,
+    /// Whether muted chats are excluded, unless they're explicitly included.
+    pub exclude_muted: bool,
+    /// Whether chats without unread messages are excluded, unless they're explicitly included.
+    pub exclude_read: bool,
+    /// Whether archived chats are excluded, unless they're explicitly included.
+    pub exclude_archived: bool,
+    /// The chats pinned at the top of the folder, which are always included.
+    pub pinned_peers: Vec<tl::enums::InputPeer>,
+    /// The chats which are always included.
+    pub included_peers: Vec<tl::enums::InputPeer>,
+    /// The chats which are never included.
+    pub excluded_peers: Vec<tl::enums::InputPeer>,
+}
+
+impl Folder {
+    /// Returns `None` for the placeholder of the default folder with all chats.
+    pub(crate) fn from_raw(filter: tl::enums::DialogFilter) -> Option<Self> {
+        Some(match filter {
+            tl::enums::DialogFilter::Filter(filter) => Self {
+                id: filter.id,
+                title: filter.title,
+                emoticon: filter.emoticon,
+                contacts: filter.contacts,
+                non_contacts: filter.non_contacts,
+                groups: filter.groups,
+                broadcasts: filter.broadcasts,
+                bots: filter.bots,
+                exclude_muted: filter.exclude_muted,
+                exclude_read: filter.exclude_read,
+                exclude_archived: filter.exclude_archived,
+                pinned_peers: filter.pinned_peers,
+                included_peers: filter.include_peers,
+                excluded_peers: filter.exclude_peers,
+            },
+            // Shared folders only contain the chats they were created with.
+            tl::enums::DialogFilter::Chatlist(filter) => Self {
+                id: filter.id,
+                title: filter.title,
+                emoticon: filter.emoticon,
+                contacts: false,
+                non_contacts: false,
+                groups: false,
+                broadcasts: false,
+                bots: false,
+                exclude_muted: false,
+                exclude_read: false,
+                exclude_archived: false,
+                pinned_peers: filter.pinned_peers,
+                included_peers: filter.include_peers,
+                excluded_peers: Vec::new(),
+            },
+            tl::enums::DialogFilter::Default => return None,
+        })
+    }
+
+    pub(crate) fn from_raw_list(filters: tl::enums::messages::DialogFilters) -> Vec<Self> {
+        let tl::enums::messages::DialogFilters::Filters(filters) = filters;
+        filters
+            .filters
+            .into_iter()
+            .filter_map(Self::from_raw)
+            .collect()
+    }
+
+    /// Check whether the dialog belongs to this folder, according to the folder's rules.
+    ///
+    /// Excluded chats are never in the folder. Pinned and included chats always are. Any other
+    /// chat is in the folder if its kind is included and it's not excluded by the other flags.
+    pub fn contains(&self, dialog: &Dialog) -> bool {
+        let chat = dialog.chat();
+        let listed = |peers: &[tl::enums::InputPeer]| peers.iter().any(|p| refers_to(p, chat));
+
+        if listed(&self.excluded_peers) {
+            return false;
+        }
+        if listed(&self.pinned_peers) || listed(&self.included_peers) {
+            return true;
+        }
+
+        let included = match chat {
+            Chat::User(user) if user.is_bot() => self.bots,
+            Chat::User(user) if user.contact() || user.is_self() => self.contacts,
+            Chat::User(_) => self.non_contacts,
+            Chat::Group(_) => self.groups,
+            Chat::Channel(_) => self.broadcasts,
+        };
+        if !included {
+            return false;
+        }
+
+        let tl::enums::Dialog::Dialog(raw) = &dialog.raw else {
+            // Folders of archived chats are not chats, so they cannot be in a folder.
+            return false;
+        };
+        !(self.exclude_archived && raw.folder_id == Some(ARCHIVE_FOLDER_ID)
+            || self.exclude_read && raw.unread_count == 0 && !raw.unread_mark
+            || self.exclude_muted && is_muted(&raw.notify_settings))
+    }
+}
+
+/// Check whether the input peer refers to the given chat.
+fn refers_to(peer: &tl::enums::InputPeer, chat: &Chat) -> bool {
+    use tl::enums::InputPeer;
+
+    match (peer, chat) {
+        (InputPeer::PeerSelf, Chat::User(user)) => user.is_self(),
+        (InputPeer::User(peer), Chat::User(user)) => peer.user_id == user.id(),
+        (InputPeer::UserFromMessage(peer), Chat::User(user)) => peer.user_id == user.id(),
+        (InputPeer::Chat(peer), Chat::Group(group)) => peer.chat_id == group.id(),
+        (InputPeer::Channel(peer), Chat::Group(_) | Chat::Channel(_)) => {
+            peer.channel_id == chat.id()
+        }
+        (InputPeer::ChannelFromMessage(peer), Chat::Group(_) | Chat::Channel(_)) => {
+            peer.channel_id == chat.id()
+        }
+        _ => false,
+    }
+}
+
+fn is_muted(settings: &tl::enums::PeerNotifySettings) -> bool {
+    let tl::enums::PeerNotifySettings::Settings(settings) = settings;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    settings
+        .mute_until
+        .is_some_and(|until| i64::from(until) > now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use crate::types::{ChatMap, User};
+    use crate::Client;
+    use std::collections::HashMap;
+
+    fn user(id: i64, bot: bool) -> tl::enums::User {
+        let mut user = User::from_raw(tl::types::UserEmpty { id }.into()).raw;
+        user.access_hash = Some(id);
+        user.bot = bot;
+        user.into()
+    }
+
+    fn dialog(user_id: i64) -> tl::enums::Dialog {
+        tl::types::Dialog {
+            pinned: false,
+            unread_mark: false,
+            view_forum_as_messages: false,
+            peer: tl::types::PeerUser { user_id }.into(),
+            top_message: 0,
+            read_inbox_max_id: 0,
+            read_outbox_max_id: 0,
+            unread_count: 0,
+            unread_mentions_count: 0,
+            unread_reactions_count: 0,
+            notify_settings: tl::types::PeerNotifySettings {
+                show_previews: None,
+                silent: None,
+                mute_until: None,
+                ios_sound: None,
+                android_sound: None,
+                other_sound: None,
+                stories_muted: None,
+                stories_hide_sender: None,
+                stories_ios_sound: None,
+                stories_android_sound: None,
+                stories_other_sound: None,
+            }
+            .into(),
+            pts: None,
+            draft: None,
+            folder_id: None,
+            ttl_period: None,
+        }
+        .into()
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_folder_rules_are_applied() {
+        let folders = Folder::from_raw_list(
+            tl::types::messages::DialogFilters {
+                tags_enabled: false,
+                filters: vec![
+                    tl::enums::DialogFilter::Default,
+                    tl::types::DialogFilter {
+                        contacts: false,
+                        non_contacts: false,
+                        groups: false,
+                        broadcasts: false,
+                        bots: true,
+                        exclude_muted: false,
+                        exclude_read: false,
+                        exclude_archived: false,
+                        id: 2,
+                        title: "Bots".to_string(),
+                        emoticon: Some("🤖".to_string()),
+                        color: None,
+                        pinned_peers: Vec::new(),
+                        include_peers: Vec::new(),
+                        exclude_peers: vec![tl::types::InputPeerUser {
+                            user_id: 20,
+                            access_hash: 20,
+                        }
+                        .into()],
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        );
+        assert_eq!(folders.len(), 1);
+        let folder = &folders[0];
+        assert_eq!(folder.id, 2);
+        assert_eq!(folder.title, "Bots");
+        assert!(folder.bots);
+
+        let chats = ChatMap::new(
+            vec![user(10, true), user(20, true), user(30, false)],
+            vec![],
+        );
+        let mut messages = HashMap::new();
+        let dialogs = [10, 20, 30]
+            .into_iter()
+            .map(|id| Dialog::new(dialog(id), &mut messages, &chats))
+            .collect::<Vec<_>>();
+
+        assert!(folder.contains(&dialogs[0]));
+        assert!(!folder.contains(&dialogs[1]));
+        assert!(!folder.contains(&dialogs[2]));
+
+        // Iterating over the dialogs of the folder skips those not in it.
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let mut iter = client.iter_dialogs().folder(folder.clone());
+            iter.buffer.extend(dialogs);
+            iter.last_chunk = true;
+
+            let mut contained = Vec::new();
+            while let Some(dialog) = iter.next().await.unwrap() {
+                contained.push(dialog.chat().id());
+            }
+            assert_eq!(contained, vec![10]);
+            // Skipped dialogs still move the offset forward.
+            assert_eq!(
+                iter.last_offset().peer,
+                chats
+                    .get(&tl::types::PeerUser { user_id: 30 }.into())
+                    .unwrap()
+                    .pack()
+                    .to_input_peer()
+            );
+        });
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::{Chat, ChatMap};
+use crate::{utils, Client};
+use chrono::{DateTime, Utc};
+use grammers_tl_types as tl;
+
+/// Who sent the original message of a forward.
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ForwardSender {
+    /// The user or channel which sent the original message.
+    Chat(Chat),
+    /// The name of a user who hid their account from forwards of their messages.
+    Hidden(String),
+}
+
+/// Information about where a forwarded message originated.
+///
+/// See [`Message::forward`](crate::types::Message::forward).
+#[derive(Clone, Debug)]
+pub struct Forward {
+    pub raw: tl::types::MessageFwdHeader,
+    sender: Option<ForwardSender>,
+}
+
+impl Forward {
+    pub(crate) fn from_raw(
+        header: tl::enums::MessageFwdHeader,
+        chats: &ChatMap,
+        client: &Client,
+    ) -> Self {
+        let tl::enums::MessageFwdHeader::Header(raw) = header;
+        let sender = match (&raw.from_id, &raw.from_name) {
+            (Some(peer), _) => Some(ForwardSender::Chat(utils::always_find_entity(
+                peer, chats, client,
+            ))),
+            (None, Some(name)) => Some(ForwardSender::Hidden(name.clone())),
+            (None, None) => None,
+        };
+        Self { raw, sender }
+    }
+
+    /// The sender of the original message.
+    ///
+    /// This is the channel itself for posts in broadcast channels. It may be unknown when
+    /// the message was imported from another application.
+    pub fn sender(&self) -> Option<&ForwardSender> {
+        self.sender.as_ref()
+    }
+
+    /// When the original message was sent.
+    pub fn date(&self) -> DateTime<Utc> {
+        utils::date(self.raw.date)
+    }
+
+    /// The identifier of the original message, if it was a post in a broadcast channel.
+    pub fn from_message_id(&self) -> Option<i32> {
+        self.raw.channel_post
+    }
+
+    /// The signature of the author of the original post, if it was signed.
+    pub fn post_author(&self) -> Option<&str> {
+        self.raw.post_author.as_deref()
+    }
+
+    /// Whether the original message was imported from another application.
+    pub fn imported(&self) -> bool {
+        self.raw.imported
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use crate::types::message::EMPTY_MESSAGE;
+    use crate::types::Message;
+
+    fn header(
+        from_id: Option<tl::enums::Peer>,
+        from_name: Option<&str>,
+        channel_post: Option<i32>,
+    ) -> tl::enums::MessageFwdHeader {
+        tl::types::MessageFwdHeader {
+            imported: false,
+            saved_out: false,
+            from_id,
+            from_name: from_name.map(str::to_string),
+            date: 1_700_000_000,
+            channel_post,
+            post_author: None,
+            saved_from_peer: None,
+            saved_from_msg_id: None,
+            saved_from_id: None,
+            saved_from_name: None,
+            saved_date: None,
+            psa_type: None,
+        }
+        .into()
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_forward_origin() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let message = |fwd_from| {
+                let raw = tl::types::Message {
+                    fwd_from: Some(fwd_from),
+                    ..EMPTY_MESSAGE
+                };
+                Message::from_raw(&client, raw.into(), &ChatMap::empty()).unwrap()
+            };
+
+            let forward = message(header(
+                Some(tl::types::PeerChannel { channel_id: 1234 }.into()),
+                None,
+                Some(56),
+            ))
+            .forward()
+            .unwrap();
+            match forward.sender() {
+                Some(ForwardSender::Chat(Chat::Channel(channel))) => {
+                    assert_eq!(channel.id(), 1234)
+                }
+                sender => panic!("unexpected sender: {sender:?}"),
+            }
+            assert_eq!(forward.from_message_id(), Some(56));
+            assert_eq!(forward.date().timestamp(), 1_700_000_000);
+
+            let forward = message(header(None, Some("Someone"), None))
+                .forward()
+                .unwrap();
+            match forward.sender() {
+                Some(ForwardSender::Hidden(name)) => assert_eq!(name, "Someone"),
+                sender => panic!("unexpected sender: {sender:?}"),
+            }
+            assert_eq!(forward.from_message_id(), None);
+
+            let message =
+                Message::from_raw(&client, EMPTY_MESSAGE.into(), &ChatMap::empty()).unwrap();
+            assert!(message.forward().is_none());
+        });
+    }
+}
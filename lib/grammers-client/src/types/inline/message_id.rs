@@ -0,0 +1,149 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use grammers_tl_types::{self as tl, Deserializable, Serializable};
+use std::fmt;
+use std::str::FromStr;
+
+// Sizes of the bare `inputBotInlineMessageID` and `inputBotInlineMessageID64`.
+const LEGACY_LEN: usize = 4 + 8 + 8;
+const LEN: usize = 4 + 8 + 4 + 8;
+
+/// The identifier of a message sent by a bot via inline mode, which is needed to edit it.
+///
+/// Such messages don't belong to any chat the bot is in, so they can only be referred to by this
+/// identifier, which is received in [`InlineSend`](super::send::InlineSend) updates and the
+/// callback queries from the buttons in the message. It can also be parsed from (and formatted
+/// as) the string used by the [Bot API].
+///
+/// [Bot API]: https://core.telegram.org/bots/api#callbackquery
+#[derive(Clone, Debug, PartialEq)]
+pub struct InlineMessageId {
+    pub raw: tl::enums::InputBotInlineMessageId,
+}
+
+/// The error type which is returned when an [`InlineMessageId`] cannot be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidInlineMessageId;
+
+impl fmt::Display for InvalidInlineMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid inline message id")
+    }
+}
+
+impl std::error::Error for InvalidInlineMessageId {}
+
+impl InlineMessageId {
+    /// The datacenter where the message is stored, which is where edits must be sent.
+    pub fn dc_id(&self) -> i32 {
+        self.raw.dc_id()
+    }
+
+    /// The identifier of the owner of the chat the message was sent to.
+    pub fn owner_id(&self) -> i64 {
+        match &self.raw {
+            // The upper half of the identifier is the owner, and the lower half the message.
+            tl::enums::InputBotInlineMessageId::Id(id) => (id.id >> 32) as i32 as i64,
+            tl::enums::InputBotInlineMessageId::Id64(id) => id.owner_id,
+        }
+    }
+
+    /// The identifier of the message within the chat it was sent to.
+    pub fn message_id(&self) -> i32 {
+        match &self.raw {
+            tl::enums::InputBotInlineMessageId::Id(id) => id.id as i32,
+            tl::enums::InputBotInlineMessageId::Id64(id) => id.id,
+        }
+    }
+
+    /// The access hash needed to use the identifier.
+    pub fn access_hash(&self) -> i64 {
+        self.raw.access_hash()
+    }
+}
+
+impl From<tl::enums::InputBotInlineMessageId> for InlineMessageId {
+    fn from(raw: tl::enums::InputBotInlineMessageId) -> Self {
+        Self { raw }
+    }
+}
+
+impl From<InlineMessageId> for tl::enums::InputBotInlineMessageId {
+    fn from(id: InlineMessageId) -> Self {
+        id.raw
+    }
+}
+
+impl FromStr for InlineMessageId {
+    type Err = InvalidInlineMessageId;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        // The identifier is the bare constructor, so its variant is known by its length.
+        let data = URL_SAFE_NO_PAD
+            .decode(id.trim_end_matches('='))
+            .map_err(|_| InvalidInlineMessageId)?;
+        let raw = match data.len() {
+            LEGACY_LEN => tl::types::InputBotInlineMessageId::from_bytes(&data)
+                .map_err(|_| InvalidInlineMessageId)?
+                .into(),
+            LEN => tl::types::InputBotInlineMessageId64::from_bytes(&data)
+                .map_err(|_| InvalidInlineMessageId)?
+                .into(),
+            _ => return Err(InvalidInlineMessageId),
+        };
+        Ok(Self { raw })
+    }
+}
+
+impl fmt::Display for InlineMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = match &self.raw {
+            tl::enums::InputBotInlineMessageId::Id(id) => id.to_bytes(),
+            tl::enums::InputBotInlineMessageId::Id64(id) => id.to_bytes(),
+        };
+        f.write_str(&URL_SAFE_NO_PAD.encode(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_inline_message_id_is_decoded() {
+        let id: InlineMessageId = "BAAAANIEAADuuPD_LvUyxRHT6_8=".parse().unwrap();
+        assert!(matches!(id.raw, tl::enums::InputBotInlineMessageId::Id(_)));
+        assert_eq!(id.dc_id(), 4);
+        assert_eq!(id.owner_id(), -1001234);
+        assert_eq!(id.message_id(), 1234);
+        assert_eq!(id.access_hash(), -5678901234567890);
+        assert_eq!(id.to_string(), "BAAAANIEAADuuPD_LvUyxRHT6_8");
+
+        let id: InlineMessageId = "AgAAACjbCwAAAAAAKgAAALFo3joAAAAA".parse().unwrap();
+        assert!(matches!(
+            id.raw,
+            tl::enums::InputBotInlineMessageId::Id64(_)
+        ));
+        assert_eq!(id.dc_id(), 2);
+        assert_eq!(id.owner_id(), 777000);
+        assert_eq!(id.message_id(), 42);
+        assert_eq!(id.access_hash(), 987654321);
+        assert_eq!(id.to_string(), "AgAAACjbCwAAAAAAKgAAALFo3joAAAAA");
+
+        assert_eq!(
+            "not an id!".parse::<InlineMessageId>(),
+            Err(InvalidInlineMessageId)
+        );
+        assert_eq!(
+            "AgAAACjbCwAA".parse::<InlineMessageId>(),
+            Err(InvalidInlineMessageId)
+        );
+    }
+}
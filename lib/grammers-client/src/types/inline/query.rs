@@ -17,7 +17,7 @@ use std::sync::Arc;
 /// inline query such as `@bot query`.
 #[derive(Clone)]
 pub struct InlineQuery {
-    raw: tl::types::UpdateBotInlineQuery,
+    pub(crate) raw: tl::types::UpdateBotInlineQuery,
     client: Client,
     chats: Arc<ChatMap>,
 }
@@ -235,7 +235,7 @@ impl From<Article> for tl::enums::InputBotInlineResult {
             // TODO: also allow other types of messages than text
             send_message: tl::enums::InputBotInlineMessage::Text(
                 tl::types::InputBotInlineMessageText {
-                    no_webpage: !article.input_message.link_preview,
+                    no_webpage: article.input_message.no_webpage,
                     invert_media: article.input_message.invert_media,
                     message: article.input_message.text,
                     entities: Some(article.input_message.entities),
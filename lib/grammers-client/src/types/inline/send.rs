@@ -6,7 +6,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::types::{Chat, User};
+use crate::types::{Chat, InlineMessageId, User};
 use crate::{ChatMap, Client, InputMessage};
 use grammers_mtsender::InvocationError;
 use grammers_tl_types as tl;
@@ -16,7 +16,7 @@ use std::sync::Arc;
 /// Represents an update of user choosing the result of inline query and sending it to their chat partner.
 #[derive(Clone)]
 pub struct InlineSend {
-    raw: tl::types::UpdateBotInlineSend,
+    pub(crate) raw: tl::types::UpdateBotInlineSend,
     client: Client,
     chats: Arc<ChatMap>,
 }
@@ -64,8 +64,8 @@ impl InlineSend {
     /// Identifier of sent inline message.
     /// Available only if there is an inline keyboard attached.
     /// Will be also received in callback queries and can be used to edit the message.
-    pub fn message_id(&self) -> Option<tl::enums::InputBotInlineMessageId> {
-        self.raw.msg_id.clone()
+    pub fn message_id(&self) -> Option<InlineMessageId> {
+        self.raw.msg_id.clone().map(InlineMessageId::from)
     }
 
     /// Edits this inline message.
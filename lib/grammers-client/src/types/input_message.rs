@@ -6,24 +6,50 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use super::attributes::Attribute;
-use crate::types::{Media, ReplyMarkup, Uploaded};
+use crate::types::{FileId, FileIdError, Media, ReplyMarkup, Uploaded};
+use grammers_session::PackedChat;
 use grammers_tl_types as tl;
+use std::time::Duration;
 use web_time::{SystemTime, UNIX_EPOCH};
 
 // https://github.com/telegramdesktop/tdesktop/blob/e7fbcce9d9f0a8944eb2c34e74bd01b8776cb891/Telegram/SourceFiles/data/data_scheduled_messages.h#L52
 const SCHEDULE_ONCE_ONLINE: i32 = 0x7ffffffe;
 
+/// Live location period that makes the location be shared until it's manually stopped.
+pub const LIVE_LOCATION_INDEFINITE: i32 = 0x7fffffff;
+const LIVE_LOCATION_PERIODS: std::ops::RangeInclusive<i32> = 60..=86400;
+
+/// Options to customize the link preview of a message.
+///
+/// See [`InputMessage::preview_options`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkPreviewOptions {
+    /// The URL to preview, which does not need to appear in the text of the message.
+    pub url: String,
+    /// Whether the preview should be shown above the text of the message instead of below.
+    pub above_text: bool,
+    /// Whether the media of the preview should be shown smaller, if possible.
+    pub small_media: bool,
+    /// Whether the media of the preview should be shown larger, if possible.
+    pub large_media: bool,
+}
+
 /// Construct and send rich text messages with various options.
 #[derive(Clone, Default)]
 pub struct InputMessage {
     pub(crate) background: bool,
     pub(crate) clear_draft: bool,
+    pub(crate) effect: Option<i64>,
     pub(crate) entities: Vec<tl::enums::MessageEntity>,
     pub(crate) invert_media: bool,
-    pub(crate) link_preview: bool,
+    pub(crate) no_webpage: bool,
     pub(crate) reply_markup: Option<tl::enums::ReplyMarkup>,
     pub(crate) reply_to: Option<i32>,
+    pub(crate) topic: Option<i32>,
+    pub(crate) quote: Option<(String, i32)>,
+    pub(crate) random_id: Option<i64>,
     pub(crate) schedule_date: Option<i32>,
+    pub(crate) send_as: Option<tl::enums::InputPeer>,
     pub(crate) silent: bool,
     pub(crate) text: String,
     pub(crate) media: Option<tl::enums::InputMedia>,
@@ -46,6 +72,16 @@ impl InputMessage {
         self
     }
 
+    /// The identifier of the animated effect to play when the message is shown.
+    ///
+    /// Effects can only be used in private chats. The available effects, and whether they
+    /// require Telegram Premium, can be retrieved with
+    /// [`Client::get_available_effects`](crate::Client::get_available_effects).
+    pub fn effect(mut self, effect_id: i64) -> Self {
+        self.effect = Some(effect_id);
+        self
+    }
+
     /// The formatting entities within the message (such as bold, italics, etc.).
     pub fn fmt_entities(mut self, entities: Vec<tl::enums::MessageEntity>) -> Self {
         self.entities = entities;
@@ -62,9 +98,41 @@ impl InputMessage {
 
     /// Whether the link preview be shown for the message.
     ///
+    /// By default, Telegram will show a preview for the first link in the text of the message.
+    ///
     /// This has no effect when sending media, which cannot contain a link preview.
     pub fn link_preview(mut self, link_preview: bool) -> Self {
-        self.link_preview = link_preview;
+        self.no_webpage = !link_preview;
+        self
+    }
+
+    /// Show a link preview with the given options, instead of letting Telegram choose one.
+    ///
+    /// The preview is sent as the media of the message, so this replaces any previous media.
+    /// If no preview can be generated for the URL, the message is sent without one.
+    pub fn preview_options(mut self, options: LinkPreviewOptions) -> Self {
+        self.no_webpage = false;
+        self.invert_media = options.above_text;
+        self.media = Some(
+            tl::types::InputMediaWebPage {
+                force_large_media: options.large_media,
+                force_small_media: options.small_media,
+                optional: true,
+                url: options.url,
+            }
+            .into(),
+        );
+        self
+    }
+
+    /// The random identifier Telegram uses to detect duplicate sends of the message.
+    ///
+    /// By default, a new one is generated every time the message is sent. Using the same
+    /// identifier when retrying a send that may have already reached Telegram (for example,
+    /// after a timeout) prevents the message from being sent twice, because Telegram ignores
+    /// sends whose random identifier has already been used in the chat.
+    pub fn random_id(mut self, random_id: i64) -> Self {
+        self.random_id = Some(random_id);
         self
     }
 
@@ -91,6 +159,26 @@ impl InputMessage {
         self
     }
 
+    /// The identifier of the forum topic in which this message should be sent, if any.
+    ///
+    /// This is the identifier of the message that started the topic. If [`Self::reply_to`]
+    /// is also set, the message will reply to that message within the topic.
+    pub fn topic(mut self, top_msg_id: Option<i32>) -> Self {
+        self.topic = top_msg_id;
+        self
+    }
+
+    /// Quote a portion of the message being replied to.
+    ///
+    /// The `offset` is where the `text` starts within the replied message, measured in UTF-16
+    /// code units (see [`crate::parsers::utf16`]).
+    ///
+    /// This has no effect unless [`Self::reply_to`] is also set.
+    pub fn quote<T: Into<String>>(mut self, text: T, offset: i32) -> Self {
+        self.quote = Some((text.into(), offset));
+        self
+    }
+
     /// If set to a distant enough future time, the message won't be sent immediately,
     /// and instead it will be scheduled to be automatically sent at a later time.
     ///
@@ -117,6 +205,16 @@ impl InputMessage {
         self
     }
 
+    /// Send the message on behalf of a different peer, such as a channel the logged-in user
+    /// owns, or the group itself to post anonymously as an administrator.
+    ///
+    /// The peers that can be used in a chat can be retrieved with
+    /// [`Client::get_send_as_peers`](crate::Client::get_send_as_peers).
+    pub fn send_as<C: Into<PackedChat>>(mut self, peer: C) -> Self {
+        self.send_as = Some(peer.into().to_input_peer());
+        self
+    }
+
     /// Whether the message should notify people or not.
     ///
     /// Defaults to `false`, which means it will notify them. Set it to `true`
@@ -273,6 +371,165 @@ impl InputMessage {
         self
     }
 
+    /// Mark the uploaded document as a video, so that Telegram can display its dimensions and
+    /// duration, and let users play it without downloading it first if it `supports_streaming`.
+    ///
+    /// This must be called *after* setting a file. Any previous video attribute is replaced.
+    pub fn video(mut self, w: i32, h: i32, duration: Duration, supports_streaming: bool) -> Self {
+        self.set_attribute(Attribute::Video {
+            round_message: false,
+            supports_streaming,
+            duration,
+            w,
+            h,
+        });
+        self
+    }
+
+    /// Mark the uploaded document as audio, so that Telegram can display its duration and
+    /// metadata. When `voice` is `true`, the audio will be shown as a voice note instead (in
+    /// which case the `title` and `performer` are not used).
+    ///
+    /// This must be called *after* setting a file. Any previous audio attribute is replaced.
+    pub fn audio(
+        mut self,
+        duration: Duration,
+        title: Option<String>,
+        performer: Option<String>,
+        voice: bool,
+    ) -> Self {
+        self.set_attribute(if voice {
+            Attribute::Voice {
+                duration,
+                waveform: None,
+            }
+        } else {
+            Attribute::Audio {
+                duration,
+                title,
+                performer,
+            }
+        });
+        self
+    }
+
+    /// Send the uploaded file as a generic document, even if it's a photo or a video.
+    ///
+    /// This must be called *after* setting a file.
+    pub fn as_document(mut self) -> Self {
+        if let Some(tl::enums::InputMedia::UploadedPhoto(photo)) = &self.media {
+            let file = Uploaded::from_raw(photo.file.clone());
+            self = self.document(file);
+        }
+        if let Some(tl::enums::InputMedia::UploadedDocument(document)) = &mut self.media {
+            document.force_file = true;
+        }
+        self
+    }
+
+    /// Send the uploaded file as a photo, even if it was set as a document.
+    ///
+    /// The Telegram server will compress the image, so any document attributes are lost.
+    ///
+    /// This must be called *after* setting a file.
+    pub fn as_photo(mut self) -> Self {
+        if let Some(tl::enums::InputMedia::UploadedDocument(document)) = &self.media {
+            let file = Uploaded::from_raw(document.file.clone());
+            self = self.photo(file);
+        }
+        self
+    }
+
+    /// Add the attribute to the uploaded document, removing any previous one of the same kind.
+    fn set_attribute(&mut self, attr: Attribute) {
+        if let Some(tl::enums::InputMedia::UploadedDocument(document)) = &mut self.media {
+            let attr = tl::enums::DocumentAttribute::from(attr);
+            document
+                .attributes
+                .retain(|a| std::mem::discriminant(a) != std::mem::discriminant(&attr));
+            document.attributes.push(attr);
+        }
+    }
+
+    /// Include a contact card in the message.
+    ///
+    /// The `vcard`, if any, is sent as-is, and may contain additional contact information.
+    pub fn contact(
+        mut self,
+        phone_number: impl Into<String>,
+        first_name: impl Into<String>,
+        last_name: impl Into<String>,
+        vcard: Option<String>,
+    ) -> Self {
+        self.media = Some(
+            (tl::types::InputMediaContact {
+                phone_number: phone_number.into(),
+                first_name: first_name.into(),
+                last_name: last_name.into(),
+                vcard: vcard.unwrap_or_default(),
+            })
+            .into(),
+        );
+        self
+    }
+
+    /// Include a location in the message, given by its latitude and longitude.
+    pub fn geo(mut self, lat: f64, long: f64) -> Self {
+        self.media = Some(
+            (tl::types::InputMediaGeoPoint {
+                geo_point: input_geo_point(lat, long),
+            })
+            .into(),
+        );
+        self
+    }
+
+    /// Include a venue in the message, such as a shop or a restaurant, at the given location.
+    ///
+    /// The `provider` is the name of the venue database the `venue_id` belongs to (currently,
+    /// only "foursquare" and "gplaces" are supported by official clients), but both may be
+    /// empty if the venue is not part of any database.
+    pub fn venue(
+        mut self,
+        lat: f64,
+        long: f64,
+        title: impl Into<String>,
+        address: impl Into<String>,
+        provider: impl Into<String>,
+        venue_id: impl Into<String>,
+    ) -> Self {
+        self.media = Some(
+            (tl::types::InputMediaVenue {
+                geo_point: input_geo_point(lat, long),
+                title: title.into(),
+                address: address.into(),
+                provider: provider.into(),
+                venue_id: venue_id.into(),
+                venue_type: String::new(),
+            })
+            .into(),
+        );
+        self
+    }
+
+    /// Include a live location in the message, which can be updated during the next `period`
+    /// seconds with [`Message::edit_live_location`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `period` is not between one minute and one day (inclusive), unless it's
+    /// [`LIVE_LOCATION_INDEFINITE`].
+    ///
+    /// [`Message::edit_live_location`]: crate::types::Message::edit_live_location
+    pub fn live_location(mut self, lat: f64, long: f64, period: i32) -> Self {
+        assert!(
+            LIVE_LOCATION_PERIODS.contains(&period) || period == LIVE_LOCATION_INDEFINITE,
+            "live location period must be within {LIVE_LOCATION_PERIODS:?} seconds or indefinite"
+        );
+        self.media = Some(input_live_location(lat, long, Some(period)));
+        self
+    }
+
     /// Copy media from an existing message.
     ///
     /// You can use this to send media from another message without re-uploading it.
@@ -281,6 +538,34 @@ impl InputMessage {
         self
     }
 
+    /// Include a file which was already sent, by its [Bot API] file identifier.
+    ///
+    /// The file is sent without uploading it again, as a photo or as a document depending on
+    /// what the identifier refers to.
+    ///
+    /// The text will be the caption of the file, which may be empty for no caption.
+    ///
+    /// Returns an error if the file identifier cannot be decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+    /// use grammers_client::InputMessage;
+    ///
+    /// let message = InputMessage::text("Again!")
+    ///     .file_id("BQACAgIAAx0BAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHQACclnlAv8LY0vrfhaCC-_d7i8E")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [Bot API]: https://core.telegram.org/bots/api#sending-files
+    pub fn file_id(mut self, file_id: &str) -> Result<Self, FileIdError> {
+        let file_id = file_id.parse::<FileId>()?;
+        self.media = Some(file_id.to_input_media(self.media_ttl));
+        Ok(self)
+    }
+
     /// Include the uploaded file as a document file in the message.
     ///
     /// You can use this to send any type of media as a simple document file.
@@ -387,6 +672,58 @@ impl InputMessage {
     }
 }
 
+impl InputMessage {
+    /// The reply information to use in the request sending this message, if any.
+    pub(crate) fn input_reply_to(&self) -> Option<tl::enums::InputReplyTo> {
+        let (reply_to_msg_id, top_msg_id) = match (self.reply_to, self.topic) {
+            (Some(reply_to), topic) => (reply_to, topic),
+            (None, Some(topic)) => (topic, None),
+            (None, None) => return None,
+        };
+        let (quote_text, quote_offset) = match (self.reply_to, &self.quote) {
+            (Some(_), Some((text, offset))) => (Some(text.clone()), Some(*offset)),
+            _ => (None, None),
+        };
+
+        Some(
+            tl::types::InputReplyToMessage {
+                reply_to_msg_id,
+                top_msg_id,
+                reply_to_peer_id: None,
+                quote_text,
+                quote_entities: None,
+                quote_offset,
+            }
+            .into(),
+        )
+    }
+}
+
+fn input_geo_point(lat: f64, long: f64) -> tl::enums::InputGeoPoint {
+    tl::types::InputGeoPoint {
+        lat,
+        long,
+        accuracy_radius: None,
+    }
+    .into()
+}
+
+/// Live location media, with a `period` only when it's first sent (not when it's updated).
+pub(crate) fn input_live_location(
+    lat: f64,
+    long: f64,
+    period: Option<i32>,
+) -> tl::enums::InputMedia {
+    tl::types::InputMediaGeoLive {
+        stopped: false,
+        geo_point: input_geo_point(lat, long),
+        heading: None,
+        period,
+        proximity_notification_radius: None,
+    }
+    .into()
+}
+
 impl From<&str> for InputMessage {
     fn from(text: &str) -> Self {
         Self::text(text)
@@ -412,3 +749,314 @@ impl From<&super::Message> for InputMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uploaded(name: &str) -> Uploaded {
+        Uploaded::from_raw(
+            tl::types::InputFile {
+                id: 1,
+                parts: 1,
+                name: name.to_string(),
+                md5_checksum: String::new(),
+            }
+            .into(),
+        )
+    }
+
+    fn uploaded_document(message: &InputMessage) -> &tl::types::InputMediaUploadedDocument {
+        match &message.media {
+            Some(tl::enums::InputMedia::UploadedDocument(document)) => document,
+            media => panic!("unexpected media: {media:?}"),
+        }
+    }
+
+    fn reply_to_message(message: &InputMessage) -> tl::types::InputReplyToMessage {
+        match message.input_reply_to() {
+            Some(tl::enums::InputReplyTo::Message(reply_to)) => reply_to,
+            reply_to => panic!("unexpected reply_to: {reply_to:?}"),
+        }
+    }
+
+    #[test]
+    fn check_file_id_is_sent_without_upload() {
+        let message = InputMessage::text("again")
+            .media_ttl(10)
+            .file_id("BQACAgIAAx0BAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHQACclnlAv8LY0vrfhaCC-_d7i8E")
+            .unwrap();
+        match message.media {
+            Some(tl::enums::InputMedia::Document(document)) => {
+                assert_eq!(
+                    document.id,
+                    tl::types::InputDocument {
+                        id: 5432198765432101234,
+                        access_hash: -1234567890123456789,
+                        file_reference: (1..30).collect(),
+                    }
+                    .into()
+                );
+                assert_eq!(document.ttl_seconds, Some(10));
+            }
+            media => panic!("unexpected media: {media:?}"),
+        }
+
+        assert!(InputMessage::text("again").file_id("invalid!").is_err());
+    }
+
+    #[test]
+    fn check_no_reply_by_default() {
+        let message = InputMessage::text("hi").quote("ignored", 0);
+        assert!(message.input_reply_to().is_none());
+    }
+
+    #[test]
+    fn check_reply_to_message() {
+        let reply_to = reply_to_message(&InputMessage::text("hi").reply_to(Some(12)));
+        assert_eq!(reply_to.reply_to_msg_id, 12);
+        assert_eq!(reply_to.top_msg_id, None);
+        assert_eq!(reply_to.quote_text, None);
+        assert_eq!(reply_to.quote_offset, None);
+    }
+
+    #[test]
+    fn check_reply_in_topic() {
+        let reply_to = reply_to_message(&InputMessage::text("hi").topic(Some(3)));
+        assert_eq!(reply_to.reply_to_msg_id, 3);
+        assert_eq!(reply_to.top_msg_id, None);
+
+        let reply_to = reply_to_message(
+            &InputMessage::text("hi")
+                .reply_to(Some(12))
+                .topic(Some(3))
+                .quote("quoted", 5),
+        );
+        assert_eq!(reply_to.reply_to_msg_id, 12);
+        assert_eq!(reply_to.top_msg_id, Some(3));
+        assert_eq!(reply_to.quote_text.as_deref(), Some("quoted"));
+        assert_eq!(reply_to.quote_offset, Some(5));
+    }
+
+    #[test]
+    fn check_send_as() {
+        assert_eq!(InputMessage::text("hi").send_as, None);
+
+        let channel = PackedChat {
+            ty: grammers_session::PackedType::Broadcast,
+            id: 1,
+            access_hash: Some(2),
+        };
+        let message = InputMessage::text("hi").send_as(channel);
+        assert_eq!(
+            message.send_as,
+            Some(
+                tl::types::InputPeerChannel {
+                    channel_id: 1,
+                    access_hash: 2,
+                }
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn check_reply_is_serialized() {
+        use grammers_tl_types::{Deserializable, Serializable};
+
+        let message = InputMessage::text("hi").reply_to(Some(12)).topic(Some(3));
+        let request = tl::functions::messages::SendMessage {
+            no_webpage: false,
+            silent: false,
+            background: false,
+            clear_draft: false,
+            noforwards: false,
+            update_stickersets_order: false,
+            invert_media: false,
+            peer: tl::enums::InputPeer::PeerSelf,
+            reply_to: message.input_reply_to(),
+            message: message.text.clone(),
+            random_id: 0,
+            reply_markup: None,
+            entities: None,
+            schedule_date: None,
+            send_as: None,
+            quick_reply_shortcut: None,
+            effect: None,
+        };
+
+        let bytes = request.to_bytes();
+        let reply_to = tl::enums::InputReplyTo::from_bytes(&bytes[4 + 4 + 4..]).unwrap();
+        assert_eq!(reply_to, message.input_reply_to().unwrap());
+    }
+
+    #[test]
+    fn check_video_attributes() {
+        let message = InputMessage::text("").document(uploaded("clip.mp4")).video(
+            1280,
+            720,
+            Duration::from_secs(90),
+            true,
+        );
+
+        let document = uploaded_document(&message);
+        assert_eq!(document.mime_type, "video/mp4");
+        assert!(!document.force_file);
+        let video = document
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                tl::enums::DocumentAttribute::Video(video) => Some(video),
+                _ => None,
+            })
+            .expect("video attribute");
+        assert_eq!((video.w, video.h), (1280, 720));
+        assert_eq!(video.duration, 90.0);
+        assert!(video.supports_streaming);
+        assert!(!video.round_message);
+    }
+
+    #[test]
+    fn check_voice_attributes() {
+        let message = InputMessage::text("")
+            .document(uploaded("note.ogg"))
+            .audio(Duration::from_secs(5), None, None, false)
+            .audio(Duration::from_secs(7), None, None, true);
+
+        let audios = uploaded_document(&message)
+            .attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                tl::enums::DocumentAttribute::Audio(audio) => Some(audio),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(audios.len(), 1);
+        assert!(audios[0].voice);
+        assert_eq!(audios[0].duration, 7);
+    }
+
+    #[test]
+    fn check_document_and_photo_conversion() {
+        let message = InputMessage::text("")
+            .photo(uploaded("a.jpg"))
+            .as_document();
+        assert!(uploaded_document(&message).force_file);
+
+        let message = InputMessage::text("")
+            .document(uploaded("a.jpg"))
+            .as_photo();
+        assert!(matches!(
+            message.media,
+            Some(tl::enums::InputMedia::UploadedPhoto(_))
+        ));
+    }
+
+    #[test]
+    fn check_venue() {
+        let message = InputMessage::text("").venue(1.5, -2.5, "Shop", "Street 1", "gplaces", "id");
+        match message.media {
+            Some(tl::enums::InputMedia::Venue(venue)) => {
+                assert_eq!(
+                    venue.geo_point,
+                    tl::types::InputGeoPoint {
+                        lat: 1.5,
+                        long: -2.5,
+                        accuracy_radius: None
+                    }
+                    .into()
+                );
+                assert_eq!(venue.title, "Shop");
+                assert_eq!(venue.address, "Street 1");
+                assert_eq!(venue.provider, "gplaces");
+                assert_eq!(venue.venue_id, "id");
+            }
+            media => panic!("unexpected media: {media:?}"),
+        }
+    }
+
+    #[test]
+    fn check_live_location() {
+        for period in [60, 86400, LIVE_LOCATION_INDEFINITE] {
+            match InputMessage::text("").live_location(1.0, 2.0, period).media {
+                Some(tl::enums::InputMedia::GeoLive(live)) => {
+                    assert_eq!(live.period, Some(period));
+                    assert!(!live.stopped);
+                }
+                media => panic!("unexpected media: {media:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn check_link_preview() {
+        assert!(!InputMessage::text("https://example.com").no_webpage);
+        assert!(
+            InputMessage::text("https://example.com")
+                .link_preview(false)
+                .no_webpage
+        );
+    }
+
+    #[test]
+    fn check_preview_options() {
+        let message = InputMessage::text("look at this").preview_options(LinkPreviewOptions {
+            url: "https://example.com".to_string(),
+            above_text: true,
+            small_media: false,
+            large_media: true,
+        });
+        assert!(message.invert_media);
+        assert!(!message.no_webpage);
+        assert_eq!(
+            message.media,
+            Some(
+                tl::types::InputMediaWebPage {
+                    force_large_media: true,
+                    force_small_media: false,
+                    optional: true,
+                    url: "https://example.com".to_string(),
+                }
+                .into()
+            )
+        );
+
+        let message = InputMessage::text("").preview_options(LinkPreviewOptions {
+            url: "https://example.com".to_string(),
+            small_media: true,
+            ..Default::default()
+        });
+        assert!(!message.invert_media);
+        match message.media {
+            Some(tl::enums::InputMedia::WebPage(web_page)) => {
+                assert!(web_page.force_small_media);
+                assert!(!web_page.force_large_media);
+            }
+            media => panic!("unexpected media: {media:?}"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_live_location_period_too_short() {
+        let _ = InputMessage::text("").live_location(1.0, 2.0, 59);
+    }
+
+    #[test]
+    fn check_contact() {
+        let vcard = "BEGIN:VCARD\nVERSION:3.0\nEND:VCARD";
+        let message = InputMessage::text("").contact("1234", "John", "Doe", Some(vcard.into()));
+        assert_eq!(
+            message.media,
+            Some(
+                tl::types::InputMediaContact {
+                    phone_number: "1234".to_string(),
+                    first_name: "John".to_string(),
+                    last_name: "Doe".to_string(),
+                    vcard: vcard.to_string(),
+                }
+                .into()
+            )
+        );
+    }
+}
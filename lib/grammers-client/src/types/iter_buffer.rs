@@ -8,8 +8,11 @@
 
 use crate::Client;
 pub use grammers_mtsender::InvocationError;
+use grammers_mtsender::ReadError;
 use std::collections::VecDeque;
 
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
 /// Common parts to all requests that are used for creating iterators.
 ///
 /// End-users should obtain particular instances of this type via client methods.
@@ -21,6 +24,10 @@ pub struct IterBuffer<R, T> {
     pub(crate) last_chunk: bool,
     pub(crate) total: Option<usize>,
     pub(crate) request: R,
+    pub(crate) stop_on_error: bool,
+    pub(crate) error: Option<InvocationError>,
+    /// Client-side filter for the items, used by the iterators which support it.
+    pub(crate) filter: Option<Filter<T>>,
 }
 
 impl<R, T> IterBuffer<R, T> {
@@ -34,6 +41,9 @@ impl<R, T> IterBuffer<R, T> {
             last_chunk: false,
             total: None,
             request,
+            stop_on_error: false,
+            error: None,
+            filter: None,
         }
     }
 
@@ -46,6 +56,33 @@ impl<R, T> IterBuffer<R, T> {
         self
     }
 
+    /// Configure whether a transient error while fetching more items should stop the iteration.
+    ///
+    /// By default, any error is returned from `next`. When enabled, network errors, flood waits
+    /// and internal server errors will instead make the iterator return `None`, as if there were
+    /// no more items left. The error that caused it can be retrieved with
+    /// [`IterBuffer::take_error`], and the iteration can be resumed later on from the offset of
+    /// the last item that was returned.
+    pub fn stop_on_error(mut self, stop: bool) -> Self {
+        self.stop_on_error = stop;
+        self
+    }
+
+    /// Take the error that stopped the iteration early, if any.
+    ///
+    /// This can only return `Some` if [`IterBuffer::stop_on_error`] was enabled.
+    pub fn take_error(&mut self) -> Option<InvocationError> {
+        self.error.take()
+    }
+
+    /// The total amount of items that can be returned, as reported by the server, if known.
+    ///
+    /// This is only known after the iterator has fetched the first page of results, and unlike
+    /// the `total` method some iterators have, it never performs a network request.
+    pub fn known_total(&self) -> Option<usize> {
+        self.total
+    }
+
     /// Checks whether the limit has been reached and no more items should be fetched.
     fn limit_reached(&self) -> bool {
         if let Some(limit) = self.limit {
@@ -81,6 +118,23 @@ impl<R, T> IterBuffer<R, T> {
         }
     }
 
+    /// Handle an error that occurred while fetching more items.
+    ///
+    /// If the iterator should stop on transient errors, the error is stored and `None` returned.
+    /// The iterator will not attempt to fetch more items after this.
+    pub(crate) fn fetch_failed<U>(
+        &mut self,
+        error: InvocationError,
+    ) -> Result<Option<U>, InvocationError> {
+        if self.stop_on_error && is_transient(&error) {
+            self.last_chunk = true;
+            self.error = Some(error);
+            Ok(None)
+        } else {
+            Err(error)
+        }
+    }
+
     /// Pop a buffered item from the queue, and increment the amount of items fetched (returned).
     pub(crate) fn pop_item(&mut self) -> Option<T> {
         if let Some(item) = self.buffer.pop_front() {
@@ -91,3 +145,14 @@ impl<R, T> IterBuffer<R, T> {
         }
     }
 }
+
+/// Whether retrying the same request later on could succeed.
+fn is_transient(error: &InvocationError) -> bool {
+    match error {
+        InvocationError::Rpc(rpc) => rpc.flood_wait().is_some() || rpc.is_transient(),
+        InvocationError::Dropped | InvocationError::WouldBlock => true,
+        InvocationError::Read(ReadError::Io(_) | ReadError::Transport(_)) => true,
+        // Any other error, including those that may be added in the future, won't go away.
+        _ => false,
+    }
+}
@@ -6,6 +6,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use crate::types::photo_sizes::{PhotoSize, VecExt};
+use crate::types::InputStickerSet;
 use chrono::{DateTime, Utc};
 use grammers_tl_types as tl;
 use std::fmt::Debug;
@@ -25,6 +26,7 @@ pub struct Sticker {
     pub document: Document,
     pub raw_attrs: tl::types::DocumentAttributeSticker,
     animated: bool,
+    custom_emoji: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -72,6 +74,7 @@ pub struct WebPage {
 
 #[derive(Clone, Debug, PartialEq)]
 #[non_exhaustive]
+#[allow(clippy::large_enum_variant)]
 pub enum Media {
     Photo(Photo),
     Document(Document),
@@ -160,6 +163,11 @@ impl Photo {
         }
     }
 
+    /// Width and height of the largest size of the photo, if known.
+    pub fn dimensions(&self) -> Option<(i32, i32)> {
+        self.thumbs().largest().and_then(|thumb| thumb.dimensions())
+    }
+
     /// Get photo thumbs.
     ///
     /// Since Telegram doesn't store the original photo, it can be presented in different sizes
@@ -256,18 +264,19 @@ impl Document {
     ///
     /// If the file was uploaded with no file name, the returned string will be empty.
     pub fn name(&self) -> &str {
-        use tl::enums::Document as D;
+        self.file_name().unwrap_or("")
+    }
 
-        match self.raw.document.as_ref().unwrap() {
-            D::Empty(_) => "",
-            D::Document(document) => document
-                .attributes
-                .iter()
-                .find_map(|attr| match attr {
+    /// Return the file's name, if it was uploaded with one.
+    pub fn file_name(&self) -> Option<&str> {
+        match self.raw.document.as_ref() {
+            Some(tl::enums::Document::Document(document)) => {
+                document.attributes.iter().find_map(|attr| match attr {
                     tl::enums::DocumentAttribute::Filename(attr) => Some(attr.file_name.as_ref()),
                     _ => None,
                 })
-                .unwrap_or(""),
+            }
+            _ => None,
         }
     }
 
@@ -412,14 +421,29 @@ impl Document {
 }
 
 impl Sticker {
+    /// Returns `None` if the document is not a sticker.
+    ///
+    /// Custom emoji are considered stickers too (see [`Sticker::is_custom_emoji`]), so messages
+    /// containing them have [`Media::Sticker`] rather than [`Media::Document`].
     pub fn from_document(document: &Document) -> Option<Self> {
         match document.raw.document {
             Some(tl::enums::Document::Document(ref doc)) => {
-                let mut animated = false;
+                let mut animated = doc.mime_type == "application/x-tgsticker";
+                let mut custom_emoji = false;
                 let mut sticker_attrs: Option<tl::types::DocumentAttributeSticker> = None;
                 for attr in &doc.attributes {
                     match attr {
                         tl::enums::DocumentAttribute::Sticker(s) => sticker_attrs = Some(s.clone()),
+                        // Custom emoji carry the same information as stickers (minus masks).
+                        tl::enums::DocumentAttribute::CustomEmoji(e) => {
+                            sticker_attrs = Some(tl::types::DocumentAttributeSticker {
+                                mask: false,
+                                alt: e.alt.clone(),
+                                stickerset: e.stickerset.clone(),
+                                mask_coords: None,
+                            });
+                            custom_emoji = true;
+                        }
                         tl::enums::DocumentAttribute::Animated => animated = true,
                         _ => (),
                     }
@@ -428,21 +452,70 @@ impl Sticker {
                     document: document.clone(),
                     raw_attrs: sticker_attrs?,
                     animated,
+                    custom_emoji,
                 })
             }
             _ => None,
         }
     }
 
+    /// Returns `None` if the document is not a sticker.
+    pub(crate) fn from_raw_document(document: tl::enums::Document) -> Option<Self> {
+        Self::from_document(&Document::from_raw_media(tl::types::MessageMediaDocument {
+            nopremium: false,
+            spoiler: false,
+            video: false,
+            round: false,
+            voice: false,
+            document: Some(document),
+            alt_document: None,
+            ttl_seconds: None,
+        }))
+    }
+
     /// Get the emoji associated with the sticker.
     pub fn emoji(&self) -> &str {
         self.raw_attrs.alt.as_str()
     }
 
     /// Is this sticker an animated sticker?
+    ///
+    /// Stickers in the TGS format (`application/x-tgsticker`) are considered animated, even
+    /// though Telegram does not mark them with the animated attribute.
     pub fn is_animated(&self) -> bool {
         self.animated
     }
+
+    /// Is this sticker a video sticker?
+    pub fn is_video(&self) -> bool {
+        self.document.mime_type() == Some("video/webm")
+    }
+
+    /// Is this sticker a custom emoji, rather than a normal sticker?
+    pub fn is_custom_emoji(&self) -> bool {
+        self.custom_emoji
+    }
+
+    /// Get the sticker set this sticker belongs to, if any.
+    ///
+    /// The set can be fetched with [`Client::get_sticker_set`](crate::Client::get_sticker_set).
+    pub fn sticker_set(&self) -> Option<InputStickerSet> {
+        match self.raw_attrs.stickerset {
+            tl::enums::InputStickerSet::Empty => None,
+            ref set => Some(set.clone().into()),
+        }
+    }
+
+    /// Get the short name of the sticker set this sticker belongs to.
+    ///
+    /// This is only known if the sticker refers to its set by name. Most stickers refer to
+    /// their set by identifier instead, in which case the set must be fetched to know its name.
+    pub fn sticker_set_name(&self) -> Option<&str> {
+        match &self.raw_attrs.stickerset {
+            tl::enums::InputStickerSet::ShortName(set) => Some(set.short_name.as_str()),
+            _ => None,
+        }
+    }
 }
 
 impl Contact {
@@ -484,6 +557,11 @@ impl Contact {
     pub fn vcard(&self) -> &str {
         self.raw.vcard.as_str()
     }
+
+    /// The identifier of the Telegram user with this phone number, if any.
+    pub fn user_id(&self) -> Option<i64> {
+        Some(self.raw.user_id).filter(|&id| id != 0)
+    }
 }
 
 impl Poll {
@@ -729,6 +807,13 @@ impl Uploaded {
             tl::enums::InputFile::StoryDocument(_) => "",
         }
     }
+
+    /// Whether the uploaded file looks like a video, judging by its name.
+    pub(crate) fn is_video(&self) -> bool {
+        mime_guess::from_path(self.name())
+            .first()
+            .is_some_and(|mime| mime.type_() == mime_guess::mime::VIDEO)
+    }
 }
 
 impl Media {
@@ -779,6 +864,71 @@ impl Media {
         }
     }
 
+    /// Get the thumbnails of this media, if it has any.
+    ///
+    /// Any of the returned sizes can be downloaded with
+    /// [`Downloadable::PhotoSize`](crate::types::Downloadable::PhotoSize). Thumbnails which
+    /// are small enough to be contained directly (such as stripped ones) won't need to be
+    /// fetched from Telegram to be "downloaded".
+    pub fn thumbs(&self) -> Vec<PhotoSize> {
+        match self {
+            Media::Photo(photo) => photo.thumbs(),
+            Media::Document(document) => document.thumbs(),
+            Media::Sticker(sticker) => sticker.document.thumbs(),
+            Media::Contact(_) => vec![],
+            Media::Poll(_) => vec![],
+            Media::Geo(_) => vec![],
+            Media::Dice(_) => vec![],
+            Media::Venue(_) => vec![],
+            Media::GeoLive(_) => vec![],
+            Media::WebPage(_) => vec![],
+        }
+    }
+
+    /// The size of the media's file, in bytes, if it has one.
+    ///
+    /// For photos, this is the size of their largest thumbnail.
+    pub fn size(&self) -> Option<i64> {
+        match self {
+            Media::Photo(photo) => photo.thumbs().largest().map(|thumb| thumb.size() as i64),
+            Media::Document(document) => document_size(document),
+            Media::Sticker(sticker) => document_size(&sticker.document),
+            _ => None,
+        }
+    }
+
+    /// The MIME type of the media's file, if known.
+    ///
+    /// Telegram does not report one for photos.
+    pub fn mime_type(&self) -> Option<&str> {
+        match self {
+            Media::Document(document) => document.mime_type(),
+            Media::Sticker(sticker) => sticker.document.mime_type(),
+            _ => None,
+        }
+    }
+
+    /// The name of the media's file, if it was uploaded with one.
+    pub fn file_name(&self) -> Option<&str> {
+        match self {
+            Media::Document(document) => document.file_name(),
+            Media::Sticker(sticker) => sticker.document.file_name(),
+            _ => None,
+        }
+    }
+
+    /// Width and height of the media, if it is visual and they are known.
+    ///
+    /// For photos, these are the dimensions of their largest thumbnail.
+    pub fn dimensions(&self) -> Option<(i32, i32)> {
+        match self {
+            Media::Photo(photo) => photo.dimensions(),
+            Media::Document(document) => document.resolution(),
+            Media::Sticker(sticker) => sticker.document.resolution(),
+            _ => None,
+        }
+    }
+
     pub fn to_raw_input_location(&self) -> Option<tl::enums::InputFileLocation> {
         match self {
             Media::Photo(photo) => photo.to_raw_input_location(),
@@ -795,8 +945,182 @@ impl Media {
     }
 }
 
+fn document_size(document: &Document) -> Option<i64> {
+    match document.raw.document.as_ref() {
+        Some(tl::enums::Document::Document(d)) => Some(d.size),
+        _ => None,
+    }
+}
+
 impl From<Photo> for Media {
     fn from(photo: Photo) -> Self {
         Self::Photo(photo)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_contact_from_raw() {
+        let media = Media::from_raw(
+            tl::types::MessageMediaContact {
+                phone_number: "1234".to_string(),
+                first_name: "John".to_string(),
+                last_name: "Doe".to_string(),
+                vcard: "BEGIN:VCARD\nEND:VCARD".to_string(),
+                user_id: 0,
+            }
+            .into(),
+        );
+
+        match media {
+            Some(Media::Contact(contact)) => {
+                assert_eq!(contact.phone_number(), "1234");
+                assert_eq!(contact.first_name(), "John");
+                assert_eq!(contact.last_name(), "Doe");
+                assert_eq!(contact.vcard(), "BEGIN:VCARD\nEND:VCARD");
+                assert_eq!(contact.user_id(), None);
+            }
+            _ => panic!("unexpected media"),
+        }
+    }
+
+    #[test]
+    fn check_document_attributes() {
+        let media = Media::from_raw(
+            tl::types::MessageMediaDocument {
+                nopremium: false,
+                spoiler: false,
+                video: false,
+                round: false,
+                voice: false,
+                document: Some(
+                    tl::types::Document {
+                        id: 1,
+                        access_hash: 2,
+                        file_reference: vec![3],
+                        date: 0,
+                        mime_type: "video/mp4".to_string(),
+                        size: 1234,
+                        thumbs: None,
+                        video_thumbs: None,
+                        dc_id: 2,
+                        attributes: vec![
+                            tl::types::DocumentAttributeFilename {
+                                file_name: "clip.mp4".to_string(),
+                            }
+                            .into(),
+                            tl::types::DocumentAttributeImageSize { w: 640, h: 480 }.into(),
+                        ],
+                    }
+                    .into(),
+                ),
+                alt_document: None,
+                ttl_seconds: None,
+            }
+            .into(),
+        )
+        .unwrap();
+
+        assert!(matches!(media, Media::Document(_)));
+        assert_eq!(media.size(), Some(1234));
+        assert_eq!(media.mime_type(), Some("video/mp4"));
+        assert_eq!(media.file_name(), Some("clip.mp4"));
+        assert_eq!(media.dimensions(), Some((640, 480)));
+    }
+
+    #[test]
+    fn check_custom_emoji_are_stickers() {
+        let media = Media::from_raw(
+            tl::types::MessageMediaDocument {
+                nopremium: false,
+                spoiler: false,
+                video: false,
+                round: false,
+                voice: false,
+                document: Some(
+                    tl::types::Document {
+                        id: 1,
+                        access_hash: 2,
+                        file_reference: vec![3],
+                        date: 0,
+                        mime_type: "application/x-tgsticker".to_string(),
+                        size: 1234,
+                        thumbs: None,
+                        video_thumbs: None,
+                        dc_id: 2,
+                        attributes: vec![tl::types::DocumentAttributeCustomEmoji {
+                            free: false,
+                            text_color: false,
+                            alt: "🦀".to_string(),
+                            stickerset: InputStickerSet::id(4, 5).raw,
+                        }
+                        .into()],
+                    }
+                    .into(),
+                ),
+                alt_document: None,
+                ttl_seconds: None,
+            }
+            .into(),
+        )
+        .unwrap();
+
+        match media {
+            Media::Sticker(sticker) => {
+                assert!(sticker.is_custom_emoji());
+                assert!(sticker.is_animated());
+                assert!(!sticker.is_video());
+                assert_eq!(sticker.emoji(), "🦀");
+                assert_eq!(sticker.sticker_set(), Some(InputStickerSet::id(4, 5)));
+            }
+            media => panic!("unexpected media: {media:?}"),
+        }
+    }
+
+    #[test]
+    fn check_photo_attributes() {
+        let size = |r#type: &str, w, h, size| {
+            tl::types::PhotoSize {
+                r#type: r#type.to_string(),
+                w,
+                h,
+                size,
+            }
+            .into()
+        };
+        let media = Media::from_raw(
+            tl::types::MessageMediaPhoto {
+                spoiler: false,
+                photo: Some(
+                    tl::types::Photo {
+                        has_stickers: false,
+                        id: 1,
+                        access_hash: 2,
+                        file_reference: vec![3],
+                        date: 0,
+                        sizes: vec![
+                            size("m", 320, 240, 1000),
+                            size("y", 1280, 960, 9000),
+                            size("x", 800, 600, 4000),
+                        ],
+                        video_sizes: None,
+                        dc_id: 2,
+                    }
+                    .into(),
+                ),
+                ttl_seconds: None,
+            }
+            .into(),
+        )
+        .unwrap();
+
+        assert!(matches!(media, Media::Photo(_)));
+        assert_eq!(media.size(), Some(9000));
+        assert_eq!(media.mime_type(), None);
+        assert_eq!(media.file_name(), None);
+        assert_eq!(media.dimensions(), Some((1280, 960)));
+    }
+}
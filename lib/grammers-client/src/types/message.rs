@@ -5,10 +5,11 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use crate::client::files;
 #[cfg(any(feature = "markdown", feature = "html"))]
 use crate::parsers;
 use crate::types::reactions::InputReactions;
-use crate::types::{InputMessage, Media, Photo};
+use crate::types::{Downloadable, InputMessage, Media, MessageEntity, Photo, ReplyMarkup};
 use crate::ChatMap;
 use crate::{types, Client};
 use crate::{utils, InputMedia};
@@ -17,14 +18,14 @@ use grammers_mtsender::InvocationError;
 use grammers_session::PackedChat;
 use grammers_tl_types as tl;
 use std::fmt;
+use std::io;
 use std::sync::Arc;
+use tokio::sync::OnceCell;
+use types::update::MessageUpdate;
 use types::Chat;
 
 #[cfg(feature = "fs")]
-use {
-    crate::types::Downloadable,
-    std::{io, path::Path},
-};
+use std::path::Path;
 
 pub(crate) const EMPTY_MESSAGE: tl::types::Message = tl::types::Message {
     out: false,
@@ -86,6 +87,10 @@ pub struct Message {
     // a message action for instance. Keeping the entire set like this allows for cheaper clones
     // and moves, and saves us from worrying about picking out all the chats we care about.
     pub(crate) chats: Arc<ChatMap>,
+    // The message this message replies to, once it has been fetched with `get_reply`.
+    pub(crate) reply: Arc<OnceCell<Option<Message>>>,
+    // The update this message was received in, if any, without the message itself.
+    pub(crate) received_in: Option<MessageUpdate>,
 }
 
 impl Message {
@@ -102,6 +107,8 @@ impl Message {
                 raw_action: None,
                 client: client.clone(),
                 chats: Arc::clone(chats),
+                reply: Arc::new(OnceCell::new()),
+                received_in: None,
             }),
             tl::enums::Message::Service(msg) => Some(Message {
                 raw: tl::types::Message {
@@ -147,6 +154,8 @@ impl Message {
                 raw_action: Some(msg.action),
                 client: client.clone(),
                 chats: Arc::clone(chats),
+                reply: Arc::new(OnceCell::new()),
+                received_in: None,
             }),
         }
     }
@@ -177,19 +186,22 @@ impl Message {
                 saved_peer_id: None,
                 fwd_from: None,
                 via_bot_id: None,
-                reply_to: input.reply_to.map(|reply_to_msg_id| {
+                reply_to: input.input_reply_to().map(|reply_to| {
+                    let tl::enums::InputReplyTo::Message(reply_to) = reply_to else {
+                        unreachable!("messages can only reply to other messages");
+                    };
                     tl::types::MessageReplyHeader {
                         reply_to_scheduled: false,
-                        forum_topic: false,
-                        quote: false,
-                        reply_to_msg_id: Some(reply_to_msg_id),
+                        forum_topic: input.topic.is_some(),
+                        quote: reply_to.quote_text.is_some(),
+                        reply_to_msg_id: Some(reply_to.reply_to_msg_id),
                         reply_to_peer_id: None,
                         reply_from: None,
                         reply_media: None,
-                        reply_to_top_id: None,
-                        quote_text: None,
+                        reply_to_top_id: reply_to.top_msg_id,
+                        quote_text: reply_to.quote_text,
                         quote_entities: None,
-                        quote_offset: None,
+                        quote_offset: reply_to.quote_offset,
                     }
                     .into()
                 }),
@@ -210,12 +222,14 @@ impl Message {
                 quick_reply_shortcut_id: None,
                 via_business_bot_id: None,
                 offline: false,
-                effect: None,
+                effect: input.effect,
                 factcheck: None,
             },
             raw_action: None,
             client: client.clone(),
             chats: ChatMap::single(Chat::unpack(chat)),
+            reply: Arc::new(OnceCell::new()),
+            received_in: None,
         }
     }
 
@@ -320,11 +334,25 @@ impl Message {
         self.raw.fwd_from.clone()
     }
 
+    /// If this message was forwarded from a previous message, return where that message
+    /// originated, such as its sender and when it was sent.
+    pub fn forward(&self) -> Option<types::Forward> {
+        self.raw
+            .fwd_from
+            .clone()
+            .map(|header| types::Forward::from_raw(header, &self.chats, &self.client))
+    }
+
     /// If this message was sent @via some inline bot, return the bot's user identifier.
     pub fn via_bot_id(&self) -> Option<i64> {
         self.raw.via_bot_id
     }
 
+    /// The identifier of the animated effect shown along with this message, if any.
+    pub fn effect(&self) -> Option<i64> {
+        self.raw.effect
+    }
+
     /// If this message is replying to a previous message, return the header with information
     /// about that reply.
     pub fn reply_header(&self) -> Option<tl::enums::MessageReplyHeader> {
@@ -399,6 +427,19 @@ impl Message {
         self.raw.entities.as_ref()
     }
 
+    /// The formatting entities used to format this message, with their byte ranges within the
+    /// [`text`](Self::text).
+    ///
+    /// Entities whose range does not fit inside the text are skipped.
+    pub fn entities(&self) -> Vec<MessageEntity> {
+        self.raw
+            .entities
+            .iter()
+            .flatten()
+            .filter_map(|entity| MessageEntity::from_raw(&self.raw.message, entity))
+            .collect()
+    }
+
     /// How many views does this message have, when applicable.
     ///
     /// The same user account can contribute to increment this counter indefinitedly, however
@@ -518,6 +559,29 @@ impl Message {
         self.raw_action.as_ref()
     }
 
+    /// The raw message as Telegram sent it, turned back into a service message if it was one.
+    pub(crate) fn raw_message(&self) -> tl::enums::Message {
+        let Some(action) = &self.raw_action else {
+            return self.raw.clone().into();
+        };
+        tl::types::MessageService {
+            out: self.raw.out,
+            mentioned: self.raw.mentioned,
+            media_unread: self.raw.media_unread,
+            silent: self.raw.silent,
+            post: self.raw.post,
+            legacy: self.raw.legacy,
+            id: self.raw.id,
+            from_id: self.raw.from_id.clone(),
+            peer_id: self.raw.peer_id.clone(),
+            reply_to: self.raw.reply_to.clone(),
+            date: self.raw.date,
+            action: action.clone(),
+            ttl_period: self.raw.ttl_period,
+        }
+        .into()
+    }
+
     /// If this message is replying to another message, return the replied message ID.
     pub fn reply_to_message_id(&self) -> Option<i32> {
         if let Some(tl::enums::MessageReplyHeader::Header(m)) = &self.raw.reply_to {
@@ -530,12 +594,17 @@ impl Message {
     /// Fetch the message that this message is replying to, or `None` if this message is not a
     /// reply to a previous message.
     ///
-    /// Shorthand for `Client::get_reply_to_message`.
+    /// The result is cached, so calling this method again (including on clones of this message)
+    /// won't fetch the reply a second time. Use `Client::get_reply_to_message` to always fetch
+    /// the latest version instead.
     pub async fn get_reply(&self) -> Result<Option<Self>, InvocationError> {
-        self.client
-            .clone() // TODO don't clone
-            .get_reply_to_message(self)
+        if self.reply_to_message_id().is_none() {
+            return Ok(None);
+        }
+        self.reply
+            .get_or_try_init(|| self.client.get_reply_to_message(self))
             .await
+            .cloned()
     }
 
     /// Respond to this message by sending a new message in the same chat, but without directly
@@ -606,6 +675,36 @@ impl Message {
             .await
     }
 
+    /// Edit this message to replace its media and caption, uploading local files first.
+    ///
+    /// Shorthand for `Client::edit_message_media`.
+    pub async fn edit_media(&self, media: InputMedia) -> Result<(), InvocationError> {
+        self.client
+            .edit_message_media(&self.chat(), self.raw.id, media)
+            .await
+    }
+
+    /// Edit the reply markup of this message, leaving its text and media untouched.
+    ///
+    /// Shorthand for `Client::edit_message_reply_markup`.
+    pub async fn edit_reply_markup<RM: ReplyMarkup>(
+        &self,
+        markup: &RM,
+    ) -> Result<(), InvocationError> {
+        self.client
+            .edit_message_reply_markup(&self.chat(), self.raw.id, markup)
+            .await
+    }
+
+    /// Update the position of this live location.
+    ///
+    /// Shorthand for `Client::edit_live_location`.
+    pub async fn edit_live_location(&self, lat: f64, long: f64) -> Result<(), InvocationError> {
+        self.client
+            .edit_live_location(&self.chat(), self.raw.id, lat, long)
+            .await
+    }
+
     /// Delete this message for everyone.
     ///
     /// Shorthand for `Client::delete_messages`. If you need to delete multiple messages
@@ -677,18 +776,64 @@ impl Message {
     ///
     /// Returns `true` if there was media to download, or `false` otherwise.
     ///
+    /// If the file reference of the media has expired, the message is fetched again and the
+    /// download retried once.
+    ///
     /// Shorthand for `Client::download_media`.
     #[cfg(feature = "fs")]
     pub async fn download_media<P: AsRef<Path>>(&self, path: P) -> Result<bool, io::Error> {
         // TODO probably encode failed download in error
-        if let Some(media) = self.media() {
-            self.client
-                .download_media(&Downloadable::Media(media), path)
-                .await
-                .map(|_| true)
-        } else {
-            Ok(false)
+        let media = match self.media() {
+            Some(media) => media,
+            None => return Ok(false),
+        };
+
+        match self
+            .client
+            .download_media(&Downloadable::Media(media), &path)
+            .await
+        {
+            Err(e)
+                if e.get_ref()
+                    .and_then(|e| e.downcast_ref::<InvocationError>())
+                    .is_some_and(files::is_file_reference_expired) =>
+            {
+                let media = files::refetch_media(&self.client, self.chat().pack(), self.id())
+                    .await
+                    .map_err(io::Error::other)?
+                    .ok_or(e)?;
+                self.client
+                    .download_media(&Downloadable::Media(media), path)
+                    .await
+            }
+            result => result,
         }
+        .map(|_| true)
+    }
+
+    /// Download the media in this message into memory, if it has any.
+    ///
+    /// Returns `None` if the message has no media. Files larger than
+    /// [`DEFAULT_MAX_DOWNLOAD_BYTES`] are not downloaded, and an error is returned instead.
+    /// Use [`Client::download_media_bytes`] to change this limit.
+    ///
+    /// If the file reference of the media has expired, the message is fetched again and the
+    /// download retried once.
+    ///
+    /// [`DEFAULT_MAX_DOWNLOAD_BYTES`]: files::DEFAULT_MAX_DOWNLOAD_BYTES
+    pub async fn download_media_bytes(&self) -> Result<Option<Vec<u8>>, io::Error> {
+        let downloadable = match self.media() {
+            Some(media) => Downloadable::Media(media),
+            None => return Ok(None),
+        };
+
+        files::check_download_size(&downloadable, files::DEFAULT_MAX_DOWNLOAD_BYTES)?;
+        self.client
+            .iter_download(&downloadable)
+            .source_message(self)
+            .collect_bytes(files::DEFAULT_MAX_DOWNLOAD_BYTES)
+            .await
+            .map(Some)
     }
 
     /// Get photo attached to the message if any.
@@ -735,3 +880,67 @@ impl fmt::Debug for Message {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use crate::types::EntityKind;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_entities() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let raw = tl::types::Message {
+                message: "Hello world, see docs".to_string(),
+                entities: Some(vec![
+                    tl::types::MessageEntityBold {
+                        offset: 0,
+                        length: 5,
+                    }
+                    .into(),
+                    tl::types::MessageEntityCode {
+                        offset: 6,
+                        length: 5,
+                    }
+                    .into(),
+                    tl::types::MessageEntityTextUrl {
+                        offset: 17,
+                        length: 4,
+                        url: "https://docs.rs/".to_string(),
+                    }
+                    .into(),
+                ]),
+                ..EMPTY_MESSAGE
+            };
+            let message = Message::from_raw(&client, raw.into(), &ChatMap::empty()).unwrap();
+
+            assert_eq!(
+                message.entities(),
+                vec![
+                    MessageEntity {
+                        range: 0..5,
+                        kind: EntityKind::Bold,
+                    },
+                    MessageEntity {
+                        range: 6..11,
+                        kind: EntityKind::Code,
+                    },
+                    MessageEntity {
+                        range: 17..21,
+                        kind: EntityKind::TextUrl {
+                            url: "https://docs.rs/".to_string(),
+                        },
+                    },
+                ]
+            );
+
+            #[cfg(feature = "markdown")]
+            assert_eq!(
+                message.markdown_text(),
+                "**Hello** `world`, see [docs](https://docs.rs/)"
+            );
+        });
+    }
+}
@@ -6,6 +6,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use grammers_tl_types as tl;
+
 /// Occurs whenever a message is deleted.
 /// Note that this event isn’t 100% reliable, since Telegram doesn’t always
 /// notify the clients that a message was deleted.
@@ -16,6 +18,8 @@
 pub struct MessageDeletion {
     pub(crate) channel_id: Option<i64>,
     pub(crate) messages: Vec<i32>,
+    // The update this deletion was received in, if any, exactly as Telegram sent it.
+    pub(crate) raw_update: Option<tl::enums::Update>,
 }
 
 impl MessageDeletion {
@@ -25,6 +29,7 @@ impl MessageDeletion {
         Self {
             channel_id: Some(channel),
             messages,
+            raw_update: None,
         }
     }
 
@@ -33,6 +38,7 @@ impl MessageDeletion {
         Self {
             channel_id: None,
             messages,
+            raw_update: None,
         }
     }
 
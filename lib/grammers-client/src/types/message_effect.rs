@@ -0,0 +1,102 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use grammers_tl_types as tl;
+
+/// An animated effect that can be played when a message is shown.
+///
+/// See [`InputMessage::effect`](crate::InputMessage::effect).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MessageEffect {
+    /// The identifier of the effect, to be used when sending messages.
+    pub id: i64,
+    /// The emoji that represents this effect.
+    pub emoticon: String,
+    /// Whether the logged-in user needs Telegram Premium to use this effect.
+    pub premium_required: bool,
+    /// The identifier of the custom emoji document used as the static icon of the effect.
+    pub static_icon_id: Option<i64>,
+    /// The identifier of the sticker document played when the effect is shown.
+    pub effect_sticker_id: i64,
+    /// The identifier of the document with the animation played around the message, if any.
+    pub effect_animation_id: Option<i64>,
+}
+
+impl MessageEffect {
+    pub(crate) fn from_raw(effect: tl::enums::AvailableEffect) -> Self {
+        let tl::enums::AvailableEffect::Effect(effect) = effect;
+        Self {
+            id: effect.id,
+            emoticon: effect.emoticon,
+            premium_required: effect.premium_required,
+            static_icon_id: effect.static_icon_id,
+            effect_sticker_id: effect.effect_sticker_id,
+            effect_animation_id: effect.effect_animation_id,
+        }
+    }
+
+    pub(crate) fn from_raw_list(effects: tl::enums::messages::AvailableEffects) -> Vec<Self> {
+        match effects {
+            tl::enums::messages::AvailableEffects::Effects(effects) => {
+                effects.effects.into_iter().map(Self::from_raw).collect()
+            }
+            // Only returned when a non-zero hash is sent, which is never the case.
+            tl::enums::messages::AvailableEffects::NotModified => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_effects_are_parsed() {
+        let effects = MessageEffect::from_raw_list(
+            tl::types::messages::AvailableEffects {
+                hash: 123,
+                effects: vec![
+                    tl::types::AvailableEffect {
+                        premium_required: false,
+                        id: 1,
+                        emoticon: "👍".to_string(),
+                        static_icon_id: None,
+                        effect_sticker_id: 10,
+                        effect_animation_id: Some(11),
+                    }
+                    .into(),
+                    tl::types::AvailableEffect {
+                        premium_required: true,
+                        id: 2,
+                        emoticon: "🔥".to_string(),
+                        static_icon_id: Some(20),
+                        effect_sticker_id: 21,
+                        effect_animation_id: None,
+                    }
+                    .into(),
+                ],
+                documents: Vec::new(),
+            }
+            .into(),
+        );
+
+        assert_eq!(effects.len(), 2);
+        assert_eq!(effects[0].id, 1);
+        assert_eq!(effects[0].emoticon, "👍");
+        assert!(!effects[0].premium_required);
+        assert_eq!(effects[0].effect_animation_id, Some(11));
+        assert_eq!(effects[1].id, 2);
+        assert!(effects[1].premium_required);
+        assert_eq!(effects[1].static_icon_id, Some(20));
+
+        assert!(
+            MessageEffect::from_raw_list(tl::enums::messages::AvailableEffects::NotModified)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::parsers::utf16::utf16_to_byte;
+use grammers_tl_types as tl;
+use std::ops::Range;
+
+/// A formatting entity within the text of a message, such as bold text or a link.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageEntity {
+    /// The byte range of the message's text that this entity applies to.
+    ///
+    /// Unlike the raw offsets used by Telegram, which are measured in UTF-16 code units, this
+    /// range can be used to index the text directly.
+    pub range: Range<usize>,
+    /// What kind of formatting is applied.
+    pub kind: EntityKind,
+}
+
+/// The kind of formatting applied by a [`MessageEntity`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    /// A mention of a user by their username, such as `@username`.
+    Mention,
+    /// A hashtag, such as `#hashtag`.
+    Hashtag,
+    /// A bot command, such as `/start`.
+    BotCommand,
+    /// A link that appears as-is in the text.
+    Url { url: String },
+    /// An email address.
+    Email,
+    /// Bold text.
+    Bold,
+    /// Italic text.
+    Italic,
+    /// Inline monospace code.
+    Code,
+    /// A block of monospace code, with the language it's written in (possibly empty).
+    Pre { language: String },
+    /// Text that links to a different URL.
+    TextUrl { url: String },
+    /// A mention of a user who may not have a username.
+    MentionName { user_id: i64 },
+    /// A phone number.
+    Phone,
+    /// A cashtag, such as `$USD`.
+    Cashtag,
+    /// Underlined text.
+    Underline,
+    /// Strikethrough text.
+    Strike,
+    /// A bank card number.
+    BankCard,
+    /// Text that is hidden until it's clicked.
+    Spoiler,
+    /// A custom emoji, with the identifier of the document containing it.
+    CustomEmoji { id: i64 },
+    /// A quotation, which is initially shown collapsed if `collapsed` is `true`.
+    Blockquote { collapsed: bool },
+    /// An entity not known to the library, or that should not appear in received messages.
+    Unknown,
+}
+
+impl MessageEntity {
+    /// Convert the raw entity applied to the given text.
+    ///
+    /// Returns `None` if the entity is out of bounds of the text.
+    pub(crate) fn from_raw(text: &str, entity: &tl::enums::MessageEntity) -> Option<Self> {
+        use tl::enums::MessageEntity as E;
+
+        let (offset, length, kind) = match entity {
+            E::Unknown(e) => (e.offset, e.length, EntityKind::Unknown),
+            E::Mention(e) => (e.offset, e.length, EntityKind::Mention),
+            E::Hashtag(e) => (e.offset, e.length, EntityKind::Hashtag),
+            E::BotCommand(e) => (e.offset, e.length, EntityKind::BotCommand),
+            E::Url(e) => (e.offset, e.length, EntityKind::Url { url: String::new() }),
+            E::Email(e) => (e.offset, e.length, EntityKind::Email),
+            E::Bold(e) => (e.offset, e.length, EntityKind::Bold),
+            E::Italic(e) => (e.offset, e.length, EntityKind::Italic),
+            E::Code(e) => (e.offset, e.length, EntityKind::Code),
+            E::Pre(e) => (
+                e.offset,
+                e.length,
+                EntityKind::Pre {
+                    language: e.language.clone(),
+                },
+            ),
+            E::TextUrl(e) => (
+                e.offset,
+                e.length,
+                EntityKind::TextUrl { url: e.url.clone() },
+            ),
+            E::MentionName(e) => (
+                e.offset,
+                e.length,
+                EntityKind::MentionName { user_id: e.user_id },
+            ),
+            E::InputMessageEntityMentionName(e) => (e.offset, e.length, EntityKind::Unknown),
+            E::Phone(e) => (e.offset, e.length, EntityKind::Phone),
+            E::Cashtag(e) => (e.offset, e.length, EntityKind::Cashtag),
+            E::Underline(e) => (e.offset, e.length, EntityKind::Underline),
+            E::Strike(e) => (e.offset, e.length, EntityKind::Strike),
+            E::BankCard(e) => (e.offset, e.length, EntityKind::BankCard),
+            E::Spoiler(e) => (e.offset, e.length, EntityKind::Spoiler),
+            E::CustomEmoji(e) => (
+                e.offset,
+                e.length,
+                EntityKind::CustomEmoji { id: e.document_id },
+            ),
+            E::Blockquote(e) => (
+                e.offset,
+                e.length,
+                EntityKind::Blockquote {
+                    collapsed: e.collapsed,
+                },
+            ),
+        };
+
+        let start = utf16_to_byte(text, usize::try_from(offset).ok()?)?;
+        let end = start + utf16_to_byte(&text[start..], usize::try_from(length).ok()?)?;
+        let kind = match kind {
+            EntityKind::Url { .. } => EntityKind::Url {
+                url: text[start..end].to_string(),
+            },
+            kind => kind,
+        };
+
+        Some(Self {
+            range: start..end,
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_offsets_are_converted_to_bytes() {
+        // '🦀' takes 2 UTF-16 code units but 4 bytes.
+        let text = "🦀 see https://example.com";
+        let entity = MessageEntity::from_raw(
+            text,
+            &tl::types::MessageEntityUrl {
+                offset: 7,
+                length: 19,
+            }
+            .into(),
+        )
+        .unwrap();
+        assert_eq!(entity.range, 9..28);
+        assert_eq!(
+            entity.kind,
+            EntityKind::Url {
+                url: "https://example.com".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn check_out_of_bounds_entity() {
+        let entity = tl::types::MessageEntityBold {
+            offset: 2,
+            length: 5,
+        }
+        .into();
+        assert_eq!(MessageEntity::from_raw("abc", &entity), None);
+    }
+}
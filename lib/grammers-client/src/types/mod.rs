@@ -11,14 +11,19 @@
 //! A lot of fields in the types exported from this module are currently public even though
 //! they directly uses `grammers-tl-types`. This will probably change before the 1.0 release.
 pub mod action;
+pub mod admin_log;
 pub mod attributes;
 pub mod button;
 pub mod callback_query;
 pub mod chat;
 pub mod chat_map;
+pub mod chat_reference;
 pub mod chats;
 pub mod dialog;
 pub mod downloadable;
+pub mod file_id;
+pub mod folder;
+pub mod forward;
 pub mod inline;
 pub mod input_media;
 pub mod input_message;
@@ -27,38 +32,58 @@ pub mod login_token;
 pub mod media;
 pub mod message;
 pub mod message_deletion;
+pub mod message_effect;
+pub mod message_entity;
 pub mod participant;
 pub mod password_token;
 pub mod permissions;
 pub mod photo_sizes;
 pub mod reactions;
 pub mod reply_markup;
+pub mod request_ext;
+pub mod send_as_peer;
+pub mod sticker_set;
+pub mod takeout;
 pub mod terms_of_service;
 pub mod update;
+pub mod upload_progress;
 
 pub use action::ActionSender;
+pub use admin_log::{AdminLogAction, AdminLogEvent};
 pub use attributes::Attribute;
 pub use callback_query::CallbackQuery;
 pub use chat::{Channel, Chat, Group, PackedChat, Platform, RestrictionReason, User};
 pub use chat_map::ChatMap;
 pub(crate) use chat_map::Peer;
+pub use chat_reference::{ChatReference, ResolvedChat};
 pub use chats::{AdminRightsBuilder, BannedRightsBuilder};
 pub use dialog::Dialog;
 pub use downloadable::{ChatPhoto, Downloadable, UserProfilePhoto};
+pub use file_id::{FileId, FileIdError, FileIdKind};
+pub use folder::Folder;
+pub use forward::{Forward, ForwardSender};
+pub use inline::message_id::{InlineMessageId, InvalidInlineMessageId};
 pub use inline::query::InlineQuery;
 pub use inline::send::InlineSend;
 pub use input_media::InputMedia;
-pub use input_message::InputMessage;
+pub use input_message::{InputMessage, LinkPreviewOptions, LIVE_LOCATION_INDEFINITE};
 pub use iter_buffer::IterBuffer;
 pub use login_token::LoginToken;
 pub(crate) use media::Uploaded;
 pub use media::{Media, Photo};
 pub use message::Message;
 pub use message_deletion::MessageDeletion;
+pub use message_effect::MessageEffect;
+pub use message_entity::{EntityKind, MessageEntity};
 pub use participant::{Participant, Role};
 pub use password_token::PasswordToken;
 pub use permissions::{Permissions, Restrictions};
 pub use reactions::InputReactions;
 pub(crate) use reply_markup::ReplyMarkup;
+pub use request_ext::RequestExt;
+pub use send_as_peer::SendAsPeer;
+pub use sticker_set::{InputStickerSet, StickerSet};
+pub use takeout::Takeout;
 pub use terms_of_service::TermsOfService;
 pub use update::Update;
+pub use upload_progress::{UploadExpired, UploadProgress};
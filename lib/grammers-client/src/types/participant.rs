@@ -128,33 +128,57 @@ impl Participant {
     pub(crate) fn from_raw_channel(
         chats: &mut ChatMap,
         participant: tl::enums::ChannelParticipant,
-    ) -> Self {
+    ) -> Option<Self> {
+        Self::from_raw_channel_with(participant, |peer| chats.remove(peer))
+    }
+
+    /// Like [`Participant::from_raw_channel`], but leaves the users in the map, so that they
+    /// can be used more than once.
+    pub(crate) fn from_raw_channel_ref(
+        chats: &ChatMap,
+        participant: tl::enums::ChannelParticipant,
+    ) -> Option<Self> {
+        Self::from_raw_channel_with(participant, |peer| chats.get(peer).cloned())
+    }
+
+    /// Returns `None` if the participant is not a user (for example, a channel banned from a
+    /// group), or if the user is missing from the chats.
+    fn from_raw_channel_with<F: FnMut(&tl::enums::Peer) -> Option<Chat>>(
+        participant: tl::enums::ChannelParticipant,
+        mut take_chat: F,
+    ) -> Option<Self> {
         use tl::enums::ChannelParticipant as P;
 
-        match participant {
+        let mut take_user = |peer: &tl::enums::Peer| match take_chat(peer)? {
+            Chat::User(user) => Some(user),
+            _ => None,
+        };
+        let user_peer = |user_id| tl::types::PeerUser { user_id }.into();
+
+        Some(match participant {
             P::Participant(p) => Self {
-                user: chats.remove_user(p.user_id).unwrap(),
+                user: take_user(&user_peer(p.user_id))?,
                 role: Role::User(Normal {
                     date: p.date,
                     inviter_id: None,
                 }),
             },
             P::ParticipantSelf(p) => Self {
-                user: chats.remove_user(p.user_id).unwrap(),
+                user: take_user(&user_peer(p.user_id))?,
                 role: Role::User(Normal {
                     date: p.date,
                     inviter_id: Some(p.inviter_id),
                 }),
             },
             P::Creator(p) => Self {
-                user: chats.remove_user(p.user_id).unwrap(),
+                user: take_user(&user_peer(p.user_id))?,
                 role: Role::Creator(Creator {
                     permissions: Permissions::from_raw(p.admin_rights.into()),
                     rank: p.rank,
                 }),
             },
             P::Admin(p) => Self {
-                user: chats.remove_user(p.user_id).unwrap(),
+                user: take_user(&user_peer(p.user_id))?,
                 role: Role::Admin(Admin {
                     can_edit: p.can_edit,
                     inviter_id: p.inviter_id,
@@ -165,10 +189,7 @@ impl Participant {
                 }),
             },
             P::Banned(p) => Self {
-                user: match chats.remove(&p.peer).unwrap() {
-                    Chat::User(user) => user,
-                    _ => todo!("figure out how to deal with non-user being banned"),
-                },
+                user: take_user(&p.peer)?,
                 role: Role::Banned(Banned {
                     left: p.left,
                     kicked_by: p.kicked_by,
@@ -177,13 +198,10 @@ impl Participant {
                 }),
             },
             P::Left(p) => Self {
-                user: match chats.remove(&p.peer).unwrap() {
-                    Chat::User(user) => user,
-                    _ => todo!("figure out how to deal with non-user leaving"),
-                },
+                user: take_user(&p.peer)?,
                 role: Role::Left(Left {}),
             },
-        }
+        })
     }
 
     pub(crate) fn from_raw_chat(
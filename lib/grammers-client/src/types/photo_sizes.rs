@@ -125,6 +125,24 @@ impl PhotoSize {
         }
     }
 
+    /// Width and height of the photo thumb, if known.
+    pub fn dimensions(&self) -> Option<(i32, i32)> {
+        match self {
+            PhotoSize::Empty(_) => None,
+            PhotoSize::Size(size) => Some((size.width, size.height)),
+            PhotoSize::Cached(size) => Some((size.width, size.height)),
+            PhotoSize::Stripped(size) => {
+                let bytes = &size.bytes;
+                if bytes.len() < 3 || bytes[0] != 0x01 {
+                    return None;
+                }
+                Some((bytes[2] as i32, bytes[1] as i32))
+            }
+            PhotoSize::Progressive(size) => Some((size.width, size.height)),
+            PhotoSize::Path(_) => None,
+        }
+    }
+
     pub fn to_raw_input_location(&self) -> Option<tl::enums::InputFileLocation> {
         match self {
             PhotoSize::Size(size) => size.to_raw_input_location(),
@@ -382,3 +400,83 @@ impl VecExt for Vec<PhotoSize> {
         self.iter().max_by_key(|x| x.size())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(sizes: Vec<tl::enums::PhotoSize>) -> tl::types::Photo {
+        tl::types::Photo {
+            has_stickers: false,
+            id: 1,
+            access_hash: 2,
+            file_reference: vec![3],
+            date: 0,
+            sizes,
+            video_sizes: None,
+            dc_id: 2,
+        }
+    }
+
+    #[test]
+    fn check_stripped_size_inflation() {
+        let size = tl::types::PhotoStrippedSize {
+            r#type: "i".to_string(),
+            bytes: vec![0x01, 0x28, 0x1e, 0xaa, 0xbb],
+        }
+        .into();
+        let size = PhotoSize::make_from(&size, &photo(vec![]));
+
+        let data = size.data();
+        assert_eq!(&data[..4], &[0xff, 0xd8, 0xff, 0xe0]);
+        assert_eq!(&data[163..167], &[0x00, 0x28, 0x00, 0x1e]);
+        assert_eq!(&data[data.len() - 4..], &[0xaa, 0xbb, 0xff, 0xd9]);
+        assert_eq!(data.len(), size.size());
+        assert_eq!(size.dimensions(), Some((30, 40)));
+        assert_eq!(size.to_raw_input_location(), None);
+    }
+
+    #[test]
+    fn check_largest_size_is_picked() {
+        let size = |r#type: &str, w, h, size| {
+            tl::types::PhotoSize {
+                r#type: r#type.to_string(),
+                w,
+                h,
+                size,
+            }
+            .into()
+        };
+        let photo = photo(vec![
+            tl::types::PhotoStrippedSize {
+                r#type: "i".to_string(),
+                bytes: vec![0x01, 0x28, 0x1e],
+            }
+            .into(),
+            size("m", 320, 240, 16_000),
+            size("y", 1280, 960, 128_000),
+            size("x", 800, 600, 64_000),
+        ]);
+        let sizes = photo
+            .sizes
+            .iter()
+            .map(|size| PhotoSize::make_from(size, &photo))
+            .collect::<Vec<_>>();
+
+        let largest = sizes.largest().unwrap();
+        assert_eq!(largest.photo_type(), "y");
+        assert_eq!(largest.dimensions(), Some((1280, 960)));
+        assert_eq!(
+            largest.to_raw_input_location(),
+            Some(
+                tl::types::InputPhotoFileLocation {
+                    id: 1,
+                    access_hash: 2,
+                    file_reference: vec![3],
+                    thumb_size: "y".to_string(),
+                }
+                .into()
+            )
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use grammers_tl_types as tl;
+
+/// Extension methods to wrap raw API calls in the `invokeWith*` and `invokeAfter*` functions.
+///
+/// The wrapped requests return the same as the original request, so they can be passed to
+/// [`Client::invoke`](crate::Client::invoke) as usual.
+///
+/// # Examples
+///
+/// ```
+/// # async fn f(client: grammers_client::Client) -> Result<(), Box<dyn std::error::Error>> {
+/// use grammers_client::grammers_tl_types as tl;
+/// use grammers_client::types::RequestExt;
+///
+/// let request = tl::functions::messages::ReadHistory {
+///     peer: tl::enums::InputPeer::PeerSelf,
+///     max_id: 0,
+/// };
+/// client.invoke(&request.without_updates()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait RequestExt: tl::RemoteCall + Sized {
+    /// Prevent the server from sending any updates caused by this request.
+    fn without_updates(self) -> tl::functions::InvokeWithoutUpdates<Self> {
+        tl::functions::InvokeWithoutUpdates { query: self }
+    }
+
+    /// Only execute this request after the message with the given identifier was processed.
+    ///
+    /// Note that `msg_id` is the identifier of the MTProto message carrying a previous request,
+    /// not the identifier of a chat message.
+    fn after(self, msg_id: i64) -> tl::functions::InvokeAfterMsg<Self> {
+        tl::functions::InvokeAfterMsg {
+            msg_id,
+            query: self,
+        }
+    }
+
+    /// Only execute this request after all the messages with the given identifiers were
+    /// processed.
+    ///
+    /// See [`RequestExt::after`] for the meaning of `msg_ids`.
+    fn after_all(self, msg_ids: Vec<i64>) -> tl::functions::InvokeAfterMsgs<Self> {
+        tl::functions::InvokeAfterMsgs {
+            msg_ids,
+            query: self,
+        }
+    }
+
+    /// Execute this request only over the chat messages with identifiers between `min_id` and
+    /// `max_id`, both inclusive.
+    fn in_messages_range(
+        self,
+        min_id: i32,
+        max_id: i32,
+    ) -> tl::functions::InvokeWithMessagesRange<Self> {
+        tl::functions::InvokeWithMessagesRange {
+            range: tl::types::MessageRange { min_id, max_id }.into(),
+            query: self,
+        }
+    }
+
+    /// Execute this request within the takeout session with the given identifier.
+    ///
+    /// Most of the time, it's more convenient to use [`Takeout::invoke`] instead.
+    ///
+    /// [`Takeout::invoke`]: super::Takeout::invoke
+    fn in_takeout(self, takeout_id: i64) -> tl::functions::InvokeWithTakeout<Self> {
+        tl::functions::InvokeWithTakeout {
+            takeout_id,
+            query: self,
+        }
+    }
+}
+
+impl<R: tl::RemoteCall> RequestExt for R {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grammers_tl_types::{Identifiable, Serializable};
+
+    fn returns_contacts<R: tl::RemoteCall<Return = tl::enums::contacts::Contacts>>(_: &R) {}
+
+    #[test]
+    fn check_requests_are_wrapped() {
+        let request = tl::functions::contacts::GetContacts { hash: 0 };
+
+        let wrapped = request.clone().without_updates();
+        returns_contacts(&wrapped);
+        let mut expected = tl::functions::InvokeWithoutUpdates::<()>::CONSTRUCTOR_ID.to_bytes();
+        expected.extend(request.to_bytes());
+        assert_eq!(wrapped.to_bytes(), expected);
+
+        let wrapped = request.clone().after(1234).without_updates();
+        returns_contacts(&wrapped);
+        assert_eq!(wrapped.query.msg_id, 1234);
+        assert_eq!(wrapped.query.query, request);
+
+        let wrapped = request.clone().in_messages_range(10, 20);
+        returns_contacts(&wrapped);
+        assert_eq!(
+            wrapped.range,
+            tl::types::MessageRange {
+                min_id: 10,
+                max_id: 20
+            }
+            .into()
+        );
+    }
+}
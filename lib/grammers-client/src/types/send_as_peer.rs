@@ -0,0 +1,80 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::{Chat, ChatMap};
+use grammers_tl_types as tl;
+
+/// A peer that messages can be sent as in a certain chat.
+///
+/// See [`InputMessage::send_as`](crate::InputMessage::send_as).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SendAsPeer {
+    /// The chat that messages would appear to be sent by.
+    pub chat: Chat,
+    /// Whether the logged-in user needs Telegram Premium to send messages as this peer.
+    pub premium_required: bool,
+}
+
+impl SendAsPeer {
+    pub(crate) fn from_raw_list(peers: tl::enums::channels::SendAsPeers) -> Vec<Self> {
+        let tl::enums::channels::SendAsPeers::Peers(peers) = peers;
+        let chats = ChatMap::new(peers.users, peers.chats);
+        peers
+            .peers
+            .into_iter()
+            .filter_map(|tl::enums::SendAsPeer::Peer(peer)| {
+                Some(Self {
+                    chat: chats.get(&peer.peer)?.clone(),
+                    premium_required: peer.premium_required,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_peers_are_resolved() {
+        let peers = SendAsPeer::from_raw_list(
+            tl::types::channels::SendAsPeers {
+                peers: vec![
+                    tl::types::SendAsPeer {
+                        premium_required: false,
+                        peer: tl::types::PeerUser { user_id: 1 }.into(),
+                    }
+                    .into(),
+                    tl::types::SendAsPeer {
+                        premium_required: true,
+                        peer: tl::types::PeerChat { chat_id: 2 }.into(),
+                    }
+                    .into(),
+                    // Peers not present in the response are skipped.
+                    tl::types::SendAsPeer {
+                        premium_required: false,
+                        peer: tl::types::PeerChat { chat_id: 3 }.into(),
+                    }
+                    .into(),
+                ],
+                chats: vec![tl::types::ChatEmpty { id: 2 }.into()],
+                users: vec![tl::types::UserEmpty { id: 1 }.into()],
+            }
+            .into(),
+        );
+
+        assert_eq!(peers.len(), 2);
+        assert!(matches!(peers[0].chat, Chat::User(_)));
+        assert_eq!(peers[0].chat.id(), 1);
+        assert!(!peers[0].premium_required);
+        assert!(matches!(peers[1].chat, Chat::Group(_)));
+        assert_eq!(peers[1].chat.id(), 2);
+        assert!(peers[1].premium_required);
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use super::media::Sticker;
+use grammers_tl_types as tl;
+
+/// A reference to a sticker set, either by its short name or by its identifier.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputStickerSet {
+    pub raw: tl::enums::InputStickerSet,
+}
+
+/// A sticker set, along with all of the stickers it contains.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StickerSet {
+    pub raw: tl::types::messages::StickerSet,
+}
+
+impl InputStickerSet {
+    /// Refer to a sticker set by its short name, as seen in `t.me/addstickers/short_name`.
+    pub fn short_name<S: Into<String>>(short_name: S) -> Self {
+        Self {
+            raw: tl::types::InputStickerSetShortName {
+                short_name: short_name.into(),
+            }
+            .into(),
+        }
+    }
+
+    /// Refer to a sticker set by its identifier and access hash.
+    pub fn id(id: i64, access_hash: i64) -> Self {
+        Self {
+            raw: tl::types::InputStickerSetId { id, access_hash }.into(),
+        }
+    }
+}
+
+impl From<tl::enums::InputStickerSet> for InputStickerSet {
+    fn from(raw: tl::enums::InputStickerSet) -> Self {
+        Self { raw }
+    }
+}
+
+impl From<&str> for InputStickerSet {
+    fn from(short_name: &str) -> Self {
+        Self::short_name(short_name)
+    }
+}
+
+impl From<String> for InputStickerSet {
+    fn from(short_name: String) -> Self {
+        Self::short_name(short_name)
+    }
+}
+
+impl StickerSet {
+    pub fn from_raw(set: tl::types::messages::StickerSet) -> Self {
+        Self { raw: set }
+    }
+
+    fn set(&self) -> &tl::types::StickerSet {
+        let tl::enums::StickerSet::Set(set) = &self.raw.set;
+        set
+    }
+
+    /// The identifier of this sticker set.
+    pub fn id(&self) -> i64 {
+        self.set().id
+    }
+
+    /// The title of this sticker set.
+    pub fn title(&self) -> &str {
+        self.set().title.as_str()
+    }
+
+    /// The short name of this sticker set, as seen in `t.me/addstickers/short_name`.
+    pub fn short_name(&self) -> &str {
+        self.set().short_name.as_str()
+    }
+
+    /// Whether this set contains custom emoji rather than normal stickers.
+    pub fn is_custom_emoji(&self) -> bool {
+        self.set().emojis
+    }
+
+    /// Whether this set is installed by the logged-in user.
+    pub fn is_installed(&self) -> bool {
+        self.set().installed_date.is_some()
+    }
+
+    /// Get a reference to this sticker set, which can be used to install or uninstall it.
+    pub fn to_input(&self) -> InputStickerSet {
+        let set = self.set();
+        InputStickerSet::id(set.id, set.access_hash)
+    }
+
+    /// Get the stickers contained in this set.
+    pub fn stickers(&self) -> Vec<Sticker> {
+        self.raw
+            .documents
+            .iter()
+            .cloned()
+            .filter_map(Sticker::from_raw_document)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sticker(id: i64, mime_type: &str, alt: &str) -> tl::enums::Document {
+        tl::types::Document {
+            id,
+            access_hash: 0,
+            file_reference: Vec::new(),
+            date: 0,
+            mime_type: mime_type.to_string(),
+            size: 0,
+            thumbs: None,
+            video_thumbs: None,
+            dc_id: 2,
+            attributes: vec![tl::types::DocumentAttributeSticker {
+                mask: false,
+                alt: alt.to_string(),
+                stickerset: InputStickerSet::id(1, 2).raw,
+                mask_coords: None,
+            }
+            .into()],
+        }
+        .into()
+    }
+
+    #[test]
+    fn check_stickers_are_surfaced() {
+        let set = StickerSet::from_raw(tl::types::messages::StickerSet {
+            set: tl::types::StickerSet {
+                archived: false,
+                official: false,
+                masks: false,
+                emojis: false,
+                text_color: false,
+                channel_emoji_status: false,
+                creator: false,
+                installed_date: None,
+                id: 1,
+                access_hash: 2,
+                title: "Title".to_string(),
+                short_name: "short_name".to_string(),
+                thumbs: None,
+                thumb_dc_id: None,
+                thumb_version: None,
+                thumb_document_id: None,
+                count: 3,
+                hash: 0,
+            }
+            .into(),
+            packs: Vec::new(),
+            keywords: Vec::new(),
+            documents: vec![
+                sticker(10, "image/webp", "😀"),
+                sticker(11, "application/x-tgsticker", "🦀"),
+                sticker(12, "video/webm", "🎉"),
+            ],
+        });
+
+        assert_eq!(set.short_name(), "short_name");
+        assert_eq!(set.to_input(), InputStickerSet::id(1, 2));
+
+        let stickers = set.stickers();
+        let summary = stickers
+            .iter()
+            .map(|s| (s.document.id(), s.emoji(), s.is_animated(), s.is_video()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (10, "😀", false, false),
+                (11, "🦀", true, false),
+                (12, "🎉", false, true),
+            ]
+        );
+        assert!(stickers
+            .iter()
+            .all(|s| s.sticker_set() == Some(set.to_input())));
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::Client;
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+
+/// A takeout session, started with [`Client::takeout`].
+///
+/// Every request invoked through it is wrapped so that it runs in the takeout session.
+///
+/// The session should be ended with [`Takeout::finish`]. If it is dropped instead, the session
+/// is finished as unsuccessful the next time the client is stepped.
+pub struct Takeout {
+    client: Client,
+    id: i64,
+    finished: bool,
+}
+
+impl Takeout {
+    pub(crate) fn new(client: &Client, id: i64) -> Self {
+        Self {
+            client: client.clone(),
+            id,
+            finished: false,
+        }
+    }
+
+    /// The identifier of this takeout session.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Invoke a raw API call within this takeout session.
+    pub async fn invoke<R: tl::RemoteCall + Clone>(
+        &self,
+        request: &R,
+    ) -> Result<R::Return, InvocationError> {
+        self.client.invoke(&self.wrap(request.clone())).await
+    }
+
+    /// Finish this takeout session, indicating whether the export was successful.
+    pub async fn finish(mut self, success: bool) -> Result<(), InvocationError> {
+        self.finished = true;
+        self.client.invoke(&self.finish_request(success)).await?;
+        Ok(())
+    }
+
+    fn wrap<R: tl::RemoteCall>(&self, query: R) -> tl::functions::InvokeWithTakeout<R> {
+        tl::functions::InvokeWithTakeout {
+            takeout_id: self.id,
+            query,
+        }
+    }
+
+    fn finish_request(
+        &self,
+        success: bool,
+    ) -> tl::functions::InvokeWithTakeout<tl::functions::account::FinishTakeoutSession> {
+        self.wrap(tl::functions::account::FinishTakeoutSession { success })
+    }
+}
+
+impl Drop for Takeout {
+    fn drop(&mut self) {
+        if !self.finished {
+            // The result can't be awaited here, so the request is only enqueued.
+            drop(
+                self.client
+                    .0
+                    .conn
+                    .request_tx
+                    .read()
+                    .unwrap()
+                    .enqueue(&self.finish_request(false)),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use grammers_tl_types::{Identifiable, Serializable};
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_requests_are_wrapped() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let takeout = Takeout::new(&client, 1234);
+
+            let request = tl::functions::contacts::GetContacts { hash: 0 };
+            let mut expected = tl::functions::InvokeWithTakeout::<()>::CONSTRUCTOR_ID.to_bytes();
+            expected.extend(1234i64.to_bytes());
+            expected.extend(request.to_bytes());
+            assert_eq!(takeout.wrap(request).to_bytes(), expected);
+
+            let finish = takeout.finish_request(true);
+            assert_eq!(finish.takeout_id, 1234);
+            assert!(finish.query.success);
+            assert_eq!(
+                &finish.to_bytes()[12..16],
+                tl::functions::account::FinishTakeoutSession::CONSTRUCTOR_ID.to_le_bytes()
+            );
+        });
+    }
+}
@@ -14,9 +14,15 @@ use grammers_tl_types as tl;
 
 #[non_exhaustive]
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Update {
     /// Occurs whenever a new text message or a message with media is produced.
     NewMessage(Message),
+    /// Occurs whenever an album (a group of messages with media) is sent.
+    ///
+    /// Only produced if [`InitParams::album_timeout`](crate::InitParams::album_timeout) is set.
+    /// Otherwise, each message in the album is a separate [`Update::NewMessage`].
+    Album(Vec<Message>),
     /// Occurs when a message is updated.
     MessageEdited(Message),
     /// Occurs when a message is deleted.
@@ -37,41 +43,57 @@ pub enum Update {
     /// variants so use this only as the workaround when such variant is not
     /// available yet.
     Raw(tl::enums::Update),
+    /// Occurs when the client starts (`started` is `true`) or finishes (`started` is `false`)
+    /// fetching updates that were missed, such as those sent while the client was offline.
+    ///
+    /// While catching up, the missed updates may arrive late and all at once, so this can be
+    /// used to let the user know (for example, by showing a spinner).
+    CatchingUp { started: bool },
 }
 
 impl Update {
     /// Create new friendly to use Update from its raw version and chat map
     pub fn new(client: &Client, update: tl::enums::Update, chats: &Arc<ChatMap>) -> Option<Self> {
         match update {
+            // Empty messages can't be wrapped, but they should not be lost either.
+            update @ (tl::enums::Update::NewMessage(tl::types::UpdateNewMessage {
+                message: tl::enums::Message::Empty(_),
+                ..
+            })
+            | tl::enums::Update::NewChannelMessage(
+                tl::types::UpdateNewChannelMessage {
+                    message: tl::enums::Message::Empty(_),
+                    ..
+                },
+            )
+            | tl::enums::Update::EditMessage(tl::types::UpdateEditMessage {
+                message: tl::enums::Message::Empty(_),
+                ..
+            })
+            | tl::enums::Update::EditChannelMessage(
+                tl::types::UpdateEditChannelMessage {
+                    message: tl::enums::Message::Empty(_),
+                    ..
+                },
+            )) => Some(Self::Raw(update)),
+
             // NewMessage
-            tl::enums::Update::NewMessage(tl::types::UpdateNewMessage { message, .. }) => {
-                Message::from_raw(client, message, chats).map(Self::NewMessage)
+            update @ (tl::enums::Update::NewMessage(_)
+            | tl::enums::Update::NewChannelMessage(_)) => {
+                message_from_update(client, update, chats).map(Self::NewMessage)
             }
-            tl::enums::Update::NewChannelMessage(tl::types::UpdateNewChannelMessage {
-                message,
-                ..
-            }) => Message::from_raw(client, message, chats).map(Self::NewMessage),
 
             // MessageEdited
-            tl::enums::Update::EditMessage(tl::types::UpdateEditMessage { message, .. }) => {
-                Message::from_raw(client, message, chats).map(Self::MessageEdited)
+            update @ (tl::enums::Update::EditMessage(_)
+            | tl::enums::Update::EditChannelMessage(_)) => {
+                message_from_update(client, update, chats).map(Self::MessageEdited)
             }
-            tl::enums::Update::EditChannelMessage(tl::types::UpdateEditChannelMessage {
-                message,
-                ..
-            }) => Message::from_raw(client, message, chats).map(Self::MessageEdited),
 
             // MessageDeleted
-            tl::enums::Update::DeleteMessages(tl::types::UpdateDeleteMessages {
-                messages, ..
-            }) => Some(Self::MessageDeleted(MessageDeletion::new(messages))),
-            tl::enums::Update::DeleteChannelMessages(tl::types::UpdateDeleteChannelMessages {
-                messages,
-                channel_id,
-                ..
-            }) => Some(Self::MessageDeleted(MessageDeletion::new_with_channel(
-                messages, channel_id,
-            ))),
+            update @ (tl::enums::Update::DeleteMessages(_)
+            | tl::enums::Update::DeleteChannelMessages(_)) => {
+                deletion_from_update(update).map(Self::MessageDeleted)
+            }
 
             // CallbackQuery
             tl::enums::Update::BotCallbackQuery(query) => Some(Self::CallbackQuery(
@@ -97,4 +119,253 @@ impl Update {
             update => Some(Self::Raw(update)),
         }
     }
+
+    /// The raw update this friendly update was made from, exactly as Telegram sent it.
+    ///
+    /// Albums are made from one update per message, so they have no single raw update. Instead,
+    /// each of their messages can be wrapped in [`Update::NewMessage`] to get its own.
+    ///
+    /// Events produced by the library itself, such as [`Update::CatchingUp`], and updates that
+    /// were not received from Telegram have no raw update either.
+    pub fn raw(&self) -> Option<tl::enums::Update> {
+        Some(match self {
+            Self::NewMessage(message) | Self::MessageEdited(message) => {
+                return message
+                    .received_in
+                    .map(|update| update.to_raw(message.raw_message()))
+            }
+            Self::MessageDeleted(deletion) => return deletion.raw_update.clone(),
+            Self::CallbackQuery(query) => match &query.inline_msg_id {
+                Some(msg_id) => tl::types::UpdateInlineBotCallbackQuery {
+                    query_id: query.raw.query_id,
+                    user_id: query.raw.user_id,
+                    msg_id: msg_id.clone(),
+                    chat_instance: query.raw.chat_instance,
+                    data: query.raw.data.clone(),
+                    game_short_name: query.raw.game_short_name.clone(),
+                }
+                .into(),
+                None => query.raw.clone().into(),
+            },
+            Self::InlineQuery(query) => query.raw.clone().into(),
+            Self::InlineSend(send) => send.raw.clone().into(),
+            Self::Raw(update) => update.clone(),
+            Self::Album(_) | Self::CatchingUp { .. } => return None,
+        })
+    }
+}
+
+/// The update a message was received in, without the message itself, so that the raw update can
+/// be rebuilt from the message rather than keeping a second copy of it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MessageUpdate {
+    kind: MessageUpdateKind,
+    pts: i32,
+    pts_count: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum MessageUpdateKind {
+    New,
+    NewChannel,
+    Edit,
+    EditChannel,
+}
+
+impl MessageUpdate {
+    /// Rebuild the raw update containing the message.
+    fn to_raw(self, message: tl::enums::Message) -> tl::enums::Update {
+        let Self {
+            kind,
+            pts,
+            pts_count,
+        } = self;
+        match kind {
+            MessageUpdateKind::New => tl::types::UpdateNewMessage {
+                message,
+                pts,
+                pts_count,
+            }
+            .into(),
+            MessageUpdateKind::NewChannel => tl::types::UpdateNewChannelMessage {
+                message,
+                pts,
+                pts_count,
+            }
+            .into(),
+            MessageUpdateKind::Edit => tl::types::UpdateEditMessage {
+                message,
+                pts,
+                pts_count,
+            }
+            .into(),
+            MessageUpdateKind::EditChannel => tl::types::UpdateEditChannelMessage {
+                message,
+                pts,
+                pts_count,
+            }
+            .into(),
+        }
+    }
+}
+
+/// Wrap the message contained in the update, remembering the rest of the update so that it can
+/// be retrieved.
+fn message_from_update(
+    client: &Client,
+    update: tl::enums::Update,
+    chats: &Arc<ChatMap>,
+) -> Option<Message> {
+    let (kind, message, pts, pts_count) = match update {
+        tl::enums::Update::NewMessage(u) => (MessageUpdateKind::New, u.message, u.pts, u.pts_count),
+        tl::enums::Update::NewChannelMessage(u) => {
+            (MessageUpdateKind::NewChannel, u.message, u.pts, u.pts_count)
+        }
+        tl::enums::Update::EditMessage(u) => {
+            (MessageUpdateKind::Edit, u.message, u.pts, u.pts_count)
+        }
+        tl::enums::Update::EditChannelMessage(u) => (
+            MessageUpdateKind::EditChannel,
+            u.message,
+            u.pts,
+            u.pts_count,
+        ),
+        _ => return None,
+    };
+    let mut message = Message::from_raw(client, message, chats)?;
+    message.received_in = Some(MessageUpdate {
+        kind,
+        pts,
+        pts_count,
+    });
+    Some(message)
+}
+
+/// Wrap the deleted messages contained in the update, keeping the update around.
+fn deletion_from_update(update: tl::enums::Update) -> Option<MessageDeletion> {
+    let mut deletion = match &update {
+        tl::enums::Update::DeleteMessages(update) => MessageDeletion::new(update.messages.clone()),
+        tl::enums::Update::DeleteChannelMessages(update) => {
+            MessageDeletion::new_with_channel(update.messages.clone(), update.channel_id)
+        }
+        _ => return None,
+    };
+    deletion.raw_update = Some(update);
+    Some(deletion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::net::block_on;
+    use crate::types::message::EMPTY_MESSAGE;
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_unknown_updates_are_raw() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chats = ChatMap::empty();
+
+            let typing = tl::enums::Update::UserTyping(tl::types::UpdateUserTyping {
+                user_id: 1,
+                action: tl::enums::SendMessageAction::SendMessageTypingAction,
+            });
+            match Update::new(&client, typing, &chats) {
+                Some(Update::Raw(tl::enums::Update::UserTyping(update))) => {
+                    assert_eq!(update.user_id, 1)
+                }
+                update => panic!("unexpected update: {update:?}"),
+            }
+
+            let empty = tl::types::UpdateNewMessage {
+                message: tl::types::MessageEmpty {
+                    id: 1,
+                    peer_id: None,
+                }
+                .into(),
+                pts: 1,
+                pts_count: 1,
+            };
+            assert!(matches!(
+                Update::new(&client, empty.into(), &chats),
+                Some(Update::Raw(tl::enums::Update::NewMessage(_)))
+            ));
+        });
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_wrapped_updates_expose_raw() {
+        block_on(async {
+            let client = Client::offline_for_tests().await;
+            let chats = ChatMap::empty();
+
+            let message = tl::types::Message {
+                id: 7,
+                peer_id: tl::types::PeerChannel { channel_id: 2 }.into(),
+                ..EMPTY_MESSAGE
+            };
+            let raw: tl::enums::Update = tl::types::UpdateNewChannelMessage {
+                message: message.clone().into(),
+                pts: 10,
+                pts_count: 1,
+            }
+            .into();
+            let update = Update::new(&client, raw.clone(), &chats).unwrap();
+            assert!(matches!(update, Update::NewMessage(_)));
+            assert_eq!(update.raw(), Some(raw.clone()));
+
+            let service: tl::enums::Update = tl::types::UpdateNewMessage {
+                message: tl::types::MessageService {
+                    out: false,
+                    mentioned: false,
+                    media_unread: false,
+                    silent: false,
+                    post: false,
+                    legacy: false,
+                    id: 8,
+                    from_id: None,
+                    peer_id: message.peer_id.clone(),
+                    reply_to: None,
+                    date: 0,
+                    action: tl::enums::MessageAction::HistoryClear,
+                    ttl_period: None,
+                }
+                .into(),
+                pts: 11,
+                pts_count: 1,
+            }
+            .into();
+            let update = Update::new(&client, service.clone(), &chats).unwrap();
+            assert_eq!(update.raw(), Some(service));
+
+            let edit: tl::enums::Update = tl::types::UpdateEditChannelMessage {
+                message: message.clone().into(),
+                pts: 12,
+                pts_count: 1,
+            }
+            .into();
+            let update = Update::new(&client, edit.clone(), &chats).unwrap();
+            assert!(matches!(update, Update::MessageEdited(_)));
+            assert_eq!(update.raw(), Some(edit));
+
+            let deletion: tl::enums::Update = tl::types::UpdateDeleteMessages {
+                messages: vec![1, 2],
+                pts: 12,
+                pts_count: 2,
+            }
+            .into();
+            let update = Update::new(&client, deletion.clone(), &chats).unwrap();
+            assert_eq!(update.raw(), Some(deletion));
+
+            let Some(Update::NewMessage(message)) = Update::new(&client, raw.clone(), &chats)
+            else {
+                panic!("message was not wrapped");
+            };
+            let album = Update::Album(vec![message.clone(), message.clone()]);
+            assert_eq!(album.raw(), None);
+            assert_eq!(Update::NewMessage(message).raw(), Some(raw));
+        });
+    }
 }
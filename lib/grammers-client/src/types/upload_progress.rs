@@ -0,0 +1,102 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::utils::generate_random_id;
+use grammers_mtsender::InvocationError;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The state of an in-progress upload, used to resume it after a failure.
+///
+/// Telegram identifies the parts of a file being uploaded by a random `file_id` chosen by the
+/// client. As long as the server still remembers the parts uploaded under that identifier,
+/// an interrupted upload can be continued by only sending the parts that are missing.
+///
+/// The progress is updated as parts are acknowledged by the server during
+/// [`Client::resume_upload`], so the same value can be passed in again after a failure.
+///
+/// [`Client::resume_upload`]: crate::Client::resume_upload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadProgress {
+    file_id: i64,
+    uploaded_parts: BTreeSet<i32>,
+}
+
+impl UploadProgress {
+    /// Starts tracking a new upload with a randomly-generated `file_id`.
+    pub fn new() -> Self {
+        Self::resume(generate_random_id(), [])
+    }
+
+    /// Continues tracking a previous upload with the given `file_id` and acknowledged parts.
+    ///
+    /// This is useful when the progress was persisted somewhere other than in this type.
+    pub fn resume<I: IntoIterator<Item = i32>>(file_id: i64, uploaded_parts: I) -> Self {
+        Self {
+            file_id,
+            uploaded_parts: uploaded_parts.into_iter().collect(),
+        }
+    }
+
+    /// The random identifier under which the parts of the file are being uploaded.
+    pub fn file_id(&self) -> i64 {
+        self.file_id
+    }
+
+    /// The zero-based indices of the parts that the server has acknowledged so far.
+    pub fn uploaded_parts(&self) -> &BTreeSet<i32> {
+        &self.uploaded_parts
+    }
+
+    /// Whether the given part has already been acknowledged and can be skipped.
+    pub(crate) fn is_uploaded(&self, part: i32) -> bool {
+        self.uploaded_parts.contains(&part)
+    }
+
+    pub(crate) fn mark_uploaded(&mut self, part: i32) {
+        self.uploaded_parts.insert(part);
+    }
+
+    /// Discard all progress and start over with a new `file_id`.
+    pub(crate) fn restart(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for UploadProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The error returned when the server no longer remembers the parts of a resumed upload.
+///
+/// By the time this error is returned, the [`UploadProgress`] has already been reset, so the
+/// upload can be retried from the start.
+#[derive(Debug)]
+pub struct UploadExpired {
+    pub(crate) error: InvocationError,
+}
+
+impl UploadExpired {
+    /// The error the server responded with when the missing parts were detected.
+    pub fn error(&self) -> &InvocationError {
+        &self.error
+    }
+}
+
+impl fmt::Display for UploadExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "upload expired and must be restarted: {}", self.error)
+    }
+}
+
+impl std::error::Error for UploadExpired {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
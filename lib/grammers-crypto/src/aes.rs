@@ -11,7 +11,7 @@ use std::mem;
 
 /// Encrypt the input plaintext in-place using the AES-IGE mode.
 pub fn ige_encrypt(buffer: &mut [u8], key: &[u8; 32], iv: &[u8; 32]) {
-    assert!(buffer.len() % 16 == 0);
+    assert!(buffer.len().is_multiple_of(16));
 
     let key = GenericArray::from_slice(key);
     let cipher = aes::Aes256::new(key);
@@ -51,7 +51,7 @@ pub fn ige_encrypt(buffer: &mut [u8], key: &[u8; 32], iv: &[u8; 32]) {
 /// Decrypt the input ciphertext using the AES-IGE mode.
 pub fn ige_decrypt(ciphertext: &[u8], key: &[u8; 32], iv: &[u8; 32]) -> Vec<u8> {
     let size = ciphertext.len();
-    assert!(size % 16 == 0);
+    assert!(size.is_multiple_of(16));
     let mut plaintext = vec![0; size];
 
     let key = GenericArray::from_slice(key);
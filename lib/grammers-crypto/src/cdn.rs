@@ -0,0 +1,67 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Cryptography used to download files from Telegram's CDN datacenters.
+//!
+//! See [Encrypted CDNs for Speed and Security](https://core.telegram.org/cdn).
+use crate::sha256;
+use aes::cipher::{generic_array::GenericArray, KeyIvInit, StreamCipher};
+
+/// Decrypt, in-place, the part of a file found at `offset` (in bytes) using AES-256-CTR.
+///
+/// The `iv` is the one given by the server when redirecting to the CDN. Its last four bytes
+/// are replaced by the `offset / 16` before being used, as the specification demands.
+///
+/// # Panics
+///
+/// Panics if the `offset` is not divisible by 16.
+pub fn decrypt_file_part(buffer: &mut [u8], key: &[u8; 32], iv: &[u8; 16], offset: i64) {
+    assert!(offset % 16 == 0);
+    let mut iv = *iv;
+    iv[12..].copy_from_slice(&((offset / 16) as u32).to_be_bytes());
+
+    ctr::Ctr128BE::<aes::Aes256>::new(GenericArray::from_slice(key), GenericArray::from_slice(&iv))
+        .apply_keystream(buffer);
+}
+
+/// Hash a range of the (decrypted) file, to compare it against the hashes given by the server.
+pub fn file_hash(data: &[u8]) -> [u8; 32] {
+    sha256!(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parts_decrypt_independently() {
+        let key = [1; 32];
+        let iv = [2; 16];
+        let plaintext = (0..64).collect::<Vec<u8>>();
+
+        // Encrypting the whole file at once must be the same as encrypting it in parts.
+        let mut whole = plaintext.clone();
+        decrypt_file_part(&mut whole, &key, &iv, 0);
+        assert_ne!(whole, plaintext);
+
+        let mut second_half = plaintext[32..].to_vec();
+        decrypt_file_part(&mut second_half, &key, &iv, 32);
+        assert_eq!(second_half, whole[32..]);
+
+        decrypt_file_part(&mut second_half, &key, &iv, 32);
+        assert_eq!(second_half, plaintext[32..]);
+    }
+
+    #[test]
+    fn check_file_hash() {
+        assert_eq!(
+            crate::hex::to_hex(&file_hash(b"data")),
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+        );
+    }
+}
@@ -5,6 +5,12 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+
+//! A buffer that can efficiently grow on both ends.
+//!
+//! This is used to build network packets, where the payload is serialized first,
+//! and then the headers of every layer (encryption, transport) are prepended to it.
+//! Reserving enough space in the front up-front means prepending needs no memory moves.
 use std::{
     ops::{Index, IndexMut},
     slice::SliceIndex,
@@ -15,6 +21,19 @@ use std::{
 /// Unlike the standard [`VecDeque`](std::collections::VecDeque),
 /// this buffer is designed to not need explicit calls to `make_contiguous`
 /// and minimize the amount of memory moves.
+///
+/// The elements are always contiguous in memory, and can be accessed via [`Self::as_slice`].
+///
+/// # Examples
+///
+/// ```
+/// use grammers_crypto::DequeBuffer;
+///
+/// let mut buffer = DequeBuffer::with_capacity(4, 2);
+/// buffer.extend(b"body");
+/// buffer.shift(2).copy_from_slice(b"hd");
+/// assert_eq!(buffer.as_slice(), b"hdbody");
+/// ```
 #[derive(Clone, Debug)]
 pub struct DequeBuffer<T: Copy + Default> {
     buffer: Vec<T>,
@@ -25,6 +44,9 @@ pub struct DequeBuffer<T: Copy + Default> {
 impl<T: Copy + Default> DequeBuffer<T> {
     /// Creates an empty deque buffer with space for at least `back_capacity` elements in the back,
     /// and exactly `front_capacity` elements in the front.
+    ///
+    /// Pushing exactly `back_capacity` elements to the back will leave [`Self::capacity`]
+    /// equal to [`Self::len`].
     pub fn with_capacity(back_capacity: usize, front_capacity: usize) -> Self {
         let mut buffer = Vec::with_capacity(front_capacity + back_capacity);
         buffer.extend((0..front_capacity).map(|_| T::default()));
@@ -42,17 +64,38 @@ impl<T: Copy + Default> DequeBuffer<T> {
         self.head = self.default_head;
     }
 
-    /// Extend the front by copying the elements from `slice`.
-    pub fn extend_front(&mut self, slice: &[T]) {
-        if self.head >= slice.len() {
-            self.head -= slice.len()
-        } else {
-            let shift = slice.len() - self.head;
+    /// Ensures there is space for at least `additional` elements in the front.
+    ///
+    /// If there is not enough space, the elements are moved to make room for exactly
+    /// `additional` elements in the front. The extra space is kept after [`Self::clear`],
+    /// so that reusing the buffer for data of the same size won't need to move it again.
+    pub fn reserve_front(&mut self, additional: usize) {
+        if self.head < additional {
+            let shift = additional - self.head;
             self.buffer.extend((0..shift).map(|_| T::default()));
             self.buffer.rotate_right(shift);
-            self.head = 0;
+            self.head = additional;
+            self.default_head += shift;
         }
-        self.buffer[self.head..self.head + slice.len()].copy_from_slice(slice);
+    }
+
+    /// Ensures there is space for at least `additional` more elements in the back.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buffer.reserve(additional);
+    }
+
+    /// Extend the front by `n` elements, and return a mutable slice to them so they can be set.
+    ///
+    /// The elements will be initialized to their default value.
+    pub fn shift(&mut self, n: usize) -> &mut [T] {
+        self.reserve_front(n);
+        self.head -= n;
+        &mut self.buffer[self.head..self.head + n]
+    }
+
+    /// Extend the front by copying the elements from `slice`.
+    pub fn extend_front(&mut self, slice: &[T]) {
+        self.shift(slice.len()).copy_from_slice(slice);
     }
 
     /// Appends an element to the back of the buffer.
@@ -69,17 +112,38 @@ impl<T: Copy + Default> DequeBuffer<T> {
     pub fn len(&self) -> usize {
         self.buffer.len() - self.head
     }
+
+    /// Returns the number of elements the buffer can hold without reallocating,
+    /// not counting the free space in the front.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity() - self.head
+    }
+
+    /// Returns the number of elements that can be prepended without moving the buffer.
+    pub fn front_capacity(&self) -> usize {
+        self.head
+    }
+
+    /// Extracts a slice containing the entire buffer.
+    pub fn as_slice(&self) -> &[T] {
+        &self.buffer[self.head..]
+    }
+
+    /// Extracts a mutable slice containing the entire buffer.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.buffer[self.head..]
+    }
 }
 
 impl<T: Copy + Default> AsRef<[T]> for DequeBuffer<T> {
     fn as_ref(&self) -> &[T] {
-        &self.buffer[self.head..]
+        self.as_slice()
     }
 }
 
 impl<T: Copy + Default> AsMut<[T]> for DequeBuffer<T> {
     fn as_mut(&mut self) -> &mut [T] {
-        &mut self.buffer[self.head..]
+        self.as_mut_slice()
     }
 }
 
@@ -211,4 +275,73 @@ mod tests {
         buffer.clear();
         assert_eq!(repr(&buffer), "[ 0 0 0 0|? ? ? ? ? ? ]");
     }
+
+    #[test]
+    fn reserve_front_only_moves_if_needed() {
+        let mut buffer = DequeBuffer::<u8>::with_capacity(4, 2);
+        buffer.extend(1..=2);
+
+        buffer.reserve_front(1);
+        sanity_checks(&buffer);
+        assert_eq!(repr(&buffer), "[ 0 0|1 2 ? ? ]");
+        assert_eq!(buffer.front_capacity(), 2);
+
+        buffer.reserve_front(3);
+        sanity_checks(&buffer);
+        assert_eq!(buffer.front_capacity(), 3);
+        assert!(repr(&buffer).starts_with("[ 0 0 0|1 2"));
+        assert_eq!(buffer.as_slice(), &[1, 2]);
+    }
+
+    #[test]
+    fn shift_prepends_header() {
+        let mut buffer = DequeBuffer::<u8>::with_capacity(3, 4);
+        buffer.extend(&[7, 8, 9]);
+
+        let header = buffer.shift(4);
+        assert_eq!(header, &[0, 0, 0, 0]);
+        header.copy_from_slice(&3u32.to_le_bytes());
+        sanity_checks(&buffer);
+        assert_eq!(repr(&buffer), "[|3 0 0 0 7 8 9 ]");
+        assert_eq!(buffer.front_capacity(), 0);
+
+        buffer.as_mut_slice()[4] = 1;
+        assert_eq!(buffer.as_slice(), &[3, 0, 0, 0, 1, 8, 9]);
+    }
+
+    #[test]
+    fn clear_keeps_reserved_space() {
+        let mut buffer = DequeBuffer::<u8>::with_capacity(4, 2);
+        buffer.extend(1..=4);
+        buffer.extend_front(&[5, 5, 5]);
+        let capacity = buffer.capacity() + buffer.front_capacity();
+
+        for _ in 0..10 {
+            buffer.clear();
+            assert_eq!(buffer.front_capacity(), 3);
+            buffer.extend(1..=4);
+            buffer.extend_front(&[5, 5, 5]);
+            assert_eq!(buffer.front_capacity(), 0);
+            assert_eq!(buffer.capacity(), capacity);
+        }
+
+        buffer.clear();
+        buffer.reserve(10);
+        assert!(buffer.capacity() >= 10);
+    }
+
+    #[test]
+    fn capacity_is_exact_when_filled() {
+        let mut buffer = DequeBuffer::<u8>::with_capacity(5, 3);
+        assert_eq!(buffer.capacity(), 5);
+
+        buffer.extend(0..5);
+        sanity_checks(&buffer);
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(buffer.capacity(), buffer.len());
+
+        buffer.extend_front(&[1, 2, 3]);
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(buffer.capacity(), buffer.len());
+    }
 }
@@ -15,22 +15,10 @@ fn gcd(mut a: u128, mut b: u128) -> u128 {
     a
 }
 
-fn modpow(mut n: u128, mut e: u128, m: u128) -> u128 {
-    if m == 1 {
-        return 0;
-    }
-
-    let mut result = 1;
-    n %= m;
-    while e > 0 {
-        if e % 2 == 1 {
-            result = (result * n) % m;
-        }
-        e >>= 1;
-        n = (n * n) % m;
-    }
-    result
-}
+/// The values used to build `f(x) = x*x + c` on each attempt.
+///
+/// Prime factors are used for the attempts in the hopes they'll be more likely to work.
+const ATTEMPTS: [u64; 5] = [43, 47, 53, 59, 61];
 
 /// Factorize the given number into its two prime factors.
 ///
@@ -40,25 +28,55 @@ fn modpow(mut n: u128, mut e: u128, m: u128) -> u128 {
 ///
 /// Pollard's rho algorithm: <https://en.wikipedia.org/wiki/Pollard%27s_rho_algorithm>
 /// Richard Brent: <https://maths-people.anu.edu.au/~brent/pd/rpb051i.pdf>
-#[allow(clippy::many_single_char_names)]
+///
+/// With the `parallel` feature enabled, all attempts run at the same time in
+/// different threads, and the first one to find the factors is used.
+///
+/// The returned pair is always sorted, so that `p < q`.
 pub fn factorize(pq: u64) -> (u64, u64) {
-    const ATTEMPTS: [u64; 5] = [43, 47, 53, 59, 61];
+    #[cfg(not(feature = "parallel"))]
+    let factors = ATTEMPTS
+        .iter()
+        .map(|&attempt| factorize_with_param(pq, param_for_attempt(pq, attempt)))
+        .find(|&(p, _)| p != 1);
+
+    #[cfg(feature = "parallel")]
+    let factors = factorize_parallel(pq);
+
+    factors.expect("failed to factorize in a fixed amount of attempts")
+}
+
+/// Run each attempt in its own thread, returning the first successful result.
+///
+/// The remaining threads are detached and finish on their own.
+#[cfg(feature = "parallel")]
+fn factorize_parallel(pq: u64) -> Option<(u64, u64)> {
+    use std::sync::mpsc;
+    use std::thread;
+
+    let (tx, rx) = mpsc::channel();
     for attempt in ATTEMPTS {
-        // > Note that this algorithm may not find the factors and will return failure for composite n.
-        // > In that case, use a different f(x) and try again [...] We choose f(x) = x*x + c
-        // Thus by choosing a different `c` we're changing `f(x)` and can try again.
-        // Prime factors are used for the attempts in the hopes they'll be more likely to work.
-        let c = attempt * (pq / 103);
-        let (p, q) = factorize_with_param(pq, c);
-        if p != 1 {
-            return (p, q);
-        }
+        let tx = tx.clone();
+        thread::spawn(move || {
+            // The receiver may be gone if another attempt succeeded first.
+            let _ = tx.send(factorize_with_param(pq, param_for_attempt(pq, attempt)));
+        });
     }
-    panic!("failed to factorize in a fixed amount of attempts")
+    drop(tx);
+
+    rx.into_iter().find(|&(p, _)| p != 1)
+}
+
+fn param_for_attempt(pq: u64, attempt: u64) -> u64 {
+    // > Note that this algorithm may not find the factors and will return failure for composite n.
+    // > In that case, use a different f(x) and try again [...] We choose f(x) = x*x + c
+    // Thus by choosing a different `c` we're changing `f(x)` and can try again.
+    attempt * (pq / 103)
 }
 
+#[allow(clippy::many_single_char_names)]
 fn factorize_with_param(pq: u64, c: u64) -> (u64, u64) {
-    if pq % 2 == 0 {
+    if pq.is_multiple_of(2) {
         return (2, pq / 2);
     }
 
@@ -67,10 +85,12 @@ fn factorize_with_param(pq: u64, c: u64) -> (u64, u64) {
         a.max(b) - a.min(b)
     }
 
+    // Both `y` and `c` are below `pq`, which fits in 64 bits, so this can't overflow.
+    let f = |y: u128| (y * y + c as u128) % pq;
+
     // Random values in the range of 1..pq, chosen by fair dice roll.
     // c is an input free to change in case the chosen value fails.
     let mut y = 3 * (pq / 7);
-    let c = c as u128;
     let m = 7 * (pq / 13);
     let mut g = 1u128;
     let mut r = 1u128;
@@ -81,14 +101,14 @@ fn factorize_with_param(pq: u64, c: u64) -> (u64, u64) {
     while g == 1 {
         x = y;
         for _ in 0..r {
-            y = (modpow(y, 2, pq) + c) % pq;
+            y = f(y);
         }
 
         let mut k = 0;
         while k < r && g == 1 {
             ys = y;
             for _ in 0..m.min(r - k) {
-                y = (modpow(y, 2, pq) + c) % pq;
+                y = f(y);
                 q = (q * abs_sub(x, y)) % pq;
             }
 
@@ -101,7 +121,7 @@ fn factorize_with_param(pq: u64, c: u64) -> (u64, u64) {
 
     if g == pq {
         loop {
-            ys = (modpow(ys, 2, pq) + c) % pq;
+            ys = f(ys);
             g = gcd(abs_sub(x, ys), pq);
             if g > 1 {
                 break;
@@ -134,4 +154,11 @@ mod tests {
         let pq = factorize(2804275833720261793);
         assert_eq!(pq, (1555252417, 1803100129));
     }
+
+    #[test]
+    fn test_factorization_large() {
+        // (2^31 - 1) * (largest prime below 2^32), close to the 2^63 limit.
+        let pq = factorize(9223372021822390277);
+        assert_eq!(pq, (2147483647, 4294967291));
+    }
 }
@@ -10,17 +10,19 @@
 
 pub mod aes;
 mod auth_key;
+pub mod cdn;
 pub mod deque_buffer;
 pub mod factorize;
 pub mod hex;
 pub mod obfuscated;
+mod random;
 pub mod rsa;
 pub mod sha;
 pub mod two_factor_auth;
 
 pub use auth_key::AuthKey;
 pub use deque_buffer::DequeBuffer;
-use getrandom::getrandom;
+pub use random::{RandomSource, SystemRandom};
 use std::fmt;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -105,8 +107,15 @@ fn determine_padding_v2_length(len: usize) -> usize {
     16 + (16 - (len % 16))
 }
 
-// Inner body of `encrypt_data_v2`, separated for testing purposes.
-fn do_encrypt_data_v2(buffer: &mut DequeBuffer<u8>, auth_key: &AuthKey, random_padding: &[u8; 32]) {
+/// Like [`encrypt_data_v2`], but using the given bytes as the padding.
+///
+/// Only as many bytes as needed are used. The padding must be random for
+/// the encryption to be secure, which `encrypt_data_v2` takes care of.
+pub fn encrypt_data_v2_with_padding(
+    buffer: &mut DequeBuffer<u8>,
+    auth_key: &AuthKey,
+    random_padding: &[u8; 32],
+) {
     // "Note that MTProto 2.0 requires from 12 to 1024 bytes of padding"
     // "[...] the resulting message length be divisible by 16 bytes"
     let padding_len = determine_padding_v2_length(buffer.len());
@@ -140,13 +149,9 @@ fn do_encrypt_data_v2(buffer: &mut DequeBuffer<u8>, auth_key: &AuthKey, random_p
 ///
 /// [MTProto 2.0 algorithm]: https://core.telegram.org/mtproto/description#defining-aes-key-and-initialization-vector
 pub fn encrypt_data_v2(buffer: &mut DequeBuffer<u8>, auth_key: &AuthKey) {
-    let random_padding = {
-        let mut rnd = [0; 32];
-        getrandom(&mut rnd).expect("failed to generate a secure padding");
-        rnd
-    };
-
-    do_encrypt_data_v2(buffer, auth_key, &random_padding)
+    let mut random_padding = [0; 32];
+    SystemRandom.fill(&mut random_padding);
+    encrypt_data_v2_with_padding(buffer, auth_key, &random_padding)
 }
 
 /// This method is the inverse of `encrypt_data_v2`.
@@ -155,11 +160,12 @@ pub fn decrypt_data_v2(ciphertext: &[u8], auth_key: &AuthKey) -> Result<Vec<u8>,
     let side = Side::Server;
     let x = side.x();
 
-    if ciphertext.len() < 24 || (ciphertext.len() - 24) % 16 != 0 {
+    if ciphertext.len() < 24 || !(ciphertext.len() - 24).is_multiple_of(16) {
         return Err(Error::InvalidBuffer);
     }
 
-    // TODO Check salt, session_id and sequence_number
+    // The salt and session_id can only be checked by the caller, which knows what to expect.
+    // TODO Check sequence_number
     let key_id = &ciphertext[..8];
     if auth_key.key_id != *key_id {
         return Err(Error::AuthKeyMismatch);
@@ -216,18 +222,24 @@ pub fn generate_key_data_from_nonce(
 
 /// Encrypt data using AES-IGE.
 pub fn encrypt_ige(plaintext: &[u8], key: &[u8; 32], iv: &[u8; 32]) -> Vec<u8> {
-    let mut padded = if plaintext.len() % 16 == 0 {
-        plaintext.to_vec()
-    } else {
-        let pad_len = (16 - (plaintext.len() % 16)) % 16;
-        let mut padded = Vec::with_capacity(plaintext.len() + pad_len);
-        padded.extend(plaintext);
-
-        let mut buffer = vec![0; pad_len];
-        getrandom(&mut buffer).expect("failed to generate random padding for encryption");
-        padded.extend(&buffer);
-        padded
-    };
+    let mut random_padding = [0; 16];
+    SystemRandom.fill(&mut random_padding);
+    encrypt_ige_with_padding(plaintext, key, iv, &random_padding)
+}
+
+/// Like [`encrypt_ige`], but using the given bytes as the padding.
+///
+/// Only as many bytes as needed to pad the plaintext to 16 bytes are used.
+pub fn encrypt_ige_with_padding(
+    plaintext: &[u8],
+    key: &[u8; 32],
+    iv: &[u8; 32],
+    random_padding: &[u8; 16],
+) -> Vec<u8> {
+    let pad_len = (16 - (plaintext.len() % 16)) % 16;
+    let mut padded = Vec::with_capacity(plaintext.len() + pad_len);
+    padded.extend(plaintext);
+    padded.extend(&random_padding[..pad_len]);
 
     aes::ige_encrypt(padded.as_mut(), key, iv);
     padded
@@ -321,7 +333,7 @@ mod tests {
             36, 61, 86, 62, 161, 128, 210, 24, 238, 117, 124, 154,
         ];
 
-        do_encrypt_data_v2(&mut buffer, &auth_key, &random_padding);
+        encrypt_data_v2_with_padding(&mut buffer, &auth_key, &random_padding);
         assert_eq!(&buffer[..], expected);
     }
 
@@ -415,6 +427,21 @@ mod tests {
         assert_eq!(encrypt_ige(&plaintext, &key, &iv), expected);
     }
 
+    #[test]
+    fn verify_ige_encryption_padding() {
+        let plaintext = b"Hello, world!";
+        let key = get_test_aes_key_or_iv();
+        let iv = get_test_aes_key_or_iv();
+        let random_padding = [0x55; 16];
+
+        let ciphertext = encrypt_ige_with_padding(plaintext, &key, &iv, &random_padding);
+        assert_eq!(ciphertext.len(), 16);
+
+        let decrypted = decrypt_ige(&ciphertext, &key, &iv);
+        assert_eq!(&decrypted[..plaintext.len()], plaintext);
+        assert_eq!(&decrypted[plaintext.len()..], [0x55; 3]);
+    }
+
     #[test]
     fn verify_ige_decryption() {
         let ciphertext = get_test_aes_key_or_iv(); // Decrypting the key with itself
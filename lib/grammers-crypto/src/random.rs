@@ -0,0 +1,28 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use getrandom::getrandom;
+
+/// A source of random bytes.
+///
+/// The library defaults to [`SystemRandom`], but a different source may be
+/// used to produce deterministic output, which can be useful for tests or
+/// fuzzing. Such sources are **not** secure and must not be used otherwise.
+pub trait RandomSource: Send {
+    /// Fill the entire buffer with random bytes.
+    fn fill(&mut self, buffer: &mut [u8]);
+}
+
+/// The secure random source provided by the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemRandom;
+
+impl RandomSource for SystemRandom {
+    fn fill(&mut self, buffer: &mut [u8]) {
+        getrandom(buffer).expect("failed to generate secure random bytes");
+    }
+}
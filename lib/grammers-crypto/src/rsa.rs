@@ -5,11 +5,14 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use num_bigint::BigUint;
 
-use crate::{aes::ige_encrypt, sha256};
+use crate::{aes::ige_encrypt, sha1, sha256};
 
 /// RSA key.
+#[derive(Clone, Debug)]
 pub struct Key {
     n: BigUint,
     e: BigUint,
@@ -22,6 +25,84 @@ impl Key {
             e: BigUint::parse_bytes(e.as_bytes(), 10)?,
         })
     }
+
+    /// Parse a PKCS#1 public key in PEM format (`-----BEGIN RSA PUBLIC KEY-----`), such as
+    /// those Telegram provides for its CDN datacenters.
+    pub fn from_pem(pem: &str) -> Option<Self> {
+        let base64 = pem
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+            .collect::<String>();
+        let der = STANDARD.decode(base64).ok()?;
+
+        // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+        let (tag, body, _) = read_der(&der)?;
+        if tag != 0x30 {
+            return None;
+        }
+        let (tag, n, rest) = read_der(body)?;
+        if tag != 0x02 {
+            return None;
+        }
+        let (tag, e, _) = read_der(rest)?;
+        if tag != 0x02 {
+            return None;
+        }
+
+        Some(Self {
+            n: BigUint::from_bytes_be(n),
+            e: BigUint::from_bytes_be(e),
+        })
+    }
+
+    /// The fingerprint of the key, which is how the server refers to it during the generation
+    /// of an authorization key.
+    ///
+    /// It consists of the lower 64 bits of the SHA1 of the TL-serialized modulus and exponent.
+    pub fn fingerprint(&self) -> i64 {
+        let mut buffer = Vec::new();
+        serialize_bytes(&self.n.to_bytes_be(), &mut buffer);
+        serialize_bytes(&self.e.to_bytes_be(), &mut buffer);
+        let sha = sha1!(&buffer);
+        i64::from_le_bytes(sha[12..].try_into().unwrap())
+    }
+}
+
+/// Serialize the data like the TL `bytes` type does.
+fn serialize_bytes(data: &[u8], buffer: &mut Vec<u8>) {
+    let len = if data.len() < 254 {
+        buffer.push(data.len() as u8);
+        data.len() + 1
+    } else {
+        buffer.push(254);
+        buffer.extend(&(data.len() as u32).to_le_bytes()[..3]);
+        data.len() + 4
+    };
+    buffer.extend(data);
+    buffer.extend(std::iter::repeat_n(0, (4 - len % 4) % 4));
+}
+
+/// Read a single DER element, returning its tag, contents and the remaining data.
+fn read_der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&len, mut data) = data.split_first()?;
+    let len = if len & 0x80 == 0 {
+        len as usize
+    } else {
+        let count = (len & 0x7f) as usize;
+        if count > std::mem::size_of::<usize>() || data.len() < count {
+            return None;
+        }
+        let (len, rest) = data.split_at(count);
+        data = rest;
+        len.iter().fold(0, |acc, &b| (acc << 8) | b as usize)
+    };
+    if data.len() < len {
+        return None;
+    }
+    let (contents, rest) = data.split_at(len);
+    Some((tag, contents, rest))
 }
 
 /// Increment data by 1 when interpreted as a big-endian big int.
@@ -132,4 +213,23 @@ mod tests {
             hex::from_hex("b610642a828b4a61fe32931815cae318d311660580f1e0df768f3140f4d37dfcfcac0c2870318de4ff2d2e0e9669bcfdc0bad06cadb1b59d9726b427368a9c7b4fc0d5e7b2e99fc571968705c03acf5341fd7021bef653fa77b3776ae430e366fc46d232459ebe128b08d80e049ae579a48b56ca93b520709468587c81af96666046e9ea85091d729e921e8d8a36f57b27644052dae7387c7f4131701d59cda75251dac66c94276280ef950d3c44c21e5a2454f7da7a6818cf23ae9c490b72b2170d7cbc24f8a93db739d76f2d241c78b80123faaff3e664f074d6375d794dbf2800a0b5bb48d54eceafedfb355bfbebd287d9023264e3b53627888250787a9e")
         );
     }
+
+    #[test]
+    fn test_key_from_pem() {
+        let key = Key::from_pem(
+            "-----BEGIN RSA PUBLIC KEY-----
+MIIBCgKCAQEA6LszBcC1LGzyr992NzE0ieY+BSaOW622Aa9Bd4ZHLl+TuFQ4lo4g
+5nKaMBwK/BIb9xUfg0Q29/2mgIR6Zr9krM7HjuIcCzFvDtr+L0GQjae9H0pRB2OO
+62cECs5HKhT5DZ98K33vmWiLowc621dQuwKWSQKjWf50XYFw42h21P2KXUGyp2y/
++aEyZ+uVgLLQbRA1dEjSDZ2iGRy12Mk5gpYc397aYp438fsJoHIgJ2lgMv5h7WY9
+t6N/byY9Nw9p21Og3AoXSL2q/2IJ1WRUhebgAdGVMlV1fkuOQoEzR7EdpqtQD9Cs
+5+bfo3Nhmcyvk5ftB0WkJ9z6bNZ7yxrP8wIDAQAB
+-----END RSA PUBLIC KEY-----",
+        )
+        .unwrap();
+
+        assert_eq!(key.e, BigUint::from(65537u32));
+        assert_eq!(key.fingerprint(), -3414540481677951611);
+        assert!(Key::from_pem("-----BEGIN RSA PUBLIC KEY-----\n!!\n").is_none());
+    }
 }
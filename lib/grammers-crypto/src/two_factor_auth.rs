@@ -86,6 +86,34 @@ pub fn calculate_2fa(
     (m1, g_a)
 }
 
+/// Compute the hash of a new password, to be sent to Telegram when setting
+/// or changing the password (without the raw password).
+///
+/// The method returns `v`, which should be sent as the `new_password_hash`.
+/// Before calling this method, 32 random bytes must be appended to `salt1`,
+/// and the extended salt must be sent back as part of the new algorithm.
+///
+/// The algorithm is described in <https://core.telegram.org/api/srp>.
+pub fn calculate_new_password_hash(
+    salt1: &[u8],
+    salt2: &[u8],
+    p: &[u8],
+    g: &i32,
+    password: impl AsRef<[u8]>,
+) -> [u8; 256] {
+    let big_p = BigInt::from_bytes_be(Sign::Plus, p);
+    let big_g = BigInt::from(*g as u32);
+
+    // x := PH2(password, salt1, salt2)
+    let x = ph2(&password, salt1, salt2);
+    let x = BigInt::from_bytes_be(Sign::Plus, &x);
+
+    // v := pow(g, x) mod p
+    let big_v = big_g.modpow(&x, &big_p);
+
+    pad_to_256(&big_v.to_bytes_be().1)
+}
+
 /// Validation for parameters required for two-factor authentication
 pub fn check_p_and_g(p: &[u8], g: &i32) -> bool {
     if !check_p_len(p) {
@@ -167,6 +195,7 @@ fn pad_to_256(data: &[u8]) -> [u8; 256] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hex::from_hex;
 
     #[test]
     fn check_calculations_1() {
@@ -302,6 +331,44 @@ mod tests {
         assert_eq!(expected_g_a, g_a);
     }
 
+    #[test]
+    fn check_new_password_hash_1() {
+        let v = calculate_new_password_hash(&[1], &[2], &pad_to_256(&[47]), &3, [7]);
+
+        assert_eq!(v, pad_to_256(&[8]));
+    }
+
+    #[test]
+    fn check_new_password_hash_2() {
+        let salt1 = (1..=8).collect::<Vec<u8>>();
+        let salt2 = (9..=16).collect::<Vec<u8>>();
+        let p = from_hex(concat!(
+            "c71caeb9c6b1c9048e6c522f70f13f73980d40238e3e21c14934d037563d930f",
+            "48198a0aa7c14058229493d22530f4dbfa336f6e0ac925139543aed44cce7c37",
+            "20fd51f69458705ac68cd4fe6b6b13abdc9746512969328454f18faf8c595f64",
+            "2477fe96bb2a941d5bcd1d4ac8cc49880708fa9b378e3c4f3a9060bee67cf9a4",
+            "a4a695811051907e162753b56b0f6b410dba74d8a84b2a14b3144e0ef1284754",
+            "fd17ed950d5965b4b9dd46582db1178d169c6bc465b0d6ff9ca3928fef5b9ae4",
+            "e418fc15e83ebea0f87fa9ff5eed70050ded2849f47bf959d956850ce929851f",
+            "0d8115f635b105ee2e4e15d04b2454bf6f4fadf034b10403119cd8e3b92fcc5b",
+        ));
+
+        let v = calculate_new_password_hash(&salt1, &salt2, &p, &3, "hunter2");
+
+        let expected_v = from_hex(concat!(
+            "824ccadad38af89b5fc73b3c98d3c44a9d62db99a129016354bc97e673290e8d",
+            "3f615512301d4d17b03f22e50eeb418125abcfb94f20a313d592d91357e158e9",
+            "2bc76cf0b051800eb7952efd939f8fda8a4bdc9c290e743114d54c48fbc01c31",
+            "5e8dfb477219aa39d2f8745d1a0497064c46dc6ca423dd50a5185729ce66b348",
+            "6b35f923dded852da16885fda2bb93ec4801e34245bde4da1f313e867e6a6061",
+            "ef8444a16d8fcbd003e9c1eb4eb696cd4e77bc78b376034ddc3d69132bfbae12",
+            "df4cfe97a76f7ef89dd90e58087f126f27d9ea9f7740d592c73c43269c762570",
+            "8409eda358530e873139cb2157d7281ee4146e6a2163f17a3b994eae95015444",
+        ));
+
+        assert_eq!(v.to_vec(), expected_v);
+    }
+
     #[test]
     fn test_check_p_and_g() {
         // Not prime
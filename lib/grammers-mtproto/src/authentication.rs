@@ -243,6 +243,17 @@ fn do_step1(random_bytes: &[u8; 16]) -> Result<(Vec<u8>, Step1), Error> {
 
 /// The second step of the process to generate an authorization key.
 pub fn step2(data: Step1, response: &[u8]) -> Result<(Vec<u8>, Step2), Error> {
+    step2_with_keys(data, response, &[])
+}
+
+/// Like [`step2`], but the server may also use any of the given RSA keys, in addition to
+/// the ones known by default. This is needed to connect to CDN datacenters, whose keys must be
+/// fetched with `help.getCdnConfig`.
+pub fn step2_with_keys(
+    data: Step1,
+    response: &[u8],
+    keys: &[rsa::Key],
+) -> Result<(Vec<u8>, Step2), Error> {
     if TRACE_AUTH_GEN {
         println!("< {}", hex::to_hex(response));
     }
@@ -257,7 +268,7 @@ pub fn step2(data: Step1, response: &[u8]) -> Result<(Vec<u8>, Step2), Error> {
         println!("r {}", hex::to_hex(&random_bytes));
     }
 
-    let res = do_step2(data, response, &random_bytes);
+    let res = do_step2(data, response, &random_bytes, keys);
     if TRACE_AUTH_GEN {
         if let Ok((x, _)) = &res {
             println!("> {}", hex::to_hex(x));
@@ -270,6 +281,7 @@ fn do_step2(
     data: Step1,
     response: &[u8],
     random_bytes: &[u8; 32 + 224],
+    keys: &[rsa::Key],
 ) -> Result<(Vec<u8>, Step2), Error> {
     // Step 2. Validate the PQ response. Return `(p, q)` if it's valid.
     let Step1 { nonce } = data;
@@ -334,22 +346,25 @@ fn do_step2(
     .to_bytes();
 
     // sha_digest + data + random_bytes
-    let fingerprint = match res_pq
-        .server_public_key_fingerprints
-        .iter()
-        .cloned()
-        .find(|&fingerprint| key_for_fingerprint(fingerprint).is_some())
-    {
-        Some(x) => x,
-        None => {
-            return Err(Error::UnknownFingerprints {
-                fingerprints: res_pq.server_public_key_fingerprints.clone(),
-            })
-        }
-    };
+    let (fingerprint, key) =
+        match res_pq
+            .server_public_key_fingerprints
+            .iter()
+            .find_map(|&fingerprint| {
+                keys.iter()
+                    .find(|key| key.fingerprint() == fingerprint)
+                    .cloned()
+                    .or_else(|| key_for_fingerprint(fingerprint))
+                    .map(|key| (fingerprint, key))
+            }) {
+            Some(x) => x,
+            None => {
+                return Err(Error::UnknownFingerprints {
+                    fingerprints: res_pq.server_public_key_fingerprints.clone(),
+                })
+            }
+        };
 
-    // Safe to unwrap because we found it just above
-    let key = key_for_fingerprint(fingerprint).unwrap();
     let ciphertext = rsa::encrypt_hashed(&pq_inner_data, &key, &random_bytes);
 
     Ok((
@@ -753,7 +768,7 @@ mod tests {
         assert_eq!(request, step1_request.to_vec());
         let response = step1_response;
 
-        let (request, data) = do_step2(data, &response, &step2_random)?;
+        let (request, data) = do_step2(data, &response, &step2_random, &[])?;
         assert_eq!(request, step2_request.to_vec());
         let response = step2_response;
 
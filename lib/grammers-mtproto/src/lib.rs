@@ -18,6 +18,8 @@
 pub mod authentication;
 mod manual_tl;
 pub mod mtp;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 pub mod transport;
 mod utils;
 
@@ -10,11 +10,14 @@ use super::{
 };
 use crate::utils::StackBuffer;
 use crate::{manual_tl, MsgId};
-use getrandom::getrandom;
-use grammers_crypto::{decrypt_data_v2, encrypt_data_v2, AuthKey, DequeBuffer};
+use grammers_crypto::{
+    decrypt_data_v2, encrypt_data_v2_with_padding, AuthKey, DequeBuffer, RandomSource, SystemRandom,
+};
 use grammers_tl_types::{self as tl, Cursor, Deserializable, Identifiable, Serializable};
 use log::info;
+use std::collections::VecDeque;
 use std::mem;
+use std::time::Duration;
 use web_time::{Instant, SystemTime, UNIX_EPOCH};
 
 /// How many future salts to fetch or have stored at a given time.
@@ -28,6 +31,18 @@ const NUM_FUTURE_SALTS: i32 = 64;
 /// Used to prevent small fluctuations in the system clock.
 const SALT_USE_DELAY: i32 = 60;
 
+/// How many of the most recent server message IDs to remember.
+///
+/// Used to detect messages that the server sent more than once, so that they are not processed
+/// twice. Message IDs are time-based, so a small window is enough to catch all duplicates.
+const RECENT_MSG_IDS_LEN: usize = 256;
+
+/// How many acknowledgements may be pending before they're sent without waiting for a request.
+const DEFAULT_MAX_PENDING_ACKS: usize = 16;
+
+/// How long acknowledgements may be pending before they're sent without waiting for a request.
+const DEFAULT_MAX_ACK_DELAY: Duration = Duration::from_secs(1);
+
 static UPDATE_IDS: [u32; 8] = [
     tl::types::UpdateShortMessage::CONSTRUCTOR_ID,
     tl::types::UpdateShortChatMessage::CONSTRUCTOR_ID,
@@ -49,6 +64,11 @@ pub struct Builder {
     time_offset: i32,
     first_salt: i64,
     compression_threshold: Option<usize>,
+    random: Box<dyn RandomSource>,
+    max_container_size: usize,
+    max_container_len: usize,
+    max_pending_acks: usize,
+    max_ack_delay: Duration,
 }
 
 /// An implementation of the [Mobile Transport Protocol] for ciphertext
@@ -62,6 +82,9 @@ pub struct Encrypted {
     /// The time offset from the server's time, in seconds.
     time_offset: i32,
 
+    /// Whether the time offset has been corrected from the time of a message sent by the server.
+    time_synced: bool,
+
     /// Salts that may be used when encrypting payload, sorted by valid date descending (so the last one is the one
     /// that can be used now).
     salts: Vec<tl::types::FutureSalt>,
@@ -74,6 +97,9 @@ pub struct Encrypted {
     /// Internal request for salts which should not be propagated.
     salt_request_msg_id: Option<MsgId>,
 
+    /// The source used to generate the `client_id` and the padding of outgoing messages.
+    random: Box<dyn RandomSource>,
+
     /// The secure, random identifier for this instance.
     client_id: i64,
 
@@ -92,11 +118,35 @@ pub struct Encrypted {
     /// [Content-related Message]: https://core.telegram.org/mtproto/description#content-related-message
     pending_ack: Vec<i64>,
 
+    /// When the oldest of the pending acknowledgements was added.
+    first_pending_ack: Option<Instant>,
+
+    /// How many acknowledgements may be pending before they must be sent on their own.
+    max_pending_acks: usize,
+
+    /// How long acknowledgements may be pending before they must be sent on their own.
+    max_ack_delay: Duration,
+
+    /// Identifiers of server messages which the server announced, but were never received, and
+    /// need to be requested again.
+    pending_resend: Vec<i64>,
+
+    /// The most recently processed server message IDs, oldest first.
+    ///
+    /// Bounded to `RECENT_MSG_IDS_LEN` items.
+    recent_msg_ids: VecDeque<i64>,
+
     /// If present, the threshold in bytes at which a message will be
     /// considered large enough to attempt compressing it. Otherwise,
     /// outgoing messages will never be compressed.
     compression_threshold: Option<usize>,
 
+    /// Maximum size in bytes of the messages in a container.
+    max_container_size: usize,
+
+    /// Maximum amount of messages in a container.
+    max_container_len: usize,
+
     /// Temporary deserialization results.
     deserialization: Vec<Deserialization>,
 
@@ -104,8 +154,19 @@ pub struct Encrypted {
     msg_count: usize,
 }
 
+/// Generates a new secure, random identifier for a session.
+fn generate_client_id(random: &mut dyn RandomSource) -> i64 {
+    let mut buffer = [0u8; 8];
+    random.fill(&mut buffer);
+    i64::from_le_bytes(buffer)
+}
+
 impl Builder {
-    /// Configures the time offset to Telegram servers.
+    /// Configures the initial time offset to Telegram servers, in seconds.
+    ///
+    /// This is used until the first message from the server is received, at which point the
+    /// offset is corrected from the server's time. Providing a previously-known offset avoids
+    /// the first requests from being rejected when the system clock is wrong.
     pub fn time_offset(mut self, offset: i32) -> Self {
         self.time_offset = offset;
         self
@@ -122,12 +183,63 @@ impl Builder {
         self
     }
 
+    /// Configures the source of randomness used for the session identifier and the padding of
+    /// outgoing messages.
+    ///
+    /// The default is [`SystemRandom`], which is secure. Other sources should only be used to
+    /// produce deterministic output, for example, during tests.
+    pub fn random_source<R: RandomSource + 'static>(mut self, random: R) -> Self {
+        self.random = Box::new(random);
+        self
+    }
+
+    /// Configures the maximum size in bytes and the maximum amount of messages that will be
+    /// batched together in a single container.
+    ///
+    /// Smaller containers reduce the cost of losing a packet on unreliable connections,
+    /// while larger ones reduce the overhead on reliable connections. The default is to use
+    /// the largest containers Telegram accepts.
+    ///
+    /// A single request larger than `max_size` may still be sent on its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_len` is zero, or if either limit exceeds the ones imposed by Telegram.
+    pub fn container_limits(mut self, max_size: usize, max_len: usize) -> Self {
+        assert!(
+            max_size <= manual_tl::MessageContainer::MAXIMUM_SIZE,
+            "container size cannot exceed {} bytes",
+            manual_tl::MessageContainer::MAXIMUM_SIZE
+        );
+        assert!(
+            (1..=manual_tl::MessageContainer::MAXIMUM_LENGTH).contains(&max_len),
+            "container length must be between 1 and {} messages",
+            manual_tl::MessageContainer::MAXIMUM_LENGTH
+        );
+        self.max_container_size = max_size;
+        self.max_container_len = max_len;
+        self
+    }
+
+    /// Configures when acknowledgements of the messages received from the server are sent,
+    /// even if there are no requests to send along with them.
+    ///
+    /// They're sent once `max_pending` accumulate, or once `max_delay` has passed since the
+    /// oldest of them was received, whichever happens first. Otherwise, the server may consider
+    /// the client unresponsive and send the messages again.
+    pub fn ack_flush_limits(mut self, max_pending: usize, max_delay: Duration) -> Self {
+        self.max_pending_acks = max_pending;
+        self.max_ack_delay = max_delay;
+        self
+    }
+
     /// Finishes the builder and returns the `MTProto` instance with all
     /// the configuration changes applied.
-    pub fn finish(self, auth_key: [u8; 256]) -> Encrypted {
+    pub fn finish(mut self, auth_key: [u8; 256]) -> Encrypted {
         Encrypted {
             auth_key: AuthKey::from_bytes(auth_key),
             time_offset: self.time_offset,
+            time_synced: false,
             salts: vec![tl::types::FutureSalt {
                 valid_since: 0,
                 valid_until: i32::MAX,
@@ -135,15 +247,19 @@ impl Builder {
             }],
             start_salt_time: None,
             salt_request_msg_id: None,
-            client_id: {
-                let mut buffer = [0u8; 8];
-                getrandom(&mut buffer).expect("failed to generate a secure client_id");
-                i64::from_le_bytes(buffer)
-            },
+            client_id: generate_client_id(self.random.as_mut()),
+            random: self.random,
             sequence: 0,
             last_msg_id: 0,
             pending_ack: vec![],
+            first_pending_ack: None,
+            max_pending_acks: self.max_pending_acks,
+            max_ack_delay: self.max_ack_delay,
+            pending_resend: vec![],
+            recent_msg_ids: VecDeque::with_capacity(RECENT_MSG_IDS_LEN),
             compression_threshold: self.compression_threshold,
+            max_container_size: self.max_container_size,
+            max_container_len: self.max_container_len,
             deserialization: Vec::new(),
             msg_count: 0,
         }
@@ -157,6 +273,11 @@ impl Encrypted {
             time_offset: 0,
             compression_threshold: crate::DEFAULT_COMPRESSION_THRESHOLD,
             first_salt: 0,
+            random: Box::new(SystemRandom),
+            max_container_size: manual_tl::MessageContainer::MAXIMUM_SIZE,
+            max_container_len: manual_tl::MessageContainer::MAXIMUM_LENGTH,
+            max_pending_acks: DEFAULT_MAX_PENDING_ACKS,
+            max_ack_delay: DEFAULT_MAX_ACK_DELAY,
         }
     }
 
@@ -165,6 +286,13 @@ impl Encrypted {
         self.auth_key.to_bytes()
     }
 
+    /// The current time offset to Telegram servers, in seconds.
+    ///
+    /// It can be persisted and used as the initial offset of future instances.
+    pub fn time_offset(&self) -> i32 {
+        self.time_offset
+    }
+
     /// Correct our time offset based on a known valid message ID.
     fn correct_time_offset(&mut self, msg_id: i64) {
         let now = SystemTime::now()
@@ -252,6 +380,12 @@ impl Encrypted {
     ///
     /// The buffer is *not* cleared, but is instead returned.
     fn finalize_plain(&mut self, buffer: &mut DequeBuffer<u8>) {
+        // Without any other message to send, acknowledgements only go out if they can't wait,
+        // while requests to re-send lost messages never wait.
+        if self.msg_count == 0 && self.should_flush_acks(Instant::now()) {
+            self.push_pending_acks(buffer);
+            self.push_pending_resend(buffer);
+        }
         if self.msg_count == 0 {
             return;
         }
@@ -285,11 +419,73 @@ impl Encrypted {
         self.msg_count = 0;
     }
 
+    /// Remember that the given server message ID needs to be acknowledged.
+    fn push_pending_ack(&mut self, msg_id: i64) {
+        if self.pending_ack.is_empty() {
+            self.first_pending_ack = Some(Instant::now());
+        }
+        self.pending_ack.push(msg_id);
+    }
+
+    /// Serialize the acknowledgements that are pending, if any.
+    fn push_pending_acks(&mut self, buffer: &mut DequeBuffer<u8>) {
+        if !self.pending_ack.is_empty() {
+            // TODO avoid to_bytes here, serialize it in-place
+            let body = tl::enums::MsgsAck::Ack(tl::types::MsgsAck {
+                msg_ids: mem::take(&mut self.pending_ack),
+            })
+            .to_bytes();
+            self.serialize_msg(buffer, &body, false);
+            self.first_pending_ack = None;
+        }
+    }
+
+    /// Serialize the request to re-send the server messages that never arrived, if any.
+    fn push_pending_resend(&mut self, buffer: &mut DequeBuffer<u8>) {
+        if !self.pending_resend.is_empty() {
+            let body = tl::enums::MsgResendReq::Req(tl::types::MsgResendReq {
+                msg_ids: mem::take(&mut self.pending_resend),
+            })
+            .to_bytes();
+            self.serialize_msg(buffer, &body, true);
+        }
+    }
+
+    /// Remember the given server message ID, returning `true` if it had already been seen.
+    fn check_duplicate(&mut self, msg_id: i64) -> bool {
+        if self.recent_msg_ids.contains(&msg_id) {
+            return true;
+        }
+        if self.recent_msg_ids.len() == RECENT_MSG_IDS_LEN {
+            self.recent_msg_ids.pop_front();
+        }
+        self.recent_msg_ids.push_back(msg_id);
+        false
+    }
+
     fn process_message(&mut self, message: manual_tl::Message) -> Result<(), DeserializeError> {
         if message.requires_ack() {
-            self.pending_ack.push(message.msg_id);
+            self.push_pending_ack(message.msg_id);
+        }
+
+        // Containers are not checked, because they may be re-sent packed differently.
+        // The messages inside are checked individually instead.
+        if message.constructor_id()? != manual_tl::MessageContainer::CONSTRUCTOR_ID
+            && self.check_duplicate(message.msg_id)
+        {
+            // The server sent this message again (perhaps it didn't get our ack in time).
+            // It has been acknowledged again above, but must not be processed twice.
+            info!("ignoring duplicate message {}", message.msg_id);
+            return Ok(());
         }
 
+        self.process_message_body(message)
+    }
+
+    fn process_message_body(
+        &mut self,
+        message: manual_tl::Message,
+    ) -> Result<(), DeserializeError> {
         // Handle all the possible Service Messages:
         // * https://core.telegram.org/mtproto/service_messages
         // * https://core.telegram.org/mtproto/service_messages_about_messages
@@ -521,7 +717,7 @@ impl Encrypted {
     fn store_own_updates(&mut self, body: &[u8]) {
         match u32::from_bytes(body) {
             Ok(body_id) => {
-                if UPDATE_IDS.iter().any(|&id| body_id == id) {
+                if UPDATE_IDS.contains(&body_id) {
                     // TODO somehow signal that this updates is our own, to avoid getting into nasty loops
                     self.deserialization
                         .push(Deserialization::Update(body.to_vec()));
@@ -561,10 +757,15 @@ impl Encrypted {
     /// unacknowledged messages from the server (say, over 16), the client
     /// transmits a stand-alone acknowledgment.
     ///
+    /// The acknowledged messages are reported, because the server may acknowledge some requests
+    /// (such as `auth.logOut`) without ever sending their result.
+    ///
     /// [Acknowledgment of Receipt]: https://core.telegram.org/mtproto/service_messages_about_messages#acknowledgment-of-receipt
-    fn handle_ack(&self, message: manual_tl::Message) -> Result<(), DeserializeError> {
-        // TODO notify about this somehow
-        let _ack = tl::enums::MsgsAck::from_bytes(&message.body)?;
+    fn handle_ack(&mut self, message: manual_tl::Message) -> Result<(), DeserializeError> {
+        let tl::enums::MsgsAck::Ack(ack) = tl::enums::MsgsAck::from_bytes(&message.body)?;
+        self.deserialization.push(Deserialization::Ack(
+            ack.msg_ids.into_iter().map(MsgId).collect(),
+        ));
         Ok(())
     }
 
@@ -679,15 +880,11 @@ impl Encrypted {
                 // Sent `msg_id` was too high (our `time_offset` is wrong).
                 self.correct_time_offset(message.msg_id);
             }
-            32 => {
-                // Sent `seq_no` was too low. Bump it by some large-ish value.
-                // TODO start with a fresh session rather than guessing
-                self.sequence += 64;
-            }
-            33 => {
-                // Sent `seq_no` was too high (this error doesn't seem to occur).
-                // TODO start with a fresh session rather than guessing
-                self.sequence -= 16;
+            32 | 33 => {
+                // Sent `seq_no` was too low or too high. Rather than guessing the right value,
+                // start with a fresh session, under which the request will be re-sent.
+                info!("got bad seq_no; starting a new session");
+                self.reset();
             }
             _ => {
                 // Just notify about it.
@@ -803,16 +1000,19 @@ impl Encrypted {
         &mut self,
         message: manual_tl::Message,
     ) -> Result<(), DeserializeError> {
-        // TODO https://github.com/telegramdesktop/tdesktop/blob/8f82880b938e06b7a2a27685ef9301edb12b4648/Telegram/SourceFiles/mtproto/connection.cpp#L1790-L1820
-        // TODO https://github.com/telegramdesktop/tdesktop/blob/8f82880b938e06b7a2a27685ef9301edb12b4648/Telegram/SourceFiles/mtproto/connection.cpp#L1822-L1845
-        let msg_detailed = tl::enums::MsgDetailedInfo::from_bytes(&message.body)?;
-        match msg_detailed {
-            tl::enums::MsgDetailedInfo::Info(x) => {
-                self.pending_ack.push(x.answer_msg_id);
-            }
-            tl::enums::MsgDetailedInfo::MsgNewDetailedInfo(x) => {
-                self.pending_ack.push(x.answer_msg_id);
-            }
+        // See https://github.com/telegramdesktop/tdesktop/blob/8f82880b938e06b7a2a27685ef9301edb12b4648/Telegram/SourceFiles/mtproto/connection.cpp#L1790-L1845
+        let answer_msg_id = match tl::enums::MsgDetailedInfo::from_bytes(&message.body)? {
+            tl::enums::MsgDetailedInfo::Info(x) => x.answer_msg_id,
+            tl::enums::MsgDetailedInfo::MsgNewDetailedInfo(x) => x.answer_msg_id,
+        };
+
+        if self.recent_msg_ids.contains(&answer_msg_id) {
+            // The answer was received, but the server didn't get our acknowledgement in time.
+            self.push_pending_ack(answer_msg_id);
+        } else if !self.pending_resend.contains(&answer_msg_id) {
+            // The answer was lost along the way, so it must be requested again.
+            info!("requesting the server to resend message {}", answer_msg_id);
+            self.pending_resend.push(answer_msg_id);
         }
         Ok(())
     }
@@ -1103,7 +1303,8 @@ impl Encrypted {
     /// [Packed Object]: https://core.telegram.org/mtproto/service_messages#packed-object
     fn handle_gzip_packed(&mut self, message: manual_tl::Message) -> Result<(), DeserializeError> {
         let container = manual_tl::GzipPacked::from_bytes(&message.body)?;
-        self.process_message(manual_tl::Message {
+        // Same `msg_id`, so the body is processed without checking for duplicates again.
+        self.process_message_body(manual_tl::Message {
             body: container.decompress()?,
             ..message
         })
@@ -1186,12 +1387,19 @@ pub const PLAIN_PACKET_HEADER_LEN: usize = 8 + 8;
 /// (`msg_id`, `seq_no` and `size`) followed by the container header (`constructor`, `len`).
 pub const MESSAGE_CONTAINER_HEADER_LEN: usize = (8 + 4 + 4) + (4 + 4);
 
-impl Mtp for Encrypted {
+impl Encrypted {
     /// Pushes a request into the internal buffer by manually serializing the messages for maximum
     /// efficiency. If the buffer is full, returns `None`.
     ///
+    /// Only content-related requests are compressed, and they increase the sequence number.
+    ///
     /// [MTProto 2.0 guidelines]: https://core.telegram.org/mtproto/description.
-    fn push(&mut self, buffer: &mut DequeBuffer<u8>, request: &[u8]) -> Option<MsgId> {
+    fn push_message(
+        &mut self,
+        buffer: &mut DequeBuffer<u8>,
+        request: &[u8],
+        content_related: bool,
+    ) -> Option<MsgId> {
         // TODO rather than taking in bytes, take requests, serialize them in place, and if too large drop the last part of the buffer
 
         // Check to see if the next salt can be used already. If it can, drop the current one and,
@@ -1215,17 +1423,14 @@ impl Mtp for Encrypted {
         // If we need to acknowledge messages, this notification goes in with the rest of requests
         // so that we can also include it. It has priority over user requests because these should
         // be sent out as soon as possible.
-        if !self.pending_ack.is_empty() {
-            // TODO avoid to_bytes here, serialize it in-place
-            let body = tl::enums::MsgsAck::Ack(tl::types::MsgsAck {
-                msg_ids: mem::take(&mut self.pending_ack),
-            })
-            .to_bytes();
-            self.serialize_msg(buffer, &body, false);
-        }
+        self.push_pending_acks(buffer);
 
-        // Serialize `MAXIMUM_LENGTH` requests at most.
-        if self.msg_count == manual_tl::MessageContainer::MAXIMUM_LENGTH {
+        // Messages the server announced but which never arrived are also requested as soon as
+        // possible, because they may contain the response to some request.
+        self.push_pending_resend(buffer);
+
+        // Serialize `max_container_len` requests at most.
+        if self.msg_count >= self.max_container_len {
             return None;
         }
 
@@ -1237,13 +1442,12 @@ impl Mtp for Encrypted {
         );
 
         // Serialized requests will always be correctly padded.
-        assert!(request.len() % 4 == 0);
+        assert!(request.len().is_multiple_of(4));
 
-        // Payload provided by the user is always considered to be
-        // content-related, which means we can apply compression.
+        // Only content-related payloads may be compressed.
         let mut body = request;
         let compressed;
-        if let Some(threshold) = self.compression_threshold {
+        if let Some(threshold) = self.compression_threshold.filter(|_| content_related) {
             if request.len() >= threshold {
                 compressed = manual_tl::GzipPacked::new(request).to_bytes();
                 if compressed.len() < request.len() {
@@ -1253,13 +1457,42 @@ impl Mtp for Encrypted {
         }
 
         let new_size = buffer.len() + body.len() + manual_tl::Message::SIZE_OVERHEAD;
-        if new_size >= manual_tl::MessageContainer::MAXIMUM_SIZE {
+        if new_size >= manual_tl::MessageContainer::MAXIMUM_SIZE
+            || (new_size >= self.max_container_size && self.msg_count != 0)
+        {
             // No more messages fit in this container.
             return None;
         }
 
         // This request still fits in the container, so give it a message ID.
-        Some(self.serialize_msg(buffer, body, true))
+        let msg_id = self.serialize_msg(buffer, body, content_related);
+        Some(msg_id)
+    }
+}
+
+impl Mtp for Encrypted {
+    fn push(&mut self, buffer: &mut DequeBuffer<u8>, request: &[u8]) -> Option<MsgId> {
+        self.push_message(buffer, request, true)
+    }
+
+    fn push_service(&mut self, buffer: &mut DequeBuffer<u8>, request: &[u8]) -> Option<MsgId> {
+        self.push_message(buffer, request, false)
+    }
+
+    fn should_flush_acks(&self, now: Instant) -> bool {
+        !self.pending_resend.is_empty()
+            || self.pending_ack.len() >= self.max_pending_acks
+            || self
+                .ack_flush_deadline()
+                .is_some_and(|deadline| deadline <= now)
+    }
+
+    fn ack_flush_deadline(&self) -> Option<Instant> {
+        if !self.pending_resend.is_empty() {
+            return Some(Instant::now());
+        }
+        self.first_pending_ack
+            .map(|first| first + self.max_ack_delay)
     }
 
     fn finalize(&mut self, buffer: &mut DequeBuffer<u8>) -> Option<MsgId> {
@@ -1267,7 +1500,9 @@ impl Mtp for Encrypted {
         if buffer.is_empty() {
             None
         } else {
-            encrypt_data_v2(buffer, &self.auth_key);
+            let mut random_padding = [0; 32];
+            self.random.fill(&mut random_padding);
+            encrypt_data_v2_with_padding(buffer, &self.auth_key, &random_padding);
             Some(MsgId(self.last_msg_id))
         }
     }
@@ -1279,13 +1514,25 @@ impl Mtp for Encrypted {
         let plaintext = decrypt_data_v2(payload, &self.auth_key)?;
         let mut buffer = Cursor::from_slice(&plaintext[..]);
 
+        // The salt is not validated. The server may use one we don't know about yet (such as
+        // right before telling us about it), and it does not protect the client in any way.
         let _salt = i64::deserialize(&mut buffer)?;
         let client_id = i64::deserialize(&mut buffer)?;
         if client_id != self.client_id {
-            panic!("wrong session id");
+            return Err(DeserializeError::SessionIdMismatch {
+                got: client_id,
+                expected: self.client_id,
+            });
         }
 
-        self.process_message(manual_tl::Message::deserialize(&mut buffer)?)?;
+        let message = manual_tl::Message::deserialize(&mut buffer)?;
+        if !self.time_synced {
+            // Message identifiers from the server contain its time, so there's no need to wait
+            // for a `bad_msg_notification` to find out our clock is wrong.
+            self.correct_time_offset(message.msg_id);
+            self.time_synced = true;
+        }
+        self.process_message(message)?;
 
         // For simplicity, and to avoid passing too much stuff around (RPC results, updates),
         // the processing result is stored in self. After processing is done, that temporary
@@ -1295,14 +1542,14 @@ impl Mtp for Encrypted {
 
     fn reset(&mut self) {
         log::info!("resetting mtp client id and related state");
-        self.client_id = {
-            let mut buffer = [0u8; 8];
-            getrandom(&mut buffer).expect("failed to generate a secure client_id");
-            i64::from_le_bytes(buffer)
-        };
+        self.client_id = generate_client_id(self.random.as_mut());
         self.sequence = 0;
         self.last_msg_id = 0;
+        self.time_synced = false;
         self.pending_ack.clear();
+        self.first_pending_ack = None;
+        self.pending_resend.clear();
+        self.recent_msg_ids.clear();
         self.msg_count = 0;
         self.salt_request_msg_id = None;
     }
@@ -1311,6 +1558,7 @@ impl Mtp for Encrypted {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{decrypt_as_server, encrypt_as_server, rpc_result_body, server_message};
 
     // salt + client_id
     const MESSAGE_PREFIX_LEN: usize = 8 + 8;
@@ -1328,6 +1576,20 @@ mod tests {
         [0; 256]
     }
 
+    /// A predictable source of randomness (xorshift), so that output can be compared.
+    struct SeededRandom(u64);
+
+    impl RandomSource for SeededRandom {
+        fn fill(&mut self, buffer: &mut [u8]) {
+            buffer.iter_mut().for_each(|b| {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                *b = self.0 as u8;
+            });
+        }
+    }
+
     fn ensure_buffer_is_message(buffer: &[u8], body: &[u8], seq_no: u8) {
         // buffer[0..8] is the msg_id, based on `SystemTime::now()`
         assert_ne!(&buffer[0..8], [0, 0, 0, 0, 0, 0, 0, 0]);
@@ -1357,6 +1619,25 @@ mod tests {
         ensure_buffer_is_message(&buffer[MESSAGE_PREFIX_LEN..], REQUEST, 1);
     }
 
+    #[test]
+    fn ensure_msg_id_is_little_endian() {
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        let mut mtproto = Encrypted::build().finish(auth_key());
+
+        let msg_id = mtproto.push(&mut buffer, REQUEST).unwrap();
+        mtproto.finalize_plain(&mut buffer);
+
+        // Built with shifts, so that it doesn't depend on the endianness of the host.
+        let expected = (0..8)
+            .map(|i| (msg_id.0 >> (8 * i)) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            &buffer[MESSAGE_PREFIX_LEN..MESSAGE_PREFIX_LEN + 8],
+            expected
+        );
+        assert_eq!(&buffer[8..16], &mtproto.client_id.to_le_bytes());
+    }
+
     #[test]
     fn ensure_correct_single_serialization() {
         let mut buffer = DequeBuffer::with_capacity(0, 0);
@@ -1401,6 +1682,26 @@ mod tests {
         ensure_buffer_is_message(&buffer[44..], REQUEST_B, 3);
     }
 
+    #[test]
+    fn ensure_service_requests_are_not_content_related() {
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        let mut mtproto = Encrypted::build()
+            .compression_threshold(None)
+            .finish(auth_key());
+
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        assert!(mtproto.push_service(&mut buffer, REQUEST_B).is_some());
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        mtproto.finalize_plain(&mut buffer);
+        let buffer = &buffer[MESSAGE_PREFIX_LEN..];
+        assert_eq!(&buffer[20..24], [3, 0, 0, 0]);
+
+        // Content-related messages use odd numbers, and the service one doesn't increase it.
+        ensure_buffer_is_message(&buffer[24..44], REQUEST, 1);
+        ensure_buffer_is_message(&buffer[44..64], REQUEST_B, 2);
+        ensure_buffer_is_message(&buffer[64..], REQUEST, 3);
+    }
+
     #[test]
     fn ensure_correct_single_large_serialization() {
         let mut buffer = DequeBuffer::with_capacity(0, 0);
@@ -1451,6 +1752,390 @@ mod tests {
         mtproto.push(&mut buffer, &[1, 2, 3]);
     }
 
+    #[test]
+    fn ensure_container_length_is_configurable() {
+        let mut mtproto = Encrypted::build()
+            .container_limits(manual_tl::MessageContainer::MAXIMUM_SIZE, 2)
+            .finish(auth_key());
+
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        assert!(mtproto.push(&mut buffer, REQUEST).is_none());
+        mtproto.finalize_plain(&mut buffer);
+
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        mtproto.finalize_plain(&mut buffer);
+        // A single message is not put inside a container.
+        ensure_buffer_is_message(&buffer[MESSAGE_PREFIX_LEN..], REQUEST, 5);
+    }
+
+    #[test]
+    fn ensure_container_size_is_configurable() {
+        let max_size = 2 * (REQUEST.len() + manual_tl::Message::SIZE_OVERHEAD);
+        let mut mtproto = Encrypted::build()
+            .container_limits(max_size, 100)
+            .finish(auth_key());
+
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        assert!(mtproto.push(&mut buffer, REQUEST).is_some());
+        assert!(mtproto.push(&mut buffer, REQUEST).is_none());
+        mtproto.finalize_plain(&mut buffer);
+
+        // Requests larger than the limit can still be sent on their own.
+        let large = vec![0; max_size];
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        assert!(mtproto.push(&mut buffer, &large).is_some());
+        assert!(mtproto.push(&mut buffer, REQUEST).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn ensure_container_limits_are_capped() {
+        Encrypted::build().container_limits(manual_tl::MessageContainer::MAXIMUM_SIZE, 101);
+    }
+
+    #[test]
+    fn ensure_duplicate_responses_are_dropped() {
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let payload = encrypt_as_server(
+            &server_message(
+                mtproto.client_id,
+                0x5e0b_8000_0000_0001,
+                1,
+                &rpc_result_body(4),
+            ),
+            &auth_key(),
+        );
+
+        let first = mtproto.deserialize(&payload).unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(matches!(
+            &first[0],
+            Deserialization::RpcResult(RpcResult {
+                msg_id: MsgId(4),
+                ..
+            })
+        ));
+
+        // The duplicate is still acknowledged, but no response is produced for it.
+        let second = mtproto.deserialize(&payload).unwrap();
+        assert!(second.is_empty());
+        assert_eq!(
+            mtproto.pending_ack,
+            vec![0x5e0b_8000_0000_0001, 0x5e0b_8000_0000_0001]
+        );
+    }
+
+    #[test]
+    fn ensure_pending_acks_are_flushed_without_requests() {
+        let mut mtproto = Encrypted::build()
+            .ack_flush_limits(2, Duration::from_secs(60))
+            .finish(auth_key());
+        let receive = |mtproto: &mut Encrypted, msg_id| {
+            let payload = encrypt_as_server(
+                &server_message(mtproto.client_id, msg_id, 1, &rpc_result_body(4)),
+                &auth_key(),
+            );
+            mtproto.deserialize(&payload).unwrap();
+        };
+        let ack_body = |msg_ids| tl::enums::MsgsAck::Ack(tl::types::MsgsAck { msg_ids }).to_bytes();
+
+        // A single ack can wait for a request to be sent, or for some time to pass.
+        receive(&mut mtproto, 0x5e0b_8000_0000_0001);
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        mtproto.finalize_plain(&mut buffer);
+        assert!(buffer.is_empty());
+        let deadline = mtproto.ack_flush_deadline().unwrap();
+        assert!(!mtproto.should_flush_acks(deadline - Duration::from_millis(1)));
+        assert!(mtproto.should_flush_acks(deadline));
+
+        // But once enough accumulate, they're sent on their own.
+        receive(&mut mtproto, 0x5e0b_8000_0000_0005);
+        assert!(mtproto.should_flush_acks(Instant::now()));
+        mtproto.finalize_plain(&mut buffer);
+        ensure_buffer_is_message(
+            &buffer[MESSAGE_PREFIX_LEN..],
+            &ack_body(vec![0x5e0b_8000_0000_0001, 0x5e0b_8000_0000_0005]),
+            0,
+        );
+        assert!(mtproto.ack_flush_deadline().is_none());
+        assert!(!mtproto.should_flush_acks(Instant::now()));
+    }
+
+    #[test]
+    fn ensure_missing_answers_are_requested_again() {
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let mut deserialize = |msg_id, body: &[u8]| {
+            let payload = encrypt_as_server(
+                &server_message(mtproto.client_id, msg_id, 2, body),
+                &auth_key(),
+            );
+            mtproto.deserialize(&payload).unwrap();
+        };
+
+        // One answer was received, while the other one never arrived.
+        deserialize(0x5e0b_8000_0000_0001, &rpc_result_body(4));
+        for (msg_id, answer_msg_id) in [
+            (0x5e0b_8000_0000_0009, 0x5e0b_8000_0000_0001),
+            (0x5e0b_8000_0000_000d, 0x5e0b_8000_0000_0005),
+        ] {
+            let info =
+                tl::enums::MsgDetailedInfo::MsgNewDetailedInfo(tl::types::MsgNewDetailedInfo {
+                    answer_msg_id,
+                    bytes: 16,
+                    status: 0,
+                });
+            deserialize(msg_id, &info.to_bytes());
+        }
+
+        assert_eq!(mtproto.pending_ack, vec![0x5e0b_8000_0000_0001]);
+        assert_eq!(mtproto.pending_resend, vec![0x5e0b_8000_0000_0005]);
+
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        mtproto.push(&mut buffer, REQUEST).unwrap();
+        let resend = tl::enums::MsgResendReq::Req(tl::types::MsgResendReq {
+            msg_ids: vec![0x5e0b_8000_0000_0005],
+        })
+        .to_bytes();
+        assert!(buffer[..]
+            .windows(resend.len())
+            .any(|window| window == resend));
+        assert!(mtproto.pending_resend.is_empty());
+    }
+
+    #[test]
+    fn ensure_missing_answers_are_requested_without_requests() {
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let info = tl::enums::MsgDetailedInfo::MsgNewDetailedInfo(tl::types::MsgNewDetailedInfo {
+            answer_msg_id: 0x5e0b_8000_0000_0005,
+            bytes: 16,
+            status: 0,
+        });
+        let payload = encrypt_as_server(
+            &server_message(
+                mtproto.client_id,
+                0x5e0b_8000_0000_0001,
+                2,
+                &info.to_bytes(),
+            ),
+            &auth_key(),
+        );
+        mtproto.deserialize(&payload).unwrap();
+
+        // Nothing else is sent, but the request to re-send the answer can't wait.
+        assert!(mtproto.ack_flush_deadline().unwrap() <= Instant::now());
+        assert!(mtproto.should_flush_acks(Instant::now()));
+
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        mtproto.finalize_plain(&mut buffer);
+        let resend = tl::enums::MsgResendReq::Req(tl::types::MsgResendReq {
+            msg_ids: vec![0x5e0b_8000_0000_0005],
+        })
+        .to_bytes();
+        assert!(buffer[..]
+            .windows(resend.len())
+            .any(|window| window == resend));
+        assert!(mtproto.pending_resend.is_empty());
+        assert!(mtproto.ack_flush_deadline().is_none());
+    }
+
+    #[test]
+    fn ensure_time_offset_is_synced_from_first_message() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let server_msg_id = |offset: i64| ((now + offset) << 32) | 1;
+
+        let mut mtproto = Encrypted::build().time_offset(10).finish(auth_key());
+        assert_eq!(mtproto.time_offset(), 10);
+
+        // The server's clock is an hour ahead of ours.
+        let payload = encrypt_as_server(
+            &server_message(
+                mtproto.client_id,
+                server_msg_id(3600),
+                2,
+                &rpc_result_body(4),
+            ),
+            &auth_key(),
+        );
+        mtproto.deserialize(&payload).unwrap();
+        assert!((3599..=3601).contains(&mtproto.time_offset()));
+        let msg_id_time = mtproto.get_new_msg_id() >> 32;
+        assert!((now + 3599..=now + 3602).contains(&msg_id_time));
+
+        // Only the first message is used to synchronize the time.
+        let payload = encrypt_as_server(
+            &server_message(
+                mtproto.client_id,
+                server_msg_id(-3600),
+                4,
+                &rpc_result_body(8),
+            ),
+            &auth_key(),
+        );
+        mtproto.deserialize(&payload).unwrap();
+        assert!((3599..=3601).contains(&mtproto.time_offset()));
+    }
+
+    #[test]
+    fn ensure_wrong_session_id_is_an_error() {
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let client_id = mtproto.client_id.wrapping_add(1);
+        let payload = encrypt_as_server(
+            &server_message(client_id, 0x5e0b_8000_0000_0001, 1, &rpc_result_body(4)),
+            &auth_key(),
+        );
+
+        let expected = mtproto.client_id;
+        assert!(matches!(
+            mtproto.deserialize(&payload),
+            Err(DeserializeError::SessionIdMismatch { got, expected: e })
+                if got == client_id && e == expected
+        ));
+        assert!(mtproto.pending_ack.is_empty());
+    }
+
+    #[test]
+    fn ensure_updates_are_dispatched() {
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let body = tl::enums::Updates::TooLong.to_bytes();
+        let payload = encrypt_as_server(
+            &server_message(mtproto.client_id, 0x5e0b_8000_0000_0001, 1, &body),
+            &auth_key(),
+        );
+
+        let result = mtproto.deserialize(&payload).unwrap();
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Deserialization::Update(update) => assert_eq!(update, &body),
+            _ => panic!("updates should not be returned as a response"),
+        }
+    }
+
+    #[test]
+    fn ensure_acks_are_reported() {
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let first_msg_id = mtproto.push(&mut buffer, REQUEST).unwrap();
+        let second_msg_id = mtproto.push(&mut buffer, REQUEST_B).unwrap();
+        mtproto.finalize_plain(&mut buffer);
+
+        let body = tl::enums::MsgsAck::Ack(tl::types::MsgsAck {
+            msg_ids: vec![first_msg_id.0, second_msg_id.0],
+        })
+        .to_bytes();
+        let payload = encrypt_as_server(
+            &server_message(mtproto.client_id, 0x5e0b_8000_0000_0001, 0, &body),
+            &auth_key(),
+        );
+
+        // It's up to the sender to decide what to do with requests that were only acknowledged.
+        let result = mtproto.deserialize(&payload).unwrap();
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Deserialization::Ack(msg_ids) => {
+                assert_eq!(msg_ids, &vec![first_msg_id, second_msg_id])
+            }
+            _ => panic!("acknowledgement should be returned"),
+        }
+        assert!(mtproto.pending_ack.is_empty());
+    }
+
+    #[test]
+    fn ensure_bad_seq_no_starts_new_session() {
+        // Re-sending the requests sent under the old session is up to the sender.
+        for error_code in [32, 33] {
+            let mut buffer = DequeBuffer::with_capacity(0, 0);
+            let mut mtproto = Encrypted::build().finish(auth_key());
+            let old_client_id = mtproto.client_id;
+
+            let msg_id = mtproto.push(&mut buffer, REQUEST).unwrap();
+            mtproto.finalize_plain(&mut buffer);
+            assert_ne!(mtproto.sequence, 0);
+
+            let body = tl::enums::BadMsgNotification::Notification(tl::types::BadMsgNotification {
+                bad_msg_id: msg_id.0,
+                bad_msg_seqno: 1,
+                error_code,
+            })
+            .to_bytes();
+            let payload = encrypt_as_server(
+                &server_message(old_client_id, 0x5e0b_8000_0000_0001, 0, &body),
+                &auth_key(),
+            );
+
+            let result = mtproto.deserialize(&payload).unwrap();
+            assert_eq!(result.len(), 1);
+            match &result[0] {
+                Deserialization::BadMessage(bad_msg) => {
+                    assert_eq!(bad_msg.msg_id, msg_id);
+                    assert!(bad_msg.retryable());
+                    assert!(bad_msg.session_reset());
+                }
+                _ => panic!("bad message should be returned"),
+            }
+
+            assert_ne!(mtproto.client_id, old_client_id);
+            assert_eq!(mtproto.sequence, 0);
+            assert_eq!(mtproto.last_msg_id, 0);
+        }
+    }
+
+    #[test]
+    fn ensure_reset_keeps_auth_key() {
+        let mut buffer = DequeBuffer::with_capacity(0, 0);
+        let mut mtproto = Encrypted::build().finish(auth_key());
+        let old_client_id = mtproto.client_id;
+
+        mtproto.push(&mut buffer, REQUEST).unwrap();
+        mtproto.finalize(&mut buffer);
+        let payload = encrypt_as_server(
+            &server_message(old_client_id, 0x5e0b_8000_0000_0001, 1, &rpc_result_body(4)),
+            &auth_key(),
+        );
+        mtproto.deserialize(&payload).unwrap();
+        assert_ne!(mtproto.sequence, 0);
+        assert_ne!(mtproto.last_msg_id, 0);
+        assert!(!mtproto.pending_ack.is_empty());
+
+        mtproto.reset();
+        assert_ne!(mtproto.client_id, old_client_id);
+        assert_eq!(mtproto.sequence, 0);
+        assert_eq!(mtproto.last_msg_id, 0);
+        assert!(mtproto.pending_ack.is_empty());
+        assert!(mtproto.recent_msg_ids.is_empty());
+        assert_eq!(mtproto.auth_key(), auth_key());
+    }
+
+    #[test]
+    fn ensure_random_source_is_honored() {
+        let mut buffers = Vec::new();
+        let mut client_ids = Vec::new();
+        for _ in 0..2 {
+            let mut buffer = DequeBuffer::with_capacity(0, 0);
+            let mut mtproto = Encrypted::build()
+                .random_source(SeededRandom(0x1234_5678))
+                .finish(auth_key());
+
+            mtproto.push(&mut buffer, REQUEST);
+            mtproto.finalize(&mut buffer);
+            client_ids.push(mtproto.client_id);
+            buffers.push(decrypt_as_server(&buffer[..], &auth_key()));
+        }
+
+        assert_eq!(client_ids[0], client_ids[1]);
+        assert_eq!(&buffers[0][8..16], client_ids[0].to_le_bytes());
+
+        // salt, client_id, msg_id, seq_no, len, body; the rest is padding.
+        let padding_start = 8 + 8 + 8 + 4 + 4 + REQUEST.len();
+        assert_eq!(buffers[0].len(), buffers[1].len());
+        assert_eq!(&buffers[0][padding_start..], &buffers[1][padding_start..]);
+    }
+
     #[test]
     fn ensure_no_compression_is_honored() {
         // A large vector of null bytes should compress
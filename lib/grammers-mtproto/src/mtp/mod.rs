@@ -29,6 +29,7 @@ use grammers_crypto as crypto;
 use grammers_tl_types as tl;
 pub use plain::Plain;
 use std::fmt;
+use web_time::Instant;
 
 pub struct RpcResult {
     pub msg_id: MsgId,
@@ -57,6 +58,8 @@ pub enum Deserialization {
     RpcError(RpcResultError),
     BadMessage(BadMessage),
     Failure(DeserializationFailure),
+    /// The server received the messages with these identifiers, but may not have answered yet.
+    Ack(Vec<MsgId>),
 }
 
 impl BadMessage {
@@ -79,11 +82,17 @@ impl BadMessage {
     }
 
     pub fn retryable(&self) -> bool {
-        [16, 17, 48].contains(&self.code)
+        [16, 17, 32, 33, 48].contains(&self.code)
+    }
+
+    /// Whether this error caused a new session to be started, in which case any request sent
+    /// under the previous session should be re-sent.
+    pub fn session_reset(&self) -> bool {
+        [32, 33].contains(&self.code)
     }
 
     pub fn fatal(&self) -> bool {
-        !self.retryable() && ![32, 33].contains(&self.code)
+        !self.retryable()
     }
 }
 
@@ -96,6 +105,9 @@ pub enum DeserializeError {
     /// The server's message ID did not match our expectations.
     BadMessageId { got: i64 },
 
+    /// The server's message was meant for a different session than ours.
+    SessionIdMismatch { got: i64, expected: i64 },
+
     /// The server's message length was not strictly positive.
     NegativeMessageLength { got: i32 },
 
@@ -128,6 +140,9 @@ impl fmt::Display for DeserializeError {
                 write!(f, "bad server auth key (got {got}, expected {expected})")
             }
             Self::BadMessageId { got } => write!(f, "bad server message id (got {got})"),
+            Self::SessionIdMismatch { got, expected } => {
+                write!(f, "wrong session id (got {got}, expected {expected})")
+            }
             Self::NegativeMessageLength { got } => {
                 write!(f, "bad server message length (got {got})")
             }
@@ -190,6 +205,37 @@ pub trait Mtp {
     /// payload is below that mark, it's safe to call.
     fn push(&mut self, buffer: &mut DequeBuffer<u8>, request: &[u8]) -> Option<MsgId>;
 
+    /// Like [`Mtp::push`], but the request is serialized as a message that is not
+    /// [content-related], such as acknowledgements, which the server does not respond to.
+    ///
+    /// Such messages use an even sequence number, while content-related ones use an odd one.
+    /// Using the wrong kind of message causes the server to report a bad sequence number.
+    ///
+    /// By default, the request is pushed as-is, for protocols without sequence numbers.
+    ///
+    /// [content-related]: https://core.telegram.org/mtproto/description#content-related-message
+    fn push_service(&mut self, buffer: &mut DequeBuffer<u8>, request: &[u8]) -> Option<MsgId> {
+        self.push(buffer, request)
+    }
+
+    /// Whether acknowledgements of the messages received have been pending for long enough, or
+    /// accumulated in large enough numbers, that [`Mtp::finalize`] will send them even if no
+    /// request was pushed. This is also the case if messages which never arrived need to be
+    /// requested again.
+    ///
+    /// By default, there are never acknowledgements to send.
+    fn should_flush_acks(&self, _now: Instant) -> bool {
+        false
+    }
+
+    /// When the pending acknowledgements (or requests to re-send messages) will need to be sent,
+    /// if nothing else is sent before.
+    ///
+    /// Senders should call [`Mtp::finalize`] at this time, even if there are no requests.
+    fn ack_flush_deadline(&self) -> Option<Instant> {
+        None
+    }
+
     /// Finalizes the buffer of requests.
     ///
     /// Note that even if there are no requests to serialize, the protocol may
@@ -205,5 +251,9 @@ pub trait Mtp {
     fn deserialize(&mut self, payload: &[u8]) -> Result<Vec<Deserialization>, DeserializeError>;
 
     /// Reset the state, as if a new instance was just created.
+    ///
+    /// This starts a new session (with a new client identifier, sequence number and message
+    /// identifiers), which is needed after unrecoverable sequence errors, but the authorization
+    /// key and the known salts are kept, so there is no need to generate a new one.
     fn reset(&mut self);
 }
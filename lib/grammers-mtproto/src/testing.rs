@@ -0,0 +1,137 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers to play the server's side of an encrypted connection in tests.
+//!
+//! Only available with the `test-util` feature.
+use crate::manual_tl;
+use grammers_crypto::{sha1, sha256};
+use grammers_tl_types::{Deserializable, Identifiable, Serializable};
+
+/// Calculate the AES key and IV, where `x` is 0 for client messages and 8 for server ones.
+fn calc_key(auth_key: &[u8; 256], msg_key: &[u8], x: usize) -> ([u8; 32], [u8; 32]) {
+    let sha256_a = sha256!(msg_key, &auth_key[x..x + 36]);
+    let sha256_b = sha256!(&auth_key[40 + x..40 + x + 36], msg_key);
+
+    let mut key = [0; 32];
+    key[0..8].copy_from_slice(&sha256_a[0..8]);
+    key[8..24].copy_from_slice(&sha256_b[8..24]);
+    key[24..32].copy_from_slice(&sha256_a[24..32]);
+    let mut iv = [0; 32];
+    iv[0..8].copy_from_slice(&sha256_b[0..8]);
+    iv[8..24].copy_from_slice(&sha256_a[8..24]);
+    iv[24..32].copy_from_slice(&sha256_b[24..32]);
+    (key, iv)
+}
+
+/// Encrypt the plaintext as the server would, so that it can be fed to
+/// [`Mtp::deserialize`](crate::mtp::Mtp::deserialize).
+pub fn encrypt_as_server(plaintext: &[u8], auth_key: &[u8; 256]) -> Vec<u8> {
+    let mut buffer = plaintext.to_vec();
+    buffer.extend((0..16 + (16 - (plaintext.len() % 16))).map(|_| 0));
+
+    let msg_key_large = sha256!(&auth_key[88 + 8..88 + 8 + 32], &buffer);
+    let msg_key = &msg_key_large[8..8 + 16];
+    let (key, iv) = calc_key(auth_key, msg_key, 8);
+    grammers_crypto::aes::ige_encrypt(&mut buffer, &key, &iv);
+
+    let mut ciphertext = auth_key_id(auth_key).to_vec();
+    ciphertext.extend(msg_key);
+    ciphertext.extend(buffer);
+    ciphertext
+}
+
+/// Decrypt the ciphertext produced by the client, as the server would.
+///
+/// The plaintext starts with the salt and the client's session identifier, followed by the
+/// first message (or container) sent.
+pub fn decrypt_as_server(ciphertext: &[u8], auth_key: &[u8; 256]) -> Vec<u8> {
+    let (key, iv) = calc_key(auth_key, &ciphertext[8..24], 0);
+    grammers_crypto::aes::ige_decrypt(&ciphertext[24..], &key, &iv)
+}
+
+/// The identifier the client sends along every packet encrypted with the given key.
+pub fn auth_key_id(auth_key: &[u8; 256]) -> [u8; 8] {
+    sha1!(auth_key)[12..12 + 8].try_into().unwrap()
+}
+
+/// A message sent by the client, as found in the decrypted plaintext.
+pub struct ClientMessage {
+    pub msg_id: i64,
+    pub seq_no: i32,
+    pub body: Vec<u8>,
+}
+
+impl ClientMessage {
+    /// Whether the message is content-related, and so the client expects an answer to it.
+    pub fn is_content_related(&self) -> bool {
+        self.seq_no % 2 == 1
+    }
+}
+
+/// Parse the plaintext decrypted by [`decrypt_as_server`], returning the client's session
+/// identifier and every message in it, after unpacking containers and compressed bodies.
+pub fn client_messages(plaintext: &[u8]) -> (i64, Vec<ClientMessage>) {
+    let client_id = i64::from_le_bytes(plaintext[8..16].try_into().unwrap());
+    let mut cursor = grammers_tl_types::Cursor::from_slice(&plaintext[16..]);
+    let message = manual_tl::Message::deserialize(&mut cursor).unwrap();
+    let messages = match message.constructor_id() {
+        Ok(manual_tl::MessageContainer::CONSTRUCTOR_ID) => {
+            manual_tl::MessageContainer::from_bytes(&message.body)
+                .unwrap()
+                .messages
+        }
+        _ => vec![message],
+    };
+
+    let messages = messages
+        .into_iter()
+        .map(|message| ClientMessage {
+            msg_id: message.msg_id,
+            seq_no: message.seq_no,
+            body: match message.constructor_id() {
+                Ok(manual_tl::GzipPacked::CONSTRUCTOR_ID) => {
+                    manual_tl::GzipPacked::from_bytes(&message.body)
+                        .unwrap()
+                        .decompress()
+                        .unwrap()
+                }
+                _ => message.body,
+            },
+        })
+        .collect();
+    (client_id, messages)
+}
+
+/// Serialize a single message from the server to the given client, ready to encrypt.
+pub fn server_message(client_id: i64, msg_id: i64, seq_no: i32, body: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    0i64.serialize(&mut buffer); // salt
+    client_id.serialize(&mut buffer);
+    manual_tl::Message {
+        msg_id,
+        seq_no,
+        body: body.to_vec(),
+    }
+    .serialize(&mut buffer);
+    buffer
+}
+
+/// Serialize an `rpc_result` with a `boolTrue` result for the given request.
+pub fn rpc_result_body(req_msg_id: i64) -> Vec<u8> {
+    rpc_result_raw_body(req_msg_id, &true.to_bytes())
+}
+
+/// Serialize an `rpc_result` with the given serialized result for the given request.
+pub fn rpc_result_raw_body(req_msg_id: i64, result: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    manual_tl::RpcResult::CONSTRUCTOR_ID.serialize(&mut buffer);
+    req_msg_id.serialize(&mut buffer);
+    buffer.extend(result);
+    buffer
+}
@@ -84,6 +84,49 @@ pub struct RpcError {
     pub caused_by: Option<u32>,
 }
 
+/// The broad category of a [`RpcError`], determined by its code and name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcErrorCategory {
+    /// Too many requests were made, and some time must pass before retrying.
+    /// See [`RpcError::flood_wait`].
+    Flood,
+
+    /// The request must be repeated in a different datacenter.
+    /// See [`RpcError::migrate_dc`].
+    Migrate,
+
+    /// The request was malformed or contained invalid parameters.
+    BadRequest,
+
+    /// The request requires an authorization which is missing or no longer valid.
+    Unauthorized,
+
+    /// The server failed to process the request, and it may be retried later.
+    Internal,
+
+    /// Any other error.
+    Other,
+}
+
+/// What must be moved to a different datacenter, according to a `*_MIGRATE` error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrateKind {
+    /// `PHONE_MIGRATE`: the phone number belongs to a different datacenter.
+    Phone,
+
+    /// `FILE_MIGRATE`: the file is stored in a different datacenter.
+    File,
+
+    /// `NETWORK_MIGRATE`: the user's network should use a different datacenter.
+    Network,
+
+    /// `USER_MIGRATE`: the account belongs to a different datacenter.
+    User,
+
+    /// `STATS_MIGRATE`: the statistics are stored in a different datacenter.
+    Stats,
+}
+
 impl std::error::Error for RpcError {}
 
 impl fmt::Display for RpcError {
@@ -156,6 +199,53 @@ impl RpcError {
         }
     }
 
+    /// If this is a flood error, returns how many seconds must pass before retrying.
+    ///
+    /// This includes `FLOOD_WAIT` as well as similar errors, such as `SLOWMODE_WAIT`.
+    pub fn flood_wait(&self) -> Option<u32> {
+        if self.code == 420 {
+            self.value
+        } else {
+            None
+        }
+    }
+
+    /// If this is a migration error, returns what must migrate and to which datacenter.
+    pub fn migrate_dc(&self) -> Option<(MigrateKind, i32)> {
+        let kind = match self.name.as_str() {
+            "PHONE_MIGRATE" => MigrateKind::Phone,
+            "FILE_MIGRATE" => MigrateKind::File,
+            "NETWORK_MIGRATE" => MigrateKind::Network,
+            "USER_MIGRATE" => MigrateKind::User,
+            "STATS_MIGRATE" => MigrateKind::Stats,
+            _ => return None,
+        };
+        self.value.map(|dc_id| (kind, dc_id as i32))
+    }
+
+    /// The broad category of this error.
+    pub fn category(&self) -> RpcErrorCategory {
+        if self.flood_wait().is_some() {
+            return RpcErrorCategory::Flood;
+        }
+        if self.migrate_dc().is_some() {
+            return RpcErrorCategory::Migrate;
+        }
+        match self.code {
+            400 => RpcErrorCategory::BadRequest,
+            401 => RpcErrorCategory::Unauthorized,
+            // Telegram uses `-503` for timeouts and may use other negative codes internally.
+            500..=599 | -503 | -500 => RpcErrorCategory::Internal,
+            _ => RpcErrorCategory::Other,
+        }
+    }
+
+    /// Whether the server failed to process the request, such as on internal errors and
+    /// timeouts, meaning the same request may succeed if it's retried later.
+    pub fn is_transient(&self) -> bool {
+        self.category() == RpcErrorCategory::Internal
+    }
+
     pub fn with_caused_by(mut self, constructor_id: u32) -> Self {
         self.caused_by = Some(constructor_id);
         self
@@ -166,7 +256,10 @@ impl RpcError {
 ///
 /// The request should be retransmited when this happens, unless the
 /// variant is `InvalidParameters`.
+///
+/// More variants may be added in the future, so matching on this error must handle the rest.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum InvocationError {
     /// The request invocation failed because it was invalid or the server
     /// could not process it successfully.
@@ -177,6 +270,26 @@ pub enum InvocationError {
 
     /// The error occured while reading the response.
     Read(ReadError),
+
+    /// The request was not sent because the sender had too many requests in flight.
+    ///
+    /// Only occurs if the sender's [`ConnectionParams`](crate::ConnectionParams) are set to not
+    /// wait for capacity.
+    WouldBlock,
+
+    /// The authorization key of the logged-in account is no longer registered, most likely
+    /// because the session was terminated from another device, and the account is now logged out.
+    ///
+    /// The key has been removed from the session, so the user must sign in again.
+    AuthKeyInvalid,
+
+    /// A file part downloaded from a CDN datacenter did not match the hashes provided by Telegram,
+    /// so the download should not continue.
+    CdnHashMismatch,
+
+    /// A file part was still missing from the CDN datacenter after asking Telegram to reupload it
+    /// several times, so the download gave up.
+    CdnReuploadLimit,
 }
 
 impl std::error::Error for InvocationError {}
@@ -187,6 +300,14 @@ impl fmt::Display for InvocationError {
             Self::Rpc(err) => write!(f, "request error: {err}"),
             Self::Dropped => write!(f, "request error: dropped (cancelled)"),
             Self::Read(err) => write!(f, "request error: {err}"),
+            Self::WouldBlock => write!(f, "request error: too many requests in flight"),
+            Self::AuthKeyInvalid => {
+                write!(f, "request error: authorization key is no longer valid")
+            }
+            Self::CdnHashMismatch => write!(f, "request error: cdn file part hash mismatch"),
+            Self::CdnReuploadLimit => {
+                write!(f, "request error: cdn file part missing after reuploading")
+            }
         }
     }
 }
@@ -236,6 +357,18 @@ impl InvocationError {
             _ => false,
         }
     }
+
+    /// Whether this is a transient server error, as opposed to an error caused by the request.
+    ///
+    /// See [`RpcError::is_transient`]. If the error is not a RPC error, returns `false`, as
+    /// network errors are instead handled by the reconnection policy.
+    #[inline]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Rpc(rpc) => rpc.is_transient(),
+            _ => false,
+        }
+    }
 }
 
 /// This error occurs when the process to generate an authorization key fails.
@@ -324,4 +457,59 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn check_rpc_error_categories() {
+        let error = |error_code, error_message: &str| {
+            RpcError::from(tl::types::RpcError {
+                error_code,
+                error_message: error_message.into(),
+            })
+        };
+
+        let flood = error(420, "FLOOD_WAIT_42");
+        assert_eq!(flood.category(), RpcErrorCategory::Flood);
+        assert_eq!(flood.flood_wait(), Some(42));
+        assert_eq!(flood.migrate_dc(), None);
+
+        let migrate = error(303, "PHONE_MIGRATE_4");
+        assert_eq!(migrate.category(), RpcErrorCategory::Migrate);
+        assert_eq!(migrate.flood_wait(), None);
+        assert_eq!(migrate.migrate_dc(), Some((MigrateKind::Phone, 4)));
+
+        let private = error(400, "CHANNEL_PRIVATE");
+        assert_eq!(private.category(), RpcErrorCategory::BadRequest);
+        assert_eq!(private.flood_wait(), None);
+        assert_eq!(private.migrate_dc(), None);
+
+        assert_eq!(
+            error(401, "AUTH_KEY_UNREGISTERED").category(),
+            RpcErrorCategory::Unauthorized
+        );
+        assert_eq!(
+            error(500, "INTERDC_2_CALL_ERROR").category(),
+            RpcErrorCategory::Internal
+        );
+        assert_eq!(
+            error(403, "CHAT_WRITE_FORBIDDEN").category(),
+            RpcErrorCategory::Other
+        );
+    }
+
+    #[test]
+    fn check_transient_errors() {
+        let error = |error_code, error_message: &str| {
+            InvocationError::Rpc(RpcError::from(tl::types::RpcError {
+                error_code,
+                error_message: error_message.into(),
+            }))
+        };
+
+        assert!(error(500, "INTERNAL").is_transient());
+        assert!(error(503, "TIMEOUT").is_transient());
+        assert!(error(-503, "TIMEOUT").is_transient());
+        assert!(!error(400, "BAD_REQUEST").is_transient());
+        assert!(!error(420, "FLOOD_WAIT_3").is_transient());
+        assert!(!InvocationError::Dropped.is_transient());
+    }
 }
@@ -9,14 +9,22 @@
 #![deny(unsafe_code)]
 
 mod errors;
+#[cfg(feature = "test-util")]
+pub mod mock;
 mod net;
 mod reconnection;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod resolver;
 pub mod utils;
 
 pub use crate::reconnection::*;
-pub use errors::{AuthorizationError, InvocationError, ReadError, RpcError};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use crate::resolver::{ResolveFuture, Resolver, SystemResolver};
+pub use errors::{
+    AuthorizationError, InvocationError, MigrateKind, ReadError, RpcError, RpcErrorCategory,
+};
 use futures_util::future::{pending, select, Either};
-use grammers_crypto::DequeBuffer;
+use grammers_crypto::{rsa, DequeBuffer};
 use grammers_mtproto::mtp::{
     self, BadMessage, Deserialization, DeserializationFailure, Mtp, RpcResult, RpcResultError,
 };
@@ -26,6 +34,7 @@ use grammers_tl_types::{self as tl, Deserializable, RemoteCall};
 use log::{debug, error, info, trace, warn};
 use net::NetStream;
 pub use net::ServerAddr;
+use std::fmt;
 use std::io;
 use std::io::Error;
 use std::ops::ControlFlow;
@@ -37,6 +46,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::watch;
 use utils::{sleep, sleep_until};
 use web_time::{Instant, SystemTime};
 
@@ -69,6 +79,10 @@ const PING_DELAY: Duration = Duration::from_secs(60);
 /// are getting through consistently enough.
 const NO_PING_DISCONNECT: i32 = 75;
 
+/// How long to wait for the answer to an acknowledged request before using the result it was
+/// enqueued with instead (see [`Enqueuer::enqueue_with_ack_result`]).
+const ACKED_ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Generate a "random" ping ID.
 pub(crate) fn generate_random_id() -> i64 {
     static LAST_ID: AtomicI64 = AtomicI64::new(0);
@@ -97,17 +111,82 @@ pub struct Sender<T: Transport, M: Mtp> {
     request_rx: mpsc::UnboundedReceiver<Request>,
     next_ping: Instant,
     reconnection_policy: &'static dyn ReconnectionPolicy,
+    state: watch::Sender<ConnectionState>,
+    params: ConnectionParams,
 
     // Transport-level buffers and positions
     read_buffer: Vec<u8>,
     read_tail: usize,
     write_buffer: DequeBuffer<u8>,
     write_head: usize,
+    traffic: TrafficStats,
+}
+
+/// Amount of data transferred by a [`Sender`] since it was created.
+///
+/// The counters include the overhead of the transport, and keep increasing across reconnections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// Total bytes written to the network.
+    pub sent: usize,
+    /// Total bytes read from the network.
+    pub received: usize,
+    /// How many transport packets were sent.
+    pub packets_sent: usize,
+    /// How many transport packets were received.
+    pub packets_received: usize,
+}
+
+/// Parameters controlling how a [`Sender`] connects and how many requests it handles at once.
+#[derive(Clone, Copy)]
+pub struct ConnectionParams {
+    /// The maximum amount of requests which may be in flight (sent, but not answered yet) at
+    /// once. Requests enqueued while the limit is reached are not admitted by the sender.
+    ///
+    /// By default, there is no limit.
+    pub max_in_flight: Option<usize>,
+    /// Whether requests enqueued while the limit is reached should wait until an in-flight
+    /// request completes. If `false`, they fail with [`InvocationError::WouldBlock`] instead.
+    ///
+    /// By default, requests wait for capacity.
+    pub wait_for_capacity: bool,
+    /// The resolver used when a hostname must be resolved to connect, such as the host of a
+    /// proxy. It's used both for the first connection and when reconnecting.
+    ///
+    /// By default, the [`SystemResolver`] is used.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub resolver: &'static dyn Resolver,
+}
+
+impl Default for ConnectionParams {
+    fn default() -> Self {
+        Self {
+            max_in_flight: None,
+            wait_for_capacity: true,
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            resolver: &SystemResolver,
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionParams")
+            .field("max_in_flight", &self.max_in_flight)
+            .field("wait_for_capacity", &self.wait_for_capacity)
+            .finish_non_exhaustive()
+    }
 }
 
 struct Request {
     body: Vec<u8>,
     state: RequestState,
+    /// Whether the server will respond to the request. If not, it completes once it's sent.
+    content_related: bool,
+    /// The result to use if the server acknowledges the request but its answer never arrives.
+    ack_result: Option<Vec<u8>>,
+    /// When the server acknowledged the request, if it has been sent and acknowledged.
+    acked_at: Option<Instant>,
     result: oneshot::Sender<Result<Vec<u8>, InvocationError>>,
 }
 
@@ -136,12 +215,53 @@ impl MsgIdPair {
 
 impl Enqueuer {
     /// Enqueue a Remote Procedure Call to be sent in future calls to `step`.
+    ///
+    /// If the [`Sender`] has reached its [`ConnectionParams::max_in_flight`], the result will
+    /// only arrive after the request is admitted, or fail with [`InvocationError::WouldBlock`].
     pub fn enqueue<R: RemoteCall>(
         &self,
         request: &R,
     ) -> oneshot::Receiver<Result<Vec<u8>, InvocationError>> {
-        // TODO we probably want a bound here (to not enqueue more than N at once)
-        let body = request.to_bytes();
+        self.enqueue_body(request.to_bytes(), true, None)
+    }
+
+    /// Like [`Enqueuer::enqueue`], but if the server acknowledges the request without answering
+    /// it, `ack_result` is used as its result once the connection is reset or the answer takes
+    /// too long to arrive.
+    ///
+    /// This is needed for requests like `auth.logOut`, which the server may only acknowledge
+    /// before closing the connection. The actual answer is used instead if it does arrive.
+    pub fn enqueue_with_ack_result<R: RemoteCall>(
+        &self,
+        request: &R,
+        ack_result: &R::Return,
+    ) -> oneshot::Receiver<Result<Vec<u8>, InvocationError>>
+    where
+        R::Return: Serializable,
+    {
+        self.enqueue_body(request.to_bytes(), true, Some(ack_result.to_bytes()))
+    }
+
+    /// Enqueue a service message which is not [content-related], such as an acknowledgement.
+    ///
+    /// The server does not respond to these, so the result arrives, empty, as soon as the
+    /// message is sent. Requests which expect a response must use [`Enqueuer::enqueue`] instead,
+    /// or the server will report a bad sequence number.
+    ///
+    /// [content-related]: https://core.telegram.org/mtproto/description#content-related-message
+    pub fn enqueue_service_request<R: Serializable>(
+        &self,
+        request: &R,
+    ) -> oneshot::Receiver<Result<Vec<u8>, InvocationError>> {
+        self.enqueue_body(request.to_bytes(), false, None)
+    }
+
+    fn enqueue_body(
+        &self,
+        body: Vec<u8>,
+        content_related: bool,
+        ack_result: Option<Vec<u8>>,
+    ) -> oneshot::Receiver<Result<Vec<u8>, InvocationError>> {
         assert!(body.len() >= 4);
         let req_id = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
         debug!(
@@ -153,6 +273,9 @@ impl Enqueuer {
         if let Err(err) = self.0.send(Request {
             body,
             state: RequestState::NotSerialized,
+            content_related,
+            ack_result,
+            acked_at: None,
             result: tx,
         }) {
             err.0.result.send(Err(InvocationError::Dropped)).unwrap();
@@ -166,9 +289,10 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
         transport: T,
         mtp: M,
         addr: ServerAddr,
+        params: ConnectionParams,
         reconnection_policy: &'static dyn ReconnectionPolicy,
     ) -> Result<(Self, Enqueuer), io::Error> {
-        let stream = NetStream::connect(&addr).await?;
+        let stream = NetStream::connect(&addr, &params).await?;
         let (tx, rx) = mpsc::unbounded_channel();
         Ok((
             Self {
@@ -180,16 +304,66 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 request_rx: rx,
                 next_ping: Instant::now() + PING_DELAY,
                 reconnection_policy,
+                state: watch::Sender::new(ConnectionState::Connected),
+                params,
 
                 read_buffer: vec![0; MAXIMUM_DATA],
                 read_tail: 0,
                 write_buffer: DequeBuffer::with_capacity(MAXIMUM_DATA, LEADING_BUFFER_SPACE),
                 write_head: 0,
+                traffic: TrafficStats::default(),
             },
             Enqueuer(tx),
         ))
     }
 
+    /// Subscribe to the state of the connection, which changes as it is lost and reconnected.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// The amount of bytes the buffer used to serialize outgoing packets can hold without
+    /// growing.
+    ///
+    /// The same buffer is reused for every packet sent, so it only grows when a packet larger
+    /// than all previous ones needs to be sent.
+    pub fn write_buffer_capacity(&self) -> usize {
+        self.write_buffer.capacity()
+    }
+
+    /// Ensure the buffer used to serialize outgoing packets can hold at least `additional` more
+    /// bytes, so that it won't need to grow while sending packets of up to that size.
+    pub fn reserve_write_buffer(&mut self, additional: usize) {
+        self.write_buffer.reserve(additional);
+    }
+
+    /// The parameters controlling how the sender connects and how many requests are handled
+    /// at once.
+    pub fn connection_params(&self) -> ConnectionParams {
+        self.params
+    }
+
+    /// Change the parameters controlling how the sender connects and how many requests are
+    /// handled at once.
+    ///
+    /// Requests which are already in flight are not affected by a lower limit. A different
+    /// resolver only takes effect the next time the sender needs to reconnect.
+    pub fn set_connection_params(&mut self, params: ConnectionParams) {
+        self.params = params;
+    }
+
+    /// Whether the limit of requests in flight has been reached.
+    fn is_full(&self) -> bool {
+        self.params
+            .max_in_flight
+            .is_some_and(|max| self.requests.len() >= max)
+    }
+
+    /// The amount of data sent and received so far.
+    pub fn traffic_stats(&self) -> TrafficStats {
+        self.traffic
+    }
+
     pub async fn invoke<R: RemoteCall>(&mut self, request: &R) -> Result<Vec<u8>, InvocationError> {
         let rx = self.enqueue_body(request.to_bytes());
         self.step_until_receive(rx).await
@@ -216,6 +390,9 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
         self.requests.push(Request {
             body,
             state: RequestState::NotSerialized,
+            content_related: true,
+            ack_result: None,
+            acked_at: None,
             result: tx,
         });
         rx
@@ -240,6 +417,10 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
     /// Step network events, writing and reading at the same time.
     ///
     /// Updates received during this step, if any, are returned.
+    ///
+    /// Nothing happens in the background: requests enqueued with an [`Enqueuer`] are only sent,
+    /// and their responses only processed, while this method is being called. It can be called
+    /// in a loop from any task, without the need to spawn a dedicated one.
     pub async fn step(&mut self) -> Result<Vec<tl::enums::Updates>, ReadError> {
         enum Sel {
             Sleep,
@@ -248,6 +429,7 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
             Write(io::Result<usize>),
         }
 
+        self.complete_acked_requests(false);
         self.try_fill_write();
         let write_len = self.write_buffer.len() - self.write_head;
         trace!(
@@ -255,10 +437,29 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
             write_len
         );
 
+        // Requests are left in the channel until there's capacity for them, unless they should
+        // fail right away, which is done once they're received.
+        let admit = !self.is_full() || !self.params.wait_for_capacity;
+        // Pending acknowledgements must be sent in time even if nothing else is. They can only be
+        // flushed with nothing else being written, and if they could, they already were.
+        let mut wake_at = match self.mtp.ack_flush_deadline() {
+            Some(deadline) if self.write_buffer.is_empty() => deadline.min(self.next_ping),
+            _ => self.next_ping,
+        };
+        // Acknowledged requests may need to complete even if nothing else happens.
+        if let Some(deadline) = self.acked_answer_deadline() {
+            wake_at = wake_at.min(deadline);
+        }
         let (mut reader, mut writer) = self.stream.split();
         let sel = {
-            let sleep = pin!(async { sleep_until(self.next_ping).await });
-            let recv_req = pin!(async { self.request_rx.recv().await });
+            let sleep = pin!(async { sleep_until(wake_at).await });
+            let recv_req = pin!(async {
+                if admit {
+                    self.request_rx.recv().await
+                } else {
+                    pending().await
+                }
+            });
             let recv_data =
                 pin!(async { reader.read(&mut self.read_buffer[self.read_tail..]).await });
             let send_data = pin!(async {
@@ -279,7 +480,13 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
 
         let res = match sel {
             Sel::Request(request) => {
-                self.requests.push(request.unwrap());
+                let request = request.unwrap();
+                if self.is_full() {
+                    debug!("too many requests in flight; failing new request");
+                    drop(request.result.send(Err(InvocationError::WouldBlock)));
+                } else {
+                    self.requests.push(request);
+                }
                 Ok(Vec::new())
             }
             Sel::Read(n) => n.map_err(ReadError::Io).and_then(|n| self.on_net_read(n)),
@@ -288,7 +495,10 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 Vec::new()
             }),
             Sel::Sleep => {
-                self.on_ping_timeout();
+                // Otherwise, it was time to flush acknowledgements, done when stepping again.
+                if Instant::now() >= self.next_ping {
+                    self.on_ping_timeout();
+                }
                 Ok(Vec::new())
             }
         };
@@ -303,19 +513,22 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
     async fn try_connect(&mut self) -> Result<(), Error> {
         let mut attempts = 0;
         loop {
-            match NetStream::connect(&self.addr).await {
+            self.state.send_replace(ConnectionState::Reconnecting {
+                attempt: attempts + 1,
+            });
+            match NetStream::connect(&self.addr, &self.params).await {
                 Ok(result) => {
                     log::info!(
                         "auto-reconnect success after {} failed attempt(s)",
                         attempts
                     );
                     self.stream = result;
+                    self.state.send_replace(ConnectionState::Connected);
                     return Ok(());
                 }
                 Err(e) => {
                     attempts += 1;
                     log::warn!("auto-reconnect failed {} time(s): {}", attempts, e);
-                    sleep(Duration::from_secs(1)).await;
 
                     match self.reconnection_policy.should_retry(attempts) {
                         ControlFlow::Break(_) => {
@@ -347,7 +560,12 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
             .filter(|r| matches!(r.state, RequestState::NotSerialized))
         {
             // TODO make mtp itself use BytesMut to avoid copies
-            if let Some(msg_id) = self.mtp.push(&mut self.write_buffer, &request.body) {
+            let msg_id = if request.content_related {
+                self.mtp.push(&mut self.write_buffer, &request.body)
+            } else {
+                self.mtp.push_service(&mut self.write_buffer, &request.body)
+            };
+            if let Some(msg_id) = msg_id {
                 assert!(request.body.len() >= 4);
                 let req_id = u32::from_le_bytes([
                     request.body[0],
@@ -365,6 +583,7 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 // Nasty bugs that take ~2h to find occur otherwise!
                 // (e.g. infinite loops leading to transport flood.)
                 request.state = RequestState::Serialized(MsgIdPair::new(msg_id));
+                request.acked_at = None;
             } else {
                 break;
             }
@@ -379,7 +598,8 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                     RequestState::NotSerialized | RequestState::Sent(..) => {}
                 }
             }
-            self.transport.pack(&mut self.write_buffer)
+            self.transport.pack(&mut self.write_buffer);
+            self.traffic.packets_sent += 1;
         }
     }
 
@@ -395,6 +615,7 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
         }
 
         self.read_tail += n;
+        self.traffic.received += n;
         trace!("read {} bytes from the network", n);
         trace!("trying to unpack buffer of {} bytes...", self.read_tail);
 
@@ -408,10 +629,24 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 .unpack(&mut self.read_buffer[next_offset..self.read_tail])
             {
                 Ok(offset) => {
+                    self.traffic.packets_received += 1;
                     debug!("deserializing valid transport packet...");
-                    let result = self.mtp.deserialize(
+                    let result = match self.mtp.deserialize(
                         &self.read_buffer[next_offset..][offset.data_start..offset.data_end],
-                    )?;
+                    ) {
+                        Ok(result) => result,
+                        // Late responses to a previous session can still arrive after starting
+                        // a new one. Anything they answer has been re-sent, so they're dropped.
+                        Err(mtp::DeserializeError::SessionIdMismatch { got, expected }) => {
+                            warn!(
+                                "ignoring packet for session {} (current is {})",
+                                got, expected
+                            );
+                            next_offset += offset.next_offset;
+                            continue;
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
 
                     self.process_mtp_buffer(result, &mut updates);
                     next_offset += offset.next_offset;
@@ -430,6 +665,7 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
     /// Handle `n` more written bytes being ready to process by the transport.
     fn on_net_write(&mut self, n: usize) {
         self.write_head += n;
+        self.traffic.sent += n;
         trace!(
             "written {} bytes to the network ({}/{})",
             n,
@@ -452,6 +688,14 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 }
             }
         }
+
+        // Nothing will answer the requests that are not content-related, so they're done.
+        for i in (0..self.requests.len()).rev() {
+            let req = &self.requests[i];
+            if !req.content_related && matches!(req.state, RequestState::Sent(_)) {
+                drop(self.requests.remove(i).result.send(Ok(Vec::new())));
+            }
+        }
     }
 
     /// Handle a ping timeout, meaning we need to enqueue a new ping request.
@@ -470,9 +714,38 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
         self.next_ping = Instant::now() + PING_DELAY;
     }
 
+    /// When the earliest acknowledged request without an answer should complete on its own.
+    fn acked_answer_deadline(&self) -> Option<Instant> {
+        self.requests
+            .iter()
+            .filter(|r| r.ack_result.is_some())
+            .filter_map(|r| r.acked_at)
+            .min()
+            .map(|acked_at| acked_at + ACKED_ANSWER_TIMEOUT)
+    }
+
+    /// Complete the acknowledged requests that have waited too long for their answer (or all of
+    /// them, if their answer can no longer arrive) with the result they were enqueued with.
+    fn complete_acked_requests(&mut self, answer_lost: bool) {
+        let now = Instant::now();
+        for i in (0..self.requests.len()).rev() {
+            let req = &self.requests[i];
+            let expired = req
+                .acked_at
+                .is_some_and(|acked_at| answer_lost || now >= acked_at + ACKED_ANSWER_TIMEOUT);
+            if expired && req.ack_result.is_some() {
+                let req = self.requests.swap_remove(i);
+                info!("request was acknowledged but not answered; completing it");
+                drop(req.result.send(Ok(req.ack_result.unwrap())));
+            }
+        }
+    }
+
     /// Handle errors that occured while performing I/O.
     async fn on_error(&mut self, error: ReadError) -> Result<Vec<tl::enums::Updates>, ReadError> {
         log::info!("handling error: {error}");
+        // The answer to acknowledged requests won't arrive through the new connection.
+        self.complete_acked_requests(true);
         self.transport.reset();
         self.mtp.reset();
         log::info!(
@@ -512,6 +785,10 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
             e => e,
         };
 
+        if let ReadError::Io(_) = error {
+            self.state.send_replace(ConnectionState::Disconnected);
+        }
+
         log::warn!(
             "marking all {} request(s) as failed: {}",
             self.requests.len(),
@@ -538,6 +815,7 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 Deserialization::RpcError(error) => self.process_error(error),
                 Deserialization::BadMessage(bad_msg) => self.process_bad_message(bad_msg),
                 Deserialization::Failure(failure) => self.process_deserialize_error(failure),
+                Deserialization::Ack(msg_ids) => self.process_ack(msg_ids),
             }
         }
     }
@@ -621,7 +899,29 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
         }
     }
 
+    fn process_ack(&mut self, msg_ids: Vec<MsgId>) {
+        let now = Instant::now();
+        for req in self.requests.iter_mut() {
+            if let RequestState::Sent(pair) = &req.state {
+                if req.ack_result.is_some() && msg_ids.contains(&pair.msg_id) {
+                    req.acked_at.get_or_insert(now);
+                }
+            }
+        }
+    }
+
     fn process_bad_message(&mut self, bad_msg: BadMessage) {
+        if bad_msg.session_reset() {
+            // Responses to anything sent under the old session will never arrive.
+            info!("{}; re-sending all sent requests", bad_msg.description());
+            for req in self.requests.iter_mut() {
+                if let RequestState::Sent(_) = req.state {
+                    req.state = RequestState::NotSerialized;
+                }
+            }
+            return;
+        }
+
         for i in (0..self.requests.len()).rev() {
             match &self.requests[i].state {
                 RequestState::Serialized(pair)
@@ -636,7 +936,6 @@ impl<T: Transport, M: Mtp> Sender<T, M> {
                 RequestState::Sent(pair)
                     if pair.msg_id == bad_msg.msg_id || pair.container_msg_id == bad_msg.msg_id =>
                 {
-                    // TODO add a test to make sure we resend the request
                     if bad_msg.retryable() {
                         info!(
                             "{}; re-sending request {:?}",
@@ -712,20 +1011,47 @@ pub async fn connect<T: Transport>(
     addr: ServerAddr,
     rc_policy: &'static dyn ReconnectionPolicy,
 ) -> Result<(Sender<T, mtp::Encrypted>, Enqueuer), AuthorizationError> {
-    let (sender, enqueuer) = Sender::connect(transport, mtp::Plain::new(), addr, rc_policy).await?;
-    generate_auth_key(sender, enqueuer).await
+    connect_with_keys(transport, addr, &[], rc_policy).await
+}
+
+/// Like [`connect`], but the authorization key may also be generated using the given RSA keys,
+/// which is needed to connect to CDN datacenters.
+pub async fn connect_with_keys<T: Transport>(
+    transport: T,
+    addr: ServerAddr,
+    keys: &[rsa::Key],
+    rc_policy: &'static dyn ReconnectionPolicy,
+) -> Result<(Sender<T, mtp::Encrypted>, Enqueuer), AuthorizationError> {
+    let (sender, enqueuer) = Sender::connect(
+        transport,
+        mtp::Plain::new(),
+        addr,
+        ConnectionParams::default(),
+        rc_policy,
+    )
+    .await?;
+    generate_auth_key_with_keys(sender, enqueuer, keys).await
 }
 
 pub async fn generate_auth_key<T: Transport>(
+    sender: Sender<T, mtp::Plain>,
+    enqueuer: Enqueuer,
+) -> Result<(Sender<T, mtp::Encrypted>, Enqueuer), AuthorizationError> {
+    generate_auth_key_with_keys(sender, enqueuer, &[]).await
+}
+
+/// Like [`generate_auth_key`], but the server may also use any of the given RSA keys.
+pub async fn generate_auth_key_with_keys<T: Transport>(
     mut sender: Sender<T, mtp::Plain>,
     enqueuer: Enqueuer,
+    keys: &[rsa::Key],
 ) -> Result<(Sender<T, mtp::Encrypted>, Enqueuer), AuthorizationError> {
     info!("generating new authorization key...");
     let (request, data) = authentication::step1()?;
     debug!("gen auth key: sending step 1");
     let response = sender.send(request).await?;
     debug!("gen auth key: starting step 2");
-    let (request, data) = authentication::step2(data, &response)?;
+    let (request, data) = authentication::step2_with_keys(data, &response, keys)?;
     debug!("gen auth key: sending step 2");
     let response = sender.send(request).await?;
     debug!("gen auth key: starting step 3");
@@ -755,8 +1081,11 @@ pub async fn generate_auth_key<T: Transport>(
             read_tail: sender.read_tail,
             write_buffer: sender.write_buffer,
             write_head: sender.write_head,
+            traffic: sender.traffic,
             addr: sender.addr,
             reconnection_policy: sender.reconnection_policy,
+            state: sender.state,
+            params: sender.params,
         },
         enqueuer,
     ))
@@ -772,7 +1101,570 @@ pub async fn connect_with_auth<T: Transport>(
         transport,
         mtp::Encrypted::build().finish(auth_key),
         addr,
+        ConnectionParams::default(),
         rc_policy,
     )
     .await
 }
+
+/// Connect using the given parameters from the start, so that they also apply to the first
+/// connection (for example, the [`ConnectionParams::resolver`]).
+///
+/// Unlike the other methods to connect, no authorization key is generated. Use
+/// [`mtp::Plain`] followed by [`generate_auth_key`] to generate one, or [`mtp::Encrypted`]
+/// with an existing key.
+pub async fn connect_with_params<T: Transport, M: Mtp>(
+    transport: T,
+    mtp: M,
+    addr: ServerAddr,
+    params: ConnectionParams,
+    rc_policy: &'static dyn ReconnectionPolicy,
+) -> Result<(Sender<T, M>, Enqueuer), io::Error> {
+    Sender::connect(transport, mtp, addr, params, rc_policy).await
+}
+
+#[cfg(test)]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod tests {
+    use super::*;
+    use crate::utils::block_on;
+    use futures_util::future::join;
+    use grammers_mtproto::testing::{
+        decrypt_as_server, encrypt_as_server, rpc_result_body, server_message,
+    };
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    static RECONNECT_ONCE: FixedReconnect = FixedReconnect {
+        attempts: 1,
+        delay: Duration::ZERO,
+    };
+
+    async fn connect_to(
+        listener: &TcpListener,
+        policy: &'static dyn ReconnectionPolicy,
+    ) -> (Sender<transport::Full, mtp::Encrypted>, Enqueuer) {
+        connect_with_auth(
+            transport::Full::new(),
+            ServerAddr::Tcp {
+                address: listener.local_addr().unwrap(),
+            },
+            [0; 256],
+            policy,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn check_state_after_reconnecting() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, _enqueuer) = connect_to(&listener, &RECONNECT_ONCE).await;
+            let (server, _) = listener.accept().await.unwrap();
+
+            let mut state = sender.connection_state();
+            assert_eq!(*state.borrow_and_update(), ConnectionState::Connected);
+
+            // Dropping the connection from the server's side makes the sender reconnect.
+            drop(server);
+            let watch_states = async {
+                let mut states = Vec::new();
+                while state.changed().await.is_ok() {
+                    let current = *state.borrow_and_update();
+                    states.push(current);
+                    if current == ConnectionState::Connected {
+                        break;
+                    }
+                }
+                states
+            };
+            let (updates, states) = join(sender.step(), watch_states).await;
+
+            assert!(matches!(
+                updates.unwrap().as_slice(),
+                [tl::enums::Updates::TooLong]
+            ));
+            assert_eq!(
+                states,
+                vec![
+                    ConnectionState::Reconnecting { attempt: 1 },
+                    ConnectionState::Connected
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn check_state_after_giving_up() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, _enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (server, _) = listener.accept().await.unwrap();
+            let state = sender.connection_state();
+
+            drop(server);
+            assert!(matches!(sender.step().await, Err(ReadError::Io(_))));
+            assert_eq!(*state.borrow(), ConnectionState::Disconnected);
+        });
+    }
+
+    /// Read a packet sent by the client as the server would, returning the client's session
+    /// identifier and the identifier of the first message in it.
+    async fn read_as_server(
+        server: &mut tokio::net::TcpStream,
+        auth_key: &[u8; 256],
+    ) -> (i64, i64) {
+        let mut len = [0; 4];
+        server.read_exact(&mut len).await.unwrap();
+        let mut packet = vec![0; u32::from_le_bytes(len) as usize - 4];
+        server.read_exact(&mut packet).await.unwrap();
+
+        // Skip the transport's sequence number and checksum.
+        let plaintext = decrypt_as_server(&packet[4..packet.len() - 4], auth_key);
+        let client_id = i64::from_le_bytes(plaintext[8..16].try_into().unwrap());
+        let msg_id = i64::from_le_bytes(plaintext[16..24].try_into().unwrap());
+        (client_id, msg_id)
+    }
+
+    /// Send a single message with the given body to the client, as the server would.
+    ///
+    /// The same transport must be used for every packet written to a given connection.
+    async fn write_as_server(
+        server: &mut tokio::net::TcpStream,
+        transport: &mut transport::Full,
+        auth_key: &[u8; 256],
+        client_id: i64,
+        msg_id: i64,
+        body: &[u8],
+    ) {
+        let plaintext = server_message(client_id, msg_id, 1, body);
+        let mut packet = DequeBuffer::with_capacity(0, 8);
+        packet.extend(encrypt_as_server(&plaintext, auth_key));
+        transport.pack(&mut packet);
+        server.write_all(&packet[..]).await.unwrap();
+    }
+
+    /// Read a packet sent by the client, and reply to the request in it with `boolTrue`, as the
+    /// server would.
+    async fn reply_as_server(server: &mut tokio::net::TcpStream, auth_key: &[u8; 256]) {
+        let (client_id, req_msg_id) = read_as_server(server, auth_key).await;
+        let body = rpc_result_body(req_msg_id);
+        let mut transport = transport::Full::new();
+        write_as_server(
+            server,
+            &mut transport,
+            auth_key,
+            client_id,
+            0x5e0b_8000_0000_0001,
+            &body,
+        )
+        .await;
+    }
+
+    #[test]
+    fn check_request_completes_by_stepping() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (mut server, _) = listener.accept().await.unwrap();
+
+            // No task is spawned; the request only progresses while the sender is stepped.
+            let mut rx = enqueuer.enqueue(&tl::functions::Ping { ping_id: 1 });
+            while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                sender.step().await.unwrap();
+            }
+            assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+            reply_as_server(&mut server, &[0; 256]).await;
+            let result = loop {
+                sender.step().await.unwrap();
+                match rx.try_recv() {
+                    Ok(result) => break result,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Closed) => panic!("request was dropped"),
+                }
+            };
+            assert_eq!(result.unwrap(), true.to_bytes());
+        });
+    }
+
+    #[test]
+    fn check_packets_for_other_sessions_are_ignored() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (mut server, _) = listener.accept().await.unwrap();
+            let mut transport = transport::Full::new();
+            let auth_key = [0; 256];
+
+            let mut rx = enqueuer.enqueue(&tl::functions::Ping { ping_id: 1 });
+            while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                sender.step().await.unwrap();
+            }
+
+            // The response meant for another session is dropped without affecting this one.
+            let (client_id, req_msg_id) = read_as_server(&mut server, &auth_key).await;
+            let body = rpc_result_body(req_msg_id);
+            for (client_id, msg_id) in [
+                (client_id.wrapping_add(1), 0x5e0b_8000_0000_0001),
+                (client_id, 0x5e0b_8000_0000_0005),
+            ] {
+                write_as_server(
+                    &mut server,
+                    &mut transport,
+                    &auth_key,
+                    client_id,
+                    msg_id,
+                    &body,
+                )
+                .await;
+            }
+
+            let result = loop {
+                sender.step().await.unwrap();
+                match rx.try_recv() {
+                    Ok(result) => break result,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Closed) => panic!("request was dropped"),
+                }
+            };
+            assert_eq!(result.unwrap(), true.to_bytes());
+            assert_eq!(sender.traffic_stats().packets_received, 2);
+        });
+    }
+
+    #[test]
+    fn check_acked_request_result_is_only_used_if_answer_is_lost() {
+        block_on(async {
+            for answer_arrives in [true, false] {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+                let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+                let (mut server, _) = listener.accept().await.unwrap();
+                let mut transport = transport::Full::new();
+                let auth_key = [0; 256];
+
+                let ack_result = tl::enums::auth::LoggedOut::Out(tl::types::auth::LoggedOut {
+                    future_auth_token: None,
+                });
+                let mut rx =
+                    enqueuer.enqueue_with_ack_result(&tl::functions::auth::LogOut {}, &ack_result);
+                while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                    sender.step().await.unwrap();
+                }
+
+                let (client_id, req_msg_id) = read_as_server(&mut server, &auth_key).await;
+                let ack = tl::enums::MsgsAck::Ack(tl::types::MsgsAck {
+                    msg_ids: vec![req_msg_id],
+                });
+                write_as_server(
+                    &mut server,
+                    &mut transport,
+                    &auth_key,
+                    client_id,
+                    0x5e0b_8000_0000_0001,
+                    &ack.to_bytes(),
+                )
+                .await;
+                sender.step().await.unwrap();
+                assert!(sender.requests[0].acked_at.is_some());
+                assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+                if answer_arrives {
+                    write_as_server(
+                        &mut server,
+                        &mut transport,
+                        &auth_key,
+                        client_id,
+                        0x5e0b_8000_0000_0005,
+                        &rpc_result_body(req_msg_id),
+                    )
+                    .await;
+                } else {
+                    drop(server);
+                }
+
+                let result = loop {
+                    let _ = sender.step().await;
+                    match rx.try_recv() {
+                        Ok(result) => break result,
+                        Err(TryRecvError::Empty) => continue,
+                        Err(TryRecvError::Closed) => panic!("request was dropped"),
+                    }
+                };
+                let expected = if answer_arrives {
+                    true.to_bytes()
+                } else {
+                    ack_result.to_bytes()
+                };
+                assert_eq!(result.unwrap(), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn check_sent_requests_are_resent_after_session_reset() {
+        block_on(async {
+            for error_code in [32, 33] {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+                let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+                let (mut server, _) = listener.accept().await.unwrap();
+                let mut transport = transport::Full::new();
+                let auth_key = [0; 256];
+
+                let mut rx = enqueuer.enqueue(&tl::functions::Ping { ping_id: 1 });
+                while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                    sender.step().await.unwrap();
+                }
+                assert!(matches!(sender.requests[0].state, RequestState::Sent(_)));
+
+                // The server rejects the message's sequence number, which starts a new session.
+                let (old_client_id, msg_id) = read_as_server(&mut server, &auth_key).await;
+                let body =
+                    tl::enums::BadMsgNotification::Notification(tl::types::BadMsgNotification {
+                        bad_msg_id: msg_id,
+                        bad_msg_seqno: 1,
+                        error_code,
+                    })
+                    .to_bytes();
+                write_as_server(
+                    &mut server,
+                    &mut transport,
+                    &auth_key,
+                    old_client_id,
+                    0x5e0b_8000_0000_0001,
+                    &body,
+                )
+                .await;
+
+                // The request is sent again under the new session without failing.
+                let sent = sender.traffic_stats().sent;
+                let resend = async {
+                    while sender.traffic_stats().sent == sent || !sender.write_buffer.is_empty() {
+                        sender.step().await.unwrap();
+                    }
+                };
+                tokio::time::timeout(Duration::from_secs(5), resend)
+                    .await
+                    .expect("request should be re-sent");
+                assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+                let (client_id, req_msg_id) = read_as_server(&mut server, &auth_key).await;
+                assert_ne!(client_id, old_client_id);
+                assert_ne!(req_msg_id, msg_id);
+
+                // The server may still answer the original request under the old session.
+                for (client_id, msg_id, req_msg_id) in [
+                    (old_client_id, 0x5e0b_8000_0000_0005, msg_id),
+                    (client_id, 0x5e0b_8000_0000_0009, req_msg_id),
+                ] {
+                    write_as_server(
+                        &mut server,
+                        &mut transport,
+                        &auth_key,
+                        client_id,
+                        msg_id,
+                        &rpc_result_body(req_msg_id),
+                    )
+                    .await;
+                }
+                let result = loop {
+                    sender.step().await.unwrap();
+                    match rx.try_recv() {
+                        Ok(result) => break result,
+                        Err(TryRecvError::Empty) => continue,
+                        Err(TryRecvError::Closed) => panic!("request was dropped"),
+                    }
+                };
+                assert_eq!(result.unwrap(), true.to_bytes());
+            }
+        });
+    }
+
+    #[test]
+    fn check_service_request_completes_once_sent() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (_server, _) = listener.accept().await.unwrap();
+
+            let mut rx =
+                enqueuer.enqueue_service_request(&tl::enums::MsgsAck::Ack(tl::types::MsgsAck {
+                    msg_ids: vec![1],
+                }));
+            while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                sender.step().await.unwrap();
+            }
+
+            // The server won't respond, so the request is not kept around.
+            assert_eq!(rx.try_recv().unwrap().unwrap(), Vec::<u8>::new());
+            assert!(sender.requests.is_empty());
+        });
+    }
+
+    #[test]
+    fn check_in_flight_limit() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (mut server, _) = listener.accept().await.unwrap();
+            sender.set_connection_params(ConnectionParams {
+                max_in_flight: Some(1),
+                wait_for_capacity: true,
+                ..ConnectionParams::default()
+            });
+
+            let mut first = enqueuer.enqueue(&tl::functions::Ping { ping_id: 1 });
+            let mut second = enqueuer.enqueue(&tl::functions::Ping { ping_id: 2 });
+            while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                sender.step().await.unwrap();
+            }
+
+            // The first request is never answered, so the second one is not admitted.
+            let step = tokio::time::timeout(Duration::from_millis(100), sender.step()).await;
+            assert!(step.is_err());
+            assert_eq!(sender.requests.len(), 1);
+            assert!(matches!(second.try_recv(), Err(TryRecvError::Empty)));
+
+            // Once it completes, the second request is admitted.
+            reply_as_server(&mut server, &[0; 256]).await;
+            while matches!(first.try_recv(), Err(TryRecvError::Empty)) {
+                sender.step().await.unwrap();
+            }
+            while sender.requests.is_empty() {
+                sender.step().await.unwrap();
+            }
+            assert!(matches!(second.try_recv(), Err(TryRecvError::Empty)));
+
+            // Without waiting for capacity, further requests fail instead.
+            sender.set_connection_params(ConnectionParams {
+                max_in_flight: Some(1),
+                wait_for_capacity: false,
+                ..ConnectionParams::default()
+            });
+            let mut third = enqueuer.enqueue(&tl::functions::Ping { ping_id: 3 });
+            let result = loop {
+                match third.try_recv() {
+                    Ok(result) => break result,
+                    Err(TryRecvError::Empty) => sender.step().await.unwrap(),
+                    Err(TryRecvError::Closed) => panic!("request was dropped"),
+                };
+            };
+            assert!(matches!(result, Err(InvocationError::WouldBlock)));
+            assert_eq!(sender.requests.len(), 1);
+        });
+    }
+
+    #[test]
+    fn check_write_buffer_is_reused() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (mut server, _) = listener.accept().await.unwrap();
+
+            let mut capacity = None;
+            for ping_id in 0..50 {
+                drop(enqueuer.enqueue(&tl::functions::Ping { ping_id }));
+                let sent = sender.traffic_stats().sent;
+                while sender.traffic_stats().sent == sent || !sender.write_buffer.is_empty() {
+                    sender.step().await.unwrap();
+                }
+                let mut packet = vec![0; sender.traffic_stats().sent - sent];
+                server.read_exact(&mut packet).await.unwrap();
+
+                // After the first packet, the buffer is cleared and reused as-is.
+                let current = sender.write_buffer_capacity();
+                assert_eq!(*capacity.get_or_insert(current), current);
+            }
+
+            sender.reserve_write_buffer(2 * MAXIMUM_DATA);
+            assert!(sender.write_buffer_capacity() >= 2 * MAXIMUM_DATA);
+        });
+    }
+
+    #[test]
+    fn check_traffic_stats() {
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let (mut sender, enqueuer) = connect_to(&listener, &NoReconnect).await;
+            let (mut server, _) = listener.accept().await.unwrap();
+            assert_eq!(sender.traffic_stats(), TrafficStats::default());
+
+            // Sending a request takes a single packet, with all of its bytes.
+            drop(enqueuer.enqueue(&tl::functions::Ping { ping_id: 1 }));
+            while sender.traffic_stats().sent == 0 || !sender.write_buffer.is_empty() {
+                sender.step().await.unwrap();
+            }
+            let sent = sender.traffic_stats().sent;
+            let mut buffer = vec![0; sent];
+            server.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(
+                u32::from_le_bytes(buffer[..4].try_into().unwrap()) as usize,
+                sent
+            );
+            assert_eq!(sender.traffic_stats().packets_sent, 1);
+
+            // Receiving a packet counts it even if its contents are not valid.
+            let payload = [0; 64];
+            let mut packet = DequeBuffer::with_capacity(payload.len(), 8);
+            packet.extend(payload);
+            transport::Full::new().pack(&mut packet);
+            server.write_all(&packet[..]).await.unwrap();
+            assert!(sender.step().await.is_err());
+            assert_eq!(
+                sender.traffic_stats(),
+                TrafficStats {
+                    sent,
+                    received: payload.len() + 12,
+                    packets_sent: 1,
+                    packets_received: 1,
+                }
+            );
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "proxy")]
+    fn check_proxy_host_is_resolved_with_custom_resolver() {
+        struct Localhost;
+
+        impl Resolver for Localhost {
+            fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+                assert_eq!(host, "proxy.invalid");
+                Box::pin(async { Ok(vec![Ipv4Addr::LOCALHOST.into()]) })
+            }
+        }
+
+        static LOCALHOST: Localhost = Localhost;
+
+        block_on(async {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            // The system resolver can't resolve the reserved domain, so the proxy can only be
+            // reached if the custom resolver is used.
+            let connect = connect_with_params(
+                transport::Full::new(),
+                mtp::Plain::new(),
+                ServerAddr::Proxied {
+                    address: (Ipv4Addr::LOCALHOST, 443).into(),
+                    proxy: format!("socks5://proxy.invalid:{port}"),
+                },
+                ConnectionParams {
+                    resolver: &LOCALHOST,
+                    ..ConnectionParams::default()
+                },
+                &NoReconnect,
+            );
+            // The proxy hangs up before completing the handshake, which makes connecting fail.
+            let accept = async {
+                tokio::time::timeout(Duration::from_secs(5), listener.accept())
+                    .await
+                    .is_ok_and(|accepted| accepted.is_ok())
+            };
+
+            let (result, accepted) = join(connect, accept).await;
+            assert!(accepted);
+            assert!(result.is_err());
+        });
+    }
+}
@@ -0,0 +1,502 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A fake sender which answers requests from a script instead of the network, and a local
+//! server to answer the requests made through real connections with it.
+//!
+//! Only available with the `test-util` feature.
+use crate::{InvocationError, RpcError};
+use grammers_tl_types::{self as tl, Deserializable, Identifiable, RemoteCall, Serializable};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use {
+    grammers_crypto::DequeBuffer,
+    grammers_mtproto::testing::{
+        auth_key_id, client_messages, decrypt_as_server, encrypt_as_server, rpc_result_raw_body,
+        server_message, ClientMessage,
+    },
+    grammers_mtproto::transport::{self, Transport as _},
+    log::warn,
+    std::io,
+    std::net::{Ipv4Addr, SocketAddr},
+    std::sync::Arc,
+    tokio::io::{AsyncReadExt as _, AsyncWriteExt as _},
+    tokio::net::{TcpListener, TcpStream},
+    tokio::sync::Mutex as AsyncMutex,
+};
+
+/// The name of the [`RpcError`] returned when no rule matches a request.
+pub const UNMATCHED_REQUEST: &str = "MOCK_UNMATCHED_REQUEST";
+
+type Matcher = Box<dyn Fn(&[u8]) -> bool + Send>;
+
+struct Rule {
+    constructor_id: u32,
+    matcher: Option<Matcher>,
+    response: Result<Vec<u8>, RpcError>,
+}
+
+impl Rule {
+    fn matches(&self, body: &[u8]) -> bool {
+        body.len() >= 4
+            && u32::from_le_bytes([body[0], body[1], body[2], body[3]]) == self.constructor_id
+            && match &self.matcher {
+                Some(matcher) => matcher(body),
+                None => true,
+            }
+    }
+}
+
+/// A sender with the same [`invoke`](MockSender::invoke) interface as [`Sender`](crate::Sender),
+/// which never touches the network.
+///
+/// Every rule answers a single request. When a request is invoked, the first rule in the queue
+/// that matches is removed and its response is returned. Requests without a matching rule fail
+/// with an [`RpcError`] named [`UNMATCHED_REQUEST`].
+///
+/// # Examples
+///
+/// ```
+/// # async fn f() {
+/// use grammers_mtsender::mock::MockSender;
+/// use grammers_tl_types as tl;
+///
+/// let mut sender = MockSender::new();
+/// sender
+///     .expect::<tl::functions::Ping>()
+///     .respond(tl::types::Pong { msg_id: 0, ping_id: 42 }.into());
+/// sender.expect::<tl::functions::Ping>().flood_wait(30);
+///
+/// let request = tl::functions::Ping { ping_id: 42 };
+/// assert!(sender.invoke(&request).await.is_ok());
+/// assert!(sender.invoke(&request).await.unwrap_err().is("FLOOD_WAIT"));
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockSender {
+    rules: VecDeque<Rule>,
+    invoked: Vec<Vec<u8>>,
+}
+
+/// A rule being added to a [`MockSender`], which is only queued once its response is set.
+#[must_use = "the rule is only added once a response is set"]
+pub struct Expectation<'a, R> {
+    sender: &'a mut MockSender,
+    matcher: Option<Matcher>,
+    _request: PhantomData<R>,
+}
+
+impl MockSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule matching any request of type `R`.
+    pub fn expect<R: RemoteCall + Identifiable>(&mut self) -> Expectation<'_, R> {
+        Expectation {
+            sender: self,
+            matcher: None,
+            _request: PhantomData,
+        }
+    }
+
+    /// Add a rule matching the requests of type `R` for which `matcher` returns `true`.
+    pub fn expect_if<R, F>(&mut self, matcher: F) -> Expectation<'_, R>
+    where
+        R: RemoteCall + Identifiable + Deserializable,
+        F: Fn(&R) -> bool + Send + 'static,
+    {
+        Expectation {
+            sender: self,
+            // Deserializing functions doesn't expect their constructor identifier.
+            matcher: Some(Box::new(move |body| {
+                R::from_bytes(&body[4..]).is_ok_and(|request| matcher(&request))
+            })),
+            _request: PhantomData,
+        }
+    }
+
+    /// Answer the request from the first matching rule, as a real sender would.
+    pub async fn invoke<R: RemoteCall>(&mut self, request: &R) -> Result<Vec<u8>, InvocationError> {
+        let body = request.to_bytes();
+        let constructor_id = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+        self.answer(body)
+            .map_err(|e| InvocationError::Rpc(e.with_caused_by(constructor_id)))
+    }
+
+    /// Answer the serialized request from the first matching rule.
+    fn answer(&mut self, body: Vec<u8>) -> Result<Vec<u8>, RpcError> {
+        let position = self.rules.iter().position(|rule| rule.matches(&body));
+        self.invoked.push(body);
+
+        match position.and_then(|i| self.rules.remove(i)) {
+            Some(rule) => rule.response,
+            None => Err(RpcError {
+                code: 400,
+                name: UNMATCHED_REQUEST.to_string(),
+                value: None,
+                caused_by: None,
+            }),
+        }
+    }
+
+    /// The serialized requests invoked so far, whether they matched a rule or not.
+    pub fn invoked(&self) -> &[Vec<u8>] {
+        &self.invoked
+    }
+
+    /// How many rules have not been used to answer a request yet.
+    pub fn remaining_rules(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+impl<R: RemoteCall + Identifiable> Expectation<'_, R> {
+    fn push(self, response: Result<Vec<u8>, RpcError>) {
+        self.sender.rules.push_back(Rule {
+            constructor_id: R::CONSTRUCTOR_ID,
+            matcher: self.matcher,
+            response,
+        });
+    }
+
+    /// Answer the matching request successfully.
+    pub fn respond(self, response: R::Return)
+    where
+        R::Return: Serializable,
+    {
+        self.push(Ok(response.to_bytes()));
+    }
+
+    /// Answer the matching request with the given serialized response.
+    pub fn respond_raw(self, response: Vec<u8>) {
+        self.push(Ok(response));
+    }
+
+    /// Fail the matching request with the given error.
+    pub fn fail(self, error: RpcError) {
+        self.push(Err(error));
+    }
+
+    /// Fail the matching request with a `FLOOD_WAIT` error asking to wait `seconds`.
+    pub fn flood_wait(self, seconds: u32) {
+        self.push(Err(RpcError::from(tl::types::RpcError {
+            error_code: 420,
+            error_message: format!("FLOOD_WAIT_{seconds}"),
+        })));
+    }
+}
+
+/// A local server which answers the requests sent through real connections to it from a
+/// [`MockSender`], so that tests exercise the same path requests take when using the network.
+///
+/// The server cannot generate authorization keys, so connections must use one of the keys it
+/// was started with. Every connection may be shared by multiple clients and datacenters.
+///
+/// The `invokeWithLayer` request used to initialize connections is answered with an empty
+/// configuration, and never reaches the sender.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub struct MockServer {
+    address: SocketAddr,
+    sender: Arc<AsyncMutex<MockSender>>,
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl MockServer {
+    /// Start serving connections made with any of the `auth_keys` in the background.
+    ///
+    /// Must be called from within a Tokio runtime, which will drive the server.
+    pub async fn start(sender: MockSender, auth_keys: &[[u8; 256]]) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let sender = Arc::new(AsyncMutex::new(sender));
+        let auth_keys = Arc::<[[u8; 256]]>::from(auth_keys);
+
+        let shared_sender = Arc::clone(&sender);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(
+                    stream,
+                    Arc::clone(&shared_sender),
+                    Arc::clone(&auth_keys),
+                ));
+            }
+        });
+        Ok(Self { address, sender })
+    }
+
+    /// The address to connect to.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The sender answering the requests, which can be used to inspect the requests made.
+    pub fn sender(&self) -> &Arc<AsyncMutex<MockSender>> {
+        &self.sender
+    }
+}
+
+/// The identifier of the first message sent by the server through every connection.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const FIRST_SERVER_MSG_ID: i64 = 0x5e0b_8000_0000_0001;
+
+/// Answer the content-related messages sent through the connection until it's closed.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+async fn serve(
+    mut stream: TcpStream,
+    sender: Arc<AsyncMutex<MockSender>>,
+    auth_keys: Arc<[[u8; 256]]>,
+) {
+    let mut transport = transport::Full::new();
+    let mut msg_id = FIRST_SERVER_MSG_ID;
+    loop {
+        let mut len = [0; 4];
+        if stream.read_exact(&mut len).await.is_err() {
+            break;
+        }
+        let mut packet = vec![0; u32::from_le_bytes(len) as usize - 4];
+        if stream.read_exact(&mut packet).await.is_err() {
+            break;
+        }
+
+        // Skip the transport's sequence number and checksum.
+        let ciphertext = &packet[4..packet.len() - 4];
+        let Some(auth_key) = auth_keys
+            .iter()
+            .find(|auth_key| auth_key_id(auth_key) == ciphertext[..8])
+        else {
+            warn!("closing mock connection made with an unknown authorization key");
+            break;
+        };
+
+        let (client_id, messages) = client_messages(&decrypt_as_server(ciphertext, auth_key));
+        for message in messages.iter().filter(|m| m.is_content_related()) {
+            let result = answer(&sender, message).await;
+            let body = rpc_result_raw_body(message.msg_id, &result);
+            let mut packet = DequeBuffer::with_capacity(0, 8);
+            packet.extend(encrypt_as_server(
+                &server_message(client_id, msg_id, 1, &body),
+                auth_key,
+            ));
+            transport.pack(&mut packet);
+            if stream.write_all(&packet[..]).await.is_err() {
+                return;
+            }
+            // Server messages answering a request have identifiers congruent to 1 modulo 4.
+            msg_id += 4;
+        }
+    }
+}
+
+/// Serialize the result of the message's request, as found in the server's `rpc_result`.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+async fn answer(sender: &AsyncMutex<MockSender>, message: &ClientMessage) -> Vec<u8> {
+    if message.body[..4] == tl::functions::InvokeWithLayer::<()>::CONSTRUCTOR_ID.to_le_bytes() {
+        return empty_config().to_bytes();
+    }
+
+    match sender.lock().await.answer(message.body.clone()) {
+        Ok(result) => result,
+        Err(error) => tl::enums::RpcError::from(tl::types::RpcError {
+            error_code: error.code,
+            error_message: match error.value {
+                Some(value) => format!("{}_{}", error.name, value),
+                None => error.name,
+            },
+        })
+        .to_bytes(),
+    }
+}
+
+/// A configuration without any datacenter options nor meaningful limits.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn empty_config() -> tl::enums::Config {
+    tl::types::Config {
+        default_p2p_contacts: false,
+        preload_featured_stickers: false,
+        revoke_pm_inbox: false,
+        blocked_mode: false,
+        force_try_ipv6: false,
+        date: 0,
+        expires: 0,
+        test_mode: false,
+        this_dc: 0,
+        dc_options: Vec::new(),
+        dc_txt_domain_name: String::new(),
+        chat_size_max: 0,
+        megagroup_size_max: 0,
+        forwarded_count_max: 0,
+        online_update_period_ms: 0,
+        offline_blur_timeout_ms: 0,
+        offline_idle_timeout_ms: 0,
+        online_cloud_timeout_ms: 0,
+        notify_cloud_delay_ms: 0,
+        notify_default_delay_ms: 0,
+        push_chat_period_ms: 0,
+        push_chat_limit: 0,
+        edit_time_limit: 0,
+        revoke_time_limit: 0,
+        revoke_pm_time_limit: 0,
+        rating_e_decay: 0,
+        stickers_recent_limit: 0,
+        channels_read_media_period: 0,
+        tmp_sessions: None,
+        call_receive_timeout_ms: 0,
+        call_ring_timeout_ms: 0,
+        call_connect_timeout_ms: 0,
+        call_packet_timeout_ms: 0,
+        me_url_prefix: String::new(),
+        autoupdate_url_prefix: None,
+        gif_search_username: None,
+        venue_search_username: None,
+        img_search_username: None,
+        static_maps_provider: None,
+        caption_length_max: 0,
+        message_length_max: 0,
+        webfile_dc_id: 0,
+        suggested_lang_code: None,
+        lang_pack_version: None,
+        base_lang_pack_version: None,
+        reactions_default: None,
+        autologin_token: None,
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::block_on;
+
+    #[test]
+    fn check_scripted_responses() {
+        let mut sender = MockSender::new();
+        sender
+            .expect_if(|r: &tl::functions::Ping| r.ping_id == 2)
+            .respond(
+                tl::types::Pong {
+                    msg_id: 0,
+                    ping_id: 2,
+                }
+                .into(),
+            );
+        sender.expect::<tl::functions::Ping>().flood_wait(30);
+
+        block_on(async {
+            let response = sender
+                .invoke(&tl::functions::Ping { ping_id: 2 })
+                .await
+                .unwrap();
+            assert_eq!(
+                tl::enums::Pong::from_bytes(&response).unwrap(),
+                tl::types::Pong {
+                    msg_id: 0,
+                    ping_id: 2,
+                }
+                .into()
+            );
+
+            // The first rule was used, so only the second one matches.
+            match sender.invoke(&tl::functions::Ping { ping_id: 2 }).await {
+                Err(InvocationError::Rpc(err)) => {
+                    assert_eq!(err.code, 420);
+                    assert_eq!(err.flood_wait(), Some(30));
+                    assert_eq!(err.caused_by, Some(tl::functions::Ping::CONSTRUCTOR_ID));
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+
+            // No rules are left.
+            let result = sender.invoke(&tl::functions::Ping { ping_id: 2 }).await;
+            assert!(result.unwrap_err().is(UNMATCHED_REQUEST));
+        });
+
+        assert_eq!(sender.invoked().len(), 3);
+        assert_eq!(sender.remaining_rules(), 0);
+    }
+
+    #[test]
+    fn check_unmatched_requests_fail() {
+        let mut sender = MockSender::new();
+        sender
+            .expect_if(|r: &tl::functions::Ping| r.ping_id == 1)
+            .respond_raw(Vec::new());
+
+        block_on(async {
+            let result = sender.invoke(&tl::functions::Ping { ping_id: 2 }).await;
+            assert!(result.unwrap_err().is(UNMATCHED_REQUEST));
+
+            let result = sender
+                .invoke(&tl::functions::PingDelayDisconnect {
+                    ping_id: 1,
+                    disconnect_delay: 0,
+                })
+                .await;
+            assert!(result.unwrap_err().is(UNMATCHED_REQUEST));
+        });
+
+        assert_eq!(sender.remaining_rules(), 1);
+    }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn check_server_answers_real_connections() {
+        let mut sender = MockSender::new();
+        sender.expect::<tl::functions::Ping>().respond(
+            tl::types::Pong {
+                msg_id: 0,
+                ping_id: 7,
+            }
+            .into(),
+        );
+        sender.expect::<tl::functions::Ping>().flood_wait(30);
+
+        block_on(async {
+            let server = MockServer::start(sender, &[[1; 256]]).await.unwrap();
+            let (mut sender, enqueuer) = crate::connect_with_auth(
+                transport::Full::new(),
+                crate::ServerAddr::Tcp {
+                    address: server.address(),
+                },
+                [1; 256],
+                &crate::NoReconnect,
+            )
+            .await
+            .unwrap();
+
+            let init = tl::functions::InvokeWithLayer {
+                layer: tl::LAYER,
+                query: tl::functions::help::GetConfig {},
+            };
+            assert!(sender.invoke(&init).await.is_ok());
+
+            let request = tl::functions::Ping { ping_id: 7 };
+            let response = sender.invoke(&request).await.unwrap();
+            let tl::enums::Pong::Pong(pong) = tl::enums::Pong::from_bytes(&response).unwrap();
+            assert_eq!(pong.ping_id, 7);
+
+            let mut rx = enqueuer.enqueue(&request);
+            let result = loop {
+                sender.step().await.unwrap();
+                if let Ok(result) = rx.try_recv() {
+                    break result;
+                }
+            };
+            match result {
+                Err(InvocationError::Rpc(err)) => assert_eq!(err.flood_wait(), Some(30)),
+                result => panic!("unexpected result: {result:?}"),
+            }
+
+            // Initializing the connection doesn't count as a request made to the sender.
+            let sender = server.sender().lock().await;
+            assert_eq!(sender.invoked().len(), 2);
+            assert_eq!(sender.remaining_rules(), 0);
+        });
+    }
+}
@@ -20,6 +20,49 @@ pub use ws::NetStream;
 #[cfg(all(target_arch = "wasm32", target_os = "unknown", feature = "proxy"))]
 compile_error!("TCP proxies are not supported when compiling for WASM");
 
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// The port used by all of the well-known Telegram datacenter addresses.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const DC_PORT: u16 = 443;
+
+/// IPv4 addresses of the production datacenters, where the index is the datacenter ID minus one.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const PROD_DC_V4: [Ipv4Addr; 5] = [
+    Ipv4Addr::new(149, 154, 175, 53),
+    Ipv4Addr::new(149, 154, 167, 51),
+    Ipv4Addr::new(149, 154, 175, 100),
+    Ipv4Addr::new(149, 154, 167, 92),
+    Ipv4Addr::new(91, 108, 56, 190),
+];
+
+/// IPv6 addresses of the production datacenters, where the index is the datacenter ID minus one.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const PROD_DC_V6: [Ipv6Addr; 5] = [
+    Ipv6Addr::new(0x2001, 0xb28, 0xf23d, 0xf001, 0, 0, 0, 0xa),
+    Ipv6Addr::new(0x2001, 0x67c, 0x4e8, 0xf002, 0, 0, 0, 0xa),
+    Ipv6Addr::new(0x2001, 0xb28, 0xf23d, 0xf003, 0, 0, 0, 0xa),
+    Ipv6Addr::new(0x2001, 0x67c, 0x4e8, 0xf004, 0, 0, 0, 0xa),
+    Ipv6Addr::new(0x2001, 0xb28, 0xf23f, 0xf005, 0, 0, 0, 0xa),
+];
+
+/// IPv4 addresses of the test datacenters, where the index is the datacenter ID minus one.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const TEST_DC_V4: [Ipv4Addr; 3] = [
+    Ipv4Addr::new(149, 154, 175, 10),
+    Ipv4Addr::new(149, 154, 167, 40),
+    Ipv4Addr::new(149, 154, 175, 117),
+];
+
+/// IPv6 addresses of the test datacenters, where the index is the datacenter ID minus one.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+const TEST_DC_V6: [Ipv6Addr; 3] = [
+    Ipv6Addr::new(0x2001, 0xb28, 0xf23d, 0xf001, 0, 0, 0, 0xe),
+    Ipv6Addr::new(0x2001, 0x67c, 0x4e8, 0xf002, 0, 0, 0, 0xe),
+    Ipv6Addr::new(0x2001, 0xb28, 0xf23d, 0xf003, 0, 0, 0, 0xe),
+];
+
 #[derive(Debug, Clone)]
 pub enum ServerAddr {
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
@@ -35,3 +78,77 @@ pub enum ServerAddr {
     #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
     Tcp { address: std::net::SocketAddr },
 }
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl ServerAddr {
+    /// The well-known IPv4 address of the production datacenter with the given ID, if any.
+    pub fn telegram_prod_dc(dc_id: i32) -> Option<Self> {
+        Self::well_known(&PROD_DC_V4, dc_id)
+    }
+
+    /// The well-known IPv6 address of the production datacenter with the given ID, if any.
+    pub fn telegram_prod_dc_v6(dc_id: i32) -> Option<Self> {
+        Self::well_known(&PROD_DC_V6, dc_id)
+    }
+
+    /// The well-known IPv4 address of the test datacenter with the given ID, if any.
+    ///
+    /// See [Test servers](https://core.telegram.org/api/auth#test-accounts) to learn more.
+    pub fn telegram_test_dc(dc_id: i32) -> Option<Self> {
+        Self::well_known(&TEST_DC_V4, dc_id)
+    }
+
+    /// The well-known IPv6 address of the test datacenter with the given ID, if any.
+    pub fn telegram_test_dc_v6(dc_id: i32) -> Option<Self> {
+        Self::well_known(&TEST_DC_V6, dc_id)
+    }
+
+    fn well_known<A: Copy + Into<IpAddr>>(addresses: &[A], dc_id: i32) -> Option<Self> {
+        let index = usize::try_from(dc_id).ok()?.checked_sub(1)?;
+        addresses.get(index).map(|&ip| Self::Tcp {
+            address: SocketAddr::new(ip.into(), DC_PORT),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod tests {
+    use super::*;
+
+    fn address(addr: Option<ServerAddr>) -> Option<String> {
+        addr.map(|addr| match addr {
+            ServerAddr::Tcp { address } => address.to_string(),
+            #[cfg(feature = "proxy")]
+            ServerAddr::Proxied { .. } => unreachable!(),
+        })
+    }
+
+    #[test]
+    fn check_well_known_test_dcs() {
+        assert_eq!(
+            address(ServerAddr::telegram_test_dc(2)).as_deref(),
+            Some("149.154.167.40:443")
+        );
+        assert_eq!(
+            address(ServerAddr::telegram_test_dc_v6(2)).as_deref(),
+            Some("[2001:67c:4e8:f002::e]:443")
+        );
+        assert_eq!(address(ServerAddr::telegram_test_dc(4)), None);
+    }
+
+    #[test]
+    fn check_well_known_prod_dcs() {
+        assert_eq!(
+            address(ServerAddr::telegram_prod_dc(5)).as_deref(),
+            Some("91.108.56.190:443")
+        );
+        assert_eq!(
+            address(ServerAddr::telegram_prod_dc_v6(1)).as_deref(),
+            Some("[2001:b28:f23d:f001::a]:443")
+        );
+        assert_eq!(address(ServerAddr::telegram_prod_dc(0)), None);
+        assert_eq!(address(ServerAddr::telegram_prod_dc(-1)), None);
+        assert_eq!(address(ServerAddr::telegram_prod_dc(6)), None);
+    }
+}
@@ -11,6 +11,7 @@ pub use tokio::net::tcp::{ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
 
 use super::ServerAddr;
+use crate::ConnectionParams;
 
 pub enum NetStream {
     Tcp(TcpStream),
@@ -19,7 +20,7 @@ pub enum NetStream {
 }
 
 impl NetStream {
-    pub(crate) fn split(&mut self) -> (ReadHalf, WriteHalf) {
+    pub(crate) fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
         match self {
             Self::Tcp(stream) => stream.split(),
             #[cfg(feature = "proxy")]
@@ -27,13 +28,18 @@ impl NetStream {
         }
     }
 
-    pub(crate) async fn connect(addr: &ServerAddr) -> Result<Self, std::io::Error> {
+    // The parameters are only needed to connect through a proxy.
+    #[cfg_attr(not(feature = "proxy"), allow(unused_variables))]
+    pub(crate) async fn connect(
+        addr: &ServerAddr,
+        params: &ConnectionParams,
+    ) -> Result<Self, std::io::Error> {
         info!("connecting...");
         match addr {
             ServerAddr::Tcp { address } => Ok(NetStream::Tcp(TcpStream::connect(address).await?)),
             #[cfg(feature = "proxy")]
             ServerAddr::Proxied { address, proxy } => {
-                Self::connect_proxy_stream(address, proxy).await
+                Self::connect_proxy_stream(address, proxy, params.resolver).await
             }
         }
     }
@@ -42,16 +48,13 @@ impl NetStream {
     async fn connect_proxy_stream(
         addr: &std::net::SocketAddr,
         proxy_url: &str,
+        resolver: &dyn crate::Resolver,
     ) -> Result<NetStream, std::io::Error> {
         use std::{
             io::{self, ErrorKind},
             net::{IpAddr, SocketAddr},
         };
 
-        use hickory_resolver::{
-            config::{ResolverConfig, ResolverOpts},
-            AsyncResolver,
-        };
         use url::Host;
 
         let proxy = url::Url::parse(proxy_url)
@@ -69,9 +72,7 @@ impl NetStream {
         let password = proxy.password().unwrap_or("");
         let socks_addr = match host {
             Host::Domain(domain) => {
-                let resolver =
-                    AsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default());
-                let response = resolver.lookup_ip(domain).await?;
+                let response = resolver.resolve(domain).await?;
                 let socks_ip_addr = response.into_iter().next().ok_or(io::Error::new(
                     ErrorKind::NotFound,
                     format!("proxy host did not return any ip address: {}", domain),
@@ -10,6 +10,7 @@ use futures_util::TryFutureExt;
 use log::info;
 
 use super::ServerAddr;
+use crate::ConnectionParams;
 
 type WsIo = async_io_stream::IoStream<ws_stream_wasm::WsStreamIo, Vec<u8>>;
 pub type ReadHalf<'a> = tokio::io::ReadHalf<&'a mut WsIo>;
@@ -22,7 +23,10 @@ impl NetStream {
         tokio::io::split(&mut self.0)
     }
 
-    pub(crate) async fn connect(addr: &ServerAddr) -> Result<Self, std::io::Error> {
+    pub(crate) async fn connect(
+        addr: &ServerAddr,
+        _params: &ConnectionParams,
+    ) -> Result<Self, std::io::Error> {
         info!("connecting...");
         match addr {
             ServerAddr::Ws { address } => {
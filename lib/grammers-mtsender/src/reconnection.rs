@@ -5,6 +5,7 @@
 // <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use grammers_crypto::{RandomSource, SystemRandom};
 use std::ops::ControlFlow;
 use std::time::Duration;
 
@@ -25,6 +26,19 @@ pub trait ReconnectionPolicy: Send + Sync {
     fn should_retry(&self, attempts: usize) -> ControlFlow<(), Duration>;
 }
 
+/// The state of the connection to Telegram, as driven by the **ReconnectionPolicy**.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection is established.
+    Connected,
+    /// The connection was lost, and a new one is being attempted.
+    ///
+    /// `attempt` starts at one for the first attempt after the connection was lost.
+    Reconnecting { attempt: usize },
+    /// The connection was lost, and the **ReconnectionPolicy** gave up on reconnecting.
+    Disconnected,
+}
+
 /// the default implementation of the **ReconnectionPolicy**.
 pub struct NoReconnect;
 
@@ -34,6 +48,43 @@ pub struct FixedReconnect {
     pub delay: Duration,
 }
 
+/// *Exponential backoff* implementation for the **ReconnectionPolicy** trait.
+///
+/// the delay starts at `base` and doubles on every failed attempt, up to `max`. after `max_attempts`
+/// failed attempts, it gives up.
+///
+/// with `jitter`, the delay is instead a random duration between zero and the exponential delay ("full
+/// jitter"), so that many clients losing the connection at the same time don't all reconnect at once.
+///
+/// # Examples
+///
+/// ```
+/// use grammers_mtsender::ExponentialBackoff;
+/// use std::time::Duration;
+///
+/// static RECONNECTION_POLICY: ExponentialBackoff = ExponentialBackoff {
+///     base: Duration::from_millis(500),
+///     max: Duration::from_secs(60),
+///     max_attempts: 10,
+///     jitter: true,
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    pub base: Duration,
+    pub max: Duration,
+    pub max_attempts: usize,
+    pub jitter: bool,
+}
+
+impl ExponentialBackoff {
+    /// the delay before the given attempt, without jitter.
+    fn delay(&self, attempts: usize) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(u32::BITS as usize - 1) as u32;
+        self.base.saturating_mul(1 << exponent).min(self.max)
+    }
+}
+
 impl ReconnectionPolicy for FixedReconnect {
     fn should_retry(&self, attempts: usize) -> ControlFlow<(), Duration> {
         if attempts <= self.attempts {
@@ -44,8 +95,91 @@ impl ReconnectionPolicy for FixedReconnect {
     }
 }
 
+impl ReconnectionPolicy for ExponentialBackoff {
+    fn should_retry(&self, attempts: usize) -> ControlFlow<(), Duration> {
+        if attempts > self.max_attempts {
+            return ControlFlow::Break(());
+        }
+
+        let delay = self.delay(attempts);
+        if self.jitter {
+            let mut buffer = [0; 8];
+            SystemRandom.fill(&mut buffer);
+            let fraction = u64::from_le_bytes(buffer) as f64 / u64::MAX as f64;
+            ControlFlow::Continue(delay.mul_f64(fraction))
+        } else {
+            ControlFlow::Continue(delay)
+        }
+    }
+}
+
 impl ReconnectionPolicy for NoReconnect {
     fn should_retry(&self, _: usize) -> ControlFlow<(), Duration> {
         ControlFlow::Break(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff(jitter: bool) -> ExponentialBackoff {
+        ExponentialBackoff {
+            base: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            max_attempts: 6,
+            jitter,
+        }
+    }
+
+    fn delay(policy: &dyn ReconnectionPolicy, attempts: usize) -> Option<Duration> {
+        match policy.should_retry(attempts) {
+            ControlFlow::Continue(delay) => Some(delay),
+            ControlFlow::Break(()) => None,
+        }
+    }
+
+    #[test]
+    fn check_exponential_delays() {
+        let policy = backoff(false);
+        let delays = (1..=5)
+            .map(|attempts| delay(&policy, attempts).unwrap().as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000]);
+    }
+
+    #[test]
+    fn check_delay_is_capped() {
+        let policy = ExponentialBackoff {
+            max_attempts: usize::MAX,
+            ..backoff(false)
+        };
+        assert_eq!(delay(&policy, 6), Some(Duration::from_secs(1)));
+        assert_eq!(delay(&policy, 1000), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn check_jitter_is_within_bounds() {
+        let policy = backoff(true);
+        for attempts in 1..=6 {
+            let max = backoff(false).delay(attempts);
+            for _ in 0..100 {
+                assert!(delay(&policy, attempts).unwrap() <= max);
+            }
+        }
+
+        // The delays should not all be the same.
+        let delays = (0..100)
+            .map(|_| delay(&policy, 6).unwrap())
+            .collect::<std::collections::HashSet<_>>();
+        assert!(delays.len() > 1);
+    }
+
+    #[test]
+    fn check_gives_up_after_max_attempts() {
+        let policy = backoff(true);
+        assert!(delay(&policy, 0).is_some());
+        assert!(delay(&policy, 6).is_some());
+        assert_eq!(delay(&policy, 7), None);
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+/// The future returned by [`Resolver::resolve`].
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<IpAddr>>> + Send + 'a>>;
+
+/// Resolves hostnames into IP addresses when connecting, such as the host of a proxy.
+///
+/// By implementing this trait and setting it as the
+/// [`ConnectionParams::resolver`](crate::ConnectionParams::resolver), you can control how
+/// hostnames are resolved (for example, using DNS over HTTPS). The default is [`SystemResolver`].
+///
+/// # Examples
+///
+/// ```
+/// use grammers_mtsender::{ResolveFuture, Resolver};
+/// use std::net::{IpAddr, Ipv4Addr};
+///
+/// struct Fixed;
+///
+/// impl Resolver for Fixed {
+///     fn resolve<'a>(&'a self, _host: &'a str) -> ResolveFuture<'a> {
+///         Box::pin(async { Ok(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]) })
+///     }
+/// }
+///
+/// static RESOLVER: Fixed = Fixed;
+/// ```
+pub trait Resolver: Send + Sync {
+    /// Resolve the hostname into the IP addresses it refers to, in order of preference.
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a>;
+}
+
+/// Resolves hostnames with the resolver of the operating system.
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            Ok(tokio::net::lookup_host((host, 0))
+                .await?
+                .map(|addr| addr.ip())
+                .collect())
+        })
+    }
+}
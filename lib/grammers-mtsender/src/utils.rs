@@ -72,7 +72,7 @@ impl Timeout {
 pub async fn sleep(duration: Duration) {
     #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
     {
-        return tokio::time::sleep(duration).await;
+        tokio::time::sleep(duration).await
     }
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
     {
@@ -84,10 +84,20 @@ pub async fn sleep(duration: Duration) {
 pub async fn sleep_until(deadline: Instant) {
     #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
     {
-        return tokio::time::sleep_until(deadline.into()).await;
+        tokio::time::sleep_until(deadline.into()).await
     }
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
     {
         Timeout::new(deadline - Instant::now()).await;
     }
 }
+
+/// Run the future to completion on a new single-threaded runtime, as tests need.
+#[cfg(test)]
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
@@ -6,15 +6,12 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-pub const TELEGRAM_TEST_DC_2: &str = "149.154.167.40:443";
-
 /// The default datacenter to connect to for testing.
-pub const TELEGRAM_DEFAULT_TEST_DC: &str = TELEGRAM_TEST_DC_2;
+pub const TELEGRAM_DEFAULT_TEST_DC: i32 = 2;
 
 use grammers_mtproto::transport;
 use grammers_mtsender::{connect, NoReconnect};
 use grammers_tl_types::{enums, functions, Deserializable, RemoteCall, LAYER};
-use std::str::FromStr;
 
 use simple_logger::SimpleLogger;
 use tokio::runtime;
@@ -33,9 +30,7 @@ fn test_invoke_encrypted_method() {
     rt.block_on(async {
         let (mut sender, enqueuer) = connect(
             transport::Full::new(),
-            grammers_mtsender::ServerAddr::Tcp {
-                address: std::net::SocketAddr::from_str(TELEGRAM_TEST_DC_2).unwrap(),
-            },
+            grammers_mtsender::ServerAddr::telegram_test_dc(TELEGRAM_DEFAULT_TEST_DC).unwrap(),
             &NoReconnect,
         )
         .await
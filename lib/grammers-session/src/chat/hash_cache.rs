@@ -53,6 +53,15 @@ impl ChatHashCache {
         })
     }
 
+    /// Iterate over all the cached chats, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = PackedChat> + '_ {
+        self.hash_map.iter().map(|(&id, &(hash, ty))| PackedChat {
+            ty,
+            id,
+            access_hash: Some(hash),
+        })
+    }
+
     #[inline]
     fn has(&self, id: i64) -> bool {
         self.hash_map.contains_key(&id)
@@ -586,3 +595,61 @@ impl ChatHashCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_iter_yields_every_chat() {
+        let mut cache = ChatHashCache::new(None);
+        cache.hash_map.insert(1, (10, PackedType::User));
+        let success = cache.extend(
+            &[],
+            &[
+                tl::types::ChannelForbidden {
+                    broadcast: true,
+                    megagroup: false,
+                    id: 2,
+                    access_hash: 20,
+                    title: String::new(),
+                    until_date: None,
+                }
+                .into(),
+                tl::types::ChannelForbidden {
+                    broadcast: false,
+                    megagroup: true,
+                    id: 3,
+                    access_hash: 30,
+                    title: String::new(),
+                    until_date: None,
+                }
+                .into(),
+            ],
+        );
+        assert!(success);
+
+        let mut chats = cache.iter().collect::<Vec<_>>();
+        chats.sort_by_key(|chat| chat.id);
+        assert_eq!(
+            chats,
+            vec![
+                PackedChat {
+                    ty: PackedType::User,
+                    id: 1,
+                    access_hash: Some(10),
+                },
+                PackedChat {
+                    ty: PackedType::Broadcast,
+                    id: 2,
+                    access_hash: Some(20),
+                },
+                PackedChat {
+                    ty: PackedType::Megagroup,
+                    id: 3,
+                    access_hash: Some(30),
+                },
+            ]
+        );
+    }
+}
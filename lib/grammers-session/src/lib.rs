@@ -11,8 +11,11 @@
 mod chat;
 mod generated;
 mod message_box;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
 pub use chat::{ChatHashCache, PackedChat, PackedType};
+pub use generated::types::PendingUpdates;
 pub use generated::types::UpdateState;
 pub use generated::types::User;
 pub use generated::LAYER as VERSION;
@@ -27,6 +30,9 @@ use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::path::Path;
 use std::sync::Mutex;
 
+/// How many resolved usernames are remembered at most.
+const MAX_RESOLVED_USERNAMES: usize = 128;
+
 // Needed for auto-generated definitions.
 use grammers_tl_types::{deserialize, Deserializable, Identifiable, Serializable};
 
@@ -42,6 +48,7 @@ impl Session {
                 dcs: Vec::new(),
                 user: None,
                 state: None,
+                usernames: None,
             }),
         }
     }
@@ -109,7 +116,7 @@ impl Session {
 
     fn insert_dc(&self, dc: enums::DataCenter) {
         let mut session = self.session.lock().unwrap();
-        if let Some(pos) = session.dcs.iter().position(|dc| dc.id() == dc.id()) {
+        if let Some(pos) = session.dcs.iter().position(|d| d.id() == dc.id()) {
             session.dcs.remove(pos);
         }
         session.dcs.push(dc);
@@ -133,6 +140,68 @@ impl Session {
         );
     }
 
+    /// Returns the stored TCP address of the datacenter, preferring IPv4 if both are known.
+    pub fn dc_addr(&self, dc_id: i32) -> Option<SocketAddr> {
+        self.session
+            .lock()
+            .unwrap()
+            .dcs
+            .iter()
+            .find_map(|dc| match dc {
+                enums::DataCenter::Center(dc) if dc.id == dc_id => {
+                    let port = dc.port as u16;
+                    match (dc.ipv4, dc.ipv6) {
+                        (Some(ip), _) => Some(SocketAddr::from((ip.to_le_bytes(), port))),
+                        (None, Some(ip)) => Some(SocketAddr::from((ip, port))),
+                        (None, None) => None,
+                    }
+                }
+                _ => None,
+            })
+    }
+
+    /// Updates the stored TCP address of the datacenter, preserving its authorization key.
+    ///
+    /// An IPv4 and an IPv6 address are stored separately, so setting one does not forget the
+    /// other. Because only one port is stored, an IPv6 address only changes it when there is no
+    /// IPv4 address. Datacenters connected through WebSockets are left untouched.
+    pub fn set_dc_addr(&self, dc_id: i32, addr: &SocketAddr) {
+        let mut session = self.session.lock().unwrap();
+        let dc = match session.dcs.iter_mut().find(|dc| dc.id() == dc_id) {
+            Some(enums::DataCenter::Center(dc)) => dc,
+            Some(enums::DataCenter::Ws(_)) => return,
+            None => {
+                session.dcs.push(
+                    types::DataCenter {
+                        id: dc_id,
+                        ipv4: None,
+                        ipv6: None,
+                        port: addr.port() as i32,
+                        auth: None,
+                    }
+                    .into(),
+                );
+                match session.dcs.last_mut() {
+                    Some(enums::DataCenter::Center(dc)) => dc,
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        match addr {
+            SocketAddr::V4(addr) => {
+                dc.ipv4 = Some(i32::from_le_bytes(addr.ip().octets()));
+                dc.port = addr.port() as i32;
+            }
+            SocketAddr::V6(addr) => {
+                dc.ipv6 = Some(addr.ip().octets());
+                if dc.ipv4.is_none() {
+                    dc.port = addr.port() as i32;
+                }
+            }
+        }
+    }
+
     pub fn insert_dc_ws(&self, id: i32, url: &str, auth: [u8; 256]) {
         self.insert_dc(
             types::DataCenterWs {
@@ -158,6 +227,78 @@ impl Session {
             .map(|enums::User::User(user)| user.clone())
     }
 
+    /// Returns the chat the username was resolved to, along with the date when this happened,
+    /// if it's still remembered. Usernames are case-insensitive.
+    pub fn resolved_username(&self, username: &str) -> Option<(PackedChat, i32)> {
+        let username = username.to_ascii_lowercase();
+        self.session
+            .lock()
+            .unwrap()
+            .usernames
+            .iter()
+            .flatten()
+            .find_map(|enums::ResolvedUsername::Username(resolved)| {
+                (resolved.username == username)
+                    .then(|| PackedChat::from_bytes(&resolved.chat).ok())
+                    .flatten()
+                    .map(|chat| (chat, resolved.date))
+            })
+    }
+
+    /// Remember the chat the username was resolved to at the given date.
+    ///
+    /// Only a limited amount of usernames are remembered, so the ones resolved the longest ago
+    /// are forgotten when there are too many.
+    pub fn set_resolved_username(&self, username: &str, chat: PackedChat, date: i32) {
+        let username = username.to_ascii_lowercase();
+        let mut session = self.session.lock().unwrap();
+        let usernames = session.usernames.get_or_insert_with(Vec::new);
+        usernames
+            .retain(|enums::ResolvedUsername::Username(resolved)| resolved.username != username);
+        if usernames.len() >= MAX_RESOLVED_USERNAMES {
+            if let Some(oldest) = usernames
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, enums::ResolvedUsername::Username(resolved))| resolved.date)
+                .map(|(i, _)| i)
+            {
+                usernames.remove(oldest);
+            }
+        }
+        usernames.push(
+            types::ResolvedUsername {
+                username,
+                chat: chat.to_bytes().to_vec(),
+                date,
+            }
+            .into(),
+        );
+    }
+
+    /// Forget the usernames which were resolved to the chat with the given identifier, such as
+    /// when its usernames change.
+    pub fn forget_resolved_usernames(&self, chat_id: i64) {
+        let mut session = self.session.lock().unwrap();
+        if let Some(usernames) = session.usernames.as_mut() {
+            usernames.retain(|enums::ResolvedUsername::Username(resolved)| {
+                PackedChat::from_bytes(&resolved.chat).is_ok_and(|chat| chat.id != chat_id)
+            });
+        }
+    }
+
+    /// Forget the logged-in user, their update state, the usernames they resolved, and the
+    /// authorization key of every datacenter, while keeping the known datacenter addresses.
+    pub fn clear_authorization(&self) {
+        let mut session = self.session.lock().unwrap();
+        session.user = None;
+        session.state = None;
+        session.usernames = None;
+        session.dcs.iter_mut().for_each(|dc| match dc {
+            enums::DataCenter::Center(dc) => dc.auth = None,
+            enums::DataCenter::Ws(dc) => dc.auth = None,
+        });
+    }
+
     pub fn get_state(&self) -> Option<UpdateState> {
         let session = self.session.lock().unwrap();
         let enums::UpdateState::State(state) = session.state.clone()?;
@@ -169,7 +310,61 @@ impl Session {
     }
 
     pub fn get_dcs(&self) -> Vec<enums::DataCenter> {
-        self.session.lock().unwrap().dcs.iter().cloned().collect()
+        self.session.lock().unwrap().dcs.to_vec()
+    }
+
+    /// Apply several changes to the session at once.
+    ///
+    /// The closure is given a copy of the session to modify. If it returns `Ok`, all of its
+    /// changes are applied together, so that other uses of the session never observe (or save)
+    /// only part of them. If it returns `Err` or panics, all of them are discarded instead, and
+    /// the session is left untouched.
+    ///
+    /// The session is not locked while the closure runs, so the closure may still use the
+    /// original session, although it won't see the changes made to the copy. Only the parts of
+    /// the session the closure changed (each datacenter, the user, the update state or the
+    /// resolved usernames) are replaced, so changes made to other parts in the meantime are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn f(session: grammers_session::Session, state: grammers_session::UpdateState) {
+    /// session
+    ///     .batch(|tx| {
+    ///         tx.set_user(1234, 2, false);
+    ///         tx.set_state(state);
+    ///         Ok::<_, std::convert::Infallible>(())
+    ///     })
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn batch<T, E, F: FnOnce(&Session) -> Result<T, E>>(&self, f: F) -> Result<T, E> {
+        let before = self.session.lock().unwrap().clone();
+        let tx = Session {
+            session: Mutex::new(before.clone()),
+        };
+        let result = f(&tx)?;
+
+        let types::Session {
+            dcs,
+            user,
+            state,
+            usernames,
+        } = tx.session.into_inner().unwrap();
+        let mut session = self.session.lock().unwrap();
+        if dcs != before.dcs {
+            merge_dcs(&mut session.dcs, &before.dcs, dcs);
+        }
+        if user != before.user {
+            session.user = user;
+        }
+        if state != before.state {
+            session.state = state;
+        }
+        if usernames != before.usernames {
+            session.usernames = usernames;
+        }
+        Ok(result)
     }
 
     #[must_use]
@@ -203,3 +398,182 @@ impl fmt::Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+/// Apply the changes from `before` to `after` to the `current` datacenters, one datacenter at a
+/// time, so that the changes made to other datacenters since `before` are kept.
+fn merge_dcs(
+    current: &mut Vec<enums::DataCenter>,
+    before: &[enums::DataCenter],
+    after: Vec<enums::DataCenter>,
+) {
+    for dc in before {
+        if !after.iter().any(|d| d.id() == dc.id()) {
+            current.retain(|d| d.id() != dc.id());
+        }
+    }
+    for dc in after {
+        if before.contains(&dc) {
+            continue;
+        }
+        match current.iter_mut().find(|d| d.id() == dc.id()) {
+            Some(existing) => *existing = dc,
+            None => current.push(dc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_insert_dc_replaces_same_id() {
+        let session = Session::new();
+        session.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [2; 256]);
+        session.insert_dc_tcp(4, &"127.0.0.2:443".parse().unwrap(), [4; 256]);
+        session.insert_dc_tcp(4, &"127.0.0.2:443".parse().unwrap(), [5; 256]);
+
+        assert_eq!(session.get_dcs().len(), 2);
+        assert_eq!(session.dc_auth_key(2), Some([2; 256]));
+        assert_eq!(session.dc_auth_key(4), Some([5; 256]));
+    }
+
+    #[test]
+    fn check_batch_is_atomic() {
+        let session = Session::new();
+        session.set_user(1, 2, false);
+        let saved = session.save();
+
+        let result = session.batch(|tx| {
+            tx.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [2; 256]);
+            tx.set_user(3, 4, true);
+            tx.set_state(UpdateState {
+                pts: 1,
+                qts: 2,
+                date: 3,
+                seq: 4,
+                channels: Vec::new(),
+            });
+            Err("failed halfway")
+        });
+        assert_eq!(result, Err::<(), _>("failed halfway"));
+        assert_eq!(session.save(), saved);
+
+        let result = session.batch(|tx| {
+            tx.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [2; 256]);
+            tx.set_user(3, 4, true);
+            Ok::<_, ()>(5)
+        });
+        assert_eq!(result, Ok(5));
+        assert_eq!(session.dc_auth_key(2), Some([2; 256]));
+        assert_eq!(session.get_user().map(|user| user.id), Some(3));
+
+        // Panicking also discards the changes, and doesn't leave the session unusable.
+        let saved = session.save();
+        let result = std::panic::catch_unwind(|| {
+            session.batch(|tx| {
+                tx.set_user(5, 6, false);
+                panic!("failed halfway");
+                #[allow(unreachable_code)]
+                Ok::<_, ()>(())
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(session.save(), saved);
+
+        // The original session can be used while the batch runs, and unrelated changes are kept.
+        let result = session.batch(|tx| {
+            session.insert_dc_tcp(4, &"127.0.0.1:443".parse().unwrap(), [4; 256]);
+            assert_eq!(session.get_user().map(|user| user.id), Some(3));
+            tx.set_user(7, 8, false);
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(session.dc_auth_key(4), Some([4; 256]));
+        assert_eq!(session.get_user().map(|user| user.id), Some(7));
+
+        // Concurrent changes to other datacenters are kept too, even when the batch changes some.
+        let result = session.batch(|tx| {
+            session.insert_dc_tcp(5, &"127.0.0.1:443".parse().unwrap(), [5; 256]);
+            tx.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [6; 256]);
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(session.dc_auth_key(2), Some([6; 256]));
+        assert_eq!(session.dc_auth_key(4), Some([4; 256]));
+        assert_eq!(session.dc_auth_key(5), Some([5; 256]));
+    }
+
+    #[test]
+    fn check_resolved_usernames_are_bounded() {
+        let chat = |id| PackedChat {
+            ty: PackedType::Broadcast,
+            id,
+            access_hash: Some(id * 10),
+        };
+        let session = Session::new();
+        session.set_resolved_username("UserName", chat(1), 100);
+        session.set_resolved_username("username", chat(2), 200);
+        assert_eq!(session.resolved_username("USERNAME"), Some((chat(2), 200)));
+
+        for i in 0..MAX_RESOLVED_USERNAMES as i64 {
+            session.set_resolved_username(&format!("name{i}"), chat(i + 10), 300 + i as i32);
+        }
+        // The username resolved the longest ago is forgotten first.
+        assert_eq!(session.resolved_username("username"), None);
+        assert_eq!(session.resolved_username("name0"), Some((chat(10), 300)));
+
+        session.forget_resolved_usernames(10);
+        assert_eq!(session.resolved_username("name0"), None);
+
+        let session = Session::load(&session.save()).unwrap();
+        assert_eq!(session.resolved_username("name1"), Some((chat(11), 301)));
+        session.clear_authorization();
+        assert_eq!(session.resolved_username("name1"), None);
+    }
+
+    #[test]
+    fn check_sessions_without_usernames_load() {
+        // An empty session, saved before the resolved usernames were stored.
+        let mut data = 0xa73eb8ce_u32.to_le_bytes().to_vec();
+        data.extend(0_u32.to_le_bytes());
+        data.extend(0x1cb5c415_u32.to_le_bytes());
+        data.extend(0_u32.to_le_bytes());
+
+        let session = Session::load(&data).unwrap();
+        assert_eq!(session.resolved_username("username"), None);
+        assert_eq!(session.save(), data);
+    }
+
+    #[test]
+    fn check_clear_authorization() {
+        let session = Session::new();
+        session.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [2; 256]);
+        session.insert_dc_ws(4, "wss://127.0.0.2", [4; 256]);
+        session.set_user(123, 2, false);
+
+        session.clear_authorization();
+
+        assert!(!session.signed_in());
+        assert_eq!(session.get_dcs().len(), 2);
+        assert_eq!(session.dc_auth_key(2), None);
+        assert_eq!(session.dc_auth_key(4), None);
+    }
+
+    #[test]
+    fn check_set_dc_addr_keeps_auth_key() {
+        let session = Session::new();
+        session.insert_dc_tcp(2, &"127.0.0.1:443".parse().unwrap(), [2; 256]);
+
+        session.set_dc_addr(2, &"[::1]:80".parse().unwrap());
+        assert_eq!(session.dc_addr(2), Some("127.0.0.1:443".parse().unwrap()));
+
+        session.set_dc_addr(2, &"127.0.0.2:80".parse().unwrap());
+        assert_eq!(session.dc_addr(2), Some("127.0.0.2:80".parse().unwrap()));
+        assert_eq!(session.dc_auth_key(2), Some([2; 256]));
+
+        session.set_dc_addr(4, &"[::1]:80".parse().unwrap());
+        assert_eq!(session.dc_addr(4), Some("[::1]:80".parse().unwrap()));
+        assert_eq!(session.dc_auth_key(4), None);
+    }
+}
@@ -68,8 +68,9 @@ pub(super) fn update_short_message(
                     .into(),
                 ),
                 from_boosts_applied: None,
-                peer_id: tl::types::PeerChat {
-                    chat_id: short.user_id,
+                // The private chat is always the other user, even for outgoing messages.
+                peer_id: tl::types::PeerUser {
+                    user_id: short.user_id,
                 }
                 .into(),
                 saved_peer_id: None,
@@ -166,6 +167,8 @@ pub(super) fn update_short_chat_message(
     })
 }
 
+// Unlike the other short constructors, this one lacks the text and chat of the message, which
+// only the code that sent it knows about. An empty message is enough to account for its `pts`.
 pub(super) fn update_short_sent_message(
     short: tl::types::UpdateShortSentMessage,
 ) -> tl::types::UpdatesCombined {
@@ -537,3 +540,95 @@ impl PtsInfo {
         .filter(|info| info.pts != NO_PTS)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_message(combined: tl::types::UpdatesCombined) -> tl::types::Message {
+        assert_eq!(combined.updates.len(), 1);
+        match combined.updates.into_iter().next().unwrap() {
+            tl::enums::Update::NewMessage(tl::types::UpdateNewMessage {
+                message: tl::enums::Message::Message(message),
+                ..
+            }) => message,
+            update => panic!("unexpected update: {update:?}"),
+        }
+    }
+
+    fn short_message(out: bool) -> tl::types::UpdateShortMessage {
+        tl::types::UpdateShortMessage {
+            out,
+            mentioned: false,
+            media_unread: false,
+            silent: true,
+            id: 10,
+            user_id: 123,
+            message: "hello".to_string(),
+            pts: 5,
+            pts_count: 1,
+            date: 1000,
+            fwd_from: None,
+            via_bot_id: None,
+            reply_to: None,
+            entities: None,
+            ttl_period: None,
+        }
+    }
+
+    #[test]
+    fn check_short_message_is_expanded() {
+        let message = new_message(update_short_message(short_message(false), 1));
+        assert_eq!(message.id, 10);
+        assert_eq!(message.message, "hello");
+        assert_eq!(message.date, 1000);
+        assert!(message.silent);
+        assert_eq!(message.peer_id, tl::types::PeerUser { user_id: 123 }.into());
+        assert_eq!(
+            message.from_id,
+            Some(tl::types::PeerUser { user_id: 123 }.into())
+        );
+
+        // Outgoing messages are sent by the logged-in user, but still in the other's chat.
+        let message = new_message(update_short_message(short_message(true), 1));
+        assert_eq!(message.peer_id, tl::types::PeerUser { user_id: 123 }.into());
+        assert_eq!(
+            message.from_id,
+            Some(tl::types::PeerUser { user_id: 1 }.into())
+        );
+    }
+
+    #[test]
+    fn check_short_chat_message_is_expanded() {
+        let message = new_message(update_short_chat_message(
+            tl::types::UpdateShortChatMessage {
+                out: false,
+                mentioned: true,
+                media_unread: false,
+                silent: false,
+                id: 20,
+                from_id: 123,
+                chat_id: 456,
+                message: "hi all".to_string(),
+                pts: 6,
+                pts_count: 1,
+                date: 2000,
+                fwd_from: None,
+                via_bot_id: None,
+                reply_to: None,
+                entities: None,
+                ttl_period: Some(60),
+            },
+        ));
+        assert_eq!(message.id, 20);
+        assert_eq!(message.message, "hi all");
+        assert_eq!(message.date, 2000);
+        assert!(message.mentioned);
+        assert_eq!(message.ttl_period, Some(60));
+        assert_eq!(message.peer_id, tl::types::PeerChat { chat_id: 456 }.into());
+        assert_eq!(
+            message.from_id,
+            Some(tl::types::PeerUser { user_id: 123 }.into())
+        );
+    }
+}
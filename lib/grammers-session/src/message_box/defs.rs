@@ -90,6 +90,12 @@ pub struct MessageBox {
 
     /// This field is merely an optimization, to reuse the same allocation.
     pub(super) tmp_entries: HashSet<Entry>,
+
+    /// After how long without updates an entry should get difference.
+    pub(super) no_updates_timeout: Duration,
+
+    /// How long to wait for a possible gap to be resolved on its own before getting difference.
+    pub(super) possible_gap_timeout: Duration,
 }
 
 /// Represents the information needed to correctly handle a specific `tl::enums::Update`.
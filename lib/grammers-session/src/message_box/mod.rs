@@ -27,13 +27,15 @@ mod defs;
 
 use super::ChatHashCache;
 use crate::generated::enums::ChannelState as ChannelStateEnum;
+use crate::generated::enums::PossibleGap as PossibleGapEnum;
+use crate::generated::enums::UpdateEntry;
 use crate::generated::types::ChannelState;
 use crate::message_box::defs::PossibleGap;
-use crate::UpdateState;
+use crate::{PendingUpdates, UpdateState};
 pub(crate) use defs::Entry;
 pub use defs::{Gap, MessageBox};
 use defs::{PtsInfo, State, NO_DATE, NO_PTS, NO_SEQ, POSSIBLE_GAP_TIMEOUT};
-use grammers_tl_types as tl;
+use grammers_tl_types::{self as tl, Deserializable, Serializable};
 use log::{debug, info, trace, warn};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
@@ -42,13 +44,19 @@ use std::time::Duration;
 use tl::enums::InputChannel;
 use web_time::Instant;
 
-fn next_updates_deadline() -> Instant {
-    Instant::now() + defs::NO_UPDATES_TIMEOUT
-}
-
 #[allow(clippy::new_without_default)]
 /// Creation, querying, and setting base state.
 impl MessageBox {
+    /// The default time without updates after which the difference is fetched.
+    ///
+    /// See [`MessageBox::with_timeouts`].
+    pub const DEFAULT_NO_UPDATES_TIMEOUT: Duration = defs::NO_UPDATES_TIMEOUT;
+
+    /// The default time to wait for a gap to be resolved before the difference is fetched.
+    ///
+    /// See [`MessageBox::with_timeouts`].
+    pub const DEFAULT_POSSIBLE_GAP_TIMEOUT: Duration = defs::POSSIBLE_GAP_TIMEOUT;
+
     /// Create a new, empty [`MessageBox`].
     ///
     /// This is the only way it may return `true` from [`MessageBox::is_empty`].
@@ -62,13 +70,15 @@ impl MessageBox {
             getting_diff_for: HashSet::new(),
             next_deadline: None,
             tmp_entries: HashSet::new(),
+            no_updates_timeout: defs::NO_UPDATES_TIMEOUT,
+            possible_gap_timeout: POSSIBLE_GAP_TIMEOUT,
         }
     }
 
     /// Create a [`MessageBox`] from a previously known update state.
     pub fn load(state: UpdateState) -> Self {
         trace!("created new message box with state: {:?}", state);
-        let deadline = next_updates_deadline();
+        let deadline = Instant::now() + defs::NO_UPDATES_TIMEOUT;
         let mut map = HashMap::with_capacity(2 + state.channels.len());
         let mut getting_diff_for = HashSet::with_capacity(2 + state.channels.len());
 
@@ -107,7 +117,7 @@ impl MessageBox {
             state
                 .channels
                 .iter()
-                .map(|ChannelStateEnum::State(c)| (Entry::Channel(c.channel_id))),
+                .map(|ChannelStateEnum::State(c)| Entry::Channel(c.channel_id)),
         );
 
         Self {
@@ -118,7 +128,106 @@ impl MessageBox {
             getting_diff_for,
             next_deadline: Some(Entry::AccountWide),
             tmp_entries: HashSet::new(),
+            no_updates_timeout: defs::NO_UPDATES_TIMEOUT,
+            possible_gap_timeout: POSSIBLE_GAP_TIMEOUT,
+        }
+    }
+
+    /// Change how long to wait before getting difference, which should be done right after
+    /// creating the [`MessageBox`].
+    ///
+    /// `no_updates_timeout` is how long an entry may go without updates before its difference
+    /// is fetched. Shorter values catch up sooner, at the cost of more requests.
+    ///
+    /// `possible_gap_timeout` is how long to wait for a gap to be resolved on its own (because
+    /// the missing updates arrive late) before its difference is fetched.
+    pub fn with_timeouts(
+        mut self,
+        no_updates_timeout: Duration,
+        possible_gap_timeout: Duration,
+    ) -> Self {
+        self.no_updates_timeout = no_updates_timeout;
+        self.possible_gap_timeout = possible_gap_timeout;
+        let deadline = self.next_updates_deadline();
+        self.map
+            .values_mut()
+            .for_each(|state| state.deadline = deadline);
+        self
+    }
+
+    /// Return the updates buffered while waiting for gaps to be resolved, along with the
+    /// entries for which difference is being fetched.
+    ///
+    /// This is not part of [`MessageBox::session_state`] because it may be large. It can be
+    /// persisted separately, and restored with [`MessageBox::with_pending_updates`], so that
+    /// restarting while catching up does not lose the buffered updates.
+    pub fn pending_updates(&self) -> PendingUpdates {
+        fn update_entry(entry: &Entry) -> UpdateEntry {
+            match entry {
+                Entry::AccountWide => UpdateEntry::AccountWide,
+                Entry::SecretChats => UpdateEntry::SecretChats,
+                Entry::Channel(id) => {
+                    crate::generated::types::UpdateEntryChannel { channel_id: *id }.into()
+                }
+            }
+        }
+
+        PendingUpdates {
+            getting_diff_for: self.getting_diff_for.iter().map(update_entry).collect(),
+            possible_gaps: self
+                .possible_gaps
+                .iter()
+                .map(|(entry, gap)| {
+                    crate::generated::types::PossibleGap {
+                        entry: update_entry(entry),
+                        updates: gap.updates.iter().map(|u| u.to_bytes()).collect(),
+                    }
+                    .into()
+                })
+                .collect(),
+        }
+    }
+
+    /// Restore the state previously obtained with [`MessageBox::pending_updates`].
+    ///
+    /// Only entries with a known state are restored. Buffered updates for entries which are
+    /// getting difference are dropped, as getting difference will return them as well. Note
+    /// that this is the case for every entry after [`MessageBox::load`].
+    pub fn with_pending_updates(mut self, pending: PendingUpdates) -> Self {
+        fn entry(entry: UpdateEntry) -> Entry {
+            match entry {
+                UpdateEntry::AccountWide => Entry::AccountWide,
+                UpdateEntry::SecretChats => Entry::SecretChats,
+                UpdateEntry::Channel(c) => Entry::Channel(c.channel_id),
+            }
+        }
+
+        for entry in pending.getting_diff_for.into_iter().map(entry) {
+            self.try_begin_get_diff(entry);
         }
+
+        let deadline = Instant::now() + self.possible_gap_timeout;
+        for PossibleGapEnum::Gap(gap) in pending.possible_gaps {
+            let entry = entry(gap.entry);
+            if !self.map.contains_key(&entry) || self.getting_diff_for.contains(&entry) {
+                continue;
+            }
+            let updates = gap
+                .updates
+                .iter()
+                .filter_map(|u| tl::enums::Update::from_bytes(u).ok())
+                .collect::<Vec<_>>();
+            if !updates.is_empty() {
+                self.possible_gaps
+                    .insert(entry, PossibleGap { deadline, updates });
+            }
+        }
+
+        self
+    }
+
+    fn next_updates_deadline(&self) -> Instant {
+        Instant::now() + self.no_updates_timeout
     }
 
     /// Return the current state in a format that sessions understand.
@@ -155,6 +264,31 @@ impl MessageBox {
         }
     }
 
+    /// Return the identifiers of all the channels for which state is being tracked.
+    pub fn channel_ids(&self) -> Vec<i64> {
+        self.map
+            .keys()
+            .filter_map(|entry| match entry {
+                Entry::Channel(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Forget the state of a channel, including any pending difference or gap for it.
+    ///
+    /// This can be used to prune the state of channels that are no longer relevant, such as
+    /// the ones the user has left. The state will be set again if updates for the channel are
+    /// received later on.
+    pub fn forget_channel(&mut self, channel_id: i64) {
+        let entry = Entry::Channel(channel_id);
+        self.possible_gaps.remove(&entry);
+        if self.getting_diff_for.contains(&entry) {
+            self.end_get_diff(entry);
+        }
+        self.map.remove(&entry);
+    }
+
     /// Return true if the message box is empty and has no state yet.
     pub fn is_empty(&self) -> bool {
         self.map
@@ -164,6 +298,12 @@ impl MessageBox {
             == NO_PTS
     }
 
+    /// Return true if the difference is being fetched for any of the entries, which happens
+    /// when a gap is detected, until the missing updates have been fetched.
+    pub fn is_getting_difference(&self) -> bool {
+        !self.getting_diff_for.is_empty()
+    }
+
     /// Return the next deadline when receiving updates should timeout.
     ///
     /// If a deadline expired, the corresponding entries will be marked as needing to get its difference.
@@ -175,7 +315,7 @@ impl MessageBox {
             return now;
         }
 
-        let deadline = next_updates_deadline();
+        let deadline = self.next_updates_deadline();
 
         // Most of the time there will be zero or one gap in flight so finding the minimum is cheap.
         let deadline =
@@ -278,7 +418,7 @@ impl MessageBox {
             Instant::now()
                 + timeout
                     .map(|t| Duration::from_secs(t as _))
-                    .unwrap_or(defs::NO_UPDATES_TIMEOUT),
+                    .unwrap_or(self.no_updates_timeout),
         );
     }
 
@@ -288,7 +428,7 @@ impl MessageBox {
     /// updates will be fetched.
     pub fn set_state(&mut self, state: tl::enums::updates::State) {
         trace!("setting state {:?}", state);
-        let deadline = next_updates_deadline();
+        let deadline = self.next_updates_deadline();
         let state: tl::types::updates::State = state.into();
         self.map.insert(
             Entry::AccountWide,
@@ -313,10 +453,10 @@ impl MessageBox {
     /// The update state will only be updated if no entry was known previously.
     pub fn try_set_channel_state(&mut self, id: i64, pts: i32) {
         trace!("trying to set channel state for {}: {}", id, pts);
-        self.map.entry(Entry::Channel(id)).or_insert_with(|| State {
-            pts,
-            deadline: next_updates_deadline(),
-        });
+        let deadline = self.next_updates_deadline();
+        self.map
+            .entry(Entry::Channel(id))
+            .or_insert_with(|| State { pts, deadline });
     }
 
     /// Try to begin getting difference for the given entry.
@@ -345,7 +485,7 @@ impl MessageBox {
         if !self.getting_diff_for.remove(&entry) {
             panic!("Called end_get_diff on an entry which was not getting diff for");
         };
-        self.reset_deadline(entry, next_updates_deadline());
+        self.reset_deadline(entry, self.next_updates_deadline());
         assert!(
             !self.possible_gaps.contains_key(&entry),
             "gaps shouldn't be created while getting difference"
@@ -475,8 +615,13 @@ impl MessageBox {
         // is `0` and `1` respectively), so we sort them first.
         updates.sort_by_key(update_sort_key);
 
-        // Adding `possible_gaps.len()` is a guesstimate. Often it's just one update.
-        let mut result = Vec::with_capacity(updates.len() + self.possible_gaps.len());
+        // Any of the updates in possible gaps may be resolved by these, so make room for them too.
+        let gap_updates = self
+            .possible_gaps
+            .values()
+            .map(|gap| gap.updates.len())
+            .sum::<usize>();
+        let mut result = Vec::with_capacity(updates.len() + gap_updates);
 
         // This loop does a lot at once to reduce the amount of times we need to iterate over
         // the updates as an optimization.
@@ -499,7 +644,7 @@ impl MessageBox {
                 any_pts_applied |= entry.is_some();
             }
         }
-        self.reset_deadlines(&reset_deadlines_for, next_updates_deadline());
+        self.reset_deadlines(&reset_deadlines_for, self.next_updates_deadline());
         reset_deadlines_for.clear();
         self.tmp_entries = reset_deadlines_for;
 
@@ -523,16 +668,12 @@ impl MessageBox {
             // For each update in possible gaps, see if the gap has been resolved already.
             let keys = self.possible_gaps.keys().copied().collect::<Vec<_>>();
             for key in keys {
-                self.possible_gaps
-                    .get_mut(&key)
-                    .unwrap()
-                    .updates
-                    .sort_by_key(update_sort_key);
+                let mut updates = mem::take(&mut self.possible_gaps.get_mut(&key).unwrap().updates);
+                updates.sort_by_key(update_sort_key);
 
-                for _ in 0..self.possible_gaps[&key].updates.len() {
-                    let update = self.possible_gaps.get_mut(&key).unwrap().updates.remove(0);
+                for update in updates {
                     // If this fails to apply, it will get re-inserted at the end.
-                    // All should fail, so the order will be preserved (it would've cycled once).
+                    // All should fail, so the order will be preserved.
                     if let (_, Some(update)) = self.apply_pts_info(update) {
                         result.push(update);
                     }
@@ -598,10 +739,11 @@ impl MessageBox {
                         pts.entry, local_pts, pts.pts_count, pts.pts
                     );
                     // TODO store chats too?
+                    let deadline = Instant::now() + self.possible_gap_timeout;
                     self.possible_gaps
                         .entry(pts.entry)
                         .or_insert_with(|| PossibleGap {
-                            deadline: Instant::now() + POSSIBLE_GAP_TIMEOUT,
+                            deadline,
                             updates: Vec::new(),
                         })
                         .updates
@@ -614,11 +756,12 @@ impl MessageBox {
         // else, there is no previous `pts` known, and because this update has to be "right"
         // (it's the first one) our `local_pts` must be `pts - pts_count`.
 
+        let deadline = self.next_updates_deadline();
         self.map
             .entry(pts.entry)
             .or_insert_with(|| State {
                 pts: NO_PTS,
-                deadline: next_updates_deadline(),
+                deadline,
             })
             .pts = pts.pts;
 
@@ -756,12 +899,13 @@ impl MessageBox {
         chat_hashes: &mut ChatHashCache,
     ) -> defs::UpdateAndPeers {
         self.map.get_mut(&Entry::AccountWide).unwrap().pts = state.pts;
+        let deadline = self.next_updates_deadline();
         self.map
             .entry(Entry::SecretChats)
             // AccountWide affects SecretChats, but this may not have been initialized yet (#258)
             .or_insert_with(|| State {
                 pts: NO_PTS,
-                deadline: next_updates_deadline(),
+                deadline,
             })
             .pts = state.qts;
         self.date = state.date;
@@ -809,6 +953,19 @@ impl MessageBox {
 
 /// Getting and applying channel difference.
 impl MessageBox {
+    /// Return the identifier of a channel for which difference needs to be fetched, but whose
+    /// access hash is not known.
+    ///
+    /// The hash should be found and added to the cache before calling
+    /// [`MessageBox::get_channel_difference`], which would otherwise stop getting difference
+    /// for the channel. If it cannot be found, [`MessageBox::forget_channel`] should be used.
+    pub fn channel_missing_hash(&self, chat_hashes: &ChatHashCache) -> Option<i64> {
+        self.getting_diff_for.iter().find_map(|&entry| match entry {
+            Entry::Channel(id) if chat_hashes.get(id).is_none() => Some(id),
+            _ => None,
+        })
+    }
+
     /// Return the request that needs to be made to get a channel's difference, if any.
     pub fn get_channel_difference(
         &mut self,
@@ -854,10 +1011,9 @@ impl MessageBox {
                 "cannot getChannelDifference for {} as we're missing its hash",
                 id
             );
-            self.end_get_diff(entry);
             // Remove the outdated `pts` entry from the map so that the next update can correct
             // it. Otherwise, it will spam that the access hash is missing.
-            self.map.remove(&entry);
+            self.forget_channel(id);
             None
         }
     }
@@ -1007,3 +1163,271 @@ pub enum PrematureEndReason {
     TemporaryServerIssues,
     Banned,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_box_with_gap(possible_gap_timeout: Duration) -> MessageBox {
+        let mut message_box = MessageBox::new()
+            .with_timeouts(MessageBox::DEFAULT_NO_UPDATES_TIMEOUT, possible_gap_timeout);
+        message_box.set_state(
+            tl::types::updates::State {
+                pts: 1,
+                qts: 1,
+                date: 1,
+                seq: 1,
+                unread_count: 0,
+            }
+            .into(),
+        );
+
+        // The update with `pts = 2` is missing.
+        let updates = tl::types::UpdateShort {
+            update: tl::types::UpdateDeleteMessages {
+                messages: vec![3],
+                pts: 3,
+                pts_count: 1,
+            }
+            .into(),
+            date: 1,
+        }
+        .into();
+        let (updates, _, _) = message_box
+            .process_updates(updates, &ChatHashCache::new(None))
+            .unwrap();
+        assert!(updates.is_empty());
+        message_box
+    }
+
+    #[test]
+    fn check_gap_timeout_is_configurable() {
+        let timeout = Duration::from_millis(1);
+        let mut short = message_box_with_gap(timeout);
+        let mut default = message_box_with_gap(MessageBox::DEFAULT_POSSIBLE_GAP_TIMEOUT);
+
+        std::thread::sleep(timeout * 2);
+
+        short.check_deadlines();
+        default.check_deadlines();
+        assert!(short.get_difference().is_some());
+        assert!(default.get_difference().is_none());
+    }
+
+    #[test]
+    fn check_pending_updates_round_trip() {
+        let mut message_box = message_box_with_gap(MessageBox::DEFAULT_POSSIBLE_GAP_TIMEOUT);
+        message_box.try_set_channel_state(123, 10);
+        message_box.try_begin_get_diff(Entry::Channel(123));
+
+        let bytes = message_box.pending_updates().to_bytes();
+        let pending = PendingUpdates::from_bytes(&bytes).unwrap();
+
+        let mut restored = MessageBox::new();
+        restored.set_state(
+            tl::types::updates::State {
+                pts: 1,
+                qts: 1,
+                date: 1,
+                seq: 1,
+                unread_count: 0,
+            }
+            .into(),
+        );
+        restored.try_set_channel_state(123, 10);
+        let restored = restored.with_pending_updates(pending);
+
+        assert_eq!(
+            restored.getting_diff_for,
+            HashSet::from([Entry::Channel(123)])
+        );
+        assert_eq!(restored.possible_gaps.len(), 1);
+        assert_eq!(
+            restored.possible_gaps[&Entry::AccountWide].updates,
+            message_box.possible_gaps[&Entry::AccountWide].updates
+        );
+    }
+
+    #[test]
+    fn check_large_batches_reset_every_deadline() {
+        const CHANNELS: i64 = 3;
+        const UPDATES_PER_ENTRY: i32 = 2500;
+
+        let mut message_box = MessageBox::new();
+        message_box.set_state(
+            tl::types::updates::State {
+                pts: 1,
+                qts: 1,
+                date: 1,
+                seq: 1,
+                unread_count: 0,
+            }
+            .into(),
+        );
+        for channel_id in 1..=CHANNELS {
+            message_box.try_set_channel_state(channel_id, 1);
+        }
+
+        let mut updates = Vec::new();
+        for pts in 2..UPDATES_PER_ENTRY + 2 {
+            updates.push(
+                tl::types::UpdateDeleteMessages {
+                    messages: Vec::new(),
+                    pts,
+                    pts_count: 1,
+                }
+                .into(),
+            );
+            for channel_id in 1..=CHANNELS {
+                updates.push(
+                    tl::types::UpdateDeleteChannelMessages {
+                        channel_id,
+                        messages: Vec::new(),
+                        pts,
+                        pts_count: 1,
+                    }
+                    .into(),
+                );
+            }
+        }
+        let count = updates.len();
+        assert_eq!(count, 10000);
+
+        let updates = tl::types::Updates {
+            updates,
+            users: Vec::new(),
+            chats: Vec::new(),
+            date: NO_DATE,
+            seq: NO_SEQ,
+        }
+        .into();
+        let before = Instant::now();
+        let (updates, _, _) = message_box
+            .process_updates(updates, &ChatHashCache::new(None))
+            .unwrap();
+
+        // The result is allocated once, with room for exactly all of the updates.
+        assert_eq!(updates.len(), count);
+        assert_eq!(updates.capacity(), count);
+
+        // Every entry had its deadline reset, once the updates were processed.
+        assert_eq!(message_box.map.len(), 2 + CHANNELS as usize);
+        for entry in (1..=CHANNELS)
+            .map(Entry::Channel)
+            .chain([Entry::AccountWide])
+        {
+            assert!(message_box.map[&entry].deadline >= before + message_box.no_updates_timeout);
+        }
+    }
+
+    #[test]
+    fn check_channel_missing_hash_can_be_backfilled() {
+        let channel = || -> tl::enums::Chat {
+            tl::types::ChannelForbidden {
+                broadcast: true,
+                megagroup: false,
+                id: 123,
+                access_hash: 456,
+                title: String::new(),
+                until_date: None,
+            }
+            .into()
+        };
+
+        let mut message_box = MessageBox::new();
+        let mut chat_hashes = ChatHashCache::new(None);
+        message_box.try_set_channel_state(123, 10);
+        message_box.try_begin_get_diff(Entry::Channel(123));
+
+        assert_eq!(message_box.channel_missing_hash(&chat_hashes), Some(123));
+        assert!(chat_hashes.extend(&[], &[channel()]));
+        assert_eq!(message_box.channel_missing_hash(&chat_hashes), None);
+
+        let request = message_box.get_channel_difference(&chat_hashes).unwrap();
+        assert_eq!(
+            request.channel,
+            tl::types::InputChannel {
+                channel_id: 123,
+                access_hash: 456,
+            }
+            .into()
+        );
+        assert_eq!(request.pts, 10);
+    }
+
+    #[test]
+    fn check_channel_can_be_forgotten() {
+        let mut message_box = MessageBox::new();
+        message_box.try_set_channel_state(123, 10);
+        message_box.try_begin_get_diff(Entry::Channel(123));
+
+        message_box.forget_channel(123);
+
+        assert!(message_box.getting_diff_for.is_empty());
+        assert!(!message_box.map.contains_key(&Entry::Channel(123)));
+        assert!(message_box
+            .get_channel_difference(&ChatHashCache::new(None))
+            .is_none());
+    }
+
+    #[test]
+    fn check_forgotten_channels_are_pruned() {
+        let mut message_box = message_box_with_gap(Duration::from_secs(60));
+        message_box.try_set_channel_state(123, 10);
+        message_box.try_set_channel_state(456, 20);
+
+        // The update with `pts = 11` is missing.
+        let updates = tl::types::UpdateShort {
+            update: tl::types::UpdateDeleteChannelMessages {
+                channel_id: 123,
+                messages: vec![3],
+                pts: 12,
+                pts_count: 1,
+            }
+            .into(),
+            date: 1,
+        }
+        .into();
+        let (updates, _, _) = message_box
+            .process_updates(updates, &ChatHashCache::new(None))
+            .unwrap();
+        assert!(updates.is_empty());
+        assert!(message_box.possible_gaps.contains_key(&Entry::Channel(123)));
+
+        let mut ids = message_box.channel_ids();
+        ids.sort();
+        assert_eq!(ids, vec![123, 456]);
+
+        message_box.forget_channel(123);
+
+        assert_eq!(message_box.channel_ids(), vec![456]);
+        assert!(message_box
+            .session_state()
+            .channels
+            .iter()
+            .all(|crate::enums::ChannelState::State(s)| s.channel_id != 123));
+        assert!(!message_box.getting_diff_for.contains(&Entry::Channel(123)));
+        assert!(!message_box.possible_gaps.contains_key(&Entry::Channel(123)));
+        assert!(!message_box.map.contains_key(&Entry::Channel(123)));
+        assert!(message_box.possible_gaps.contains_key(&Entry::AccountWide));
+    }
+
+    #[test]
+    fn check_no_updates_timeout_is_configurable() {
+        let timeout = Duration::from_secs(60);
+        let mut message_box =
+            MessageBox::new().with_timeouts(timeout, MessageBox::DEFAULT_POSSIBLE_GAP_TIMEOUT);
+        message_box.set_state(
+            tl::types::updates::State {
+                pts: 1,
+                qts: 1,
+                date: 1,
+                seq: 1,
+                unread_count: 0,
+            }
+            .into(),
+        );
+
+        assert!(message_box.check_deadlines() <= Instant::now() + timeout);
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers to build the values Telegram would respond with, to test update handling offline.
+//!
+//! Only available with the `test-util` feature.
+use grammers_tl_types as tl;
+
+/// Build the update state with the given values and no unread messages.
+pub fn state(pts: i32, qts: i32, date: i32, seq: i32) -> tl::enums::updates::State {
+    tl::types::updates::State {
+        pts,
+        qts,
+        date,
+        seq,
+        unread_count: 0,
+    }
+    .into()
+}
+
+/// Assembles the result of [`updates.getDifference`], as fed to
+/// [`MessageBox::apply_difference`](crate::MessageBox::apply_difference).
+///
+/// The contents are only used by the variants which can carry them. An empty difference only
+/// has a date and sequence number, and a difference which is too long only has a `pts`.
+///
+/// [`updates.getDifference`]: https://core.telegram.org/method/updates.getDifference
+///
+/// # Examples
+///
+/// ```
+/// use grammers_session::testing::{state, DifferenceBuilder};
+/// use grammers_tl_types as tl;
+///
+/// let difference = DifferenceBuilder::new()
+///     .with_message(tl::types::MessageEmpty { id: 1, peer_id: None }.into())
+///     .slice(state(2, 0, 1, 1));
+///
+/// assert!(matches!(difference, tl::enums::updates::Difference::Slice(_)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DifferenceBuilder {
+    new_messages: Vec<tl::enums::Message>,
+    new_encrypted_messages: Vec<tl::enums::EncryptedMessage>,
+    other_updates: Vec<tl::enums::Update>,
+    chats: Vec<tl::enums::Chat>,
+    users: Vec<tl::enums::User>,
+}
+
+impl DifferenceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a new message which was sent while the client was offline.
+    pub fn with_message(mut self, message: tl::enums::Message) -> Self {
+        self.new_messages.push(message);
+        self
+    }
+
+    /// Add a new message from a secret chat.
+    pub fn with_encrypted_message(mut self, message: tl::enums::EncryptedMessage) -> Self {
+        self.new_encrypted_messages.push(message);
+        self
+    }
+
+    /// Add any other update which occurred while the client was offline.
+    pub fn with_update(mut self, update: tl::enums::Update) -> Self {
+        self.other_updates.push(update);
+        self
+    }
+
+    /// Add a chat mentioned by the messages or updates.
+    pub fn with_chat(mut self, chat: tl::enums::Chat) -> Self {
+        self.chats.push(chat);
+        self
+    }
+
+    /// Add a user mentioned by the messages or updates.
+    pub fn with_user(mut self, user: tl::enums::User) -> Self {
+        self.users.push(user);
+        self
+    }
+
+    /// A difference without any new updates.
+    pub fn empty(date: i32, seq: i32) -> tl::enums::updates::Difference {
+        tl::types::updates::DifferenceEmpty { date, seq }.into()
+    }
+
+    /// A difference with too many updates, after which the state should be fetched again.
+    pub fn too_long(pts: i32) -> tl::enums::updates::Difference {
+        tl::types::updates::DifferenceTooLong { pts }.into()
+    }
+
+    /// The complete difference, after which the client is up to date with `state`.
+    pub fn difference(self, state: tl::enums::updates::State) -> tl::enums::updates::Difference {
+        tl::types::updates::Difference {
+            new_messages: self.new_messages,
+            new_encrypted_messages: self.new_encrypted_messages,
+            other_updates: self.other_updates,
+            chats: self.chats,
+            users: self.users,
+            state,
+        }
+        .into()
+    }
+
+    /// Part of the difference, after which more must be fetched starting at `intermediate_state`.
+    pub fn slice(
+        self,
+        intermediate_state: tl::enums::updates::State,
+    ) -> tl::enums::updates::Difference {
+        tl::types::updates::DifferenceSlice {
+            new_messages: self.new_messages,
+            new_encrypted_messages: self.new_encrypted_messages,
+            other_updates: self.other_updates,
+            chats: self.chats,
+            users: self.users,
+            intermediate_state,
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChatHashCache, MessageBox, UpdateState};
+    use tl::{Deserializable, Serializable};
+
+    fn message(id: i32) -> tl::enums::Message {
+        tl::types::MessageEmpty { id, peer_id: None }.into()
+    }
+
+    fn loaded_message_box() -> MessageBox {
+        // Loading a previous state starts getting the difference.
+        MessageBox::load(UpdateState {
+            pts: 1,
+            qts: 1,
+            date: 1,
+            seq: 1,
+            channels: Vec::new(),
+        })
+    }
+
+    fn assert_state(message_box: &MessageBox, pts: i32, date: i32, seq: i32) {
+        let state = message_box.session_state();
+        assert_eq!((state.pts, state.date, state.seq), (pts, date, seq));
+    }
+
+    #[test]
+    fn check_built_differences_are_applied() {
+        let mut chat_hashes = ChatHashCache::new(None);
+
+        let slice = DifferenceBuilder::new()
+            .with_message(message(2))
+            .with_message(message(3))
+            .slice(state(3, 1, 2, 2));
+        let difference = DifferenceBuilder::new()
+            .with_message(message(4))
+            .difference(state(4, 1, 3, 3));
+        for variant in [&slice, &difference] {
+            let bytes = variant.to_bytes();
+            assert_eq!(
+                &tl::enums::updates::Difference::from_bytes(&bytes).unwrap(),
+                variant
+            );
+        }
+
+        // A slice is applied, but more difference must be fetched from its state.
+        let mut message_box = loaded_message_box();
+        let (updates, _, _) = message_box.apply_difference(slice, &mut chat_hashes);
+        assert_eq!(updates.len(), 2);
+        assert_state(&message_box, 3, 2, 2);
+        assert_eq!(message_box.get_difference().unwrap().pts, 3);
+
+        let (updates, _, _) = message_box.apply_difference(difference, &mut chat_hashes);
+        assert_eq!(updates.len(), 1);
+        assert_state(&message_box, 4, 3, 3);
+        assert!(message_box.get_difference().is_none());
+
+        // Neither an empty nor a too-long difference carry updates.
+        let mut message_box = loaded_message_box();
+        let (updates, _, _) =
+            message_box.apply_difference(DifferenceBuilder::empty(5, 6), &mut chat_hashes);
+        assert!(updates.is_empty());
+        assert_state(&message_box, 1, 5, 6);
+        assert!(message_box.get_difference().is_none());
+
+        let mut message_box = loaded_message_box();
+        let (updates, _, _) =
+            message_box.apply_difference(DifferenceBuilder::too_long(100), &mut chat_hashes);
+        assert!(updates.is_empty());
+        assert_state(&message_box, 100, 1, 1);
+        assert!(message_box.get_difference().is_none());
+    }
+}
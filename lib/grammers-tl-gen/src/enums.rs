@@ -174,6 +174,55 @@ fn write_common_field_impl<W: Write>(
     Ok(())
 }
 
+/// Writes impl for getting the constructor identifier of the enum variant
+///
+/// ```ignore
+/// impl Name {
+///     pub fn constructor_id(&self) -> u32 {
+///         use crate::Identifiable;
+///         match self {
+///             Self::Variant(_) => crate::types::Name::CONSTRUCTOR_ID,
+///         }
+///     }
+/// }
+/// ```
+fn write_constructor_id_impl<W: Write>(
+    file: &mut W,
+    indent: &str,
+    ty: &Type,
+    metadata: &Metadata,
+) -> io::Result<()> {
+    writeln!(
+        file,
+        "{}impl {} {{",
+        indent,
+        rustifier::types::type_name(ty)
+    )?;
+    writeln!(
+        file,
+        "{indent}    /// The constructor identifier of the variant, as sent over the network."
+    )?;
+    // Crates which only use the definitions privately may never need this helper.
+    writeln!(file, "{indent}    #[allow(dead_code)]")?;
+    writeln!(file, "{indent}    pub fn constructor_id(&self) -> u32 {{")?;
+    writeln!(file, "{indent}        use crate::Identifiable;")?;
+    writeln!(file, "{indent}        match self {{")?;
+    for d in metadata.defs_with_type(ty) {
+        writeln!(
+            file,
+            "{}            Self::{}{} => {}::CONSTRUCTOR_ID,",
+            indent,
+            rustifier::definitions::variant_name(d),
+            if d.params.is_empty() { "" } else { "(_)" },
+            rustifier::definitions::qual_name(d)
+        )?;
+    }
+    writeln!(file, "{indent}        }}")?;
+    writeln!(file, "{indent}    }}")?;
+    writeln!(file, "{indent}}}")?;
+    Ok(())
+}
+
 /// Defines the `impl Serializable` corresponding to the type definitions:
 ///
 /// ```ignore
@@ -364,6 +413,7 @@ fn write_definition<W: Write>(
 ) -> io::Result<()> {
     write_enum(file, indent, ty, metadata, config)?;
     write_common_field_impl(file, indent, ty, metadata, config)?;
+    write_constructor_id_impl(file, indent, ty, metadata)?;
     write_serializable(file, indent, ty, metadata)?;
     write_deserializable(file, indent, ty, metadata)?;
     if config.impl_from_type {
@@ -0,0 +1,172 @@
+// Copyright 2020 - developers of the `grammers` project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Code to generate the `enum` able to hold any deserialized function.
+
+use crate::rustifier;
+use crate::Config;
+use grammers_tl_parser::tl::{Category, Definition, ParameterType};
+use std::io::{self, Write};
+
+/// Functions with generic parameters can't be deserialized without knowing which type they wrap.
+fn is_generic(def: &Definition) -> bool {
+    def.params.iter().any(|param| match &param.ty {
+        ParameterType::Flags => false,
+        ParameterType::Normal { ty, .. } => ty.generic_ref,
+    })
+}
+
+/// The name of the variant for the function, including its namespace.
+///
+/// For example, transforms `"help.getNearestDc"` into `"HelpGetNearestDc"`.
+fn variant_name(def: &Definition) -> String {
+    let mut result = String::new();
+    def.namespace.iter().for_each(|ns| {
+        let mut chars = ns.chars();
+        if let Some(c) = chars.next() {
+            result.push(c.to_ascii_uppercase());
+            result.extend(chars);
+        }
+    });
+    result.push_str(&rustifier::definitions::type_name(def));
+    result
+}
+
+/// The qualified path to the function's `struct`.
+fn qual_name(def: &Definition) -> String {
+    let mut result = String::new();
+    result.push_str("crate::functions::");
+    def.namespace.iter().for_each(|ns| {
+        result.push_str(ns);
+        result.push_str("::");
+    });
+    result.push_str(&rustifier::definitions::type_name(def));
+    result
+}
+
+/// Writes an enumeration with a variant for every non-generic function, along with the code
+/// needed to deserialize it by dispatching on the constructor identifier:
+///
+/// ```ignore
+/// pub enum AnyFunction {
+///     NsName(crate::functions::ns::Name),
+/// }
+///
+/// impl AnyFunction {
+///     pub fn deserialize_body(id: u32, buf: crate::deserialize::Buffer) -> crate::deserialize::Result<Self> {
+///         Ok(match id {
+///             0x1234 => Self::NsName(crate::functions::ns::Name::deserialize(buf)?),
+///             _ => return Err(crate::deserialize::Error::UnexpectedConstructor { id }),
+///         })
+///     }
+/// }
+/// ```
+pub(crate) fn write_any_function_enum<W: Write>(
+    file: &mut W,
+    definitions: &[Definition],
+    config: &Config,
+) -> io::Result<()> {
+    let functions = definitions
+        .iter()
+        .filter(|def| def.category == Category::Functions && !is_generic(def))
+        .collect::<Vec<_>>();
+
+    writeln!(
+        file,
+        "\
+         /// Any of the [`functions`] without generic parameters.\n\
+         ///\n\
+         /// Deserializing this type dispatches on the constructor identifier to produce the\n\
+         /// right function, which is useful to inspect the requests sent by a client.\n\
+         ///\n\
+         /// [`functions`]: functions/index.html"
+    )?;
+    if config.impl_debug {
+        writeln!(file, "#[derive(Debug)]")?;
+    }
+    if config.impl_serde {
+        writeln!(
+            file,
+            "#[derive(serde_derive::Serialize, serde_derive::Deserialize)]"
+        )?;
+    }
+    writeln!(file, "#[derive(Clone, PartialEq)]")?;
+    writeln!(file, "#[allow(clippy::large_enum_variant)]")?;
+    writeln!(file, "pub enum AnyFunction {{")?;
+    for def in functions.iter() {
+        writeln!(file, "    {}({}),", variant_name(def), qual_name(def))?;
+    }
+    writeln!(file, "}}")?;
+
+    writeln!(file, "#[allow(clippy::unreadable_literal)]")?;
+    writeln!(file, "impl AnyFunction {{")?;
+    writeln!(
+        file,
+        "    /// Deserialize the function with the given constructor identifier from the buffer,\n\
+         \x20   /// which should only contain the rest of the function after the identifier."
+    )?;
+    writeln!(
+        file,
+        "    pub fn deserialize_body(id: u32, buf: crate::deserialize::Buffer) -> crate::deserialize::Result<Self> {{"
+    )?;
+    writeln!(file, "        use crate::Deserializable;")?;
+    writeln!(file, "        Ok(match id {{")?;
+    for def in functions.iter() {
+        writeln!(
+            file,
+            "            0x{:x} => Self::{}({}::deserialize(buf)?),",
+            def.id,
+            variant_name(def),
+            qual_name(def)
+        )?;
+    }
+    writeln!(
+        file,
+        "            _ => return Err(crate::deserialize::Error::UnexpectedConstructor {{ id }}),"
+    )?;
+    writeln!(file, "        }})")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    writeln!(file, "impl crate::Deserializable for AnyFunction {{")?;
+    writeln!(
+        file,
+        "    fn deserialize(buf: crate::deserialize::Buffer) -> crate::deserialize::Result<Self> {{"
+    )?;
+    writeln!(file, "        let id = u32::deserialize(buf)?;")?;
+    writeln!(file, "        Self::deserialize_body(id, buf)")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_variant_name() {
+        let def = "help.getNearestDc = NearestDc".parse().unwrap();
+        assert_eq!(variant_name(&def), "HelpGetNearestDc");
+
+        let def = "ping#7abe77ec ping_id:long = Pong".parse().unwrap();
+        assert_eq!(variant_name(&def), "Ping");
+    }
+
+    #[test]
+    fn check_generic_functions() {
+        let def = "invokeWithLayer {X:Type} layer:int query:!X = X"
+            .parse()
+            .unwrap();
+        assert!(is_generic(&def));
+
+        let def = "help.getNearestDc = NearestDc".parse().unwrap();
+        assert!(!is_generic(&def));
+    }
+}
@@ -12,6 +12,7 @@
 #![deny(unsafe_code)]
 
 mod enums;
+mod functions;
 mod grouper;
 mod metadata;
 mod rustifier;
@@ -99,6 +100,9 @@ pub fn name_for_id(id: u32) -> &'static str {{
     structs::write_category_mod(file, Category::Types, definitions, &metadata, config)?;
     structs::write_category_mod(file, Category::Functions, definitions, &metadata, config)?;
     enums::write_enums_mod(file, definitions, &metadata, config)?;
+    if config.deserializable_functions {
+        functions::write_any_function_enum(file, definitions, config)?;
+    }
 
     Ok(())
 }
@@ -72,6 +72,23 @@ fn recursive_types_direct_boxed() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn recursive_types_try_from_unboxed() -> io::Result<()> {
+    let definitions = get_definitions(
+        "
+        textPlain#744694e0 text:string = RichText;
+        textBold#6724abc4 text:RichText = RichText;
+    ",
+    );
+    let result = gen_rust_code(&definitions)?;
+    eprintln!("{result}");
+    assert!(result.contains("impl TryFrom<crate::enums::RichText> for TextBold {"));
+    assert!(result.contains("crate::enums::RichText::TextBold(x) => Ok(*x),"));
+    assert!(result.contains("impl TryFrom<crate::enums::RichText> for TextPlain {"));
+    assert!(result.contains("crate::enums::RichText::TextPlain(x) => Ok(x),"));
+    Ok(())
+}
+
 #[test]
 fn recursive_types_indirect_boxed() -> io::Result<()> {
     let definitions = get_definitions(
@@ -136,3 +153,25 @@ fn generic_bytes_with_serde_bytes() -> io::Result<()> {
     assert!(result.contains("pub stripped_thumb: Option<Vec<u8>>,"));
     Ok(())
 }
+
+#[test]
+fn enums_expose_variant_constructor_id() -> io::Result<()> {
+    let definitions = get_definitions(
+        "
+        inputPeerEmpty#7f3b18ea = InputPeer;
+        inputPeerChat#35a95cb9 chat_id:long = InputPeer;
+        textPlain#744694e0 text:string = RichText;
+        textBold#6724abc4 text:RichText = RichText;
+        ",
+    );
+
+    let result = gen_rust_code(&definitions)?;
+    eprintln!("{result}");
+    assert!(result.contains("impl InputPeer {"));
+    assert!(result.contains("pub fn constructor_id(&self) -> u32 {"));
+    assert!(result.contains("Self::Empty => crate::types::InputPeerEmpty::CONSTRUCTOR_ID,"));
+    assert!(result.contains("Self::Chat(_) => crate::types::InputPeerChat::CONSTRUCTOR_ID,"));
+    assert!(result.contains("impl RichText {"));
+    assert!(result.contains("Self::TextBold(_) => crate::types::TextBold::CONSTRUCTOR_ID,"));
+    Ok(())
+}
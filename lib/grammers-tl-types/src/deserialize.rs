@@ -100,6 +100,9 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// This trait allows for data serialized according to the
 /// [Binary Data Serialization] to be deserialized into concrete instances.
 ///
+/// Numbers are always read in little-endian byte order, as the protocol
+/// requires, no matter the endianness of the host.
+///
 /// [Binary Data Serialization]: https://core.telegram.org/mtproto/serialize
 pub trait Deserializable {
     /// Deserializes an instance of the type from a given buffer.
@@ -53,7 +53,8 @@
 //! * `deserializable-functions`: implements [`Deserializable`] for
 //!   [`functions`]. This might be of interest for server implementations,
 //!   which need to deserialize the client's requests, but is otherwise not
-//!   required.
+//!   required. It also generates [`AnyFunction`], which can deserialize any
+//!   request that isn't generic.
 //!
 //! * `impl-debug`: implements `Debug` for the generated code.
 //!
@@ -83,6 +84,8 @@ mod generated;
 pub mod serialize;
 
 pub use deserialize::{Cursor, Deserializable};
+#[cfg(feature = "deserializable-functions")]
+pub use generated::AnyFunction;
 pub use generated::{enums, functions, name_for_id, types, LAYER};
 pub use serialize::Serializable;
 
@@ -9,6 +9,9 @@
 /// This trait allows for concrete instances to be serialized into
 /// binary data as specified by the [Binary Data Serialization].
 ///
+/// Numbers are always serialized in little-endian byte order, as the
+/// protocol requires, no matter the endianness of the host.
+///
 /// [Binary Data Serialization]: https://core.telegram.org/mtproto/serialize
 pub trait Serializable {
     /// Serializes the instance into the given buffer.
@@ -22,6 +25,54 @@ pub trait Serializable {
         self.serialize(&mut buffer);
         buffer
     }
+
+    /// The exact amount of bytes the instance serializes into.
+    ///
+    /// This performs a serialization pass without storing any of the bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use grammers_tl_types::Serializable;
+    ///
+    /// assert_eq!(0i64.serialized_len(), 8);
+    /// assert_eq!(vec![1i32, 2, 3].serialized_len(), 20);
+    /// ```
+    fn serialized_len(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        self.serialize(&mut counter);
+        counter.0
+    }
+
+    /// Like [`Serializable::to_bytes`], but the returned buffer is allocated once with the
+    /// exact size needed, rather than growing as the bytes are written.
+    ///
+    /// This avoids repeated reallocations when serializing large objects, at the cost of
+    /// computing their [`Serializable::serialized_len`] beforehand.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use grammers_tl_types::Serializable;
+    ///
+    /// let bytes = (0..1000).collect::<Vec<i32>>().to_bytes_with_capacity();
+    /// assert_eq!(bytes.len(), 4008);
+    /// assert_eq!(bytes.capacity(), bytes.len());
+    /// ```
+    fn to_bytes_with_capacity(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.serialized_len());
+        self.serialize(&mut buffer);
+        buffer
+    }
+}
+
+/// A sink which only counts how many bytes would have been written to it.
+struct ByteCounter(usize);
+
+impl Extend<u8> for ByteCounter {
+    fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+        self.0 += iter.into_iter().count();
+    }
 }
 
 impl Serializable for bool {